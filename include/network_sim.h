@@ -0,0 +1,128 @@
+#ifndef NETWORK_SIM_H
+#define NETWORK_SIM_H
+
+/* Generated with cbindgen from src/ffi.rs, don't edit by hand */
+
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+
+// Analyzed result of a run, created with `ns_run` or `ns_replay`
+typedef struct NsResult NsResult;
+
+// Simulation settings, created with `ns_settings_new` or `ns_settings_from_ron`
+typedef struct NsSettings NsSettings;
+
+// One frame of a result
+typedef struct {
+  // 0 for the client, 1 for the server
+  uint8_t side;
+  // Whether the frame belongs to `entity` rather than the behaviour's primary entity
+  bool has_entity;
+  uint32_t entity;
+  float render_time;
+  float net_time;
+  // Position with z as height
+  float pos[3];
+  float alpha;
+} NsFrame;
+
+// Buffer of `len` bytes, freed with `ns_free`, for hosts without a C allocator to pass
+// strings in, like the web viewer's JavaScript
+uint8_t *ns_alloc(size_t len);
+
+// # Safety
+// `buf` must come from `ns_alloc` with the same `len` and not be used after
+void ns_free(uint8_t *buf, size_t len);
+
+// Message of the last failed call on the calling thread, null if none failed. Valid until the
+// next failing call on the thread
+const char *ns_last_error(void);
+
+// Replays the sync messages the client received in a recorded result into the behaviour of
+// `settings` and analyzes it, see `replay::PacketSchedule`. Null if the result doesn't parse or
+// the replay fails. It needs no apps, so unlike `ns_run` it works in the wasm32 build
+//
+// # Safety
+// `settings` must be live settings and `result_json` a valid nul-terminated string with a
+// result exported as JSON, e.g. with `--out`
+NsResult *ns_replay(const NsSettings *settings, const char *result_json);
+
+// # Safety
+// `result` must come from `ns_run` or `ns_replay` and not be used after
+void ns_result_free(NsResult *result);
+
+// Writes frame `idx` to `frame`, false if it's out of range
+//
+// # Safety
+// `result` must be a live result and `frame` writable
+bool ns_result_frame(const NsResult *result, size_t idx, NsFrame *frame);
+
+// Number of frames of both sides
+//
+// # Safety
+// `result` must be a live result
+size_t ns_result_frame_count(const NsResult *result);
+
+// Number of metric channels
+//
+// # Safety
+// `result` must be a live result
+size_t ns_result_metric_count(const NsResult *result);
+
+// Number of samples of metric channel `metric`, 0 if it's out of range
+//
+// # Safety
+// `result` must be a live result
+size_t ns_result_metric_len(const NsResult *result, size_t metric);
+
+// Name of metric channel `metric`, null if it's out of range. Valid as long as the result
+//
+// # Safety
+// `result` must be a live result
+const char *ns_result_metric_name(const NsResult *result, size_t metric);
+
+// Writes the time and value of sample `idx` of metric channel `metric`, false if either is out
+// of range
+//
+// # Safety
+// `result` must be a live result and `time` and `value` writable
+bool ns_result_metric_sample(const NsResult *result,
+                             size_t metric,
+                             size_t idx,
+                             float *time,
+                             float *value);
+
+// Runs and analyzes a simulation, null if the run fails. Blocks until the run is done. Not in
+// the wasm32 build
+//
+// # Safety
+// `settings` must be live settings
+NsResult *ns_run(const NsSettings *settings);
+
+// # Safety
+// `settings` must come from `ns_settings_new` or `ns_settings_from_ron` and not be used after
+void ns_settings_free(NsSettings *settings);
+
+// Settings read from the RON of a settings file, null if they don't parse
+//
+// # Safety
+// `ron` must be a valid nul-terminated string
+NsSettings *ns_settings_from_ron(const char *ron);
+
+// Default settings, freed with `ns_settings_free`
+NsSettings *ns_settings_new(void);
+
+// Sets the behaviour by its display name, false if there's no such behaviour
+//
+// # Safety
+// `settings` must be live settings and `name` a valid nul-terminated string
+bool ns_settings_set_behaviour(NsSettings *settings, const char *name);
+
+// Sets a numeric field by its name in the settings file, false if there's no such field
+//
+// # Safety
+// `settings` must be live settings and `name` a valid nul-terminated string
+bool ns_settings_set_field(NsSettings *settings, const char *name, double value);
+
+#endif /* NETWORK_SIM_H */
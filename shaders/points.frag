@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) in vec2 corner;
+layout(location = 1) in vec4 frag_color;
+
+layout(location = 0) out vec4 out_color;
+
+// width of the ring as a fraction of the radius
+const float RING = 0.25;
+
+void main() {
+    float dist = length(corner);
+    if (dist > 1.0 || dist < 1.0 - RING) {
+        discard;
+    }
+    out_color = frag_color;
+}
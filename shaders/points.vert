@@ -0,0 +1,28 @@
+#version 450
+
+layout(std140, set = 0, binding = 0) uniform ViewArgs {
+    uniform mat4 proj;
+    uniform mat4 view;
+};
+
+// per point
+layout(location = 0) in vec2 pos;
+layout(location = 1) in float radius;
+layout(location = 2) in vec4 color;
+
+layout(location = 0) out vec2 corner;
+layout(location = 1) out vec4 frag_color;
+
+// quad around the point as a triangle strip
+const vec2 corners[4] = vec2[](
+    vec2(-1.0, -1.0),
+    vec2(1.0, -1.0),
+    vec2(-1.0, 1.0),
+    vec2(1.0, 1.0)
+);
+
+void main() {
+    corner = corners[gl_VertexIndex];
+    frag_color = color;
+    gl_Position = proj * view * vec4(pos + corner * radius, 0.0, 1.0);
+}
@@ -0,0 +1,1036 @@
+use crate::{
+    comparison::{compare_behaviours, write_csv, COMPARISON_COLUMNS},
+    metrics::{
+        correlation, difference, ConfidenceInterval, MetricSummary, Metrics, Resample, Timeline,
+    },
+    reliability::InputDelivery,
+    report::{write_report, Report, ReportRun, SweepRow},
+    service::serve,
+    sim::{analyze, run_simulation, Sample, SimSettings, SimWorldPool, SimulationResult},
+    sim_behaviours::{
+        behaviour_by_name, HIT_COMPENSATIONS, HIT_REGISTRATION, SIM_BEHAVIOURS, SPAWNING_ENTITIES,
+    },
+    telemetry::{analyze_capture, capture_result, read_capture},
+    validation::{enforce, has_errors, validate},
+};
+use amethyst::Result;
+use clap::{App, Arg, ArgMatches, SubCommand};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    fs::File,
+    io::{self, Write},
+    path::Path,
+};
+
+#[derive(Deserialize)]
+struct SweepParameter {
+    /// `SimSettings` field name
+    name: String,
+    values: Vec<f64>,
+}
+/// Runs every combination of the behaviours and parameter values on top of the base settings
+#[derive(Deserialize)]
+struct SweepFile {
+    #[serde(default)]
+    base: SimSettings,
+    /// Registry names of the behaviours to run every parameter combination with, the base
+    /// settings' behaviour alone if empty
+    #[serde(default)]
+    behaviours: Vec<String>,
+    parameters: Vec<SweepParameter>,
+    /// Metric whose mean is printed as a behaviour × parameter grid when sweeping behaviours
+    #[serde(default = "default_grid_metric")]
+    grid_metric: String,
+    /// Runs per parameter combination, each with the next seed after the base seed
+    #[serde(default = "default_repetitions")]
+    repetitions: u32,
+}
+fn default_repetitions() -> u32 {
+    1
+}
+fn default_grid_metric() -> String {
+    "error".to_owned()
+}
+#[derive(Serialize)]
+struct SweepPoint {
+    behaviour: String,
+    parameters: Vec<(String, f64)>,
+    /// Metrics of the first repetition
+    metrics: BTreeMap<String, MetricSummary>,
+    /// Mean of every metric over all repetitions
+    intervals: BTreeMap<String, ConfidenceInterval>,
+}
+
+/// Runs each client against a server at each candidate host location, with the latencies
+/// between locations taken from the base settings' latency matrix
+#[derive(Deserialize)]
+struct HostSelectionFile {
+    #[serde(default)]
+    base: SimSettings,
+    /// Names of the latency matrix rows
+    #[serde(default)]
+    locations: Vec<String>,
+    /// Latency matrix rows the clients are at
+    clients: Vec<usize>,
+    /// Latency matrix rows to try hosting at
+    candidates: Vec<usize>,
+}
+#[derive(Serialize)]
+struct HostClient {
+    location: String,
+    /// Mean of the one-way latencies to and from the host in ms
+    latency: f32,
+    metrics: BTreeMap<String, MetricSummary>,
+}
+#[derive(Serialize)]
+struct HostCandidate {
+    location: String,
+    clients: Vec<HostClient>,
+    /// Largest mean error of any client
+    worst_error: f32,
+}
+
+/// Cost of replicating one entity count with the spawning entities behaviour
+#[derive(Serialize)]
+struct ScalingPoint {
+    entity_count: u32,
+    /// Codec payload of the sync messages
+    snapshot_bytes: MetricSummary,
+    /// Sync message bytes per second, envelope included
+    bandwidth: f32,
+    /// Microseconds the client spends on a frame
+    client_frame_time: MetricSummary,
+}
+
+fn report_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("report")
+        .long("report")
+        .takes_value(true)
+        .help("Markdown or HTML (.html) file to write a report to")
+}
+
+fn resample_arg<'a, 'b>() -> Arg<'a, 'b> {
+    Arg::with_name("resample")
+        .long("resample")
+        .takes_value(true)
+        .possible_values(&["linear", "hold", "mean"])
+        .default_value("linear")
+        .help("How metric channels are resampled: interpolated, holding the latest sample, or the mean of the samples nearest each time")
+}
+
+fn resample_timeline(m: &ArgMatches, from: f32, to: f32) -> Result<(Timeline, Resample)> {
+    let rate: f32 = m.value_of("rate").unwrap().parse()?;
+    if !(rate > 0.) {
+        return Err(invalid_input(format!("invalid rate {}", rate)).into());
+    }
+    let mode = m
+        .value_of("resample")
+        .unwrap()
+        .parse()
+        .map_err(invalid_input)?;
+    Ok((Timeline::new(from, to, rate), mode))
+}
+
+pub fn app<'a, 'b>() -> App<'a, 'b> {
+    let scenario = Arg::with_name("scenario")
+        .required(true)
+        .help("RON file containing the simulation settings");
+    App::new("network_sim")
+        .about("Simulates and visualizes network state synchronization strategies")
+        .subcommand(
+            SubCommand::with_name("run")
+                .about("Runs a scenario headlessly")
+                .arg(scenario.clone())
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("JSON file to write the full simulation result to"),
+                )
+                .arg(report_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("validate")
+                .about("Checks a scenario's settings and prints the problems found as JSON, failing if any is an error")
+                .arg(scenario.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("sweep")
+                .about("Runs a scenario for every combination of the swept behaviours and parameters")
+                .arg(
+                    Arg::with_name("sweep")
+                        .required(true)
+                        .help("RON file with base settings, behaviours and parameters to sweep"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("JSON file to write the sweep results to"),
+                )
+                .arg(report_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("scaling")
+                .about("Runs a scenario with the spawning entities behaviour at each entity count")
+                .arg(scenario.clone())
+                .arg(
+                    Arg::with_name("counts")
+                        .long("counts")
+                        .takes_value(true)
+                        .default_value("1,2,4,8,16,32,64,128,256,512")
+                        .help("Comma separated entity counts to run"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("JSON file to write the per entity count results to"),
+                )
+                .arg(report_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("import")
+                .about("Analyzes a telemetry capture of a game build like the result of a run")
+                .arg(
+                    Arg::with_name("capture")
+                        .required(true)
+                        .help("JSON Lines file of telemetry records, see the telemetry module"),
+                )
+                .arg(
+                    Arg::with_name("scenario")
+                        .long("scenario")
+                        .takes_value(true)
+                        .help("RON file whose settings the capture is analyzed with, the defaults otherwise"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("JSON file to write the analyzed capture to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("serve")
+                .about("Runs scenarios posted to /run or queued on /jobs over HTTP and exposes aggregate metrics of the runs on /metrics")
+                .arg(
+                    Arg::with_name("addr")
+                        .long("addr")
+                        .takes_value(true)
+                        .default_value("0.0.0.0:3459")
+                        .help("Address to listen on"),
+                )
+                .arg(
+                    Arg::with_name("jobs")
+                        .long("jobs")
+                        .takes_value(true)
+                        .default_value("jobs")
+                        .help("Directory the queued jobs and their results are stored in"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("compare")
+                .about("Compares a metric between two simulation results")
+                .arg(Arg::with_name("a").required(true))
+                .arg(Arg::with_name("b").required(true))
+                .arg(
+                    Arg::with_name("metric")
+                        .long("metric")
+                        .takes_value(true)
+                        .default_value("error"),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .long("rate")
+                        .takes_value(true)
+                        .help("Also compares the metric element-wise, resampled to this many samples per second over the time both results cover"),
+                )
+                .arg(resample_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("hosts")
+                .about("Runs every client against each candidate host location")
+                .arg(
+                    Arg::with_name("hosts")
+                        .required(true)
+                        .help("RON file with base settings, client locations and candidate hosts"),
+                )
+                .arg(
+                    Arg::with_name("out")
+                        .long("out")
+                        .takes_value(true)
+                        .help("JSON file to write the per host results to"),
+                )
+                .arg(report_arg()),
+        )
+        .subcommand(
+            SubCommand::with_name("inputs")
+                .about("Runs a scenario with each client input delivery strategy and compares them")
+                .arg(scenario.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("hits")
+                .about("Runs a scenario with the hit registration behaviour at each latency and compares the hit rates of each compensation strategy")
+                .arg(scenario.clone())
+                .arg(
+                    Arg::with_name("latencies")
+                        .long("latencies")
+                        .takes_value(true)
+                        .default_value("0,25,50,100,150,200")
+                        .help("Comma separated minimum one-way latencies in ms, the scenario's jitter is kept"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("behaviours")
+                .about("Runs a scenario with each behaviour and tabulates their error, pops, perceived latency, bandwidth and corrections")
+                .arg(scenario.clone())
+                .arg(
+                    Arg::with_name("behaviours")
+                        .long("behaviours")
+                        .takes_value(true)
+                        .help("Comma separated behaviour names to compare, every behaviour by default"),
+                )
+                .arg(
+                    Arg::with_name("csv")
+                        .long("csv")
+                        .takes_value(true)
+                        .help("CSV file to write the table to"),
+                ),
+        )
+        .subcommand(
+            SubCommand::with_name("export")
+                .about("Runs a scenario and exports the recorded frames and metrics as CSV")
+                .arg(scenario)
+                .arg(
+                    Arg::with_name("frames")
+                        .long("frames")
+                        .takes_value(true)
+                        .help("CSV file for the recorded frames"),
+                )
+                .arg(
+                    Arg::with_name("metrics")
+                        .long("metrics")
+                        .takes_value(true)
+                        .help("CSV file for the recorded metric channels"),
+                )
+                .arg(
+                    Arg::with_name("aligned")
+                        .long("aligned")
+                        .takes_value(true)
+                        .help("CSV file with a row per resampled time and a column per metric channel"),
+                )
+                .arg(
+                    Arg::with_name("rate")
+                        .long("rate")
+                        .takes_value(true)
+                        .default_value("60")
+                        .help("Samples per second of the aligned metric channels"),
+                )
+                .arg(resample_arg()),
+        )
+}
+
+fn invalid_input(msg: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, msg)
+}
+
+pub fn load_settings(path: &Path) -> Result<SimSettings> {
+    let contents = std::fs::read_to_string(path)?;
+    Ok(ron::de::from_str(&contents)?)
+}
+/// Prints the problems with `settings` to stderr, failing if any is an error
+fn check_settings(settings: &SimSettings) -> Result<()> {
+    let issues = validate(settings);
+    for issue in issues.iter() {
+        eprintln!("{}", issue);
+    }
+    if has_errors(&issues) {
+        return Err(invalid_input("invalid settings".to_owned()).into());
+    }
+    Ok(())
+}
+
+fn write_json<T: Serialize>(path: &str, value: &T) -> Result<()> {
+    serde_json::to_writer_pretty(File::create(path)?, value)?;
+    Ok(())
+}
+
+fn print_metrics(metrics: &BTreeMap<String, MetricSummary>) {
+    for (name, summary) in metrics {
+        println!(
+            "  {}: mean {:.4} min {:.4} max {:.4} ({} samples)",
+            name, summary.mean, summary.min, summary.max, summary.count
+        );
+    }
+}
+
+/// Result and peak interpolation buffer bytes of a run, recorded by `SimWorldPool::simulate`
+fn memory_usage(metrics: &Metrics) -> (f32, f32) {
+    let peak = |name| metrics.get(name).map_or(0., |c| c.max());
+    (peak("result bytes"), peak("peak buffer bytes"))
+}
+fn print_memory(label: &str, (result, buffers): (f32, f32)) {
+    println!(
+        "{}: results {:.1} MiB, interpolation buffers {:.1} KiB",
+        label,
+        result / (1024. * 1024.),
+        buffers / 1024.
+    );
+}
+
+/// Mean of `metric` over the repetitions of each sweep point, a row per behaviour and a column
+/// per parameter combination
+fn print_behaviour_grid(metric: &str, points: &[SweepPoint]) {
+    let mut rows: Vec<(&str, Vec<&SweepPoint>)> = Vec::new();
+    for point in points {
+        match rows.iter_mut().find(|(b, _)| *b == point.behaviour) {
+            Some((_, row)) => row.push(point),
+            None => rows.push((&point.behaviour, vec![point])),
+        }
+    }
+    let columns: Vec<String> = rows[0]
+        .1
+        .iter()
+        .map(|p| {
+            let label: Vec<String> = p
+                .parameters
+                .iter()
+                .map(|(name, value)| format!("{}={}", name, value))
+                .collect();
+            label.join(" ")
+        })
+        .collect();
+    let width = rows.iter().map(|(b, _)| b.len()).max().unwrap_or(0);
+    println!("{} by behaviour", metric);
+    print!("{:<width$}", "", width = width);
+    for column in columns.iter() {
+        print!(" {:>12}", column);
+    }
+    println!();
+    for (behaviour, row) in rows.iter() {
+        print!("{:<width$}", behaviour, width = width);
+        for point in row {
+            match point.intervals.get(metric) {
+                Some(interval) => print!(" {:>12.4}", interval.mean),
+                None => print!(" {:>12}", "-"),
+            }
+        }
+        println!();
+    }
+}
+
+fn metric_count(metrics: &Metrics, name: &str) -> usize {
+    metrics.get(name).map(|c| c.values.len()).unwrap_or(0)
+}
+fn metric_mean(metrics: &Metrics, name: &str) -> f32 {
+    metrics.get(name).map(|c| c.mean()).unwrap_or(0.)
+}
+
+pub fn run(matches: &ArgMatches) -> Result<()> {
+    match matches.subcommand() {
+        ("run", Some(m)) => {
+            let settings = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            check_settings(&settings)?;
+            let mut reported = false;
+            let raw = SimWorldPool::default().simulate_with_progress(&settings, |progress| {
+                reported = true;
+                eprint!(
+                    "\r{:3.0}% done, {:.1} s left ",
+                    progress.done * 100.,
+                    progress.eta.as_secs_f32()
+                );
+            })?;
+            if reported {
+                eprintln!();
+            }
+            let result = analyze(&settings, raw)?;
+            println!(
+                "analyzed window {:.2} s to {:.2} s",
+                result.window.0, result.window.1
+            );
+            print_metrics(&result.metrics.summary());
+            print_memory("memory", memory_usage(&result.metrics));
+            if let Some(out) = m.value_of("out") {
+                result.to_json_file(Path::new(out))?;
+            }
+            if let Some(report) = m.value_of("report") {
+                let scenario = m.value_of("scenario").unwrap();
+                let run = ReportRun {
+                    title: scenario.to_owned(),
+                    settings: &settings,
+                    metrics: &result.metrics,
+                };
+                let report_data = Report {
+                    title: scenario.to_owned(),
+                    runs: vec![run],
+                    sweep: Vec::new(),
+                };
+                write_report(Path::new(report), &report_data)?;
+            }
+        }
+        ("sweep", Some(m)) => {
+            let contents = std::fs::read_to_string(m.value_of("sweep").unwrap())?;
+            let sweep: SweepFile = ron::de::from_str(&contents)?;
+            for param in sweep.parameters.iter() {
+                if !sweep.base.clone().set_field(&param.name, 0.) {
+                    return Err(
+                        invalid_input(format!("unknown sweep parameter {}", param.name)).into(),
+                    );
+                }
+            }
+            let mut points: Vec<Vec<(String, f64)>> = vec![Vec::new()];
+            for param in sweep.parameters.iter() {
+                points = points
+                    .into_iter()
+                    .flat_map(|point| {
+                        param.values.iter().map(move |v| {
+                            let mut point = point.clone();
+                            point.push((param.name.clone(), *v));
+                            point
+                        })
+                    })
+                    .collect();
+            }
+            let behaviours = if sweep.behaviours.is_empty() {
+                vec![sweep.base.behaviour.clone()]
+            } else {
+                sweep
+                    .behaviours
+                    .iter()
+                    .map(|name| {
+                        behaviour_by_name(name)
+                            .ok_or_else(|| invalid_input(format!("unknown behaviour {}", name)))
+                    })
+                    .collect::<io::Result<Vec<_>>>()?
+            };
+            let mut results = Vec::new();
+            let mut runs = Vec::new();
+            let mut peak_memory = (0f32, 0f32);
+            for (behaviour, parameters) in behaviours
+                .iter()
+                .flat_map(|b| points.iter().map(move |p| (b, p.clone())))
+            {
+                let mut base = sweep.base.clone();
+                base.behaviour = behaviour.clone();
+                let mut settings = base.clone();
+                for (name, value) in parameters.iter() {
+                    settings.set_field(name, *value);
+                }
+                enforce(&mut settings, &base);
+                let mut first = None;
+                let mut means: BTreeMap<String, Vec<f32>> = BTreeMap::new();
+                for repetition in 0..sweep.repetitions.max(1) {
+                    let mut settings = settings.clone();
+                    settings.seed = settings.seed.wrapping_add(repetition as u64);
+                    let result = run_simulation(&settings)?;
+                    let (result_bytes, buffer_bytes) = memory_usage(&result.metrics);
+                    peak_memory.0 = peak_memory.0.max(result_bytes);
+                    peak_memory.1 = peak_memory.1.max(buffer_bytes);
+                    for (name, channel) in result.metrics.iter() {
+                        means.entry(name.clone()).or_default().push(channel.mean());
+                    }
+                    if first.is_none() {
+                        first = Some(result.metrics);
+                    }
+                }
+                let first = first.unwrap();
+                let point = SweepPoint {
+                    behaviour: behaviour.to_string(),
+                    parameters,
+                    metrics: first.summary(),
+                    intervals: means
+                        .iter()
+                        .map(|(name, means)| {
+                            (name.clone(), ConfidenceInterval::from_samples(means))
+                        })
+                        .collect(),
+                };
+                let mut label: Vec<String> = point
+                    .parameters
+                    .iter()
+                    .map(|(name, value)| format!("{}={}", name, value))
+                    .collect();
+                if !sweep.behaviours.is_empty() {
+                    label.insert(0, format!("behaviour={}", point.behaviour));
+                }
+                println!("{}", label.join(" "));
+                if sweep.repetitions > 1 {
+                    for (name, interval) in point.intervals.iter() {
+                        println!(
+                            "  {}: mean {:.4} ± {:.4} (95% CI over {} runs)",
+                            name, interval.mean, interval.half_width, interval.count
+                        );
+                    }
+                } else {
+                    print_metrics(&point.metrics);
+                }
+                runs.push((label.join(" "), settings, first, point.intervals.clone()));
+                results.push(point);
+            }
+            if behaviours.len() > 1 {
+                print_behaviour_grid(&sweep.grid_metric, &results);
+            }
+            print_memory("peak memory of a run", peak_memory);
+            if let Some(out) = m.value_of("out") {
+                write_json(out, &results)?;
+            }
+            if let Some(report) = m.value_of("report") {
+                let report_data = Report {
+                    title: m.value_of("sweep").unwrap().to_owned(),
+                    runs: runs
+                        .iter()
+                        .map(|(title, settings, metrics, _)| ReportRun {
+                            title: title.clone(),
+                            settings,
+                            metrics,
+                        })
+                        .collect(),
+                    sweep: runs
+                        .iter()
+                        .map(|(label, _, _, intervals)| SweepRow {
+                            label: label.clone(),
+                            intervals: intervals.clone(),
+                        })
+                        .collect(),
+                };
+                write_report(Path::new(report), &report_data)?;
+            }
+        }
+        ("scaling", Some(m)) => {
+            let base = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            let mut counts = Vec::new();
+            for count in m.value_of("counts").unwrap().split(',') {
+                counts.push(
+                    count
+                        .trim()
+                        .parse::<u32>()
+                        .map_err(|_| invalid_input(format!("invalid entity count {}", count)))?,
+                );
+            }
+            println!(
+                "{:>8} {:>12} {:>12} {:>12} {:>12} {:>12}",
+                "entities", "snapshot", "max", "bytes/s", "frame µs", "max µs"
+            );
+            let mut points = Vec::new();
+            let mut runs = Vec::new();
+            for count in counts {
+                let mut settings = base.clone();
+                settings.behaviour = behaviour_by_name(SPAWNING_ENTITIES).unwrap();
+                settings.entity_count = count;
+                let result = run_simulation(&settings)?;
+                let metrics = result.metrics.summary();
+                let summary = |name: &str| {
+                    metrics.get(name).cloned().unwrap_or(MetricSummary {
+                        count: 0,
+                        mean: 0.,
+                        min: 0.,
+                        max: 0.,
+                    })
+                };
+                let window = (result.window.1 - result.window.0).max(std::f32::EPSILON);
+                let sync_bytes = result
+                    .metrics
+                    .get("sync bytes")
+                    .map(|c| c.values.clone())
+                    .unwrap_or_default();
+                let point = ScalingPoint {
+                    entity_count: count,
+                    snapshot_bytes: summary("payload bytes"),
+                    bandwidth: sync_bytes.iter().sum::<f32>() / window,
+                    client_frame_time: summary("client frame time"),
+                };
+                println!(
+                    "{:>8} {:>12.1} {:>12.0} {:>12.0} {:>12.1} {:>12.1}",
+                    count,
+                    point.snapshot_bytes.mean,
+                    point.snapshot_bytes.max,
+                    point.bandwidth,
+                    point.client_frame_time.mean,
+                    point.client_frame_time.max
+                );
+                // per message intervals, the bandwidth one scaled by the message rate
+                let interval = |name: &str| {
+                    ConfidenceInterval::from_samples(
+                        result
+                            .metrics
+                            .get(name)
+                            .map(|c| &c.values[..])
+                            .unwrap_or(&[]),
+                    )
+                };
+                let rate = sync_bytes.len() as f32 / window;
+                let mut bandwidth = ConfidenceInterval::from_samples(&sync_bytes);
+                bandwidth.mean *= rate;
+                bandwidth.half_width *= rate;
+                let mut intervals = BTreeMap::new();
+                intervals.insert("snapshot bytes".to_owned(), interval("payload bytes"));
+                intervals.insert("sync bytes/s".to_owned(), bandwidth);
+                intervals.insert("client frame µs".to_owned(), interval("client frame time"));
+                runs.push((
+                    format!("entities={}", count),
+                    settings,
+                    result.metrics,
+                    intervals,
+                ));
+                points.push(point);
+            }
+            if let Some(out) = m.value_of("out") {
+                write_json(out, &points)?;
+            }
+            if let Some(report) = m.value_of("report") {
+                let report_data = Report {
+                    title: format!("{} entity scaling", m.value_of("scenario").unwrap()),
+                    runs: runs
+                        .iter()
+                        .map(|(title, settings, metrics, _)| ReportRun {
+                            title: title.clone(),
+                            settings,
+                            metrics,
+                        })
+                        .collect(),
+                    sweep: runs
+                        .iter()
+                        .map(|(label, _, _, intervals)| SweepRow {
+                            label: label.clone(),
+                            intervals: intervals.clone(),
+                        })
+                        .collect(),
+                };
+                write_report(Path::new(report), &report_data)?;
+            }
+        }
+        ("import", Some(m)) => {
+            let settings = match m.value_of("scenario") {
+                Some(path) => load_settings(Path::new(path))?,
+                None => SimSettings::default(),
+            };
+            let records = read_capture(Path::new(m.value_of("capture").unwrap()))?;
+            let result = analyze_capture(&settings, capture_result(&records))?;
+            println!(
+                "{} records, {:.2} s captured",
+                records.len(),
+                result.window.1 - result.window.0
+            );
+            print_metrics(&result.metrics.summary());
+            if let Some(out) = m.value_of("out") {
+                result.to_json_file(Path::new(out))?;
+            }
+        }
+        ("validate", Some(m)) => {
+            let settings = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            let issues = validate(&settings);
+            println!("{}", serde_json::to_string_pretty(&issues)?);
+            if has_errors(&issues) {
+                return Err(invalid_input("invalid settings".to_owned()).into());
+            }
+        }
+        ("serve", Some(m)) => serve(
+            m.value_of("addr").unwrap(),
+            Path::new(m.value_of("jobs").unwrap()),
+        )?,
+        ("compare", Some(m)) => {
+            let load = |path: &str| SimulationResult::<Sample>::from_json_file(Path::new(path));
+            let a = load(m.value_of("a").unwrap())?;
+            let b = load(m.value_of("b").unwrap())?;
+            let metric = m.value_of("metric").unwrap();
+            let (a_channel, b_channel) = match (a.metrics.get(metric), b.metrics.get(metric)) {
+                (Some(a), Some(b)) => (a, b),
+                _ => {
+                    return Err(
+                        invalid_input(format!("metric {} missing from input", metric)).into(),
+                    )
+                }
+            };
+            let (a_metric, b_metric) = (a_channel.summary(), b_channel.summary());
+            println!("{:>6} {:>12} {:>12} {:>12}", "", "a", "b", "b - a");
+            for (label, a, b) in vec![
+                ("mean", a_metric.mean, b_metric.mean),
+                ("min", a_metric.min, b_metric.min),
+                ("max", a_metric.max, b_metric.max),
+            ] {
+                println!("{:>6} {:>12.4} {:>12.4} {:>12.4}", label, a, b, b - a);
+            }
+            if m.value_of("rate").is_some() {
+                let (from, to) = match (a.metrics.time_range(), b.metrics.time_range()) {
+                    (Some((a_from, a_to)), Some((b_from, b_to))) => {
+                        (a_from.max(b_from), a_to.min(b_to))
+                    }
+                    _ => return Err(invalid_input("results have no samples".to_owned()).into()),
+                };
+                let (timeline, mode) = resample_timeline(m, from, to)?;
+                let a_values = a_channel.resample(&timeline, mode);
+                let b_values = b_channel.resample(&timeline, mode);
+                let diff: Vec<f32> = difference(&a_values, &b_values)
+                    .into_iter()
+                    .filter_map(|d| d)
+                    .collect();
+                let rms =
+                    (diff.iter().map(|d| d * d).sum::<f32>() / diff.len().max(1) as f32).sqrt();
+                println!(
+                    "{} aligned samples from {:.2}s to {:.2}s, rms b - a {:.4}, correlation {}",
+                    diff.len(),
+                    from,
+                    to,
+                    rms,
+                    correlation(&a_values, &b_values)
+                        .map(|c| format!("{:.3}", c))
+                        .unwrap_or_else(|| "-".to_owned())
+                );
+            }
+        }
+        ("hosts", Some(m)) => {
+            let contents = std::fs::read_to_string(m.value_of("hosts").unwrap())?;
+            let hosts: HostSelectionFile = ron::de::from_str(&contents)?;
+            let matrix = &hosts.base.latency_matrix;
+            if let Some(idx) = hosts
+                .clients
+                .iter()
+                .chain(hosts.candidates.iter())
+                .find(|idx| matrix.get(**idx).map(|row| row.len()) != Some(matrix.len()))
+            {
+                return Err(
+                    invalid_input(format!("location {} missing from latency matrix", idx)).into(),
+                );
+            }
+            let location = |idx: usize| {
+                hosts
+                    .locations
+                    .get(idx)
+                    .cloned()
+                    .unwrap_or_else(|| idx.to_string())
+            };
+            let mut candidates = Vec::new();
+            let mut runs = Vec::new();
+            for host in hosts.candidates.iter().cloned() {
+                println!("host {}", location(host));
+                let mut clients = Vec::new();
+                for client in hosts.clients.iter().cloned() {
+                    let latency = (matrix[client][host] + matrix[host][client]) / 2.;
+                    let mut settings = hosts.base.clone();
+                    settings.peer_to_peer = false;
+                    settings.set_base_latency(latency);
+                    let result = run_simulation(&settings)?;
+                    let metrics = result.metrics.summary();
+                    let error = metrics.get("error").map(|e| (e.mean, e.max));
+                    println!(
+                        "  {}: latency {:.0} ms, error mean {:.4} max {:.4}",
+                        location(client),
+                        latency,
+                        error.map(|e| e.0).unwrap_or(0.),
+                        error.map(|e| e.1).unwrap_or(0.)
+                    );
+                    clients.push(HostClient {
+                        location: location(client),
+                        latency,
+                        metrics,
+                    });
+                    runs.push((
+                        format!("host {}, client {}", location(host), location(client)),
+                        settings,
+                        result.metrics,
+                    ));
+                }
+                let worst_error = clients
+                    .iter()
+                    .filter_map(|c| c.metrics.get("error").map(|e| e.mean))
+                    .fold(0., f32::max);
+                println!("  worst client error {:.4}", worst_error);
+                candidates.push(HostCandidate {
+                    location: location(host),
+                    clients,
+                    worst_error,
+                });
+            }
+            if let Some(best) = candidates
+                .iter()
+                .min_by(|a, b| a.worst_error.partial_cmp(&b.worst_error).unwrap())
+            {
+                println!("best host {}", best.location);
+            }
+            if let Some(out) = m.value_of("out") {
+                write_json(out, &candidates)?;
+            }
+            if let Some(report) = m.value_of("report") {
+                let report_data = Report {
+                    title: m.value_of("hosts").unwrap().to_owned(),
+                    runs: runs
+                        .iter()
+                        .map(|(title, settings, metrics)| ReportRun {
+                            title: title.clone(),
+                            settings,
+                            metrics,
+                        })
+                        .collect(),
+                    sweep: Vec::new(),
+                };
+                write_report(Path::new(report), &report_data)?;
+            }
+        }
+        ("inputs", Some(m)) => {
+            let settings = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            println!(
+                "{:>12} {:>10} {:>8} {:>8} {:>12} {:>12}",
+                "delivery", "bytes", "lost", "gaps", "latency", "max latency"
+            );
+            for delivery in [
+                InputDelivery::Unreliable,
+                InputDelivery::Redundant,
+                InputDelivery::Reliable,
+            ]
+            .iter()
+            {
+                let mut settings = settings.clone();
+                settings.input_delivery = *delivery;
+                let metrics = run_simulation(&settings)?.metrics;
+                let bytes = metrics
+                    .get("input bytes")
+                    .map(|c| c.values.iter().sum::<f32>())
+                    .unwrap_or(0.);
+                // inputs still in flight when the run ends count as lost
+                let lost = metric_count(&metrics, "inputs sent")
+                    .saturating_sub(metric_count(&metrics, "input latency"));
+                let max_latency = metrics.get("input latency").map(|c| c.max()).unwrap_or(0.);
+                // inputs skipped over by the end of the run
+                let gaps = metrics
+                    .get("input gaps")
+                    .and_then(|c| c.values.last().copied())
+                    .unwrap_or(0.);
+                println!(
+                    "{:>12} {:>10} {:>8} {:>8} {:>12.4} {:>12.4}",
+                    delivery.to_string(),
+                    bytes,
+                    lost,
+                    gaps,
+                    metric_mean(&metrics, "input latency"),
+                    max_latency
+                );
+            }
+        }
+        ("hits", Some(m)) => {
+            let base = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            let mut latencies = Vec::new();
+            for latency in m.value_of("latencies").unwrap().split(',') {
+                latencies.push(
+                    latency
+                        .trim()
+                        .parse::<f32>()
+                        .map_err(|_| invalid_input(format!("invalid latency {}", latency)))?,
+                );
+            }
+            print!("{:>8} {:>6}", "latency", "shots");
+            for compensation in HIT_COMPENSATIONS.iter() {
+                print!(" {:>16}", compensation.to_string());
+            }
+            println!(" {:>12}", "rewind ms");
+            for latency in latencies {
+                let mut settings = base.clone();
+                settings.behaviour = behaviour_by_name(HIT_REGISTRATION).unwrap();
+                settings.set_base_latency(latency);
+                let metrics = run_simulation(&settings)?.metrics;
+                print!(
+                    "{:>8} {:>6}",
+                    latency,
+                    metric_count(&metrics, &HIT_COMPENSATIONS[0].metric())
+                );
+                for compensation in HIT_COMPENSATIONS.iter() {
+                    print!(
+                        " {:>15.1}%",
+                        metric_mean(&metrics, &compensation.metric()) * 100.
+                    );
+                }
+                println!(" {:>12.1}", metric_mean(&metrics, "rewind time"));
+            }
+        }
+        ("behaviours", Some(m)) => {
+            let settings = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            let behaviours = match m.value_of("behaviours") {
+                Some(names) => names
+                    .split(',')
+                    .map(|name| {
+                        behaviour_by_name(name.trim()).ok_or_else(|| {
+                            invalid_input(format!("unknown behaviour {}", name.trim()))
+                        })
+                    })
+                    .collect::<io::Result<Vec<_>>>()?,
+                None => SIM_BEHAVIOURS.iter().map(|(b, _)| b.clone()).collect(),
+            };
+            let rows = compare_behaviours(&settings, &behaviours)?;
+            let width = rows.iter().map(|r| r.behaviour.len()).max().unwrap_or(0);
+            print!("{:<width$}", COMPARISON_COLUMNS[0], width = width);
+            for column in COMPARISON_COLUMNS[1..].iter() {
+                print!(" {:>10}", column);
+            }
+            println!();
+            for row in rows.iter() {
+                let cells = row.cells();
+                print!("{:<width$}", cells[0], width = width);
+                for (cell, column) in cells[1..].iter().zip(COMPARISON_COLUMNS[1..].iter()) {
+                    print!(" {:>width$}", cell, width = column.len().max(10));
+                }
+                println!();
+            }
+            if let Some(path) = m.value_of("csv") {
+                write_csv(Path::new(path), &rows)?;
+            }
+        }
+        ("export", Some(m)) => {
+            let settings = load_settings(Path::new(m.value_of("scenario").unwrap()))?;
+            let result = run_simulation(&settings)?;
+            if let Some(path) = m.value_of("frames") {
+                let mut file = File::create(path)?;
+                writeln!(file, "side,entity,label,render_time,net_time,x,y,z")?;
+                for frame in result.frames.iter() {
+                    writeln!(
+                        file,
+                        "{:?},{},\"{}\",{},{},{},{},{}",
+                        frame.side,
+                        frame.entity.map(|e| e.to_string()).unwrap_or_default(),
+                        frame
+                            .label
+                            .as_ref()
+                            .map(|l| l.replace('"', "\"\""))
+                            .unwrap_or_default(),
+                        frame.render_time,
+                        frame.net_time,
+                        frame.sample.pos.x,
+                        frame.sample.pos.y,
+                        frame.sample.pos.z
+                    )?;
+                }
+            }
+            if let Some(path) = m.value_of("metrics") {
+                let mut file = File::create(path)?;
+                writeln!(file, "metric,time,value")?;
+                for (name, channel) in result.metrics.iter() {
+                    for (t, v) in channel.times.iter().zip(channel.values.iter()) {
+                        writeln!(file, "{},{},{}", name, t, v)?;
+                    }
+                }
+            }
+            if let Some(path) = m.value_of("aligned") {
+                let (from, to) = result.metrics.time_range().unwrap_or((0., 0.));
+                let (timeline, mode) = resample_timeline(m, from, to)?;
+                let channels = result.metrics.resample(&timeline, mode);
+                let mut file = File::create(path)?;
+                write!(file, "time")?;
+                for name in channels.keys() {
+                    write!(file, ",\"{}\"", name.replace('"', "\"\""))?;
+                }
+                writeln!(file)?;
+                for (idx, t) in timeline.times().enumerate() {
+                    write!(file, "{}", t)?;
+                    for values in channels.values() {
+                        match values[idx] {
+                            Some(v) => write!(file, ",{}", v)?,
+                            None => write!(file, ",")?,
+                        }
+                    }
+                    writeln!(file)?;
+                }
+            }
+        }
+        _ => {}
+    }
+    Ok(())
+}
@@ -0,0 +1,272 @@
+use crate::{
+    entropy::{ByteCounts, HuffmanTable},
+    field_sizes::field_sizes,
+    protobuf::{self, read_varint, unzigzag, write_varint, zigzag},
+    varint,
+};
+use serde::{Deserialize, Serialize};
+use std::{cell::RefCell, collections::VecDeque, fmt};
+
+thread_local! {
+    /// Field sizes of the messages encoded during `measure_fields`
+    static FIELD_SIZES: RefCell<Option<Vec<(String, usize)>>> = RefCell::new(None);
+}
+
+/// Keyframes a `DeltaDecoder` keeps for deltas arriving after a newer keyframe
+const KEYFRAME_HISTORY: usize = 4;
+
+/// Serialization of the behaviours' sync messages. The message envelope stays bincode, so only
+/// the behaviour specific part of the sync bytes depends on the codec.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SyncCodec {
+    Bincode,
+    /// Protocol Buffers wire format, with the sync type as the schema
+    Protobuf,
+    /// Floats quantized to integers and every value a varint, delta encoded against keyframes
+    Varint,
+}
+impl fmt::Display for SyncCodec {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SyncCodec::Bincode => write!(f, "Bincode"),
+            SyncCodec::Protobuf => write!(f, "Protobuf"),
+            SyncCodec::Varint => write!(f, "Varint Delta"),
+        }
+    }
+}
+pub const SYNC_CODECS: [SyncCodec; 3] =
+    [SyncCodec::Bincode, SyncCodec::Protobuf, SyncCodec::Varint];
+
+impl SyncCodec {
+    pub fn encode<T: Serialize>(self, value: &T) -> Vec<u8> {
+        FIELD_SIZES.with(|sizes| {
+            if let Some(sizes) = sizes.borrow_mut().as_mut() {
+                sizes.extend(field_sizes(self, value));
+            }
+        });
+        match self {
+            SyncCodec::Bincode => bincode::serialize(value).unwrap(),
+            SyncCodec::Protobuf => protobuf::to_vec(value).unwrap(),
+            SyncCodec::Varint => varint::to_vec(value).unwrap(),
+        }
+    }
+    /// Panics if `msg` wasn't encoded by `encode` of the same codec and type
+    pub fn decode<'a, T: Deserialize<'a>>(self, msg: &'a [u8]) -> T {
+        match self {
+            SyncCodec::Bincode => bincode::deserialize(msg).unwrap(),
+            SyncCodec::Protobuf => protobuf::from_slice(msg).unwrap(),
+            SyncCodec::Varint => varint::from_slice(msg).unwrap(),
+        }
+    }
+}
+
+/// Runs `f`, returning the bytes of every struct field of the messages it encoded with
+/// `SyncCodec::encode`, by field path, before any delta or entropy coding
+pub fn measure_fields<R>(f: impl FnOnce() -> R) -> (R, Vec<(String, usize)>) {
+    FIELD_SIZES.with(|sizes| *sizes.borrow_mut() = Some(Vec::new()));
+    let result = f();
+    let sizes = FIELD_SIZES.with(|sizes| sizes.borrow_mut().take());
+    (result, sizes.unwrap_or_default())
+}
+
+fn read_varints(mut buf: &[u8]) -> Option<Vec<u64>> {
+    let mut values = Vec::new();
+    while !buf.is_empty() {
+        values.push(read_varint(&mut buf).ok()?);
+    }
+    Some(values)
+}
+
+/// Number of fields of a `SyncCodec::Varint` message, every field being a varint
+pub fn field_count(msg: &[u8]) -> usize {
+    read_varints(msg).map(|values| values.len()).unwrap_or(0)
+}
+
+/// Delta encodes `SyncCodec::Varint` messages on the server. Every `interval`th message is a
+/// keyframe sent as is, the others are the zigzag encoded differences of each varint to the one
+/// at the same position in the latest keyframe, so a lost delta doesn't affect later ones.
+///
+/// Messages start with the number of messages since their keyframe, 0 for keyframes. With
+/// entropy coding, keyframes then carry a Huffman code fitted to the bytes of the deltas since
+/// the previous keyframe, which codes the deltas up to the next keyframe.
+pub struct DeltaEncoder {
+    interval: u32,
+    entropy_coding: bool,
+    /// Sequence number and varints of the latest keyframe
+    keyframe: Option<(u16, Vec<u64>)>,
+    /// Code of the deltas of the latest keyframe
+    table: Option<HuffmanTable>,
+    /// Bytes of the deltas since the latest keyframe, for the next keyframe's code
+    counts: ByteCounts,
+}
+impl DeltaEncoder {
+    pub fn new(interval: u32, entropy_coding: bool) -> Self {
+        Self {
+            interval: interval.max(1),
+            entropy_coding,
+            keyframe: None,
+            table: None,
+            counts: ByteCounts::default(),
+        }
+    }
+    /// Encodes the message sent with sequence number `seq`
+    pub fn encode(&mut self, seq: u16, msg: &[u8]) -> Vec<u8> {
+        let values = read_varints(msg).expect("message is not a varint sequence");
+        let mut out = Vec::with_capacity(msg.len() + 1);
+        match (&self.keyframe, &self.table) {
+            (Some((keyframe_seq, keyframe)), table)
+                if (seq.wrapping_sub(*keyframe_seq) as u32) < self.interval =>
+            {
+                write_varint(&mut out, seq.wrapping_sub(*keyframe_seq) as u64);
+                let mut deltas = Vec::with_capacity(msg.len());
+                for (i, v) in values.iter().enumerate() {
+                    let base = keyframe.get(i).cloned().unwrap_or(0);
+                    write_varint(&mut deltas, zigzag(v.wrapping_sub(base) as i64));
+                }
+                match table {
+                    Some(table) => {
+                        self.counts.add(&deltas);
+                        table.encode(&deltas, &mut out);
+                    }
+                    None => out.extend_from_slice(&deltas),
+                }
+            }
+            _ => {
+                write_varint(&mut out, 0);
+                if self.entropy_coding {
+                    let table = HuffmanTable::from_counts(&self.counts);
+                    table.write(&mut out);
+                    self.table = Some(table);
+                    self.counts = ByteCounts::default();
+                }
+                out.extend_from_slice(msg);
+                self.keyframe = Some((seq, values));
+            }
+        }
+        out
+    }
+}
+
+struct Keyframe {
+    seq: u16,
+    values: Vec<u64>,
+    table: Option<HuffmanTable>,
+}
+
+/// Reverses `DeltaEncoder` on the client
+pub struct DeltaDecoder {
+    entropy_coding: bool,
+    keyframes: VecDeque<Keyframe>,
+}
+impl DeltaDecoder {
+    pub fn new(entropy_coding: bool) -> Self {
+        Self {
+            entropy_coding,
+            keyframes: VecDeque::new(),
+        }
+    }
+    /// Decodes the message received with sequence number `seq`, or `None` if its keyframe was
+    /// lost or is too old
+    pub fn decode(&mut self, seq: u16, msg: &[u8]) -> Option<Vec<u8>> {
+        let mut buf = msg;
+        let since = read_varint(&mut buf).ok()? as u16;
+        if since == 0 {
+            let table = if self.entropy_coding {
+                Some(HuffmanTable::read(&mut buf)?)
+            } else {
+                None
+            };
+            let values = read_varints(buf)?;
+            if !self.keyframes.iter().any(|k| k.seq == seq) {
+                self.keyframes.push_back(Keyframe { seq, values, table });
+                if self.keyframes.len() > KEYFRAME_HISTORY {
+                    self.keyframes.pop_front();
+                }
+            }
+            return Some(buf.to_vec());
+        }
+        let keyframe_seq = seq.wrapping_sub(since);
+        let keyframe = self.keyframes.iter().find(|k| k.seq == keyframe_seq)?;
+        let deltas = match &keyframe.table {
+            Some(table) => read_varints(&table.decode(buf)?)?,
+            None => read_varints(buf)?,
+        };
+        let mut out = Vec::with_capacity(msg.len());
+        for (i, delta) in deltas.iter().enumerate() {
+            let base = keyframe.values.get(i).cloned().unwrap_or(0);
+            write_varint(&mut out, base.wrapping_add(unzigzag(*delta) as u64));
+        }
+        Some(out)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Varint message of a state moving along a circle at step `n`
+    fn state(n: u32) -> Vec<u8> {
+        let t = n as f32 * 0.1;
+        varint::to_vec(&(n, t.sin() * 5., t.cos() * 5., vec![t, -t])).unwrap()
+    }
+
+    /// Encodes the messages of `steps` with sequence numbers from `first_seq` on and decodes
+    /// those `received` lets through, with the step of each
+    fn round_trip(
+        interval: u32,
+        entropy_coding: bool,
+        first_seq: u16,
+        steps: std::ops::Range<u32>,
+        received: impl Fn(u32) -> bool,
+    ) -> Vec<(u32, Option<Vec<u8>>)> {
+        let mut encoder = DeltaEncoder::new(interval, entropy_coding);
+        let mut decoder = DeltaDecoder::new(entropy_coding);
+        let mut decoded = Vec::new();
+        for n in steps {
+            let seq = first_seq.wrapping_add(n as u16);
+            let msg = encoder.encode(seq, &state(n));
+            if received(n) {
+                decoded.push((n, decoder.decode(seq, &msg)));
+            }
+        }
+        decoded
+    }
+
+    #[test]
+    fn round_trips_across_keyframes() {
+        for entropy_coding in [false, true].iter() {
+            let decoded = round_trip(4, *entropy_coding, 100, 0..20, |_| true);
+            assert_eq!(decoded.len(), 20);
+            for (n, msg) in decoded {
+                assert_eq!(msg, Some(state(n)), "step {}", n);
+            }
+        }
+    }
+
+    #[test]
+    fn deltas_without_their_keyframe_are_dropped() {
+        for entropy_coding in [false, true].iter() {
+            // the keyframe of steps 4 to 7 is lost
+            let decoded = round_trip(4, *entropy_coding, 0, 0..12, |n| n != 4);
+            for (n, msg) in decoded {
+                if n > 4 && n < 8 {
+                    assert_eq!(msg, None, "step {}", n);
+                } else {
+                    assert_eq!(msg, Some(state(n)), "step {}", n);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn round_trips_across_sequence_wraparound() {
+        for entropy_coding in [false, true].iter() {
+            // keyframes at 65533 and 1, deltas on both sides of the wrap
+            let decoded = round_trip(4, *entropy_coding, 65533, 0..12, |_| true);
+            assert_eq!(decoded.len(), 12);
+            for (n, msg) in decoded {
+                assert_eq!(msg, Some(state(n)), "step {}", n);
+            }
+        }
+    }
+}
@@ -0,0 +1,97 @@
+//! Runs one scenario under several behaviours and tabulates the headline metrics of each
+use crate::sim::{
+    analyze, Sample, SimSettings, SimWorldPool, SimulationBehaviour, SimulationResult,
+};
+use amethyst::Result;
+use std::{fs::File, io::Write, path::Path, sync::Arc};
+
+/// Headline metrics of one behaviour's run
+#[derive(Clone, Debug)]
+pub struct ComparisonRow {
+    pub behaviour: String,
+    pub error_p50: f32,
+    pub error_p95: f32,
+    pub pop_p95: f32,
+    pub pop_max: f32,
+    /// Mean "perceived latency" in ms where the behaviour records it, the mean display lag
+    /// otherwise
+    pub perceived_latency: f32,
+    /// Sync bytes per second over the analyzed window
+    pub bandwidth: f32,
+    pub corrections: usize,
+}
+pub const COMPARISON_COLUMNS: [&str; 8] = [
+    "behaviour",
+    "error p50",
+    "error p95",
+    "pop p95",
+    "pop max",
+    "perceived latency ms",
+    "bytes/s",
+    "corrections",
+];
+impl ComparisonRow {
+    pub fn new(behaviour: String, result: &SimulationResult<Sample>) -> Self {
+        let metrics = &result.metrics;
+        let percentile = |name, p| metrics.get(name).map(|c| c.percentile(p)).unwrap_or(0.);
+        let duration = (result.window.1 - result.window.0).max(std::f32::EPSILON);
+        Self {
+            behaviour,
+            error_p50: percentile("error", 50.),
+            error_p95: percentile("error", 95.),
+            pop_p95: percentile("pop", 95.),
+            pop_max: metrics.get("pop").map(|c| c.max()).unwrap_or(0.),
+            perceived_latency: metrics
+                .get("perceived latency")
+                .or_else(|| metrics.get("display lag"))
+                .map(|c| c.mean())
+                .unwrap_or(0.),
+            bandwidth: metrics
+                .get("sync bytes")
+                .map(|c| c.values.iter().sum::<f32>())
+                .unwrap_or(0.)
+                / duration,
+            corrections: metrics.get("correction").map(|c| c.len()).unwrap_or(0),
+        }
+    }
+    /// Values in the order of `COMPARISON_COLUMNS`
+    pub fn cells(&self) -> Vec<String> {
+        vec![
+            self.behaviour.clone(),
+            format!("{:.3}", self.error_p50),
+            format!("{:.3}", self.error_p95),
+            format!("{:.3}", self.pop_p95),
+            format!("{:.3}", self.pop_max),
+            format!("{:.1}", self.perceived_latency),
+            format!("{:.0}", self.bandwidth),
+            self.corrections.to_string(),
+        ]
+    }
+}
+
+/// Runs `settings` with each of `behaviours`
+pub fn compare_behaviours(
+    settings: &SimSettings,
+    behaviours: &[Arc<dyn SimulationBehaviour>],
+) -> Result<Vec<ComparisonRow>> {
+    let mut rows = Vec::new();
+    let mut pool = SimWorldPool::default();
+    for behaviour in behaviours {
+        let mut settings = settings.clone();
+        settings.behaviour = behaviour.clone();
+        let result = analyze(&settings, pool.simulate(&settings)?)?;
+        rows.push(ComparisonRow::new(behaviour.to_string(), &result));
+    }
+    Ok(rows)
+}
+
+pub fn write_csv(path: &Path, rows: &[ComparisonRow]) -> Result<()> {
+    let mut file = File::create(path)?;
+    writeln!(file, "{}", COMPARISON_COLUMNS.join(","))?;
+    for row in rows {
+        let mut cells = row.cells();
+        cells[0] = format!("\"{}\"", cells[0].replace('"', "\"\""));
+        writeln!(file, "{}", cells.join(","))?;
+    }
+    Ok(())
+}
@@ -1,15 +1,104 @@
-use crate::sim::{run_simulation, Sample, SimSettings, SimulationResult};
+use crate::{
+    render::{sim_bounding_box_render, PlayheadLabels},
+    session::{Bookmarks, Session},
+    tour::TOUR,
+};
+use network_sim::{
+    codec::{SyncCodec, SYNC_CODECS},
+    comparison::{compare_behaviours, write_csv, ComparisonRow, COMPARISON_COLUMNS},
+    filter::{FilterKind, FILTER_KINDS},
+    latency::{LatencyModel, LATENCY_MODELS},
+    loss::burst_loss_stats,
+    metrics::MetricChannel,
+    p2p::{InputDelayPolicy, INPUT_DELAY_POLICIES, TOPOLOGIES},
+    pipeline::{is_composed, PipelineStage, PIPELINE_STAGES},
+    reliability::{InputDelivery, INPUT_DELIVERIES, RETRANSMIT_POLICIES},
+    scenario::TELEPORT_POLICIES,
+    sim::{
+        analyze, refilter, LogEntry, PacketKind, Sample, SimSettings, SimSide, SimWorldPool,
+        SimulationResult, COMPENSATION_POLICIES,
+    },
+    sim_behaviours::{AnimationPolicy, ANIMATION_POLICIES, INTERACTION_PREDICTIONS},
+    style::PALETTES,
+    telemetry::{analyze_capture, capture_result, read_capture, LiveCapture},
+    truth::ERROR_REFERENCES,
+    validation::{
+        enforce, has_errors, max_render_time_variance, validate, Severity, SETTINGS_LINKS,
+    },
+    view::{ScaleMode, SCALE_MODES},
+};
 
 use amethyst::{
     core::Time,
-    ecs::{ReadExpect, WriteExpect},
+    ecs::{Read, ReadExpect, WriteExpect},
     prelude::*,
     window::ScreenDimensions,
 };
+use amethyst_imgui::imgui::{ImStr, ImString, Ui};
 use std::{
+    collections::BTreeSet,
     fmt::Debug,
+    path::Path,
     sync::{Arc, Mutex},
+    time::{Duration, Instant},
 };
+const ITEM_WIDTH: f32 = 300.;
+/// Viewer frames the frame time plot covers
+const FRAME_TIME_HISTORY: usize = 240;
+/// Smallest top of the frame time plot's scale in ms
+const FRAME_TIME_SCALE: f32 = 33.;
+/// Runs the log console keeps the logs of
+const LOG_RUNS: usize = 10;
+/// Wall-clock time between two analyses of a live capture receiving records
+const LIVE_REFRESH: Duration = Duration::from_millis(250);
+/// UDP port a live capture listens on by default
+const LIVE_PORT: i32 = 3458;
+/// Height in pixels of the bar time ranges are selected on
+const SELECTION_BAR_HEIGHT: f32 = 8.;
+/// Height of the latency budget bar
+const BUDGET_BAR_HEIGHT: f32 = 16.;
+/// Colors of the network, sync quantization, interpolation and render frame terms of the
+/// latency budget
+const BUDGET_COLORS: [[f32; 4]; 4] = [
+    [0.3, 0.6, 1.0, 1.0],
+    [1.0, 0.6, 0.2, 1.0],
+    [0.5, 1.0, 0.5, 1.0],
+    [1.0, 0.3, 1.0, 1.0],
+];
+const PLOT_SIZE: [f32; 2] = [500., 60.];
+/// Factor the visible time range of the plots scales by per mouse wheel step
+const PLOT_ZOOM: f32 = 0.8;
+/// Shortest time range in seconds the plots zoom in to
+const MIN_PLOT_SPAN: f32 = 0.01;
+/// Prefix of the metrics of the bytes of each sync message field
+const FIELD_BYTES_PREFIX: &str = "field bytes ";
+const CORRECTION_HISTOGRAM_BINS: usize = 20;
+/// Time slice in seconds that corrections are counted over
+const CORRECTION_COUNT_INTERVAL: f32 = 0.5;
+/// Degrees the 3d view orbits per pixel of mouse movement
+const ORBIT_SENSITIVITY: f32 = 0.5;
+
+/// Render time of the client frame following (or preceding) `time`
+fn step_client_frame(sim: &SimulationResult<Sample>, time: f32, forward: bool) -> Option<f32> {
+    let times = sim
+        .frames
+        .iter()
+        .filter(|f| f.side == SimSide::Client)
+        .map(|f| f.render_time);
+    if forward {
+        times
+            .filter(|t| *t > time)
+            .fold(None, |min: Option<f32>, t| {
+                Some(min.map(|m| m.min(t)).unwrap_or(t))
+            })
+    } else {
+        times
+            .filter(|t| *t < time)
+            .fold(None, |max: Option<f32>, t| {
+                Some(max.map(|m| m.max(t)).unwrap_or(t))
+            })
+    }
+}
 fn sim_min_max_time<M: Debug + Clone>(sim: &SimulationResult<M>) -> (f32, f32) {
     let mut min_time = sim
         .frames
@@ -31,43 +120,488 @@ fn sim_min_max_time<M: Debug + Clone>(sim: &SimulationResult<M>) -> (f32, f32) {
     (min_time, max_time)
 }
 
+/// Time range shared by the metric plots, zoomed with the mouse wheel and panned by dragging
+#[derive(Default)]
+struct PlotView {
+    /// Visible time range, `None` for the whole run
+    range: Option<(f32, f32)>,
+    /// Whether the plot being pressed was dragged, so releasing it doesn't move the playhead
+    panned: bool,
+}
+impl PlotView {
+    /// Plots `channel` against time over the visible range with a cursor at `curr_time`.
+    /// Clicking a plot moves `curr_time` to the clicked time.
+    fn plot(
+        &mut self,
+        ui: &Ui,
+        label: &ImStr,
+        channel: &MetricChannel,
+        overlay: &str,
+        (min_time, max_time): (f32, f32),
+        curr_time: &mut f32,
+        selection: Option<(f32, f32)>,
+    ) {
+        let (from, to) = self.range.unwrap_or((min_time, max_time));
+        let span = (to - from).max(std::f32::EPSILON);
+        let origin = ui.get_cursor_screen_pos();
+        let size = PLOT_SIZE;
+        let clicked = ui.invisible_button(label, size);
+        let time_at = |x: f32| from + (x - origin[0]) / size[0] * span;
+        let x_of = |t: f32| origin[0] + (t - from) / span * size[0];
+        if ui.is_item_hovered() && ui.io().mouse_wheel != 0. {
+            // zoom around the time under the mouse
+            let t = time_at(ui.io().mouse_pos[0]);
+            let scale = PLOT_ZOOM.powf(ui.io().mouse_wheel);
+            let span = (span * scale).max(MIN_PLOT_SPAN).min(max_time - min_time);
+            let from = (t - (t - from) * span / (to - from)).max(min_time);
+            let from = from.min(max_time - span);
+            self.range = Some((from, from + span));
+        }
+        if ui.is_item_active() {
+            let dx = ui.io().mouse_delta[0];
+            if dx != 0. {
+                self.panned = true;
+                let shift = -dx / size[0] * span;
+                let from = (from + shift).max(min_time).min(max_time - span);
+                self.range = Some((from, from + span));
+            }
+        } else if clicked {
+            if !self.panned {
+                *curr_time = time_at(ui.io().mouse_pos[0]).max(min_time).min(max_time);
+            }
+            self.panned = false;
+        }
+        let draw_list = ui.get_window_draw_list();
+        let max = [origin[0] + size[0], origin[1] + size[1]];
+        draw_list
+            .add_rect(origin, max, [0.2, 0.2, 0.2, 1.0])
+            .filled(true)
+            .build();
+        if let Some((sel_from, sel_to)) = selection {
+            let (a, b) = (x_of(sel_from).max(origin[0]), x_of(sel_to).min(max[0]));
+            if a < b {
+                draw_list
+                    .add_rect([a, origin[1]], [b, max[1]], [0.3, 0.6, 1.0, 0.25])
+                    .filled(true)
+                    .build();
+            }
+        }
+        // (first, last, min, max) value of the samples in each pixel column
+        let mut columns = vec![None; size[0] as usize];
+        let (mut low, mut high) = (std::f32::INFINITY, std::f32::NEG_INFINITY);
+        for (t, v) in channel.times.iter().zip(channel.values.iter()) {
+            if *t < from || *t > to {
+                continue;
+            }
+            let column = (((t - from) / span * size[0]) as usize).min(columns.len() - 1);
+            let entry = columns[column].get_or_insert((*v, *v, *v, *v));
+            entry.1 = *v;
+            entry.2 = entry.2.min(*v);
+            entry.3 = entry.3.max(*v);
+            low = low.min(*v);
+            high = high.max(*v);
+        }
+        if high == low {
+            high = low + 1.;
+        }
+        let y_of = |v: f32| max[1] - (v - low) / (high - low) * size[1];
+        let color = [0.8, 0.8, 0.8, 1.0];
+        let mut prev: Option<[f32; 2]> = None;
+        for (column, entry) in columns.iter().enumerate() {
+            if let Some((first, last, min, max)) = entry {
+                let x = origin[0] + column as f32;
+                if let Some(prev) = prev {
+                    draw_list.add_line(prev, [x, y_of(*first)], color).build();
+                }
+                draw_list
+                    .add_line([x, y_of(*min)], [x, y_of(*max) - 1.], color)
+                    .build();
+                prev = Some([x, y_of(*last)]);
+            }
+        }
+        if *curr_time >= from && *curr_time <= to {
+            let x = x_of(*curr_time);
+            draw_list
+                .add_line([x, origin[1]], [x, max[1]], [1.0, 1.0, 0.3, 1.0])
+                .build();
+        }
+        draw_list.add_text(
+            [origin[0] + 4., origin[1] + 2.],
+            [1.0, 1.0, 1.0, 1.0],
+            overlay,
+        );
+        ui.same_line(0.);
+        ui.text(label.to_str());
+    }
+}
+
 pub struct GuiSystemDesc;
 
 impl<'a, 'b> SystemDesc<'a, 'b, GuiSystem> for GuiSystemDesc {
     fn build(self, world: &mut World) -> GuiSystem {
         let settings = SimSettings::default();
-        let sim = run_simulation(&settings).unwrap();
+        let mut pool = SimWorldPool::default();
+        let raw = pool.simulate(&settings).unwrap();
+        let sim = analyze(&settings, raw.clone()).unwrap();
+        let log_runs = vec![(1, "initial run".to_owned(), sim.log.clone())];
         world.insert(Arc::new(Mutex::new(sim)));
-        world.insert(settings);
-        GuiSystem
+        world.insert(settings.clone());
+        world.insert(Bookmarks::default());
+        GuiSystem {
+            session_path: ImString::new("session.ron"),
+            capture_path: ImString::new("capture.jsonl"),
+            new_stage: 0,
+            live: None,
+            live_port: LIVE_PORT,
+            live_follow: true,
+            live_pending: false,
+            live_refreshed: None,
+            bookmark_name: ImString::with_capacity(64),
+            last_run: settings,
+            raw: Some(raw),
+            pool,
+            settings_diff: Vec::new(),
+            selection: None,
+            brush_start: None,
+            plots: PlotView::default(),
+            tour_step: None,
+            last_frame: None,
+            comparison: Vec::new(),
+            comparison_path: ImString::new("comparison.csv"),
+            log_runs,
+            log_run: 0,
+            log_filter: ImString::with_capacity(64),
+            show_log: false,
+            frame_times: vec![0.; FRAME_TIME_HISTORY],
+            frame_time_idx: 0,
+        }
     }
 }
-pub struct GuiSystem;
+pub struct GuiSystem {
+    session_path: ImString,
+    /// Telemetry capture of a game build to show instead of a run
+    capture_path: ImString,
+    /// Index into `PIPELINE_STAGES` of the stage the pipeline editor adds
+    new_stage: usize,
+    /// Capture streamed by a running game, shown instead of a run while listening
+    live: Option<LiveCapture>,
+    live_port: i32,
+    /// Keep the playhead at the latest frame of the live capture
+    live_follow: bool,
+    /// Whether the live capture received records or the settings changed since its last analysis
+    live_pending: bool,
+    live_refreshed: Option<Instant>,
+    bookmark_name: ImString,
+    // settings of the currently displayed run, and how they differ from the run before it
+    last_run: SimSettings,
+    /// Unanalyzed result of the latest run of the apps, refiltered when only the client filter
+    /// changes
+    raw: Option<SimulationResult<Sample>>,
+    /// Apps the runs reuse
+    pool: SimWorldPool,
+    settings_diff: Vec<String>,
+    /// Time range selected on the timeline the selection stats cover
+    selection: Option<(f32, f32)>,
+    /// Time the selection being dragged started at
+    brush_start: Option<f32>,
+    plots: PlotView,
+    /// Step of the guided tour being shown, None outside the tour
+    tour_step: Option<usize>,
+    /// Wall-clock time of the previous run of the system, for wall-clock playback
+    last_frame: Option<Instant>,
+    /// Rows of the latest behaviour comparison, run with the settings at the time
+    comparison: Vec<ComparisonRow>,
+    comparison_path: ImString,
+    /// Number, settings change and log of the latest `LOG_RUNS` runs, oldest first
+    log_runs: Vec<(usize, String, Vec<LogEntry>)>,
+    /// Index into `log_runs` of the run shown in the log console
+    log_run: usize,
+    /// Text the shown log messages must contain
+    log_filter: ImString,
+    show_log: bool,
+    /// Wall-clock duration in ms of the latest `FRAME_TIME_HISTORY` viewer frames, a ring
+    /// buffer whose oldest value is at `frame_time_idx`
+    frame_times: Vec<f32>,
+    frame_time_idx: usize,
+}
 impl<'s> amethyst::ecs::System<'s> for GuiSystem {
     type SystemData = (
         ReadExpect<'s, ScreenDimensions>,
         ReadExpect<'s, Time>,
         WriteExpect<'s, Arc<Mutex<SimulationResult<Sample>>>>,
         WriteExpect<'s, SimSettings>,
+        WriteExpect<'s, Bookmarks>,
+        Read<'s, PlayheadLabels>,
     );
-    fn run(&mut self, (_screen_dimensions, time, sim, mut settings): Self::SystemData) {
+    fn run(
+        &mut self,
+        (_screen_dimensions, time, sim, mut settings, mut bookmarks, labels): Self::SystemData,
+    ) {
         let mut sim = sim.lock().unwrap();
+        if let Some(live) = &mut self.live {
+            self.live_pending |= live.poll();
+            let due = self
+                .live_refreshed
+                .map_or(true, |t| t.elapsed() >= LIVE_REFRESH);
+            if self.live_pending && due && !live.is_empty() {
+                match analyze_capture(&settings, live.result()) {
+                    Ok(capture) => {
+                        *sim = capture;
+                        if self.live_follow {
+                            settings.curr_time = sim.window.1;
+                        }
+                    }
+                    Err(e) => log::error!("failed to analyze live capture: {}", e),
+                }
+                self.live_pending = false;
+                self.live_refreshed = Some(Instant::now());
+            }
+        }
         let (min_time, max_time) = sim_min_max_time(&sim);
+        let now = Instant::now();
+        let wall_delta = self
+            .last_frame
+            .replace(now)
+            .map(|prev| (now - prev).as_secs_f32())
+            .unwrap_or(0.);
+        self.frame_times[self.frame_time_idx] = wall_delta * 1000.;
+        self.frame_time_idx = (self.frame_time_idx + 1) % FRAME_TIME_HISTORY;
         if settings.playing {
-            settings.curr_time += time.delta_seconds() * settings.sim_time_scale;
-            settings.curr_time = settings.curr_time % max_time;
+            let next = if settings.step_frames {
+                step_client_frame(&sim, settings.curr_time, true)
+            } else {
+                let delta = if settings.wall_clock_playback {
+                    wall_delta
+                } else {
+                    time.delta_seconds()
+                };
+                Some(settings.curr_time + delta * settings.sim_time_scale)
+                    .filter(|t| *t <= max_time)
+            };
+            match next {
+                Some(t) => settings.curr_time = t,
+                None if settings.pause_at_end => {
+                    settings.curr_time = max_time;
+                    settings.playing = false;
+                }
+                None => settings.curr_time = min_time,
+            }
         }
         amethyst_imgui::with(|ui| {
             use amethyst_imgui::imgui::*;
+            let mut tour_changed = false;
+            if let Some(step_idx) = self.tour_step {
+                let mut next_step = None;
+                let mut opened = true;
+                Window::new(im_str!("tour"))
+                    .size([400., 250.], Condition::Once)
+                    .opened(&mut opened)
+                    .build(ui, || {
+                        let step = &TOUR.steps[step_idx];
+                        ui.text(format!(
+                            "{}/{}: {}",
+                            step_idx + 1,
+                            TOUR.steps.len(),
+                            step.title
+                        ));
+                        ui.separator();
+                        ui.text_wrapped(&ImString::new(step.caption.as_str()));
+                        ui.separator();
+                        if step_idx > 0 && ui.small_button(im_str!("< previous")) {
+                            next_step = Some(step_idx - 1);
+                        }
+                        if step_idx + 1 < TOUR.steps.len() {
+                            ui.same_line(0.);
+                            if ui.small_button(im_str!("next >")) {
+                                next_step = Some(step_idx + 1);
+                            }
+                        }
+                        ui.same_line(0.);
+                        if ui.small_button(im_str!("replay")) {
+                            next_step = Some(step_idx);
+                        }
+                    });
+                if !opened {
+                    self.tour_step = None;
+                } else if let Some(next_step) = next_step {
+                    self.tour_step = Some(next_step);
+                    *settings = TOUR.steps[next_step].apply(&settings);
+                    tour_changed = true;
+                }
+            }
             Window::new(im_str!("control"))
                 .size([550., 400.], Condition::Once)
                 .build(ui, || {
-                    ui.push_item_width(300.0);
+                    ui.push_item_width(ITEM_WIDTH);
                     Slider::new(im_str!("sim time"), min_time..=max_time)
                         .build(ui, &mut settings.curr_time);
-                    Slider::new(im_str!("sim time scale"), 0.1..=1.)
+                    {
+                        // bookmark ticks on top of the timeline slider
+                        let slider_min = ui.get_item_rect_min();
+                        let slider_max = ui.get_item_rect_max();
+                        let draw_list = ui.get_window_draw_list();
+                        let range = (max_time - min_time).max(std::f32::EPSILON);
+                        for bookmark in bookmarks.0.iter() {
+                            let x = slider_min[0] + (bookmark.time - min_time) / range * ITEM_WIDTH;
+                            draw_list
+                                .add_line(
+                                    [x, slider_min[1]],
+                                    [x, slider_max[1]],
+                                    settings.style.bookmark,
+                                )
+                                .build();
+                        }
+                    }
+                    {
+                        // dragging across the bar below the timeline selects a time range
+                        let bar_min = ui.get_cursor_screen_pos();
+                        ui.invisible_button(
+                            im_str!("selection"),
+                            [ITEM_WIDTH, SELECTION_BAR_HEIGHT],
+                        );
+                        let range = (max_time - min_time).max(std::f32::EPSILON);
+                        let x_of = |t: f32| bar_min[0] + (t - min_time) / range * ITEM_WIDTH;
+                        if ui.is_item_active() {
+                            let mouse_x = ui.io().mouse_pos[0];
+                            let t = (min_time + (mouse_x - bar_min[0]) / ITEM_WIDTH * range)
+                                .max(min_time)
+                                .min(max_time);
+                            let start = *self.brush_start.get_or_insert(t);
+                            self.selection = Some((start.min(t), start.max(t)));
+                        } else if self.brush_start.take().is_some() {
+                            // a click without dragging clears the selection
+                            if let Some((from, to)) = self.selection {
+                                if x_of(to) - x_of(from) < 2. {
+                                    self.selection = None;
+                                }
+                            }
+                        }
+                        let draw_list = ui.get_window_draw_list();
+                        let bar_max = [bar_min[0] + ITEM_WIDTH, bar_min[1] + SELECTION_BAR_HEIGHT];
+                        draw_list
+                            .add_rect(bar_min, bar_max, [0.5, 0.5, 0.5, 0.3])
+                            .filled(true)
+                            .build();
+                        if let Some((from, to)) = self.selection {
+                            draw_list
+                                .add_rect(
+                                    [x_of(from), bar_min[1]],
+                                    [x_of(to), bar_max[1]],
+                                    [0.3, 0.6, 1.0, 0.8],
+                                )
+                                .filled(true)
+                                .build();
+                        }
+                    }
+                    Slider::new(im_str!("sim time scale"), 0.01..=1.)
+                        .power(3.)
                         .build(ui, &mut settings.sim_time_scale);
-                    let mut changed = Slider::new(im_str!("server fps"), 1..=240)
+                    ui.checkbox(
+                        im_str!("step one client frame per frame"),
+                        &mut settings.step_frames,
+                    );
+                    ui.checkbox(
+                        im_str!("wall-clock playback"),
+                        &mut settings.wall_clock_playback,
+                    );
+                    ui.same_line(0.);
+                    ui.checkbox(im_str!("pause at end"), &mut settings.pause_at_end);
+                    ui.same_line(0.);
+                    ui.checkbox(im_str!("audio cues"), &mut settings.audio_cues);
+                    if settings.audio_cues {
+                        Slider::new(im_str!("pop cue threshold"), 0.0..=100.0)
+                            .power(2.)
+                            .build(ui, &mut settings.pop_cue_threshold);
+                    }
+                    if ui.small_button(im_str!("< frame")) {
+                        if let Some(t) = step_client_frame(&sim, settings.curr_time, false) {
+                            settings.curr_time = t;
+                        }
+                    }
+                    ui.same_line(0.);
+                    if ui.small_button(im_str!("frame >")) {
+                        if let Some(t) = step_client_frame(&sim, settings.curr_time, true) {
+                            settings.curr_time = t;
+                        }
+                    }
+                    ui.checkbox(im_str!("second playhead"), &mut settings.second_playhead);
+                    if settings.second_playhead {
+                        Slider::new(im_str!("second playhead offset ms"), -500.0..=500.0)
+                            .build(ui, &mut settings.playhead_offset);
+                    }
+                    ui.checkbox(im_str!("3d view"), &mut settings.view_3d);
+                    ui.same_line(0.);
+                    ui.checkbox(im_str!("minimap"), &mut settings.minimap);
+                    ui.same_line(0.);
+                    ui.checkbox(im_str!("playhead labels"), &mut settings.playhead_labels);
+                    ui.same_line(0.);
+                    ui.checkbox(im_str!("frame times"), &mut settings.viewer_frame_times);
+                    if settings.view_3d {
+                        Slider::new(im_str!("orbit yaw"), -180.0..=180.0)
+                            .build(ui, &mut settings.orbit_yaw);
+                        Slider::new(im_str!("orbit pitch"), 0.0..=90.0)
+                            .build(ui, &mut settings.orbit_pitch);
+                    }
+                    let mut scale_idx = SCALE_MODES
+                        .iter()
+                        .position(|m| *m == settings.scale_mode)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("scale")).build_simple(
+                        ui,
+                        &mut scale_idx,
+                        &SCALE_MODES,
+                        &|m| std::borrow::Cow::Owned(ImString::new(m.to_string())),
+                    ) {
+                        // start from the extents on screen, so switching doesn't move the curves
+                        if settings.scale_mode == ScaleMode::Auto
+                            || SCALE_MODES[scale_idx] == ScaleMode::Baseline
+                        {
+                            let (min, max) = sim_bounding_box_render(&sim, |f| f.sample.pos);
+                            settings.scale_min = min.into();
+                            settings.scale_max = max.into();
+                        }
+                        settings.scale_mode = SCALE_MODES[scale_idx];
+                    }
+                    match settings.scale_mode {
+                        ScaleMode::Auto => {}
+                        ScaleMode::Fixed => {
+                            ui.input_float3(im_str!("min extents"), &mut settings.scale_min)
+                                .build();
+                            ui.input_float3(im_str!("max extents"), &mut settings.scale_max)
+                                .build();
+                        }
+                        ScaleMode::Baseline => {
+                            ui.text(format!(
+                                "baseline extents {:.2?} to {:.2?}",
+                                settings.scale_min, settings.scale_max
+                            ));
+                            ui.same_line(0.);
+                            if ui.small_button(im_str!("lock to this run")) {
+                                let (min, max) = sim_bounding_box_render(&sim, |f| f.sample.pos);
+                                settings.scale_min = min.into();
+                                settings.scale_max = max.into();
+                            }
+                        }
+                    }
+                    ui.checkbox(im_str!("equal axis scale"), &mut settings.equal_axis_scale);
+                    ui.same_line(0.);
+                    if ui.checkbox(im_str!("lock y range"), &mut settings.lock_y) && settings.lock_y
+                    {
+                        let (min, max) = sim_bounding_box_render(&sim, |f| f.sample.pos);
+                        settings.y_min = min.y;
+                        settings.y_max = max.y;
+                    }
+                    if settings.lock_y {
+                        ui.input_float(im_str!("y min"), &mut settings.y_min)
+                            .build();
+                        ui.input_float(im_str!("y max"), &mut settings.y_max)
+                            .build();
+                    }
+                    let mut changed = tour_changed;
+                    let prev_settings = settings.clone();
+                    // only the client filter changed, so the last run can be refiltered
+                    let mut filter_changed = false;
+                    changed |= Slider::new(im_str!("server fps"), 1..=240)
                         .build(ui, &mut settings.server_fps);
                     changed |= Slider::new(im_str!("client fps"), 1..=240)
                         .build(ui, &mut settings.render_fps);
@@ -75,26 +609,310 @@ impl<'s> amethyst::ecs::System<'s> for GuiSystem {
                         .build(ui, &mut settings.sync_rate);
                     changed |= Slider::new(im_str!("render interpolation delay ms"), 0.0..=500.0)
                         .build(ui, &mut settings.render_interpolation_delay);
-                    let max_variance = (1000.0 / settings.render_fps as f32) * 0.5;
+                    let max_variance = max_render_time_variance(&settings);
                     changed |= Slider::new(im_str!("render time variance ms"), 0.0..=max_variance)
                         .build(ui, &mut settings.render_time_variance);
-                    if settings.render_time_variance > max_variance {
-                        settings.render_time_variance = max_variance;
+                    changed |= Slider::new(im_str!("client cpu budget ms"), 0.0..=33.0)
+                        .build(ui, &mut settings.client_cpu_budget);
+                    if settings.client_cpu_budget > 0. {
+                        changed |= Slider::new(im_str!("resimulation cost ms"), 0.0..=10.0)
+                            .build(ui, &mut settings.resimulation_cost);
+                    }
+                    let mut latency_idx = LATENCY_MODELS
+                        .iter()
+                        .position(|m| *m == settings.latency_model)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("latency model")).build_simple(
+                        ui,
+                        &mut latency_idx,
+                        &LATENCY_MODELS,
+                        &|m| std::borrow::Cow::Owned(ImString::new(m.to_string())),
+                    ) {
+                        changed = true;
+                        settings.latency_model = LATENCY_MODELS[latency_idx];
+                    }
+                    match settings.latency_model {
+                        LatencyModel::Uniform => {
+                            changed |= Slider::new(im_str!("min latency ms"), 0.0..=500.0)
+                                .build(ui, &mut settings.min_latency);
+                            changed |= Slider::new(im_str!("max latency ms"), 0.0..=500.0)
+                                .build(ui, &mut settings.max_latency);
+                        }
+                        LatencyModel::Normal => {
+                            changed |= Slider::new(im_str!("mean latency ms"), 0.0..=500.0)
+                                .build(ui, &mut settings.latency_mean);
+                            changed |= Slider::new(im_str!("latency stddev ms"), 0.0..=200.0)
+                                .build(ui, &mut settings.latency_stddev);
+                        }
+                        LatencyModel::Pareto => {
+                            changed |= Slider::new(im_str!("pareto scale ms"), 1.0..=500.0)
+                                .build(ui, &mut settings.latency_pareto_scale);
+                            changed |= Slider::new(im_str!("pareto shape"), 0.5..=10.0)
+                                .build(ui, &mut settings.latency_pareto_shape);
+                            changed |= Slider::new(im_str!("pareto max latency ms"), 1.0..=5000.0)
+                                .build(ui, &mut settings.latency_pareto_max);
+                        }
+                    }
+                    changed |= ui.checkbox(im_str!("burst loss"), &mut settings.burst_loss);
+                    if settings.burst_loss {
+                        changed |= Slider::new(im_str!("good→bad probability"), 0.0..=1.0)
+                            .build(ui, &mut settings.burst_good_to_bad);
+                        changed |= Slider::new(im_str!("bad→good probability"), 0.0..=1.0)
+                            .build(ui, &mut settings.burst_bad_to_good);
+                        changed |= Slider::new(im_str!("good state loss"), 0.0..=1.0)
+                            .build(ui, &mut settings.burst_good_loss);
+                        changed |= Slider::new(im_str!("bad state loss"), 0.0..=1.0)
+                            .build(ui, &mut settings.burst_bad_loss);
+                        let (loss, burst) = burst_loss_stats(&settings);
+                        ui.text(match burst {
+                            Some(burst) => format!(
+                                "mean loss {:.1}%, bad state lasts {:.1} packets",
+                                loss * 100.,
+                                burst
+                            ),
+                            None => format!("mean loss {:.1}%, bad state never ends", loss * 100.),
+                        });
+                    } else {
+                        changed |= Slider::new(im_str!("loss percentage"), 0.0..=1.0)
+                            .build(ui, &mut settings.loss_percentage);
+                    }
+                    changed |= Slider::new(im_str!("skipped send percentage"), 0.0..=1.0)
+                        .build(ui, &mut settings.send_skip_percentage);
+                    let mut policy_idx = RETRANSMIT_POLICIES
+                        .iter()
+                        .position(|p| *p == settings.retransmit_policy)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("retransmit policy")).build_simple(
+                        ui,
+                        &mut policy_idx,
+                        &RETRANSMIT_POLICIES,
+                        &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                    ) {
+                        changed = true;
+                        settings.retransmit_policy = RETRANSMIT_POLICIES[policy_idx];
+                    }
+                    changed |= Slider::new(im_str!("retransmit timeout ms"), 10.0..=1000.0)
+                        .build(ui, &mut settings.retransmit_timeout);
+                    let mut input_idx = INPUT_DELIVERIES
+                        .iter()
+                        .position(|d| *d == settings.input_delivery)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("input delivery")).build_simple(
+                        ui,
+                        &mut input_idx,
+                        &INPUT_DELIVERIES,
+                        &|d| std::borrow::Cow::Owned(ImString::new(d.to_string())),
+                    ) {
+                        changed = true;
+                        settings.input_delivery = INPUT_DELIVERIES[input_idx];
+                    }
+                    if settings.input_delivery == InputDelivery::Redundant {
+                        let mut redundancy = settings.input_redundancy as i32;
+                        if Slider::new(im_str!("input redundancy"), 1..=16)
+                            .build(ui, &mut redundancy)
+                        {
+                            changed = true;
+                            settings.input_redundancy = redundancy as u32;
+                        }
+                    }
+                    let mut codec_idx = SYNC_CODECS
+                        .iter()
+                        .position(|c| *c == settings.codec)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("sync codec")).build_simple(
+                        ui,
+                        &mut codec_idx,
+                        &SYNC_CODECS,
+                        &|c| std::borrow::Cow::Owned(ImString::new(c.to_string())),
+                    ) {
+                        changed = true;
+                        settings.codec = SYNC_CODECS[codec_idx];
+                    }
+                    if settings.codec == SyncCodec::Varint {
+                        let mut interval = settings.keyframe_interval as i32;
+                        if Slider::new(im_str!("keyframe interval"), 1..=30)
+                            .build(ui, &mut interval)
+                        {
+                            changed = true;
+                            settings.keyframe_interval = interval as u32;
+                        }
+                        changed |=
+                            ui.checkbox(im_str!("entropy coding"), &mut settings.entropy_coding);
+                    }
+                    let mut teleport_idx = TELEPORT_POLICIES
+                        .iter()
+                        .position(|p| *p == settings.teleport_policy)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("teleport policy")).build_simple(
+                        ui,
+                        &mut teleport_idx,
+                        &TELEPORT_POLICIES,
+                        &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                    ) {
+                        changed = true;
+                        settings.teleport_policy = TELEPORT_POLICIES[teleport_idx];
+                    }
+                    let mut filter_idx = FILTER_KINDS
+                        .iter()
+                        .position(|f| *f == settings.filter)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("client filter")).build_simple(
+                        ui,
+                        &mut filter_idx,
+                        &FILTER_KINDS,
+                        &|f| std::borrow::Cow::Owned(ImString::new(f.to_string())),
+                    ) {
+                        filter_changed = true;
+                        settings.filter = FILTER_KINDS[filter_idx];
                     }
-                    changed |= Slider::new(im_str!("min latency ms"), 0.0..=500.0)
-                        .build(ui, &mut settings.min_latency);
-                    if settings.min_latency > settings.max_latency {
-                        settings.max_latency = settings.min_latency;
+                    match settings.filter {
+                        FilterKind::None => {}
+                        FilterKind::Exponential => {
+                            filter_changed |= Slider::new(im_str!("filter alpha"), 0.01..=1.0)
+                                .build(ui, &mut settings.filter_alpha);
+                        }
+                        FilterKind::DoubleExponential => {
+                            filter_changed |= Slider::new(im_str!("filter alpha"), 0.01..=1.0)
+                                .build(ui, &mut settings.filter_alpha);
+                            filter_changed |= Slider::new(im_str!("filter beta"), 0.01..=1.0)
+                                .build(ui, &mut settings.filter_beta);
+                        }
+                        FilterKind::Kalman => {
+                            filter_changed |= Slider::new(im_str!("process noise"), 1.0..=100000.0)
+                                .power(4.)
+                                .build(ui, &mut settings.kalman_process_noise);
+                            filter_changed |=
+                                Slider::new(im_str!("measurement noise"), 0.1..=1000.0)
+                                    .power(4.)
+                                    .build(ui, &mut settings.kalman_measurement_noise);
+                        }
+                        FilterKind::OneEuro => {
+                            filter_changed |= Slider::new(im_str!("1€ min cutoff Hz"), 0.01..=30.0)
+                                .power(3.)
+                                .build(ui, &mut settings.one_euro_min_cutoff);
+                            filter_changed |= Slider::new(im_str!("1€ beta"), 0.0..=1.0)
+                                .power(4.)
+                                .build(ui, &mut settings.one_euro_beta);
+                        }
                     }
-                    changed |= Slider::new(im_str!("max latency ms"), 0.0..=500.0)
-                        .build(ui, &mut settings.max_latency);
-                    if settings.min_latency > settings.max_latency {
-                        settings.min_latency = settings.max_latency;
+                    changed |= Slider::new(im_str!("correction dead-band"), 0.0..=50.0)
+                        .build(ui, &mut settings.correction_deadband);
+                    if settings.correction_deadband > 0. {
+                        changed |= Slider::new(im_str!("dead-band hysteresis"), 0.0..=1.0)
+                            .build(ui, &mut settings.correction_hysteresis);
                     }
-                    changed |= Slider::new(im_str!("loss percentage"), 0.0..=1.0)
-                        .build(ui, &mut settings.loss_percentage);
+                    changed |= ui.checkbox(im_str!("peer to peer"), &mut settings.peer_to_peer);
+                    if settings.peer_to_peer {
+                        let mut input_delay = settings.input_delay as i32;
+                        if Slider::new(im_str!("input delay frames"), 0..=10)
+                            .build(ui, &mut input_delay)
+                        {
+                            changed = true;
+                            settings.input_delay = input_delay as u32;
+                        }
+                        let mut delay_policy_idx = INPUT_DELAY_POLICIES
+                            .iter()
+                            .position(|p| *p == settings.input_delay_policy)
+                            .unwrap_or(0);
+                        if ComboBox::new(im_str!("input delay policy")).build_simple(
+                            ui,
+                            &mut delay_policy_idx,
+                            &INPUT_DELAY_POLICIES,
+                            &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                        ) {
+                            changed = true;
+                            settings.input_delay_policy = INPUT_DELAY_POLICIES[delay_policy_idx];
+                        }
+                        if settings.input_delay_policy == InputDelayPolicy::Negotiated {
+                            changed |= Slider::new(im_str!("renegotiation interval s"), 0.1..=5.0)
+                                .build(ui, &mut settings.renegotiation_interval);
+                        }
+                        let mut topology_idx = TOPOLOGIES
+                            .iter()
+                            .position(|t| *t == settings.topology)
+                            .unwrap_or(0);
+                        if ComboBox::new(im_str!("topology")).build_simple(
+                            ui,
+                            &mut topology_idx,
+                            &TOPOLOGIES,
+                            &|t| std::borrow::Cow::Owned(ImString::new(t.to_string())),
+                        ) {
+                            changed = true;
+                            settings.topology = TOPOLOGIES[topology_idx];
+                        }
+                        if !settings.latency_matrix.is_empty() {
+                            ui.text(format!(
+                                "{} peers from latency matrix",
+                                settings.latency_matrix.len()
+                            ));
+                        }
+                        let mut max_prediction = settings.max_prediction as i32;
+                        if Slider::new(im_str!("max prediction frames"), 1..=20)
+                            .build(ui, &mut max_prediction)
+                        {
+                            changed = true;
+                            settings.max_prediction = max_prediction as u32;
+                        }
+                        changed |= Slider::new(im_str!("peer start offset ms"), 0.0..=500.0)
+                            .build(ui, &mut settings.peer_start_offset);
+                    }
+                    changed |= Slider::new(im_str!("fast-forward"), 1.0..=16.0)
+                        .build(ui, &mut settings.fast_forward);
                     changed |= Slider::new(im_str!("sim duration"), 0.1..=5.0)
                         .build(ui, &mut settings.duration);
+                    changed |= Slider::new(im_str!("warm-up s"), 0.0..=2.0)
+                        .build(ui, &mut settings.warm_up);
+                    if ui.collapsing_header(im_str!("linked settings")).build() {
+                        for default in SETTINGS_LINKS.iter() {
+                            let idx = settings.links.iter().position(|l| l.same_fields(default));
+                            let mut linked = idx.is_some();
+                            if ui.checkbox(&ImString::new(default.to_string()), &mut linked) {
+                                changed = true;
+                                match idx {
+                                    Some(idx) => {
+                                        settings.links.remove(idx);
+                                    }
+                                    None => settings.links.push(*default),
+                                }
+                            }
+                            if let Some(link) =
+                                settings.links.iter_mut().find(|l| l.same_fields(default))
+                            {
+                                ui.same_line(0.);
+                                changed |= Slider::new(
+                                    &ImString::new(format!("n##{}", default)),
+                                    0.5..=8.0,
+                                )
+                                .build(ui, link.factor_mut());
+                            }
+                        }
+                    }
+                    let mut compensation_idx = COMPENSATION_POLICIES
+                        .iter()
+                        .position(|p| *p == settings.compensation)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("client compensation")).build_simple(
+                        ui,
+                        &mut compensation_idx,
+                        &COMPENSATION_POLICIES,
+                        &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                    ) {
+                        changed = true;
+                        settings.compensation = COMPENSATION_POLICIES[compensation_idx];
+                    }
+                    let mut reference_idx = ERROR_REFERENCES
+                        .iter()
+                        .position(|r| *r == settings.error_reference)
+                        .unwrap_or(0);
+                    if ComboBox::new(im_str!("error reference")).build_simple(
+                        ui,
+                        &mut reference_idx,
+                        &ERROR_REFERENCES,
+                        &|r| std::borrow::Cow::Owned(ImString::new(r.to_string())),
+                    ) {
+                        changed = true;
+                        settings.error_reference = ERROR_REFERENCES[reference_idx];
+                    }
                     let toggle_playing = if settings.playing {
                         ui.small_button(im_str!("Pause"))
                     } else {
@@ -108,30 +926,773 @@ impl<'s> amethyst::ecs::System<'s> for GuiSystem {
                     };
                     if toggle_playing {
                         settings.playing = !settings.playing;
+                        if settings.playing && settings.curr_time >= max_time {
+                            settings.curr_time = min_time;
+                        }
                     }
                     let current_id = settings.behaviour.type_id();
-                    let mut selected_idx = crate::sim_behaviours::SIM_BEHAVIOURS
+                    let mut selected_idx = network_sim::sim_behaviours::SIM_BEHAVIOURS
                         .iter()
                         .position(|x| x.0.type_id() == current_id)
                         .unwrap_or(0);
                     if ComboBox::new(im_str!("Mode")).build_simple(
                         ui,
                         &mut selected_idx,
-                        &crate::sim_behaviours::SIM_BEHAVIOURS,
+                        &network_sim::sim_behaviours::SIM_BEHAVIOURS,
                         &|x| unsafe {
                             std::borrow::Cow::Borrowed(ImStr::from_cstr_unchecked(x.1.as_c_str()))
                         },
                     ) {
                         changed = true;
-                        settings.behaviour = crate::sim_behaviours::SIM_BEHAVIOURS[selected_idx]
+                        settings.behaviour = network_sim::sim_behaviours::SIM_BEHAVIOURS
+                            [selected_idx]
                             .0
                             .clone();
                     }
-                    if changed {
-                        let new_sim = run_simulation(&settings).unwrap();
+                    ui.same_line(0.);
+                    if ui.small_button(im_str!("Tour")) {
+                        self.tour_step = Some(0);
+                        *settings = TOUR.steps[0].apply(&settings);
+                        changed = true;
+                    }
+                    if ui.collapsing_header(im_str!("about this mode")).build() {
+                        ui.text_wrapped(&ImString::new(settings.behaviour.description()));
+                    }
+                    if is_composed(settings.behaviour.as_ref())
+                        && ui
+                            .collapsing_header(im_str!("pipeline"))
+                            .default_open(true)
+                            .build()
+                    {
+                        let mut raise = None;
+                        let mut remove = None;
+                        for (idx, stage) in settings.pipeline.iter_mut().enumerate() {
+                            match stage {
+                                PipelineStage::Buffer { delay } => {
+                                    let label = format!("{}. buffer delay ms##stage", idx + 1);
+                                    changed |= Slider::new(&ImString::new(label), 0.0..=500.0)
+                                        .build(ui, delay);
+                                }
+                                PipelineStage::Predict => ui.text(format!("{}. predict", idx + 1)),
+                                PipelineStage::Reconcile { deadband } => {
+                                    let label = format!("{}. reconcile dead-band##stage", idx + 1);
+                                    changed |= Slider::new(&ImString::new(label), 0.0..=1.0)
+                                        .build(ui, deadband);
+                                }
+                                PipelineStage::Smooth { window } => {
+                                    let label = format!("{}. smoothing window ms##stage", idx + 1);
+                                    changed |= Slider::new(&ImString::new(label), 0.0..=500.0)
+                                        .build(ui, window);
+                                }
+                                PipelineStage::Filter { kind } => {
+                                    let mut kind_idx =
+                                        FILTER_KINDS.iter().position(|f| f == kind).unwrap_or(0);
+                                    let label = format!("{}. filter##stage", idx + 1);
+                                    if ComboBox::new(&ImString::new(label)).build_simple(
+                                        ui,
+                                        &mut kind_idx,
+                                        &FILTER_KINDS,
+                                        &|f| std::borrow::Cow::Owned(ImString::new(f.to_string())),
+                                    ) {
+                                        changed = true;
+                                        *kind = FILTER_KINDS[kind_idx];
+                                    }
+                                }
+                            }
+                            if idx > 0 {
+                                ui.same_line(0.);
+                                if ui.small_button(&ImString::new(format!("up##stage{}", idx))) {
+                                    raise = Some(idx);
+                                }
+                            }
+                            ui.same_line(0.);
+                            if ui.small_button(&ImString::new(format!("x##stage{}", idx))) {
+                                remove = Some(idx);
+                            }
+                        }
+                        if let Some(idx) = raise {
+                            settings.pipeline.swap(idx - 1, idx);
+                            changed = true;
+                        }
+                        if let Some(idx) = remove {
+                            settings.pipeline.remove(idx);
+                            changed = true;
+                        }
+                        ComboBox::new(im_str!("##new stage")).build_simple(
+                            ui,
+                            &mut self.new_stage,
+                            &PIPELINE_STAGES,
+                            &|s| std::borrow::Cow::Owned(ImString::new(s.to_string())),
+                        );
+                        ui.same_line(0.);
+                        if ui.small_button(im_str!("Add stage")) {
+                            settings.pipeline.push(PIPELINE_STAGES[self.new_stage]);
+                            changed = true;
+                        }
+                    }
+                    if settings.behaviour.to_string()
+                        == network_sim::sim_behaviours::SPAWNING_ENTITIES
+                    {
+                        let mut count = settings.entity_count as i32;
+                        if Slider::new(im_str!("entity count"), 1..=1000)
+                            .power(3.)
+                            .build(ui, &mut count)
+                        {
+                            changed = true;
+                            settings.entity_count = count as u32;
+                        }
+                    }
+                    if settings.behaviour.to_string()
+                        == network_sim::sim_behaviours::DOOR_INTERACTION
+                    {
+                        let mut prediction_idx = INTERACTION_PREDICTIONS
+                            .iter()
+                            .position(|p| *p == settings.interaction_prediction)
+                            .unwrap_or(0);
+                        if ComboBox::new(im_str!("interaction")).build_simple(
+                            ui,
+                            &mut prediction_idx,
+                            &INTERACTION_PREDICTIONS,
+                            &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                        ) {
+                            changed = true;
+                            settings.interaction_prediction =
+                                INTERACTION_PREDICTIONS[prediction_idx];
+                        }
+                    }
+                    if settings.behaviour.to_string()
+                        == network_sim::sim_behaviours::ANIMATION_STATES
+                    {
+                        let mut policy_idx = ANIMATION_POLICIES
+                            .iter()
+                            .position(|p| *p == settings.animation_policy)
+                            .unwrap_or(0);
+                        if ComboBox::new(im_str!("animation switch")).build_simple(
+                            ui,
+                            &mut policy_idx,
+                            &ANIMATION_POLICIES,
+                            &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                        ) {
+                            changed = true;
+                            settings.animation_policy = ANIMATION_POLICIES[policy_idx];
+                        }
+                        if settings.animation_policy == AnimationPolicy::Crossfade {
+                            changed |= Slider::new(im_str!("crossfade window ms"), 0.0..=500.0)
+                                .build(ui, &mut settings.crossfade_window);
+                        }
+                    }
+                    if ui.collapsing_header(im_str!("style")).build() {
+                        let style = &mut settings.style;
+                        let mut palette_idx = PALETTES
+                            .iter()
+                            .position(|p| *p == style.palette)
+                            .unwrap_or(0);
+                        if ComboBox::new(im_str!("palette")).build_simple(
+                            ui,
+                            &mut palette_idx,
+                            &PALETTES,
+                            &|p| std::borrow::Cow::Owned(ImString::new(p.to_string())),
+                        ) {
+                            *style = PALETTES[palette_idx].style();
+                        }
+                        ColorEdit::new(im_str!("server color"), &mut style.server).build(ui);
+                        ColorEdit::new(im_str!("client color"), &mut style.client).build(ui);
+                        ColorEdit::new(im_str!("ghost color"), &mut style.ghost).build(ui);
+                        ColorEdit::new(im_str!("unfiltered color"), &mut style.unfiltered)
+                            .build(ui);
+                        ui.checkbox(im_str!("color entities"), &mut style.color_entities);
+                        if style.color_entities {
+                            for (i, color) in style.entities.iter_mut().enumerate() {
+                                let label = ImString::new(format!("entity color {}", i));
+                                ColorEdit::new(&label, color).build(ui);
+                            }
+                        }
+                        Slider::new(im_str!("marker scale"), 0.25..=4.0)
+                            .build(ui, &mut style.marker_scale);
+                        Slider::new(im_str!("line width"), 1.0..=5.0)
+                            .build(ui, &mut style.line_width);
+                    }
+                    if ui.collapsing_header(im_str!("curves")).build() {
+                        let curves = &mut settings.curves;
+                        ui.checkbox(im_str!("server"), &mut curves.server);
+                        ui.checkbox(im_str!("client"), &mut curves.client);
+                        ui.checkbox(im_str!("ghosts"), &mut curves.ghosts);
+                        ui.checkbox(im_str!("unfiltered client"), &mut curves.unfiltered);
+                        ui.checkbox(im_str!("level of detail"), &mut settings.lod);
+                        ui.checkbox(im_str!("point cloud"), &mut settings.point_cloud);
+                    }
+                    let entities: BTreeSet<u32> =
+                        sim.frames.iter().filter_map(|f| f.entity).collect();
+                    if !entities.is_empty() && ui.collapsing_header(im_str!("entities")).build() {
+                        for entity in entities {
+                            let mut visible = !settings.hidden_entities.contains(&entity);
+                            let label = ImString::new(format!("entity {}", entity));
+                            if ui.checkbox(&label, &mut visible) {
+                                if visible {
+                                    settings.hidden_entities.remove(&entity);
+                                } else {
+                                    settings.hidden_entities.insert(entity);
+                                }
+                            }
+                        }
+                    }
+                    let labelled: Vec<(f32, SimSide, String)> = sim
+                        .frames
+                        .iter()
+                        .filter_map(|f| f.label.clone().map(|l| (f.render_time, f.side, l)))
+                        .collect();
+                    if !labelled.is_empty() && ui.collapsing_header(im_str!("frame labels")).build()
+                    {
+                        for (i, (time, side, label)) in labelled.iter().enumerate() {
+                            let label = ImString::new(format!(
+                                "{:.3}s {:?}: {}##label{}",
+                                time, side, label, i
+                            ));
+                            if ui.small_button(&label) {
+                                settings.curr_time = *time;
+                            }
+                        }
+                    }
+                    if !sim.debug_states.is_empty()
+                        && ui.collapsing_header(im_str!("inspector")).build()
+                    {
+                        match sim.debug_state_at(settings.curr_time) {
+                            Some(debug) => {
+                                ui.text(format!("client state at {:.3}s", debug.time));
+                                ui.text(
+                                    serde_json::to_string_pretty(&debug.state).unwrap_or_default(),
+                                );
+                            }
+                            None => ui.text("no state before the playhead"),
+                        }
+                    }
+                    let anomalies = [
+                        ("largest error", "error", ""),
+                        ("largest pop", "pop", ""),
+                        ("longest underrun", "underrun", " s"),
+                    ];
+                    if ui.collapsing_header(im_str!("anomalies")).build() {
+                        for (label, name, unit) in anomalies.iter() {
+                            match sim.metrics.get(name).and_then(|c| c.peak()) {
+                                Some((time, value)) => {
+                                    let label = ImString::new(format!(
+                                        "{} {:.3}{} at {:.3}s",
+                                        label, value, unit, time
+                                    ));
+                                    if ui.small_button(&label) {
+                                        settings.curr_time = time;
+                                        settings.playing = false;
+                                    }
+                                }
+                                None => ui.text(format!("no {}", name)),
+                            }
+                        }
+                    }
+                    if ui.collapsing_header(im_str!("bookmarks")).build() {
+                        ui.input_text(im_str!("bookmark name"), &mut self.bookmark_name)
+                            .build();
+                        ui.same_line(0.);
+                        if ui.small_button(im_str!("Add at playhead")) {
+                            bookmarks
+                                .add(settings.curr_time, self.bookmark_name.to_str().to_owned());
+                            self.bookmark_name.clear();
+                        }
+                        let mut remove = None;
+                        for (i, bookmark) in bookmarks.0.iter().enumerate() {
+                            let label = ImString::new(format!(
+                                "{:.3}s {}##{}",
+                                bookmark.time, bookmark.name, i
+                            ));
+                            if ui.small_button(&label) {
+                                settings.curr_time = bookmark.time;
+                            }
+                            ui.same_line(0.);
+                            if ui.small_button(&ImString::new(format!("x##bookmark{}", i))) {
+                                remove = Some(i);
+                            }
+                        }
+                        if let Some(i) = remove {
+                            bookmarks.0.remove(i);
+                        }
+                    }
+                    if ui.collapsing_header(im_str!("compare behaviours")).build() {
+                        if ui.small_button(im_str!("Run every behaviour")) {
+                            let behaviours: Vec<_> = network_sim::sim_behaviours::SIM_BEHAVIOURS
+                                .iter()
+                                .map(|(b, _)| b.clone())
+                                .collect();
+                            match compare_behaviours(&settings, &behaviours) {
+                                Ok(rows) => self.comparison = rows,
+                                Err(e) => log::error!("failed to compare behaviours: {}", e),
+                            }
+                        }
+                        if !self.comparison.is_empty() {
+                            ui.columns(
+                                COMPARISON_COLUMNS.len() as i32,
+                                im_str!("comparison"),
+                                true,
+                            );
+                            for column in COMPARISON_COLUMNS.iter() {
+                                ui.text(column);
+                                ui.next_column();
+                            }
+                            for row in self.comparison.iter() {
+                                for cell in row.cells() {
+                                    ui.text(cell);
+                                    ui.next_column();
+                                }
+                            }
+                            ui.columns(1, im_str!("comparison"), false);
+                            ui.input_text(im_str!("comparison file"), &mut self.comparison_path)
+                                .build();
+                            ui.same_line(0.);
+                            if ui.small_button(im_str!("Export CSV")) {
+                                let path = Path::new(self.comparison_path.to_str());
+                                if let Err(e) = write_csv(path, &self.comparison) {
+                                    log::error!("failed to export comparison: {}", e);
+                                }
+                            }
+                        }
+                    }
+                    ui.input_text(im_str!("session file"), &mut self.session_path)
+                        .build();
+                    if ui.small_button(im_str!("Save session")) {
+                        let session = Session {
+                            settings: (*settings).clone(),
+                            bookmarks: bookmarks.0.clone(),
+                        };
+                        if let Err(e) = session.save(Path::new(self.session_path.to_str())) {
+                            log::error!("failed to save session: {}", e);
+                        }
+                    }
+                    ui.same_line(0.);
+                    if ui.small_button(im_str!("Load session")) {
+                        match Session::load(Path::new(self.session_path.to_str())) {
+                            Ok(session) => {
+                                *settings = session.settings;
+                                bookmarks.0 = session.bookmarks;
+                                changed = true;
+                            }
+                            Err(e) => log::error!("failed to load session: {}", e),
+                        }
+                    }
+                    ui.input_text(im_str!("capture file"), &mut self.capture_path)
+                        .build();
+                    if ui.small_button(im_str!("Import capture")) {
+                        let path = Path::new(self.capture_path.to_str());
+                        match read_capture(path).and_then(|records| {
+                            analyze_capture(&settings, capture_result(&records))
+                        }) {
+                            Ok(capture) => {
+                                *sim = capture;
+                                // the next change of the settings runs the simulation again
+                                self.raw = None;
+                                settings.curr_time = 0.;
+                                let run = self.log_runs.last().map(|r| r.0 + 1).unwrap_or(1);
+                                self.log_runs.push((
+                                    run,
+                                    format!("capture {}", path.display()),
+                                    sim.log.clone(),
+                                ));
+                                if self.log_runs.len() > LOG_RUNS {
+                                    self.log_runs.remove(0);
+                                }
+                                self.log_run = self.log_runs.len() - 1;
+                            }
+                            Err(e) => log::error!("failed to import capture: {}", e),
+                        }
+                    }
+                    ui.input_int(im_str!("live port"), &mut self.live_port)
+                        .build();
+                    ui.same_line(0.);
+                    if self.live.is_none() {
+                        if ui.small_button(im_str!("Listen")) {
+                            match LiveCapture::bind(self.live_port as u16) {
+                                Ok(live) => {
+                                    self.live = Some(live);
+                                    self.raw = None;
+                                    self.live_pending = false;
+                                    self.live_refreshed = None;
+                                }
+                                Err(e) => log::error!("failed to listen for live capture: {}", e),
+                            }
+                        }
+                    } else if ui.small_button(im_str!("Stop listening")) {
+                        self.live = None;
+                    }
+                    if let Some(live) = &self.live {
+                        ui.same_line(0.);
+                        ui.text(format!("{} live records", live.len()));
+                        ui.same_line(0.);
+                        ui.checkbox(im_str!("follow live"), &mut self.live_follow);
+                    }
+                    ui.same_line(0.);
+                    ui.checkbox(
+                        &ImString::new(format!("log console ({})##log", sim.log.len())),
+                        &mut self.show_log,
+                    );
+                    ui.text(format!(
+                        "packets: {} sync, {} retransmitted, {} acks, {} inputs",
+                        sim.packet_count(SimSide::Server, PacketKind::Sync),
+                        sim.packet_count(SimSide::Server, PacketKind::Retransmit),
+                        sim.packet_count(SimSide::Client, PacketKind::Ack),
+                        sim.packet_count(SimSide::Client, PacketKind::Input),
+                    ));
+                    ui.text(format!(
+                        "analyzed window: {:.2} s to {:.2} s",
+                        sim.window.0, sim.window.1
+                    ));
+                    if let Some(result_bytes) = sim.metrics.get("result bytes") {
+                        let peak_buffers = sim.metrics.get("peak buffer bytes");
+                        ui.text(format!(
+                            "memory: results {:.1} MiB, interpolation buffers {:.1} KiB",
+                            result_bytes.max() / (1024. * 1024.),
+                            peak_buffers.map_or(0., |c| c.max()) / 1024.
+                        ));
+                    }
+                    if let Some(budget) = sim.latency_budget_at(&settings, settings.curr_time) {
+                        if ui.collapsing_header(im_str!("latency budget")).build() {
+                            let total = budget.total();
+                            ui.text(format!("perceived delay at playhead: {:.1} ms", total));
+                            let bar_min = ui.get_cursor_screen_pos();
+                            ui.invisible_button(
+                                im_str!("latency budget bar"),
+                                [ITEM_WIDTH, BUDGET_BAR_HEIGHT],
+                            );
+                            let draw_list = ui.get_window_draw_list();
+                            let mut x = bar_min[0];
+                            for ((_, ms), color) in budget.terms().iter().zip(BUDGET_COLORS.iter())
+                            {
+                                let width = ms.max(0.) / total.max(std::f32::EPSILON) * ITEM_WIDTH;
+                                draw_list
+                                    .add_rect(
+                                        [x, bar_min[1]],
+                                        [x + width, bar_min[1] + BUDGET_BAR_HEIGHT],
+                                        *color,
+                                    )
+                                    .filled(true)
+                                    .build();
+                                x += width;
+                            }
+                            for ((name, ms), color) in
+                                budget.terms().iter().zip(BUDGET_COLORS.iter())
+                            {
+                                ui.text_colored(
+                                    *color,
+                                    ImString::new(format!(
+                                        "{}: {:.1} ms ({:.0}%)",
+                                        name,
+                                        ms,
+                                        ms / total.max(std::f32::EPSILON) * 100.
+                                    )),
+                                );
+                            }
+                        }
+                    }
+                    if let Some(payload) = sim.metrics.get("payload bytes") {
+                        if ui.collapsing_header(im_str!("bandwidth")).build() {
+                            let mean = |name| sim.metrics.get(name).map(|c| c.mean()).unwrap_or(0.);
+                            ui.text(format!(
+                                "{} payload: mean {:.1} bytes, max {:.0} bytes",
+                                settings.codec,
+                                payload.mean(),
+                                payload.max()
+                            ));
+                            let bincode = mean("bincode payload bytes");
+                            if bincode > 0. {
+                                ui.text(format!(
+                                    "{:.0}% of bincode's {:.1} bytes",
+                                    payload.mean() / bincode * 100.,
+                                    bincode
+                                ));
+                            }
+                            if let Some(bits) = sim.metrics.get("bits per field") {
+                                ui.text(format!(
+                                    "{:.2} bits per field, {:.2} at most",
+                                    bits.mean(),
+                                    bits.max()
+                                ));
+                            }
+                            // fields are named by their path, parents sort before their fields
+                            for (name, channel) in sim.metrics.iter() {
+                                if name.starts_with(FIELD_BYTES_PREFIX) {
+                                    let path = &name[FIELD_BYTES_PREFIX.len()..];
+                                    let depth = path.matches('.').count();
+                                    ui.text(format!(
+                                        "{}{}: mean {:.1} bytes, {:.0} bytes total",
+                                        "  ".repeat(depth),
+                                        path,
+                                        channel.mean(),
+                                        channel.mean() * channel.len() as f32
+                                    ));
+                                }
+                            }
+                            if let Some(missing) = sim.metrics.get("missing keyframes") {
+                                ui.text(format!(
+                                    "{} deltas dropped for a missing keyframe",
+                                    missing.len()
+                                ));
+                            }
+                            ui.text(format!(
+                                "sync message: mean {:.1} bytes, {:.0} bytes/s",
+                                mean("sync bytes"),
+                                mean("sync bytes") * settings.sync_rate as f32
+                            ));
+                            ui.text(format!(
+                                "encode {:.1} us, decode {:.1} us",
+                                mean("encode time"),
+                                mean("decode time")
+                            ));
+                        }
+                    }
+                    if let Some(corrections) = sim.metrics.get("correction") {
+                        if ui.collapsing_header(im_str!("corrections")).build() {
+                            let overlay = ImString::new(format!(
+                                "{} corrections, 0 - {:.1}",
+                                corrections.len(),
+                                corrections.max()
+                            ));
+                            ui.plot_histogram(
+                                im_str!("magnitude"),
+                                &corrections.histogram(CORRECTION_HISTOGRAM_BINS),
+                            )
+                            .graph_size([500., 80.])
+                            .overlay_text(&overlay)
+                            .build();
+                            let counts = corrections.count_over_time(CORRECTION_COUNT_INTERVAL);
+                            let overlay = ImString::new(format!(
+                                "max {} per {}s",
+                                counts.iter().cloned().fold(0., f32::max),
+                                CORRECTION_COUNT_INTERVAL
+                            ));
+                            ui.plot_lines(im_str!("count"), &counts)
+                                .graph_size([500., 60.])
+                                .overlay_text(&overlay)
+                                .build();
+                            if let Some(mispredictions) = sim.metrics.get("misprediction") {
+                                ui.text(format!(
+                                    "misprediction rate {:.1}% of {} reconciliations",
+                                    mispredictions.mean() * 100.,
+                                    mispredictions.len()
+                                ));
+                            }
+                        }
+                    }
+                    if let Some((from, to)) = self.selection {
+                        if ui
+                            .collapsing_header(im_str!("selection"))
+                            .default_open(true)
+                            .build()
+                        {
+                            ui.text(format!("{:.2} s to {:.2} s", from, to));
+                            ui.same_line(0.);
+                            if ui.small_button(im_str!("clear selection")) {
+                                self.selection = None;
+                            }
+                            let metrics = sim.metrics.window(from, to);
+                            let duration = (to - from).max(std::f32::EPSILON);
+                            if let Some(error) = metrics.get("error") {
+                                ui.text(format!(
+                                    "error: mean {:.2}, max {:.2}",
+                                    error.mean(),
+                                    error.max()
+                                ));
+                            }
+                            if let Some(pop) = metrics.get("pop") {
+                                ui.text(format!(
+                                    "pop: mean {:.2}, max {:.2}",
+                                    pop.mean(),
+                                    pop.max()
+                                ));
+                            }
+                            for name in ["sync bytes", "input bytes", "upstream bytes"].iter() {
+                                if let Some(bytes) = metrics.get(name) {
+                                    let total = bytes.mean() * bytes.len() as f32;
+                                    ui.text(format!(
+                                        "{}: {:.0} bytes, {:.0} bytes/s",
+                                        name,
+                                        total,
+                                        total / duration
+                                    ));
+                                }
+                            }
+                            if let Some(corrections) = metrics.get("correction") {
+                                ui.text(format!(
+                                    "{} corrections, max {:.2}",
+                                    corrections.len(),
+                                    corrections.max()
+                                ));
+                            }
+                            if ui.collapsing_header(im_str!("selected metrics")).build() {
+                                for (name, channel) in metrics.iter() {
+                                    ui.text(format!(
+                                        "{}: mean {:.3} min {:.3} max {:.3} ({} samples)",
+                                        name,
+                                        channel.mean(),
+                                        channel.min(),
+                                        channel.max(),
+                                        channel.len()
+                                    ));
+                                }
+                            }
+                        }
+                    }
+                    if !sim.metrics.is_empty() && ui.collapsing_header(im_str!("metrics")).build() {
+                        if let Some((from, to)) = self.plots.range {
+                            ui.text(format!("{:.2} s to {:.2} s", from, to));
+                            ui.same_line(0.);
+                            if ui.small_button(im_str!("reset zoom")) {
+                                self.plots.range = None;
+                            }
+                        } else {
+                            ui.text(
+                                "scroll a plot to zoom, drag to pan, click to move the playhead",
+                            );
+                        }
+                        for (name, channel) in sim.metrics.iter() {
+                            let overlay =
+                                format!("mean {:.3} max {:.3}", channel.mean(), channel.max());
+                            self.plots.plot(
+                                ui,
+                                &ImString::new(name.as_str()),
+                                channel,
+                                &overlay,
+                                (min_time, max_time),
+                                &mut settings.curr_time,
+                                self.selection,
+                            );
+                        }
+                    }
+                    if !self.settings_diff.is_empty() {
+                        ui.text(format!("changed: {}", self.settings_diff.join(", ")));
+                    }
+                    enforce(&mut settings, &prev_settings);
+                    let issues = validate(&settings);
+                    for issue in issues.iter() {
+                        let color = match issue.severity {
+                            Severity::Error => [1.0, 0.3, 0.3, 1.0],
+                            Severity::Warning => [1.0, 0.8, 0.2, 1.0],
+                        };
+                        ui.text_colored(color, ImString::new(issue.to_string()));
+                    }
+                    if (changed || filter_changed) && self.live.is_some() {
+                        // analyze the live capture again instead of running the simulation
+                        self.live_pending = true;
+                    } else if (changed || filter_changed) && !has_errors(&issues) {
+                        let new_sim = match &self.raw {
+                            Some(raw) if !changed => refilter(&settings, raw).unwrap(),
+                            _ => {
+                                let raw = self.pool.simulate(&settings).unwrap();
+                                let new_sim = analyze(&settings, raw.clone()).unwrap();
+                                self.raw = Some(raw);
+                                new_sim
+                            }
+                        };
                         *sim = new_sim;
+                        let diff = settings.diff(&self.last_run);
+                        let run = self.log_runs.last().map(|r| r.0 + 1).unwrap_or(1);
+                        let label = if diff.is_empty() {
+                            "no change".to_owned()
+                        } else {
+                            diff.join(", ")
+                        };
+                        self.log_runs.push((run, label, sim.log.clone()));
+                        if self.log_runs.len() > LOG_RUNS {
+                            self.log_runs.remove(0);
+                        }
+                        self.log_run = self.log_runs.len() - 1;
+                        if !diff.is_empty() {
+                            self.settings_diff = diff;
+                        }
+                        self.last_run = (*settings).clone();
                     }
                 });
+            if settings.viewer_frame_times {
+                let display_size = ui.io().display_size;
+                let frame_times = &self.frame_times;
+                let latest = (self.frame_time_idx + FRAME_TIME_HISTORY - 1) % FRAME_TIME_HISTORY;
+                let mean = frame_times.iter().sum::<f32>() / FRAME_TIME_HISTORY as f32;
+                let max = frame_times.iter().cloned().fold(0., f32::max);
+                let overlay = ImString::new(format!(
+                    "{:.1} ms, mean {:.1} ms, max {:.1} ms",
+                    frame_times[latest], mean, max
+                ));
+                Window::new(im_str!("viewer frame times"))
+                    .size([300., 100.], Condition::Once)
+                    .position([display_size[0] - 310., 10.], Condition::Once)
+                    .build(ui, || {
+                        ui.plot_lines(im_str!("##frame times"), frame_times)
+                            .values_offset(self.frame_time_idx)
+                            .graph_size([280., 60.])
+                            .scale_min(0.)
+                            .scale_max(max.max(FRAME_TIME_SCALE))
+                            .overlay_text(&overlay)
+                            .build();
+                    });
+            }
+            if self.show_log {
+                let mut show_log = true;
+                Window::new(im_str!("log"))
+                    .size([500., 300.], Condition::Once)
+                    .opened(&mut show_log)
+                    .build(ui, || {
+                        ComboBox::new(im_str!("run")).build_simple(
+                            ui,
+                            &mut self.log_run,
+                            &self.log_runs,
+                            &|(run, label, log)| {
+                                std::borrow::Cow::Owned(ImString::new(format!(
+                                    "#{} {} ({} messages)",
+                                    run,
+                                    label,
+                                    log.len()
+                                )))
+                            },
+                        );
+                        ui.input_text(im_str!("filter"), &mut self.log_filter)
+                            .build();
+                        let filter = self.log_filter.to_str();
+                        ChildWindow::new(im_str!("log messages"))
+                            .border(true)
+                            .build(ui, || {
+                                let log = self.log_runs.get(self.log_run).map(|r| &r.2);
+                                for (i, entry) in log.into_iter().flatten().enumerate() {
+                                    if !entry.message.contains(filter) {
+                                        continue;
+                                    }
+                                    let label = ImString::new(format!(
+                                        "{:.3}s {:?}: {}##log{}",
+                                        entry.time, entry.side, entry.message, i
+                                    ));
+                                    if ui.small_button(&label) {
+                                        settings.curr_time = entry.time;
+                                    }
+                                }
+                            });
+                    });
+                self.show_log = show_log;
+            }
+            let display_size = ui.io().display_size;
+            let draw_list = ui.get_foreground_draw_list();
+            for label in labels.0.iter() {
+                let pos = [
+                    label.pos[0] * display_size[0],
+                    (1. - label.pos[1]) * display_size[1],
+                ];
+                draw_list.add_text(pos, label.color, &label.text);
+            }
+            // dragging with the right mouse button outside the windows orbits the 3d view
+            if settings.view_3d
+                && ui.is_mouse_dragging(MouseButton::Right)
+                && !ui.io().want_capture_mouse
+            {
+                let delta = ui.io().mouse_delta;
+                settings.orbit_yaw =
+                    (settings.orbit_yaw - delta[0] * ORBIT_SENSITIVITY + 540.) % 360. - 180.;
+                settings.orbit_pitch = (settings.orbit_pitch + delta[1] * ORBIT_SENSITIVITY)
+                    .max(0.)
+                    .min(90.);
+            }
         });
     }
 }
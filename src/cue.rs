@@ -0,0 +1,106 @@
+//! Audible clicks when playback crosses a pop or an underrun, as stutter is often easier to
+//! notice by ear during a demo than in the plots
+use network_sim::sim::{Sample, SimSettings, SimulationResult};
+
+use amethyst::{
+    assets::{AssetStorage, Loader},
+    audio::{output::Output, AudioData, Source, SourceHandle},
+    ecs::{Read, ReadExpect},
+};
+use std::sync::{Arc, Mutex};
+
+const SAMPLE_RATE: u32 = 44100;
+/// Length of a click in seconds
+const CLICK_LENGTH: f32 = 0.015;
+/// Pitch in Hz of the click of a pop and of an underrun, so the two can be told apart
+const POP_PITCH: f32 = 2000.;
+const UNDERRUN_PITCH: f32 = 800.;
+const CLICK_VOLUME: f32 = 0.5;
+
+/// Mono 16-bit WAV file of a decaying tone of `pitch` Hz
+fn click_wav(pitch: f32) -> Vec<u8> {
+    let samples: Vec<i16> = (0..(SAMPLE_RATE as f32 * CLICK_LENGTH) as u32)
+        .map(|i| {
+            let t = i as f32 / SAMPLE_RATE as f32;
+            let envelope = (-t / CLICK_LENGTH * 5.).exp();
+            ((t * pitch * std::f32::consts::PI * 2.).sin() * envelope * std::i16::MAX as f32) as i16
+        })
+        .collect();
+    let data_len = samples.len() as u32 * 2;
+    let mut wav = Vec::with_capacity(44 + data_len as usize);
+    wav.extend_from_slice(b"RIFF");
+    wav.extend_from_slice(&(36 + data_len).to_le_bytes());
+    wav.extend_from_slice(b"WAVEfmt ");
+    wav.extend_from_slice(&16u32.to_le_bytes());
+    // PCM, one channel
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&1u16.to_le_bytes());
+    wav.extend_from_slice(&SAMPLE_RATE.to_le_bytes());
+    wav.extend_from_slice(&(SAMPLE_RATE * 2).to_le_bytes());
+    wav.extend_from_slice(&2u16.to_le_bytes());
+    wav.extend_from_slice(&16u16.to_le_bytes());
+    wav.extend_from_slice(b"data");
+    wav.extend_from_slice(&data_len.to_le_bytes());
+    for sample in samples {
+        wav.extend_from_slice(&sample.to_le_bytes());
+    }
+    wav
+}
+
+/// Plays a click for the pops over `SimSettings::pop_cue_threshold` and the underruns the
+/// playhead passed since the previous frame while playing. At most one click of each kind per
+/// frame, so dense artifacts don't turn into noise
+#[derive(Default)]
+pub struct AudioCueSystem {
+    clicks: Option<(SourceHandle, SourceHandle)>,
+    /// Run and playhead time of the previous frame
+    last: Option<(u64, f32)>,
+}
+impl<'s> amethyst::ecs::System<'s> for AudioCueSystem {
+    type SystemData = (
+        ReadExpect<'s, Arc<Mutex<SimulationResult<Sample>>>>,
+        ReadExpect<'s, SimSettings>,
+        ReadExpect<'s, Loader>,
+        Read<'s, AssetStorage<Source>>,
+        Option<Read<'s, Output>>,
+    );
+    fn run(&mut self, (sim, settings, loader, sources, output): Self::SystemData) {
+        let sim = sim.lock().unwrap();
+        let last = self.last.replace((sim.run, settings.curr_time));
+        let output = match output {
+            Some(output) if settings.audio_cues && settings.playing => output,
+            _ => return,
+        };
+        // nothing was crossed on the first frame of a run or when playback wrapped around
+        let from = match last {
+            Some((run, t)) if run == sim.run && t < settings.curr_time => t,
+            _ => return,
+        };
+        let to = settings.curr_time;
+        let (pop_click, underrun_click) = self.clicks.get_or_insert_with(|| {
+            (
+                loader.load_from_data(AudioData(click_wav(POP_PITCH)), (), &sources),
+                loader.load_from_data(AudioData(click_wav(UNDERRUN_PITCH)), (), &sources),
+            )
+        });
+        let crossed = |name: &str, threshold: f32| {
+            sim.metrics.get(name).map_or(false, |channel| {
+                channel
+                    .times
+                    .iter()
+                    .zip(channel.values.iter())
+                    .any(|(t, v)| *t > from && *t <= to && *v >= threshold)
+            })
+        };
+        if crossed("pop", settings.pop_cue_threshold) {
+            if let Some(click) = sources.get(pop_click) {
+                output.play_once(click, CLICK_VOLUME);
+            }
+        }
+        if crossed("underrun", 0.) {
+            if let Some(click) = sources.get(underrun_click) {
+                output.play_once(click, CLICK_VOLUME);
+            }
+        }
+    }
+}
@@ -0,0 +1,244 @@
+//! Canonical Huffman coding of bytes, for the entropy coding stage of `codec::DeltaEncoder`
+use crate::protobuf::{read_varint, write_varint};
+use std::{cmp::Reverse, collections::BinaryHeap};
+
+/// Symbol of bytes without a code, followed by the byte's 8 bits
+const ESCAPE: usize = 256;
+const SYMBOLS: usize = 257;
+const MAX_CODE_LENGTH: u8 = 15;
+
+/// Number of occurrences of every byte, to build a `HuffmanTable` from
+#[derive(Clone)]
+pub struct ByteCounts([u32; SYMBOLS]);
+impl Default for ByteCounts {
+    fn default() -> Self {
+        Self([0; SYMBOLS])
+    }
+}
+impl ByteCounts {
+    pub fn add(&mut self, bytes: &[u8]) {
+        for b in bytes {
+            self.0[*b as usize] += 1;
+        }
+    }
+}
+
+/// Code lengths of a Huffman code over the symbols with a non-zero count
+fn code_lengths(counts: &[u32; SYMBOLS]) -> [u8; SYMBOLS] {
+    let mut counts = *counts;
+    loop {
+        let mut lengths = [0; SYMBOLS];
+        // leaves are the symbols, followed by the merged nodes
+        let mut parents: Vec<usize> = vec![0; SYMBOLS];
+        let mut heap: BinaryHeap<_> = (0..SYMBOLS)
+            .filter(|s| counts[*s] > 0)
+            .map(|s| Reverse((counts[s] as u64, s)))
+            .collect();
+        if heap.is_empty() {
+            return lengths;
+        }
+        if heap.len() == 1 {
+            let Reverse((_, s)) = heap.pop().unwrap();
+            lengths[s] = 1;
+            return lengths;
+        }
+        while heap.len() > 1 {
+            let Reverse((a_weight, a)) = heap.pop().unwrap();
+            let Reverse((b_weight, b)) = heap.pop().unwrap();
+            let node = parents.len();
+            parents.push(node);
+            parents[a] = node;
+            parents[b] = node;
+            heap.push(Reverse((a_weight + b_weight, node)));
+        }
+        let root = parents.len() - 1;
+        for s in (0..SYMBOLS).filter(|s| counts[*s] > 0) {
+            let mut node = s;
+            while node != root {
+                node = parents[node];
+                lengths[s] += 1;
+            }
+        }
+        if lengths.iter().all(|l| *l <= MAX_CODE_LENGTH) {
+            return lengths;
+        }
+        // flatten the distribution until the longest code fits
+        for c in counts.iter_mut().filter(|c| **c > 0) {
+            *c = (*c + 1) / 2;
+        }
+    }
+}
+
+/// Canonical Huffman code over bytes, plus an escape symbol for bytes without a code
+pub struct HuffmanTable {
+    lengths: [u8; SYMBOLS],
+    codes: [u16; SYMBOLS],
+    /// Symbols ordered by code length then symbol, for decoding
+    sorted: Vec<u16>,
+    /// Number of codes of each length
+    length_counts: [u16; MAX_CODE_LENGTH as usize + 1],
+}
+impl HuffmanTable {
+    /// Code fitted to `counts`, with the escape symbol as rare as the rarest byte
+    pub fn from_counts(counts: &ByteCounts) -> Self {
+        let mut counts = counts.0;
+        counts[ESCAPE] = 1;
+        Self::from_lengths(code_lengths(&counts))
+    }
+    fn from_lengths(lengths: [u8; SYMBOLS]) -> Self {
+        let mut length_counts = [0u16; MAX_CODE_LENGTH as usize + 1];
+        for l in lengths.iter().filter(|l| **l > 0) {
+            length_counts[*l as usize] += 1;
+        }
+        let mut sorted: Vec<u16> = (0..SYMBOLS as u16)
+            .filter(|s| lengths[*s as usize] > 0)
+            .collect();
+        sorted.sort_by_key(|s| (lengths[*s as usize], *s));
+        let mut codes = [0; SYMBOLS];
+        let mut code = 0u16;
+        let mut prev_length = 0;
+        for s in sorted.iter() {
+            let length = lengths[*s as usize];
+            code <<= length - prev_length;
+            codes[*s as usize] = code;
+            code += 1;
+            prev_length = length;
+        }
+        Self {
+            lengths,
+            codes,
+            sorted,
+            length_counts,
+        }
+    }
+    /// Writes the symbols that have a code as gaps between them in ascending order, followed by
+    /// their code lengths packed two per byte
+    pub fn write(&self, out: &mut Vec<u8>) {
+        let symbols: Vec<usize> = (0..SYMBOLS).filter(|s| self.lengths[*s] > 0).collect();
+        write_varint(out, symbols.len() as u64);
+        let mut prev = 0;
+        for s in symbols.iter() {
+            write_varint(out, (s - prev) as u64);
+            prev = *s;
+        }
+        for pair in symbols.chunks(2) {
+            let high = self.lengths[pair[0]] << 4;
+            out.push(high | pair.get(1).map(|s| self.lengths[*s]).unwrap_or(0));
+        }
+    }
+    pub fn read(buf: &mut &[u8]) -> Option<Self> {
+        let count = read_varint(buf).ok()? as usize;
+        if count > SYMBOLS {
+            return None;
+        }
+        let mut symbols = Vec::with_capacity(count);
+        let mut prev = 0;
+        for _ in 0..count {
+            prev += read_varint(buf).ok()? as usize;
+            if prev >= SYMBOLS {
+                return None;
+            }
+            symbols.push(prev);
+        }
+        let mut lengths = [0; SYMBOLS];
+        for pair in symbols.chunks(2) {
+            let (packed, rest) = buf.split_first()?;
+            *buf = rest;
+            lengths[pair[0]] = packed >> 4;
+            if let Some(s) = pair.get(1) {
+                lengths[*s] = packed & 0xf;
+            }
+        }
+        if symbols.iter().any(|s| lengths[*s] == 0) {
+            return None;
+        }
+        Some(Self::from_lengths(lengths))
+    }
+    pub fn encode(&self, bytes: &[u8], out: &mut Vec<u8>) {
+        write_varint(out, bytes.len() as u64);
+        let mut bits = BitWriter {
+            out,
+            acc: 0,
+            len: 0,
+        };
+        for b in bytes {
+            let s = *b as usize;
+            if self.lengths[s] > 0 {
+                bits.write(self.codes[s] as u32, self.lengths[s]);
+            } else {
+                bits.write(self.codes[ESCAPE] as u32, self.lengths[ESCAPE]);
+                bits.write(s as u32, 8);
+            }
+        }
+        bits.flush();
+    }
+    pub fn decode(&self, mut buf: &[u8]) -> Option<Vec<u8>> {
+        let len = read_varint(&mut buf).ok()? as usize;
+        let mut bits = BitReader { buf, pos: 0 };
+        let mut out = Vec::with_capacity(len);
+        while out.len() < len {
+            let mut code = 0u32;
+            let mut first = 0u32;
+            let mut index = 0usize;
+            let mut symbol = None;
+            for length in 1..=MAX_CODE_LENGTH as usize {
+                code |= bits.read(1)?;
+                let count = self.length_counts[length] as u32;
+                if code < first + count {
+                    symbol = Some(self.sorted[index + (code - first) as usize]);
+                    break;
+                }
+                index += count as usize;
+                first = (first + count) << 1;
+                code <<= 1;
+            }
+            match symbol? as usize {
+                ESCAPE => out.push(bits.read(8)? as u8),
+                s => out.push(s as u8),
+            }
+        }
+        Some(out)
+    }
+}
+
+/// Writes bits most significant first
+struct BitWriter<'a> {
+    out: &'a mut Vec<u8>,
+    acc: u8,
+    len: u8,
+}
+impl<'a> BitWriter<'a> {
+    fn write(&mut self, value: u32, len: u8) {
+        for i in (0..len).rev() {
+            self.acc = self.acc << 1 | ((value >> i) & 1) as u8;
+            self.len += 1;
+            if self.len == 8 {
+                self.out.push(self.acc);
+                self.acc = 0;
+                self.len = 0;
+            }
+        }
+    }
+    fn flush(self) {
+        if self.len > 0 {
+            self.out.push(self.acc << (8 - self.len));
+        }
+    }
+}
+
+struct BitReader<'a> {
+    buf: &'a [u8],
+    /// Position in bits
+    pos: usize,
+}
+impl<'a> BitReader<'a> {
+    fn read(&mut self, len: u8) -> Option<u32> {
+        let mut value = 0;
+        for _ in 0..len {
+            let byte = self.buf.get(self.pos / 8)?;
+            value = value << 1 | ((byte >> (7 - self.pos % 8)) & 1) as u32;
+            self.pos += 1;
+        }
+        Some(value)
+    }
+}
@@ -0,0 +1,334 @@
+//! C API for embedding the simulation core in engine tooling: settings are created and set
+//! field by field, a run returns a result whose frames and metrics are read by index. The header
+//! in include/network_sim.h is generated from this module with `cbindgen`, see cbindgen.toml
+//!
+//! The wasm32 build exports the API without `ns_run`, as the apps can't run there. Recorded
+//! results can still be replayed with `ns_replay`, which the web viewer does through `web`
+#[cfg(not(target_arch = "wasm32"))]
+use crate::sim::run_simulation;
+use crate::{
+    metrics::MetricChannel,
+    replay::PacketSchedule,
+    sim::{Sample, SimSettings, SimSide, SimulationResult},
+    sim_behaviours::behaviour_by_name,
+};
+use amethyst::Result;
+use std::{
+    cell::RefCell,
+    ffi::{CStr, CString},
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr, thread,
+};
+
+thread_local! {
+    /// Message of the last failed call on this thread
+    static LAST_ERROR: RefCell<Option<CString>> = RefCell::new(None);
+}
+pub(crate) fn set_last_error(msg: String) {
+    let msg = CString::new(msg.replace('\0', " ")).unwrap();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(msg));
+}
+
+/// Simulation settings, created with `ns_settings_new` or `ns_settings_from_ron`
+pub struct NsSettings(SimSettings);
+
+/// Analyzed result of a run, created with `ns_run` or `ns_replay`
+pub struct NsResult {
+    result: SimulationResult<Sample>,
+    /// Metric channels in name order, with their names as C strings
+    metrics: Vec<(CString, MetricChannel)>,
+}
+
+/// One frame of a result
+#[repr(C)]
+pub struct NsFrame {
+    /// 0 for the client, 1 for the server
+    pub side: u8,
+    /// Whether the frame belongs to `entity` rather than the behaviour's primary entity
+    pub has_entity: bool,
+    pub entity: u32,
+    pub render_time: f32,
+    pub net_time: f32,
+    /// Position with z as height
+    pub pos: [f32; 3],
+    pub alpha: f32,
+}
+
+/// Message of the last failed call on the calling thread, null if none failed. Valid until the
+/// next failing call on the thread
+#[no_mangle]
+pub extern "C" fn ns_last_error() -> *const c_char {
+    LAST_ERROR.with(|e| e.borrow().as_ref().map_or(ptr::null(), |e| e.as_ptr()))
+}
+
+/// Buffer of `len` bytes, freed with `ns_free`, for hosts without a C allocator to pass
+/// strings in, like the web viewer's JavaScript
+#[no_mangle]
+pub extern "C" fn ns_alloc(len: usize) -> *mut u8 {
+    let mut buf = Vec::<u8>::with_capacity(len);
+    let ptr = buf.as_mut_ptr();
+    std::mem::forget(buf);
+    ptr
+}
+
+/// # Safety
+/// `buf` must come from `ns_alloc` with the same `len` and not be used after
+#[no_mangle]
+pub unsafe extern "C" fn ns_free(buf: *mut u8, len: usize) {
+    if !buf.is_null() {
+        drop(Vec::from_raw_parts(buf, 0, len));
+    }
+}
+
+/// Default settings, freed with `ns_settings_free`
+#[no_mangle]
+pub extern "C" fn ns_settings_new() -> *mut NsSettings {
+    Box::into_raw(Box::new(NsSettings(SimSettings::default())))
+}
+
+/// Settings read from the RON of a settings file, null if they don't parse
+///
+/// # Safety
+/// `ron` must be a valid nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn ns_settings_from_ron(ron: *const c_char) -> *mut NsSettings {
+    let ron = match CStr::from_ptr(ron).to_str() {
+        Ok(ron) => ron,
+        Err(e) => {
+            set_last_error(e.to_string());
+            return ptr::null_mut();
+        }
+    };
+    match ron::de::from_str(ron) {
+        Ok(settings) => Box::into_raw(Box::new(NsSettings(settings))),
+        Err(e) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `settings` must come from `ns_settings_new` or `ns_settings_from_ron` and not be used after
+#[no_mangle]
+pub unsafe extern "C" fn ns_settings_free(settings: *mut NsSettings) {
+    if !settings.is_null() {
+        drop(Box::from_raw(settings));
+    }
+}
+
+/// Sets a numeric field by its name in the settings file, false if there's no such field
+///
+/// # Safety
+/// `settings` must be live settings and `name` a valid nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn ns_settings_set_field(
+    settings: *mut NsSettings,
+    name: *const c_char,
+    value: f64,
+) -> bool {
+    let name = CStr::from_ptr(name).to_string_lossy();
+    if (*settings).0.set_field(&name, value) {
+        true
+    } else {
+        set_last_error(format!("unknown field {}", name));
+        false
+    }
+}
+
+/// Sets the behaviour by its display name, false if there's no such behaviour
+///
+/// # Safety
+/// `settings` must be live settings and `name` a valid nul-terminated string
+#[no_mangle]
+pub unsafe extern "C" fn ns_settings_set_behaviour(
+    settings: *mut NsSettings,
+    name: *const c_char,
+) -> bool {
+    let name = CStr::from_ptr(name).to_string_lossy();
+    match behaviour_by_name(&name) {
+        Some(behaviour) => {
+            (*settings).0.behaviour = behaviour;
+            true
+        }
+        None => {
+            set_last_error(format!("unknown behaviour {}", name));
+            false
+        }
+    }
+}
+
+/// Result for the caller of a run or replay that may have failed or panicked, null if it did
+fn into_ns_result(outcome: thread::Result<Result<SimulationResult<Sample>>>) -> *mut NsResult {
+    match outcome {
+        Ok(Ok(result)) => {
+            let metrics = result
+                .metrics
+                .iter()
+                .map(|(name, channel)| {
+                    (
+                        CString::new(name.replace('\0', " ")).unwrap(),
+                        channel.clone(),
+                    )
+                })
+                .collect();
+            Box::into_raw(Box::new(NsResult { result, metrics }))
+        }
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("the run panicked".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// Runs and analyzes a simulation, null if the run fails. Blocks until the run is done. Not in
+/// the wasm32 build
+///
+/// # Safety
+/// `settings` must be live settings
+#[cfg(not(target_arch = "wasm32"))]
+#[no_mangle]
+pub unsafe extern "C" fn ns_run(settings: *const NsSettings) -> *mut NsResult {
+    let settings = &(*settings).0;
+    // a panic must not unwind into the caller's frames
+    into_ns_result(panic::catch_unwind(AssertUnwindSafe(|| {
+        run_simulation(settings)
+    })))
+}
+
+/// Replays the sync messages the client received in a recorded result into the behaviour of
+/// `settings` and analyzes it, see `replay::PacketSchedule`. Null if the result doesn't parse or
+/// the replay fails. It needs no apps, so unlike `ns_run` it works in the wasm32 build
+///
+/// # Safety
+/// `settings` must be live settings and `result_json` a valid nul-terminated string with a
+/// result exported as JSON, e.g. with `--out`
+#[no_mangle]
+pub unsafe extern "C" fn ns_replay(
+    settings: *const NsSettings,
+    result_json: *const c_char,
+) -> *mut NsResult {
+    let settings = &(*settings).0;
+    let json = CStr::from_ptr(result_json).to_string_lossy();
+    into_ns_result(panic::catch_unwind(AssertUnwindSafe(|| {
+        let recorded: SimulationResult<Sample> = serde_json::from_str(&json)?;
+        Ok(PacketSchedule::from_result(&recorded)
+            .replay(settings)?
+            .result)
+    })))
+}
+
+/// # Safety
+/// `result` must come from `ns_run` or `ns_replay` and not be used after
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_free(result: *mut NsResult) {
+    if !result.is_null() {
+        drop(Box::from_raw(result));
+    }
+}
+
+/// Number of frames of both sides
+///
+/// # Safety
+/// `result` must be a live result
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_frame_count(result: *const NsResult) -> usize {
+    (&*result).result.frames.len()
+}
+
+/// Writes frame `idx` to `frame`, false if it's out of range
+///
+/// # Safety
+/// `result` must be a live result and `frame` writable
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_frame(
+    result: *const NsResult,
+    idx: usize,
+    frame: *mut NsFrame,
+) -> bool {
+    let f = match (&*result).result.frames.get(idx) {
+        Some(f) => f,
+        None => return false,
+    };
+    *frame = NsFrame {
+        side: match f.side {
+            SimSide::Client => 0,
+            SimSide::Server => 1,
+        },
+        has_entity: f.entity.is_some(),
+        entity: f.entity.unwrap_or(0),
+        render_time: f.render_time,
+        net_time: f.net_time,
+        pos: [f.sample.pos.x, f.sample.pos.y, f.sample.pos.z],
+        alpha: f.sample.alpha,
+    };
+    true
+}
+
+/// Number of metric channels
+///
+/// # Safety
+/// `result` must be a live result
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_metric_count(result: *const NsResult) -> usize {
+    (&*result).metrics.len()
+}
+
+/// Name of metric channel `metric`, null if it's out of range. Valid as long as the result
+///
+/// # Safety
+/// `result` must be a live result
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_metric_name(
+    result: *const NsResult,
+    metric: usize,
+) -> *const c_char {
+    (&*result)
+        .metrics
+        .get(metric)
+        .map_or(ptr::null(), |(name, _)| name.as_ptr())
+}
+
+/// Number of samples of metric channel `metric`, 0 if it's out of range
+///
+/// # Safety
+/// `result` must be a live result
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_metric_len(result: *const NsResult, metric: usize) -> usize {
+    (&*result)
+        .metrics
+        .get(metric)
+        .map_or(0, |(_, channel)| channel.len())
+}
+
+/// Writes the time and value of sample `idx` of metric channel `metric`, false if either is out
+/// of range
+///
+/// # Safety
+/// `result` must be a live result and `time` and `value` writable
+#[no_mangle]
+pub unsafe extern "C" fn ns_result_metric_sample(
+    result: *const NsResult,
+    metric: usize,
+    idx: usize,
+    time: *mut f32,
+    value: *mut f32,
+) -> bool {
+    let channel = match (&*result).metrics.get(metric) {
+        Some((_, channel)) => channel,
+        None => return false,
+    };
+    match (channel.times.get(idx), channel.values.get(idx)) {
+        (Some(t), Some(v)) => {
+            *time = *t;
+            *value = *v;
+            true
+        }
+        _ => false,
+    }
+}
@@ -0,0 +1,200 @@
+//! Bytes each struct field of a sync message takes in a codec, see `codec::measure_fields`
+use crate::{codec::SyncCodec, protobuf, protobuf::Error, varint};
+use serde::{ser, Serialize};
+
+/// Bytes of every struct field of `value` with its path, parents before their fields. A parent's
+/// bytes include its fields' and its own framing, so the top level fields add up to the size of a
+/// struct message.
+pub fn field_sizes<T: Serialize + ?Sized>(codec: SyncCodec, value: &T) -> Vec<(String, usize)> {
+    let mut sizes = Vec::new();
+    let _ = value.serialize(FieldWalker {
+        codec,
+        path: "",
+        sizes: &mut sizes,
+    });
+    sizes
+}
+
+/// Bytes `value` adds to its struct as field `index`
+fn field_len<T: Serialize + ?Sized>(codec: SyncCodec, index: u32, value: &T) -> usize {
+    match codec {
+        SyncCodec::Bincode => bincode::serialized_size(value).unwrap_or(0) as usize,
+        SyncCodec::Protobuf => protobuf::field_len(index + 1, value).unwrap_or(0),
+        SyncCodec::Varint => varint::to_vec(value).map(|v| v.len()).unwrap_or(0),
+    }
+}
+
+/// Descends into structs, ignoring every other value
+struct FieldWalker<'a> {
+    codec: SyncCodec,
+    path: &'a str,
+    sizes: &'a mut Vec<(String, usize)>,
+}
+
+struct Fields<'a> {
+    codec: SyncCodec,
+    path: &'a str,
+    sizes: &'a mut Vec<(String, usize)>,
+    index: u32,
+}
+impl<'a> ser::SerializeStruct for Fields<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let path = if self.path.is_empty() {
+            key.to_owned()
+        } else {
+            format!("{}.{}", self.path, key)
+        };
+        self.sizes
+            .push((path.clone(), field_len(self.codec, self.index, value)));
+        self.index += 1;
+        // fields that aren't structs stop the walk with an error
+        let _ = value.serialize(FieldWalker {
+            codec: self.codec,
+            path: &path,
+            sizes: self.sizes,
+        });
+        Ok(())
+    }
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for FieldWalker<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = ser::Impossible<(), Error>;
+    type SerializeTuple = ser::Impossible<(), Error>;
+    type SerializeTupleStruct = ser::Impossible<(), Error>;
+    type SerializeTupleVariant = ser::Impossible<(), Error>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = Fields<'a>;
+    type SerializeStructVariant = ser::Impossible<(), Error>;
+
+    fn serialize_bool(self, _v: bool) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_i8(self, _v: i8) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_i16(self, _v: i16) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_i32(self, _v: i32) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_i64(self, _v: i64) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_u8(self, _v: u8) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_u16(self, _v: u16) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_u32(self, _v: u32) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_u64(self, _v: u64) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_f32(self, _v: f32) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_f64(self, _v: f64) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_char(self, _v: char) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_str(self, _v: &str) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_bytes(self, _v: &[u8]) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _value: &T,
+    ) -> Result<(), Error> {
+        Ok(())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+    fn serialize_tuple_struct(
+        self,
+        _name: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Fields<'a>, Error> {
+        Ok(Fields {
+            codec: self.codec,
+            path: self.path,
+            sizes: self.sizes,
+            index: 0,
+        })
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        _variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error("not a struct".to_owned()))
+    }
+}
@@ -0,0 +1,247 @@
+use crate::sim::SimSettings;
+use amethyst::core::math::{self, Vector3};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum FilterKind {
+    None,
+    /// Exponential moving average
+    Exponential,
+    /// Holt's double exponential smoothing, tracking level and trend
+    DoubleExponential,
+    /// Constant-velocity Kalman filter
+    Kalman,
+    /// Speed-adaptive low-pass filter by Casiez et al.
+    OneEuro,
+}
+impl fmt::Display for FilterKind {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FilterKind::None => write!(f, "None"),
+            FilterKind::Exponential => write!(f, "Exponential"),
+            FilterKind::DoubleExponential => write!(f, "Double Exponential"),
+            FilterKind::Kalman => write!(f, "Kalman"),
+            FilterKind::OneEuro => write!(f, "1€"),
+        }
+    }
+}
+pub const FILTER_KINDS: [FilterKind; 5] = [
+    FilterKind::None,
+    FilterKind::Exponential,
+    FilterKind::DoubleExponential,
+    FilterKind::Kalman,
+    FilterKind::OneEuro,
+];
+
+/// Smooths the client's rendered positions after interpolation.
+pub trait PositionFilter: Send + Sync {
+    /// Filters the position rendered at time `t` in seconds
+    fn filter(&mut self, t: f32, pos: Vector3<f32>) -> Vector3<f32>;
+}
+
+pub fn new_filter(settings: &SimSettings) -> Box<dyn PositionFilter> {
+    match settings.filter {
+        FilterKind::None => Box::new(NoFilter),
+        FilterKind::Exponential => Box::new(Exponential {
+            alpha: settings.filter_alpha,
+            value: None,
+        }),
+        FilterKind::DoubleExponential => Box::new(DoubleExponential {
+            alpha: settings.filter_alpha,
+            beta: settings.filter_beta,
+            state: None,
+        }),
+        FilterKind::Kalman => Box::new(Kalman {
+            process_noise: settings.kalman_process_noise,
+            measurement_noise: settings.kalman_measurement_noise,
+            axes: None,
+        }),
+        FilterKind::OneEuro => Box::new(OneEuro {
+            min_cutoff: settings.one_euro_min_cutoff,
+            beta: settings.one_euro_beta,
+            state: None,
+        }),
+    }
+}
+
+struct NoFilter;
+impl PositionFilter for NoFilter {
+    fn filter(&mut self, _t: f32, pos: Vector3<f32>) -> Vector3<f32> {
+        pos
+    }
+}
+
+struct Exponential {
+    alpha: f32,
+    value: Option<Vector3<f32>>,
+}
+impl PositionFilter for Exponential {
+    fn filter(&mut self, _t: f32, pos: Vector3<f32>) -> Vector3<f32> {
+        let alpha = self.alpha;
+        let value = self.value.map(|v| v + (pos - v) * alpha).unwrap_or(pos);
+        self.value = Some(value);
+        value
+    }
+}
+
+struct DoubleExponential {
+    alpha: f32,
+    beta: f32,
+    /// (time, level, trend per second)
+    state: Option<(f32, Vector3<f32>, Vector3<f32>)>,
+}
+impl PositionFilter for DoubleExponential {
+    fn filter(&mut self, t: f32, pos: Vector3<f32>) -> Vector3<f32> {
+        let (alpha, beta) = (self.alpha, self.beta);
+        let (level, trend) = match self.state {
+            Some((prev_t, level, trend)) if t > prev_t => {
+                let dt = t - prev_t;
+                let new_level = pos * alpha + (level + trend * dt) * (1. - alpha);
+                let new_trend = (new_level - level) / dt * beta + trend * (1. - beta);
+                (new_level, new_trend)
+            }
+            Some((_, level, trend)) => (level, trend),
+            None => (pos, math::zero()),
+        };
+        self.state = Some((t, level, trend));
+        level
+    }
+}
+
+/// Position and velocity estimate of one axis, with its covariance
+#[derive(Clone, Copy)]
+struct KalmanAxis {
+    pos: f32,
+    vel: f32,
+    p: [[f32; 2]; 2],
+}
+impl KalmanAxis {
+    fn new(pos: f32, measurement_noise: f32) -> Self {
+        Self {
+            pos,
+            vel: 0.,
+            p: [[measurement_noise, 0.], [0., measurement_noise]],
+        }
+    }
+    fn step(&mut self, dt: f32, measurement: f32, q: f32, r: f32) {
+        // predict with a constant velocity model
+        self.pos += self.vel * dt;
+        let p = self.p;
+        let dt2 = dt * dt;
+        self.p = [
+            [
+                p[0][0] + dt * (p[1][0] + p[0][1]) + dt2 * p[1][1] + q * dt2 * dt / 3.,
+                p[0][1] + dt * p[1][1] + q * dt2 / 2.,
+            ],
+            [p[1][0] + dt * p[1][1] + q * dt2 / 2., p[1][1] + q * dt],
+        ];
+        // correct with the measured position
+        let innovation = measurement - self.pos;
+        let s = self.p[0][0] + r;
+        let k = [self.p[0][0] / s, self.p[1][0] / s];
+        self.pos += k[0] * innovation;
+        self.vel += k[1] * innovation;
+        let p = self.p;
+        self.p = [
+            [(1. - k[0]) * p[0][0], (1. - k[0]) * p[0][1]],
+            [p[1][0] - k[1] * p[0][0], p[1][1] - k[1] * p[0][1]],
+        ];
+    }
+}
+
+struct Kalman {
+    process_noise: f32,
+    measurement_noise: f32,
+    /// Time of the last update and the x, y and z axis estimates
+    axes: Option<(f32, [KalmanAxis; 3])>,
+}
+impl PositionFilter for Kalman {
+    fn filter(&mut self, t: f32, pos: Vector3<f32>) -> Vector3<f32> {
+        let (q, r) = (self.process_noise, self.measurement_noise);
+        let (prev_t, mut axes) = self.axes.unwrap_or_else(|| {
+            (
+                t,
+                [
+                    KalmanAxis::new(pos.x, r),
+                    KalmanAxis::new(pos.y, r),
+                    KalmanAxis::new(pos.z, r),
+                ],
+            )
+        });
+        let dt = (t - prev_t).max(0.);
+        for (axis, measurement) in axes.iter_mut().zip(pos.iter()) {
+            axis.step(dt, *measurement, q, r);
+        }
+        self.axes = Some((t, axes));
+        Vector3::new(axes[0].pos, axes[1].pos, axes[2].pos)
+    }
+}
+
+/// Cutoff frequency in Hz of the 1€ filter's speed estimate
+const ONE_EURO_SPEED_CUTOFF: f32 = 1.;
+
+/// Smoothing factor of a first order low-pass filter with cutoff frequency `cutoff` in Hz
+fn low_pass_alpha(cutoff: f32, dt: f32) -> f32 {
+    let tau = 1. / (2. * std::f32::consts::PI * cutoff);
+    1. / (1. + tau / dt)
+}
+
+/// Low-pass filter whose cutoff frequency rises with speed, trading jitter for lag at low
+/// speeds and lag for jitter at high speeds.
+struct OneEuro {
+    /// Cutoff frequency in Hz when stationary
+    min_cutoff: f32,
+    /// Cutoff frequency increase per unit of speed
+    beta: f32,
+    /// (time, filtered position, filtered velocity)
+    state: Option<(f32, Vector3<f32>, Vector3<f32>)>,
+}
+impl PositionFilter for OneEuro {
+    fn filter(&mut self, t: f32, pos: Vector3<f32>) -> Vector3<f32> {
+        let (value, velocity) = match self.state {
+            Some((prev_t, prev, prev_velocity)) if t > prev_t => {
+                let dt = t - prev_t;
+                let speed_alpha = low_pass_alpha(ONE_EURO_SPEED_CUTOFF, dt);
+                let velocity = prev_velocity + ((pos - prev) / dt - prev_velocity) * speed_alpha;
+                let cutoff = self.min_cutoff + self.beta * velocity.norm();
+                let value = prev + (pos - prev) * low_pass_alpha(cutoff, dt);
+                (value, velocity)
+            }
+            Some((_, prev, prev_velocity)) => (prev, prev_velocity),
+            None => (pos, math::zero()),
+        };
+        self.state = Some((t, value, velocity));
+        value
+    }
+}
+
+/// Ignores corrections smaller than `threshold`. Once a correction reaches the threshold,
+/// corrections keep being applied until they drop below `threshold * (1 - hysteresis)`.
+#[derive(Clone, Debug)]
+pub struct DeadBand {
+    threshold: f32,
+    hysteresis: f32,
+    correcting: bool,
+}
+impl DeadBand {
+    pub fn new(threshold: f32, hysteresis: f32) -> Self {
+        Self {
+            threshold,
+            hysteresis,
+            correcting: false,
+        }
+    }
+    /// Whether a correction of size `error` should be applied
+    pub fn accept(&mut self, error: f32) -> bool {
+        if self.threshold <= 0. {
+            return true;
+        }
+        if self.correcting {
+            self.correcting = error >= self.threshold * (1. - self.hysteresis);
+        } else {
+            self.correcting = error >= self.threshold;
+        }
+        self.correcting
+    }
+}
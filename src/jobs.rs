@@ -0,0 +1,163 @@
+//! Durable queue of simulation jobs for the headless service. The queue is a directory of files
+//! named by job id, so submitted jobs survive restarts and their results stay addressable:
+//!
+//! - `<id>.ron`: settings of the job, written on submission
+//! - `<id>.json`: summary of the job's metrics, written when it's done
+//! - `<id>.result.json`: full result of the job
+//! - `<id>.error`: why the job failed
+//!
+//! Each file is written to a temporary file first and renamed into place, so a crash never leaves
+//! half a file behind. Jobs with settings but neither a summary nor an error are queued again on
+//! opening the directory
+use crate::sim::{Sample, SimSettings, SimulationResult};
+use amethyst::Result;
+use std::{
+    collections::VecDeque,
+    fs,
+    path::{Path, PathBuf},
+    sync::{Condvar, Mutex},
+};
+
+/// Where a job is in the queue
+#[derive(Clone, Debug, PartialEq)]
+pub enum JobStatus {
+    Pending,
+    Running,
+    /// Done, with the JSON summary of its metrics
+    Done(String),
+    /// Failed, with the error
+    Failed(String),
+}
+
+struct QueueState {
+    pending: VecDeque<u64>,
+    running: Option<u64>,
+    next_id: u64,
+}
+
+pub struct JobQueue {
+    dir: PathBuf,
+    state: Mutex<QueueState>,
+    queued: Condvar,
+}
+impl JobQueue {
+    /// Opens the queue in `dir`, creating the directory if needed, and queues the jobs that
+    /// weren't finished before in submission order
+    pub fn open(dir: &Path) -> Result<Self> {
+        fs::create_dir_all(dir)?;
+        let mut ids = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(false, |e| e == "ron") {
+                if let Some(id) = path
+                    .file_stem()
+                    .and_then(|s| s.to_str())
+                    .and_then(|s| s.parse::<u64>().ok())
+                {
+                    ids.push(id);
+                }
+            }
+        }
+        ids.sort();
+        let next_id = ids.last().map_or(1, |id| id + 1);
+        let queue = JobQueue {
+            dir: dir.to_owned(),
+            state: Mutex::new(QueueState {
+                pending: VecDeque::new(),
+                running: None,
+                next_id,
+            }),
+            queued: Condvar::new(),
+        };
+        {
+            let mut state = queue.state.lock().unwrap();
+            state.pending = ids
+                .into_iter()
+                .filter(|id| {
+                    !queue.path(*id, "json").exists() && !queue.path(*id, "error").exists()
+                })
+                .collect();
+            if !state.pending.is_empty() {
+                log::info!("resuming {} queued jobs", state.pending.len());
+            }
+        }
+        Ok(queue)
+    }
+    fn path(&self, id: u64, extension: &str) -> PathBuf {
+        self.dir.join(format!("{}.{}", id, extension))
+    }
+    fn write(&self, id: u64, extension: &str, contents: &[u8]) -> Result<()> {
+        let tmp = self.path(id, &format!("{}.tmp", extension));
+        fs::write(&tmp, contents)?;
+        fs::rename(&tmp, self.path(id, extension))?;
+        Ok(())
+    }
+    /// Stores and queues a job with the RON settings `settings`, returning its id. Settings that
+    /// don't parse are rejected rather than queued
+    pub fn submit(&self, settings: &str) -> Result<u64> {
+        ron::de::from_str::<SimSettings>(settings)?;
+        let mut state = self.state.lock().unwrap();
+        let id = state.next_id;
+        self.write(id, "ron", settings.as_bytes())?;
+        state.next_id += 1;
+        state.pending.push_back(id);
+        self.queued.notify_one();
+        Ok(id)
+    }
+    /// Number of jobs waiting to run
+    pub fn pending(&self) -> usize {
+        self.state.lock().unwrap().pending.len()
+    }
+    pub fn status(&self, id: u64) -> Option<JobStatus> {
+        let state = self.state.lock().unwrap();
+        if state.running == Some(id) {
+            return Some(JobStatus::Running);
+        }
+        if state.pending.contains(&id) {
+            return Some(JobStatus::Pending);
+        }
+        if let Ok(summary) = fs::read_to_string(self.path(id, "json")) {
+            return Some(JobStatus::Done(summary));
+        }
+        if let Ok(error) = fs::read_to_string(self.path(id, "error")) {
+            return Some(JobStatus::Failed(error));
+        }
+        None
+    }
+    /// Full result of a done job as JSON
+    pub fn result(&self, id: u64) -> Option<String> {
+        fs::read_to_string(self.path(id, "result.json")).ok()
+    }
+    /// Waits for the next job and marks it running, returning its id and settings
+    pub fn next(&self) -> (u64, Result<SimSettings>) {
+        let mut state = self.state.lock().unwrap();
+        loop {
+            if let Some(id) = state.pending.pop_front() {
+                state.running = Some(id);
+                let settings = fs::read_to_string(self.path(id, "ron"))
+                    .map_err(Into::into)
+                    .and_then(|s| Ok(ron::de::from_str(&s)?));
+                return (id, settings);
+            }
+            state = self.queued.wait(state).unwrap();
+        }
+    }
+    /// Stores the outcome of the running job `id`, its summary and full result or its error
+    pub fn finish(
+        &self,
+        id: u64,
+        outcome: Result<(String, SimulationResult<Sample>)>,
+    ) -> Result<()> {
+        let stored = match outcome {
+            Ok((summary, result)) => {
+                let tmp = self.path(id, "result.json.tmp");
+                result.to_json_file(&tmp)?;
+                fs::rename(&tmp, self.path(id, "result.json"))?;
+                self.write(id, "json", summary.as_bytes())
+            }
+            Err(e) => self.write(id, "error", e.to_string().as_bytes()),
+        };
+        self.state.lock().unwrap().running = None;
+        stored
+    }
+}
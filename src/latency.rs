@@ -0,0 +1,158 @@
+//! Latency distributions of the simulated network. `monkey::SimMonkey` draws the latency of
+//! every packet sent from the model of the settings
+use crate::sim::SimSettings;
+use rand::{rngs::SmallRng, Rng};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum LatencyModel {
+    /// Uniform between `min_latency` and `max_latency`
+    Uniform,
+    /// Normal with `latency_mean` and `latency_stddev`, never below zero
+    Normal,
+    /// Pareto with `latency_pareto_scale` as the smallest latency and `latency_pareto_shape` as
+    /// the shape, a long tail that gets heavier as the shape gets smaller, cut at
+    /// `latency_pareto_max`
+    Pareto,
+}
+impl fmt::Display for LatencyModel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LatencyModel::Uniform => write!(f, "Uniform"),
+            LatencyModel::Normal => write!(f, "Normal"),
+            LatencyModel::Pareto => write!(f, "Pareto"),
+        }
+    }
+}
+pub const LATENCY_MODELS: [LatencyModel; 3] = [
+    LatencyModel::Uniform,
+    LatencyModel::Normal,
+    LatencyModel::Pareto,
+];
+
+/// Smallest latency in ms the model of `settings` draws
+fn min_ms(settings: &SimSettings) -> f32 {
+    let min = match settings.latency_model {
+        LatencyModel::Uniform => settings.min_latency,
+        LatencyModel::Normal => 0.,
+        LatencyModel::Pareto => settings
+            .latency_pareto_scale
+            .min(settings.latency_pareto_max),
+    };
+    min.max(0.)
+}
+/// Smallest latency in seconds `sample` draws with `settings`, which no packet arrives faster
+/// than
+pub fn min_latency(settings: &SimSettings) -> f32 {
+    min_ms(settings) / 1000.
+}
+
+/// Latency in seconds of a packet, drawn from the model of `settings`
+pub fn sample(settings: &SimSettings, rng: &mut SmallRng) -> f32 {
+    let ms = match settings.latency_model {
+        LatencyModel::Uniform if settings.max_latency > settings.min_latency => {
+            rng.gen_range(settings.min_latency, settings.max_latency)
+        }
+        LatencyModel::Uniform => settings.min_latency,
+        LatencyModel::Normal => rng.sample(rand::distributions::Normal::new(
+            settings.latency_mean as f64,
+            settings.latency_stddev.max(0.) as f64,
+        )) as f32,
+        LatencyModel::Pareto => {
+            let u: f32 = rng.gen_range(std::f32::EPSILON, 1.);
+            let shape = settings.latency_pareto_shape.max(std::f32::EPSILON);
+            (settings.latency_pareto_scale * u.powf(-1. / shape)).min(settings.latency_pareto_max)
+        }
+    };
+    ms.max(min_ms(settings)) / 1000.
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// `count` latencies in ms drawn from the model of `settings`
+    fn draw(settings: &SimSettings, count: usize) -> Vec<f32> {
+        let mut rng = SmallRng::seed_from_u64(7);
+        (0..count)
+            .map(|_| sample(settings, &mut rng) * 1000.)
+            .collect()
+    }
+
+    #[test]
+    fn normal_mean_and_stddev() {
+        let mut settings = SimSettings::default();
+        settings.latency_model = LatencyModel::Normal;
+        settings.latency_mean = 80.;
+        settings.latency_stddev = 15.;
+        let samples = draw(&settings, 20_000);
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        let variance =
+            samples.iter().map(|s| (s - mean).powi(2)).sum::<f32>() / samples.len() as f32;
+        assert!((mean - 80.).abs() < 0.5, "mean {}", mean);
+        assert!(
+            (variance.sqrt() - 15.).abs() < 0.5,
+            "stddev {}",
+            variance.sqrt()
+        );
+    }
+
+    #[test]
+    fn pareto_tail() {
+        let mut settings = SimSettings::default();
+        settings.latency_model = LatencyModel::Pareto;
+        settings.latency_pareto_scale = 20.;
+        settings.latency_pareto_shape = 3.;
+        let samples = draw(&settings, 20_000);
+        assert!(samples.iter().all(|s| *s >= 20. - 1e-3));
+        // P(X > x) = (scale / x)^shape
+        let above = |x: f32| samples.iter().filter(|s| **s > x).count() as f32 / 20_000.;
+        assert!((above(40.) - 0.125).abs() < 0.01, "{}", above(40.));
+        assert!((above(80.) - 0.0156).abs() < 0.004, "{}", above(80.));
+    }
+
+    #[test]
+    fn pareto_tail_is_cut() {
+        let mut settings = SimSettings::default();
+        settings.latency_model = LatencyModel::Pareto;
+        settings.latency_pareto_scale = 20.;
+        settings.latency_pareto_shape = 0.05;
+        settings.latency_pareto_max = 500.;
+        assert!(draw(&settings, 1000)
+            .iter()
+            .all(|latency| latency.is_finite() && *latency <= 500.));
+        assert_eq!(settings.latency_range(), (20., 500.));
+    }
+
+    #[test]
+    fn uniform_range() {
+        let mut settings = SimSettings::default();
+        settings.latency_model = LatencyModel::Uniform;
+        settings.min_latency = 30.;
+        settings.max_latency = 70.;
+        let samples = draw(&settings, 20_000);
+        assert!(samples.iter().all(|s| *s >= 30. - 1e-3 && *s < 70. + 1e-3));
+        let mean = samples.iter().sum::<f32>() / samples.len() as f32;
+        assert!((mean - 50.).abs() < 0.5, "mean {}", mean);
+        settings.max_latency = 30.;
+        assert!(draw(&settings, 100).iter().all(|s| (*s - 30.).abs() < 1e-3));
+    }
+
+    #[test]
+    fn never_below_min_latency() {
+        let mut settings = SimSettings::default();
+        settings.min_latency = 30.;
+        settings.max_latency = 70.;
+        settings.latency_mean = 10.;
+        settings.latency_stddev = 40.;
+        for model in LATENCY_MODELS.iter() {
+            settings.latency_model = *model;
+            let min = min_latency(&settings) * 1000.;
+            assert!(draw(&settings, 5000).iter().all(|s| *s >= min), "{}", model);
+        }
+        settings.latency_model = LatencyModel::Uniform;
+        assert_eq!(min_latency(&settings), 0.03);
+    }
+}
@@ -0,0 +1,44 @@
+//! Simulation core of the network simulator: the simulated server and client apps, the
+//! behaviours they run, the analysis of their results and the headless command line runner.
+//! Builds on stable Rust, the viewer binary adds the GUI on top. The wasm32 build is the core of
+//! the web viewer, see `web`, without the command line runner, the service, its job queue and
+//! the telemetry listener, as they need threads, sockets and a file system. Check it with
+//! `cargo check-web`
+#[cfg(not(target_arch = "wasm32"))]
+pub mod cli;
+pub mod codec;
+pub mod comparison;
+pub mod entropy;
+pub mod ffi;
+pub mod field_sizes;
+pub mod filter;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod jobs;
+pub mod latency;
+pub mod loss;
+pub mod metrics;
+pub mod monkey;
+pub mod p2p;
+pub mod pipeline;
+pub mod protobuf;
+pub mod reliability;
+pub mod replay;
+pub mod report;
+pub mod rng;
+pub mod scenario;
+pub mod sequence;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod service;
+pub mod sim;
+pub mod sim_behaviours;
+pub mod snapshot_buffer;
+pub mod stepping;
+pub mod stopwatch;
+pub mod style;
+#[cfg(not(target_arch = "wasm32"))]
+pub mod telemetry;
+pub mod truth;
+pub mod validation;
+pub mod varint;
+pub mod view;
+pub mod web;
@@ -0,0 +1,127 @@
+//! Per-packet loss of the simulated network, which `monkey::SimMonkey` decides either by the
+//! uniform `loss_percentage` or by the Gilbert–Elliott chain of good and bad states below
+use crate::sim::SimSettings;
+use rand::{rngs::SmallRng, Rng};
+
+/// Two-state Markov chain stepped once per packet sent. In the good state packets are lost
+/// with `burst_good_loss`, in the bad state with `burst_bad_loss`
+pub struct GilbertElliott {
+    bad: bool,
+    good_to_bad: f32,
+    bad_to_good: f32,
+    good_loss: f32,
+    bad_loss: f32,
+    rng: SmallRng,
+}
+impl GilbertElliott {
+    /// Chain with the parameters of `settings`, starting in the good state
+    pub fn new(settings: &SimSettings, rng: SmallRng) -> Self {
+        Self {
+            bad: false,
+            good_to_bad: settings.burst_good_to_bad,
+            bad_to_good: settings.burst_bad_to_good,
+            good_loss: settings.burst_good_loss,
+            bad_loss: settings.burst_bad_loss,
+            rng,
+        }
+    }
+    /// Whether the next packet is lost, stepping the chain first
+    pub fn drops(&mut self) -> bool {
+        let switch = if self.bad {
+            self.bad_to_good
+        } else {
+            self.good_to_bad
+        };
+        if self.rng.gen::<f32>() < switch {
+            self.bad = !self.bad;
+        }
+        let loss = if self.bad {
+            self.bad_loss
+        } else {
+            self.good_loss
+        };
+        self.rng.gen::<f32>() < loss
+    }
+}
+
+/// Fraction of packets the chain of `settings` loses in the long run, and the mean number of
+/// packets sent per stay in the bad state, None if the chain never leaves it
+pub fn burst_loss_stats(settings: &SimSettings) -> (f32, Option<f32>) {
+    let switches = settings.burst_good_to_bad + settings.burst_bad_to_good;
+    let bad_share = if switches > 0. {
+        settings.burst_good_to_bad / switches
+    } else {
+        0.
+    };
+    let loss = bad_share * settings.burst_bad_loss + (1. - bad_share) * settings.burst_good_loss;
+    let burst = if settings.burst_bad_to_good > 0. {
+        Some(1. / settings.burst_bad_to_good)
+    } else {
+        None
+    };
+    (loss, burst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::SeedableRng;
+
+    /// Loss rate and mean stay in the bad state in packets of `packets` sent through the chain
+    fn measure(settings: &SimSettings, packets: usize) -> (f32, f32) {
+        let mut chain = GilbertElliott::new(settings, SmallRng::seed_from_u64(11));
+        let (mut lost, mut bad, mut bursts) = (0, 0, 0);
+        let mut was_bad = false;
+        for _ in 0..packets {
+            if chain.drops() {
+                lost += 1;
+            }
+            if chain.bad {
+                bad += 1;
+                if !was_bad {
+                    bursts += 1;
+                }
+            }
+            was_bad = chain.bad;
+        }
+        (
+            lost as f32 / packets as f32,
+            bad as f32 / bursts.max(1) as f32,
+        )
+    }
+
+    #[test]
+    fn stationary_loss_and_burst_length() {
+        let mut settings = SimSettings::default();
+        settings.burst_good_to_bad = 0.02;
+        settings.burst_bad_to_good = 0.2;
+        settings.burst_good_loss = 0.01;
+        settings.burst_bad_loss = 0.6;
+        let (loss, burst) = burst_loss_stats(&settings);
+        let (measured_loss, measured_burst) = measure(&settings, 400_000);
+        assert!(
+            (measured_loss - loss).abs() < 0.005,
+            "{} {}",
+            measured_loss,
+            loss
+        );
+        let burst = burst.unwrap();
+        assert!(
+            (measured_burst - burst).abs() < 0.05 * burst,
+            "{} {}",
+            measured_burst,
+            burst
+        );
+    }
+
+    #[test]
+    fn bad_state_without_exit() {
+        let mut settings = SimSettings::default();
+        settings.burst_good_to_bad = 0.1;
+        settings.burst_bad_to_good = 0.;
+        settings.burst_bad_loss = 0.5;
+        let (loss, burst) = burst_loss_stats(&settings);
+        assert_eq!(burst, None);
+        assert!((loss - 0.5).abs() < 1e-6);
+    }
+}
@@ -1,4 +1,6 @@
-#![feature(const_fn)]
+#[cfg(not(target_arch = "wasm32"))]
+use amethyst::Result;
+#[cfg(feature = "viewer")]
 use amethyst::{
     core::transform::{Transform, TransformBundle},
     prelude::*,
@@ -11,18 +13,37 @@ use amethyst::{
     },
     utils::application_root_dir,
     window::ScreenDimensions,
-    Result,
 };
+#[cfg(not(target_arch = "wasm32"))]
+use network_sim::cli;
+#[cfg(feature = "viewer")]
 use std::net::TcpListener;
 
+#[cfg(feature = "viewer")]
 mod control;
+#[cfg(feature = "viewer")]
+mod cue;
+#[cfg(feature = "viewer")]
+mod points;
+#[cfg(feature = "viewer")]
 mod render;
-mod sim;
-mod sim_behaviours;
+#[cfg(feature = "viewer")]
+mod session;
+#[cfg(feature = "viewer")]
+mod tour;
 
+#[cfg(feature = "viewer")]
 use control::GuiSystemDesc;
+#[cfg(feature = "viewer")]
+use cue::AudioCueSystem;
+#[cfg(feature = "viewer")]
+use network_sim::view::ViewSystem;
+#[cfg(feature = "viewer")]
+use points::RenderPoints;
+#[cfg(feature = "viewer")]
 use render::SimRenderSystem;
 
+#[cfg(not(target_arch = "wasm32"))]
 fn main() -> Result<()> {
     use amethyst::LoggerConfig;
     amethyst::start_logger(LoggerConfig {
@@ -30,6 +51,27 @@ fn main() -> Result<()> {
         ..Default::default()
     });
 
+    let matches = cli::app().get_matches();
+    if matches.subcommand_name().is_some() {
+        return cli::run(&matches);
+    }
+    run_viewer()
+}
+
+/// Without the viewer only the subcommands run
+#[cfg(all(not(feature = "viewer"), not(target_arch = "wasm32")))]
+fn run_viewer() -> Result<()> {
+    cli::app().print_help()?;
+    println!();
+    Ok(())
+}
+
+/// The wasm32 build is the library the web viewer in web/ loads, see `network_sim::web`
+#[cfg(target_arch = "wasm32")]
+fn main() {}
+
+#[cfg(feature = "viewer")]
+fn run_viewer() -> Result<()> {
     let listener = TcpListener::bind("0.0.0.0:3457")?;
     listener.set_nonblocking(true).unwrap();
 
@@ -42,8 +84,11 @@ fn main() -> Result<()> {
     let render_data = GameDataBuilder::default()
         .with_bundle(TransformBundle::new())?
         .with_barrier()
-        .with(SimRenderSystem, "sim_render", &[])
+        .with(ViewSystem::default(), "view", &[])
+        .with(SimRenderSystem::default(), "sim_render", &["view"])
         .with_system_desc(GuiSystemDesc, "gui_system", &[])
+        .with(AudioCueSystem::default(), "audio_cues", &["gui_system"])
+        .with_bundle(amethyst::audio::AudioBundle::default())?
         .with_bundle(amethyst::input::InputBundle::<
             amethyst::input::StringBindings,
         >::default())?
@@ -53,6 +98,7 @@ fn main() -> Result<()> {
                     RenderToWindow::from_config_path(display_config_path)
                         .with_clear([0.0, 0.0, 0.0, 1.0]),
                 )
+                .with_plugin(RenderPoints::default())
                 .with_plugin(RenderDebugLines::default())
                 .with_plugin(
                     amethyst_imgui::RenderImgui::<amethyst::input::StringBindings>::default(),
@@ -63,8 +109,10 @@ fn main() -> Result<()> {
     Ok(())
 }
 
+#[cfg(feature = "viewer")]
 struct RenderState;
 
+#[cfg(feature = "viewer")]
 impl SimpleState for RenderState {
     fn on_start(&mut self, data: StateData<'_, GameData<'_, '_>>) {
         // Setup debug lines as a resource
@@ -120,18 +168,4 @@ impl SimpleState for RenderState {
             .with(local_transform)
             .build();
     }
-    fn update(&mut self, data: &mut StateData<'_, GameData<'_, '_>>) -> SimpleTrans {
-        let (screen_w, screen_h) = {
-            let screen_dimensions = data.world.read_resource::<ScreenDimensions>();
-            (
-                screen_dimensions.width() * screen_dimensions.hidpi_factor() as f32,
-                screen_dimensions.height() * screen_dimensions.hidpi_factor() as f32,
-            )
-        };
-        use amethyst::ecs::Join;
-        for camera in (&mut data.world.write_component::<Camera>()).join() {
-            *camera = Camera::standard_2d(screen_w, screen_h);
-        }
-        Trans::None
-    }
 }
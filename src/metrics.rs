@@ -0,0 +1,412 @@
+use serde::{Deserialize, Serialize};
+use std::{collections::BTreeMap, fmt, str::FromStr};
+
+/// A named series of (time, value) samples recorded during a run.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct MetricChannel {
+    pub times: Vec<f32>,
+    pub values: Vec<f32>,
+}
+impl MetricChannel {
+    pub fn push(&mut self, t: f32, v: f32) {
+        self.times.push(t);
+        self.values.push(v);
+    }
+    pub fn len(&self) -> usize {
+        self.values.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.values.is_empty()
+    }
+    /// Samples of both channels by time, this channel's first at equal times
+    pub fn merge(self, other: MetricChannel) -> MetricChannel {
+        let samples = |c: MetricChannel| c.times.into_iter().zip(c.values).collect();
+        let (times, values) = merge_by_time(samples(self), samples(other), |s| s.0)
+            .into_iter()
+            .unzip();
+        MetricChannel { times, values }
+    }
+    pub fn mean(&self) -> f32 {
+        if self.values.is_empty() {
+            return 0.;
+        }
+        self.values.iter().sum::<f32>() / self.values.len() as f32
+    }
+    pub fn min(&self) -> f32 {
+        self.values
+            .iter()
+            .cloned()
+            .fold(std::f32::INFINITY, f32::min)
+    }
+    pub fn max(&self) -> f32 {
+        self.values
+            .iter()
+            .cloned()
+            .fold(std::f32::NEG_INFINITY, f32::max)
+    }
+    /// Value below which `p` percent of the samples fall, by nearest rank
+    pub fn percentile(&self, p: f32) -> f32 {
+        if self.values.is_empty() {
+            return 0.;
+        }
+        let mut sorted = self.values.clone();
+        sorted.sort_by(|a, b| a.partial_cmp(b).unwrap());
+        let rank = (p / 100. * sorted.len() as f32).ceil() as usize;
+        sorted[rank.max(1).min(sorted.len()) - 1]
+    }
+    /// Time and value of the largest sample, the earliest one if several are equal
+    pub fn peak(&self) -> Option<(f32, f32)> {
+        self.times
+            .iter()
+            .zip(self.values.iter())
+            .fold(None, |peak: Option<(f32, f32)>, (t, v)| match peak {
+                Some((_, max)) if max >= *v => peak,
+                _ => Some((*t, *v)),
+            })
+    }
+    /// Number of samples in each of `bins` equal width value ranges from 0 to the maximum value
+    pub fn histogram(&self, bins: usize) -> Vec<f32> {
+        let mut counts = vec![0.; bins];
+        let width = self.max().max(std::f32::EPSILON) / bins as f32;
+        for v in self.values.iter() {
+            let bin = ((v / width) as usize).min(bins - 1);
+            counts[bin] += 1.;
+        }
+        counts
+    }
+    /// Number of samples in each `interval` seconds long time slice, from time 0 to the last sample
+    pub fn count_over_time(&self, interval: f32) -> Vec<f32> {
+        let slices = self
+            .times
+            .last()
+            .map(|t| (t / interval) as usize + 1)
+            .unwrap_or(0);
+        let mut counts = vec![0.; slices];
+        for t in self.times.iter() {
+            counts[((t / interval) as usize).min(slices - 1)] += 1.;
+        }
+        counts
+    }
+}
+
+/// Fixed rate sample times from `from` to `to` seconds, to resample channels onto
+#[derive(Debug, Clone, Copy)]
+pub struct Timeline {
+    pub from: f32,
+    /// Samples per second
+    pub rate: f32,
+    pub len: usize,
+}
+impl Timeline {
+    pub fn new(from: f32, to: f32, rate: f32) -> Self {
+        Self {
+            from,
+            rate,
+            len: ((to - from).max(0.) * rate) as usize + 1,
+        }
+    }
+    pub fn time(&self, idx: usize) -> f32 {
+        self.from + idx as f32 / self.rate
+    }
+    pub fn times(&self) -> impl Iterator<Item = f32> + '_ {
+        (0..self.len).map(move |idx| self.time(idx))
+    }
+}
+
+/// How a channel's value at a timeline sample is derived from its irregular samples
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Resample {
+    /// Interpolates between the samples around the time, for continuous quantities like error
+    Linear,
+    /// Holds the latest sample at or before the time
+    Hold,
+    /// Mean of the samples within half a timeline step of the time, for per-event channels
+    BinMean,
+}
+impl fmt::Display for Resample {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Resample::Linear => write!(f, "linear"),
+            Resample::Hold => write!(f, "hold"),
+            Resample::BinMean => write!(f, "mean"),
+        }
+    }
+}
+pub const RESAMPLE_MODES: [Resample; 3] = [Resample::Linear, Resample::Hold, Resample::BinMean];
+impl FromStr for Resample {
+    type Err = String;
+    fn from_str(s: &str) -> Result<Self, String> {
+        RESAMPLE_MODES
+            .iter()
+            .find(|mode| mode.to_string() == s)
+            .cloned()
+            .ok_or_else(|| format!("unknown resampling {}", s))
+    }
+}
+
+impl MetricChannel {
+    /// Value at every sample of `timeline`, `None` where the channel has no value: before its
+    /// first sample, after its last one except when holding, and in bins without samples
+    pub fn resample(&self, timeline: &Timeline, mode: Resample) -> Vec<Option<f32>> {
+        let mut out = Vec::with_capacity(timeline.len);
+        // index of the first sample after the current time, samples being in time order
+        let mut next = 0;
+        for t in timeline.times() {
+            let value = match mode {
+                Resample::Linear | Resample::Hold => {
+                    while next < self.times.len() && self.times[next] <= t {
+                        next += 1;
+                    }
+                    match (next.checked_sub(1), self.times.get(next)) {
+                        (Some(prev), _) if self.times[prev] == t || mode == Resample::Hold => {
+                            Some(self.values[prev])
+                        }
+                        (Some(prev), Some(t1)) => {
+                            let t0 = self.times[prev];
+                            let (v0, v1) = (self.values[prev], self.values[next]);
+                            Some(v0 + (v1 - v0) * (t - t0) / (t1 - t0).max(std::f32::EPSILON))
+                        }
+                        _ => None,
+                    }
+                }
+                Resample::BinMean => {
+                    let half_step = 0.5 / timeline.rate;
+                    let (sum, count) = self
+                        .times
+                        .iter()
+                        .zip(self.values.iter())
+                        .skip(next)
+                        .take_while(|(time, _)| **time < t + half_step)
+                        .filter(|(time, _)| **time >= t - half_step)
+                        .fold((0., 0), |(sum, count), (_, v)| (sum + v, count + 1));
+                    while next < self.times.len() && self.times[next] < t + half_step {
+                        next += 1;
+                    }
+                    if count > 0 {
+                        Some(sum / count as f32)
+                    } else {
+                        None
+                    }
+                }
+            };
+            out.push(value);
+        }
+        out
+    }
+}
+
+/// Items of `a` and `b` merged by `time`, keeping the order within each and taking `a`'s items
+/// first at equal times
+pub fn merge_by_time<T>(a: Vec<T>, b: Vec<T>, time: impl Fn(&T) -> f32) -> Vec<T> {
+    let mut merged = Vec::with_capacity(a.len() + b.len());
+    let (mut a, mut b) = (a.into_iter().peekable(), b.into_iter().peekable());
+    loop {
+        let next = match (a.peek(), b.peek()) {
+            (Some(x), Some(y)) if time(x) <= time(y) => a.next(),
+            (Some(_), Some(_)) => b.next(),
+            (Some(_), None) => a.next(),
+            (None, Some(_)) => b.next(),
+            (None, None) => return merged,
+        };
+        merged.extend(next);
+    }
+}
+
+/// Pearson correlation of two resampled channels over the samples both have a value at
+pub fn correlation(a: &[Option<f32>], b: &[Option<f32>]) -> Option<f32> {
+    let pairs: Vec<(f32, f32)> = a
+        .iter()
+        .zip(b.iter())
+        .filter_map(|(a, b)| Some(((*a)?, (*b)?)))
+        .collect();
+    if pairs.len() < 2 {
+        return None;
+    }
+    let n = pairs.len() as f32;
+    let mean_a = pairs.iter().map(|(a, _)| a).sum::<f32>() / n;
+    let mean_b = pairs.iter().map(|(_, b)| b).sum::<f32>() / n;
+    let (mut cov, mut var_a, mut var_b) = (0., 0., 0.);
+    for (a, b) in pairs.iter() {
+        cov += (a - mean_a) * (b - mean_b);
+        var_a += (a - mean_a) * (a - mean_a);
+        var_b += (b - mean_b) * (b - mean_b);
+    }
+    if var_a == 0. || var_b == 0. {
+        return None;
+    }
+    Some(cov / (var_a * var_b).sqrt())
+}
+
+/// Element-wise `b - a` of two resampled channels
+pub fn difference(a: &[Option<f32>], b: &[Option<f32>]) -> Vec<Option<f32>> {
+    a.iter()
+        .zip(b.iter())
+        .map(|(a, b)| Some((*b)? - (*a)?))
+        .collect()
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct MetricSummary {
+    pub count: usize,
+    pub mean: f32,
+    pub min: f32,
+    pub max: f32,
+}
+impl MetricChannel {
+    pub fn summary(&self) -> MetricSummary {
+        MetricSummary {
+            count: self.len(),
+            mean: self.mean(),
+            min: self.min(),
+            max: self.max(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Metrics {
+    channels: BTreeMap<String, MetricChannel>,
+}
+impl Metrics {
+    pub fn record(&mut self, name: &str, t: f32, v: f32) {
+        if let Some(channel) = self.channels.get_mut(name) {
+            channel.push(t, v);
+        } else {
+            let mut channel = MetricChannel::default();
+            channel.push(t, v);
+            self.channels.insert(name.to_owned(), channel);
+        }
+    }
+    pub fn get(&self, name: &str) -> Option<&MetricChannel> {
+        self.channels.get(name)
+    }
+    pub fn remove(&mut self, name: &str) -> Option<MetricChannel> {
+        self.channels.remove(name)
+    }
+    pub fn iter(&self) -> impl Iterator<Item = (&String, &MetricChannel)> {
+        self.channels.iter()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.channels.is_empty()
+    }
+    /// Approximate heap bytes of the names and samples of the channels
+    pub fn memory_bytes(&self) -> usize {
+        self.channels
+            .iter()
+            .map(|(name, channel)| {
+                name.capacity()
+                    + (channel.times.capacity() + channel.values.capacity())
+                        * std::mem::size_of::<f32>()
+            })
+            .sum()
+    }
+    /// Adds the channels of `other`, merging the ones both have by time with this one's samples
+    /// first at equal times
+    pub fn merge(&mut self, other: Metrics) {
+        for (name, channel) in other.channels {
+            let merged = match self.channels.remove(&name) {
+                Some(mine) => mine.merge(channel),
+                None => channel,
+            };
+            self.channels.insert(name, merged);
+        }
+    }
+    /// Drops the samples recorded outside of times `from` to `to`, and the channels left without
+    /// samples
+    pub fn retain_window(&mut self, from: f32, to: f32) {
+        for channel in self.channels.values_mut() {
+            let (times, values) = channel
+                .times
+                .iter()
+                .zip(channel.values.iter())
+                .filter(|(time, _)| **time >= from && **time <= to)
+                .unzip();
+            channel.times = times;
+            channel.values = values;
+        }
+        self.channels.retain(|_, channel| !channel.is_empty());
+    }
+    /// Copy of the samples recorded from times `from` to `to`
+    pub fn window(&self, from: f32, to: f32) -> Metrics {
+        let mut window = self.clone();
+        window.retain_window(from, to);
+        window
+    }
+    pub fn summary(&self) -> BTreeMap<String, MetricSummary> {
+        self.channels
+            .iter()
+            .map(|(name, channel)| (name.clone(), channel.summary()))
+            .collect()
+    }
+    /// Earliest and latest sample time over every channel
+    pub fn time_range(&self) -> Option<(f32, f32)> {
+        self.channels
+            .values()
+            .filter(|channel| !channel.is_empty())
+            .map(|channel| (channel.times[0], channel.times[channel.times.len() - 1]))
+            .fold(None, |range, (from, to)| match range {
+                Some((f, t)) => Some((f32::min(f, from), f32::max(t, to))),
+                None => Some((from, to)),
+            })
+    }
+    /// Every channel resampled onto `timeline`, see `MetricChannel::resample`
+    pub fn resample(
+        &self,
+        timeline: &Timeline,
+        mode: Resample,
+    ) -> BTreeMap<String, Vec<Option<f32>>> {
+        self.channels
+            .iter()
+            .map(|(name, channel)| (name.clone(), channel.resample(timeline, mode)))
+            .collect()
+    }
+}
+
+/// Two-sided 95% Student's t critical values for 1 to 30 degrees of freedom
+const T_95: [f32; 30] = [
+    12.706, 4.303, 3.182, 2.776, 2.571, 2.447, 2.365, 2.306, 2.262, 2.228, 2.201, 2.179, 2.160,
+    2.145, 2.131, 2.120, 2.110, 2.101, 2.093, 2.086, 2.080, 2.074, 2.069, 2.064, 2.060, 2.056,
+    2.052, 2.048, 2.045, 2.042,
+];
+
+/// Mean of repeated measurements with the half width of its 95% confidence interval
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct ConfidenceInterval {
+    pub count: usize,
+    pub mean: f32,
+    pub half_width: f32,
+}
+impl ConfidenceInterval {
+    pub fn from_samples(values: &[f32]) -> Self {
+        let count = values.len();
+        if count == 0 {
+            return Self {
+                count,
+                mean: 0.,
+                half_width: 0.,
+            };
+        }
+        let mean = values.iter().sum::<f32>() / count as f32;
+        if count == 1 {
+            return Self {
+                count,
+                mean,
+                half_width: 0.,
+            };
+        }
+        let variance =
+            values.iter().map(|v| (v - mean) * (v - mean)).sum::<f32>() / (count - 1) as f32;
+        let t = T_95.get(count - 2).cloned().unwrap_or(1.96);
+        Self {
+            count,
+            mean,
+            half_width: t * (variance / count as f32).sqrt(),
+        }
+    }
+    pub fn low(&self) -> f32 {
+        self.mean - self.half_width
+    }
+    pub fn high(&self) -> f32 {
+        self.mean + self.half_width
+    }
+}
@@ -0,0 +1,157 @@
+//! Simulated network between the server and the client world. The monkey of each world decides
+//! for every packet the world sends whether it's lost and when it arrives, uniform loss by
+//! `loss_percentage` and burst loss by `loss::GilbertElliott` as well as the latency of every
+//! `LatencyModel`. The packet then waits in the other world's `InFlight` queue until it arrives.
+//!
+//! Packets are sent and received at the virtual time the world's step ends at, so what a step
+//! receives only depends on when the steps of both worlds end, not on how they interleave
+use crate::{latency, loss::GilbertElliott, rng::SimRng, sim::SimSettings};
+use rand::{rngs::SmallRng, Rng};
+use std::sync::{Arc, Mutex};
+
+/// Packets on their way to a world with their arrival time in seconds, shared by the sending
+/// and the receiving world
+#[derive(Clone, Default)]
+pub struct InFlight(Arc<Mutex<Vec<(f32, Vec<u8>)>>>);
+impl InFlight {
+    /// Sends `payload` to arrive at `arrival`
+    pub fn push(&self, arrival: f32, payload: Vec<u8>) {
+        self.0.lock().unwrap().push((arrival, payload));
+    }
+    /// Takes the payloads arrived by `now` in arrival order, those arriving together in the
+    /// order they were sent
+    pub fn arrived(&self, now: f32) -> Vec<Vec<u8>> {
+        let mut queue = self.0.lock().unwrap();
+        let (mut due, rest): (Vec<_>, Vec<_>) =
+            queue.drain(..).partition(|(arrival, _)| *arrival <= now);
+        *queue = rest;
+        due.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        due.into_iter().map(|(_, payload)| payload).collect()
+    }
+}
+
+enum Loss {
+    /// Every packet lost independently with the probability
+    Uniform(f32, SmallRng),
+    Burst(GilbertElliott),
+}
+
+/// A world's end of the simulated network
+pub struct SimMonkey {
+    settings: SimSettings,
+    loss: Loss,
+    latency_rng: SmallRng,
+    outgoing: InFlight,
+    incoming: InFlight,
+    /// Virtual time in seconds the world's current step ends at
+    now: f32,
+}
+impl SimMonkey {
+    /// Ends of the server and the client world of a run with `settings`, each drawing from
+    /// streams of its own
+    pub fn pair(settings: &SimSettings, sim_rng: &SimRng) -> (SimMonkey, SimMonkey) {
+        let (to_server, to_client) = (InFlight::default(), InFlight::default());
+        (
+            Self::new(
+                settings,
+                sim_rng,
+                "server",
+                to_client.clone(),
+                to_server.clone(),
+            ),
+            Self::new(settings, sim_rng, "client", to_server, to_client),
+        )
+    }
+    fn new(
+        settings: &SimSettings,
+        sim_rng: &SimRng,
+        side: &str,
+        outgoing: InFlight,
+        incoming: InFlight,
+    ) -> Self {
+        let loss_rng = sim_rng.stream(&format!("{} loss", side));
+        let loss = if settings.burst_loss {
+            Loss::Burst(GilbertElliott::new(settings, loss_rng))
+        } else {
+            Loss::Uniform(settings.loss_percentage, loss_rng)
+        };
+        Self {
+            settings: settings.clone(),
+            loss,
+            latency_rng: sim_rng.stream(&format!("{} latency", side)),
+            outgoing,
+            incoming,
+            now: 0.,
+        }
+    }
+    /// Sets the virtual time the world's next step ends at, which its packets are sent and
+    /// received at
+    pub fn set_now(&mut self, now: f32) {
+        self.now = now;
+    }
+    /// Whether the next packet sent is lost
+    fn drops(&mut self) -> bool {
+        match &mut self.loss {
+            Loss::Uniform(loss, rng) => rng.gen::<f32>() < *loss,
+            Loss::Burst(chain) => chain.drops(),
+        }
+    }
+    /// Sends `payload` to the other world, unless it's lost
+    pub fn send(&mut self, payload: &[u8]) {
+        if self.drops() {
+            return;
+        }
+        let arrival = self.now + latency::sample(&self.settings, &mut self.latency_rng);
+        self.outgoing.push(arrival, payload.to_vec());
+    }
+    /// Takes the payloads from the other world that arrived by now
+    pub fn receive(&mut self) -> Vec<Vec<u8>> {
+        self.incoming.arrived(self.now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_loss_rate() {
+        let mut settings = SimSettings::default();
+        settings.loss_percentage = 0.2;
+        let (mut monkey, _) = SimMonkey::pair(&settings, &SimRng::new(3));
+        let lost = (0..50_000).filter(|_| monkey.drops()).count() as f32 / 50_000.;
+        assert!((lost - 0.2).abs() < 0.01, "{}", lost);
+        settings.loss_percentage = 0.;
+        let (mut monkey, _) = SimMonkey::pair(&settings, &SimRng::new(3));
+        assert!((0..1000).all(|_| !monkey.drops()));
+    }
+
+    #[test]
+    fn receives_in_arrival_order() {
+        let mut settings = SimSettings::default();
+        settings.loss_percentage = 0.;
+        settings.latency_model = latency::LatencyModel::Normal;
+        settings.latency_mean = 50.;
+        settings.latency_stddev = 30.;
+        let (mut server, mut client) = SimMonkey::pair(&settings, &SimRng::new(7));
+        for idx in 0..100u8 {
+            server.set_now(idx as f32 * 0.01);
+            server.send(&[idx]);
+        }
+        let mut expected: Vec<(f32, Vec<u8>)> = client.incoming.0.lock().unwrap().clone();
+        expected.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let mut received = Vec::new();
+        for step in 1..=200 {
+            let now = step as f32 * 0.01;
+            client.set_now(now);
+            for payload in client.receive() {
+                let arrival = expected.iter().find(|(_, p)| *p == payload).unwrap().0;
+                assert!(arrival <= now);
+                received.push(payload);
+            }
+        }
+        let expected: Vec<_> = expected.into_iter().map(|(_, payload)| payload).collect();
+        assert_eq!(received, expected);
+        assert!(server.receive().is_empty());
+    }
+}
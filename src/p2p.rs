@@ -0,0 +1,287 @@
+use crate::{metrics::Metrics, rng::SimRng, sim::SimSettings};
+use amethyst::Result;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::{fmt, io};
+
+/// Number of peers without a latency matrix
+const DEFAULT_PEER_COUNT: usize = 2;
+/// Peer that forwards all other peers' messages in a star topology
+const STAR_HOST: usize = 0;
+/// Weight of a new round trip time sample in the smoothed estimate
+const RTT_SMOOTHING: f32 = 0.1;
+
+/// How the peers choose their input delay
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputDelayPolicy {
+    /// Always use the configured input delay
+    Fixed,
+    /// Periodically agree on the delay covering the largest one-way latency any peer measured
+    Negotiated,
+}
+impl fmt::Display for InputDelayPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputDelayPolicy::Fixed => write!(f, "Fixed"),
+            InputDelayPolicy::Negotiated => write!(f, "Negotiated"),
+        }
+    }
+}
+pub const INPUT_DELAY_POLICIES: [InputDelayPolicy; 2] =
+    [InputDelayPolicy::Fixed, InputDelayPolicy::Negotiated];
+
+/// Route the input messages between two peers take
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Topology {
+    /// Every peer sends directly to every other peer
+    Mesh,
+    /// Peers send to the host peer, which forwards to the other peers
+    Star,
+    /// Peers send to a relay server, which forwards to the other peers
+    Relay,
+}
+impl fmt::Display for Topology {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Topology::Mesh => write!(f, "Mesh"),
+            Topology::Star => write!(f, "Star"),
+            Topology::Relay => write!(f, "Relay"),
+        }
+    }
+}
+pub const TOPOLOGIES: [Topology; 3] = [Topology::Mesh, Topology::Star, Topology::Relay];
+
+/// Latency range of the peer-to-peer links from `time` on
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct LatencyChange {
+    /// Seconds since the start of the run
+    pub time: f32,
+    pub min_latency: f32,
+    pub max_latency: f32,
+}
+
+/// Latency range in ms at `time`, by default the base latency and 95th percentile of the latency
+/// model, the range being the jitter added to every hop
+fn latency_at(settings: &SimSettings, time: f32) -> (f32, f32) {
+    settings
+        .latency_changes
+        .iter()
+        .filter(|c| c.time <= time)
+        .max_by(|a, b| a.time.partial_cmp(&b.time).unwrap())
+        .map(|c| (c.min_latency, c.max_latency))
+        .unwrap_or_else(|| settings.latency_range())
+}
+
+fn peer_count(settings: &SimSettings) -> usize {
+    if settings.latency_matrix.is_empty() {
+        DEFAULT_PEER_COUNT
+    } else {
+        settings.latency_matrix.len()
+    }
+}
+
+/// Base latency in ms of each hop from peer `from` to peer `to`
+fn route(settings: &SimSettings, from: usize, to: usize, min_latency: f32) -> Vec<f32> {
+    let direct = |a: usize, b: usize| {
+        settings
+            .latency_matrix
+            .get(a)
+            .and_then(|row| row.get(b))
+            .cloned()
+            .unwrap_or(min_latency)
+    };
+    let relay = |peer: usize| {
+        settings
+            .relay_latencies
+            .get(peer)
+            .cloned()
+            .unwrap_or(min_latency)
+    };
+    match settings.topology {
+        Topology::Star if from != STAR_HOST && to != STAR_HOST => {
+            vec![direct(from, STAR_HOST), direct(STAR_HOST, to)]
+        }
+        Topology::Mesh | Topology::Star => vec![direct(from, to)],
+        Topology::Relay => vec![relay(from), relay(to)],
+    }
+}
+
+/// Inputs of every frame up to `frame + input_delay`, sent by peer `from` while simulating `frame`
+struct InputMessage {
+    from: usize,
+    to: usize,
+    /// Send time plus one-way latency in seconds
+    arrival: f32,
+    latency: f32,
+    frame: i64,
+}
+
+struct Peer {
+    next_tick: f32,
+    /// Last simulated frame, -1 before the first
+    frame: i64,
+    /// Latest frame each remote peer sent its inputs at, with the latency and arrival time of
+    /// that message
+    remote: Vec<Option<(i64, f32, f32)>>,
+    /// Smoothed round trip time in seconds, assuming symmetric links
+    rtt: Option<f32>,
+}
+impl Peer {
+    /// Last frame the inputs of remote peer `idx` are known for
+    fn confirmed(&self, idx: usize, input_delay: i64) -> i64 {
+        // frames before the input delay have no inputs
+        self.remote[idx]
+            .map(|(frame, _, _)| frame + input_delay)
+            .unwrap_or(input_delay - 1)
+    }
+    /// Frames this peer is ahead of the furthest behind remote peer, estimating each remote's
+    /// current frame from its latest input message, GGPO style
+    fn frame_advantage(&self, idx: usize, now: f32, fps: f32) -> f32 {
+        self.remote
+            .iter()
+            .enumerate()
+            .filter(|(remote, _)| *remote != idx)
+            .map(|(_, remote)| match remote {
+                Some((frame, latency, arrival)) => {
+                    self.frame as f32 - (*frame as f32 + (latency + now - arrival) * fps)
+                }
+                None => 0.,
+            })
+            .fold(std::f32::INFINITY, f32::min)
+    }
+}
+
+/// Runs peers that exchange only their inputs and simulate ahead of the confirmed inputs with
+/// rollback, recording per peer "frame advantage", "rollback frames" and "stall frames" metrics.
+/// Every late input is assumed to have been mispredicted, so the rollback cost is an upper
+/// bound.
+pub fn simulate(settings: &SimSettings, metrics: &mut Metrics) -> Result<()> {
+    let peer_count = peer_count(settings);
+    if settings
+        .latency_matrix
+        .iter()
+        .any(|row| row.len() != peer_count)
+    {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "latency matrix is not square".to_owned(),
+        )
+        .into());
+    }
+    let mut rng = SimRng::new(settings.seed).stream("peer to peer");
+    let fps = settings.server_fps as f32;
+    let frame_time = 1. / fps;
+    let mut input_delay = settings.input_delay as i64;
+    let mut next_negotiation = 0.;
+    let mut peers: Vec<Peer> = (0..peer_count)
+        .map(|idx| Peer {
+            next_tick: idx as f32 * settings.peer_start_offset / 1000.,
+            frame: -1,
+            remote: vec![None; peer_count],
+            rtt: None,
+        })
+        .collect();
+    let mut in_flight: Vec<InputMessage> = Vec::new();
+    loop {
+        let (idx, now) = peers
+            .iter()
+            .enumerate()
+            .map(|(idx, peer)| (idx, peer.next_tick))
+            .fold(
+                (0, std::f32::INFINITY),
+                |a, b| if b.1 < a.1 { b } else { a },
+            );
+        if now > settings.duration {
+            break;
+        }
+        if settings.input_delay_policy == InputDelayPolicy::Negotiated && now >= next_negotiation {
+            let rtt = peers
+                .iter()
+                .filter_map(|p| p.rtt)
+                .fold(None, |max: Option<f32>, rtt| {
+                    Some(max.map_or(rtt, |m| m.max(rtt)))
+                });
+            if let Some(rtt) = rtt {
+                input_delay = (rtt / 2. * fps).ceil() as i64;
+            }
+            next_negotiation = now + settings.renegotiation_interval;
+        }
+        if idx == 0 {
+            metrics.record("input delay", now, input_delay as f32);
+        }
+        let peer = &mut peers[idx];
+        peer.next_tick += frame_time;
+        let confirmed_before: Vec<i64> = (0..peer_count)
+            .map(|r| peer.confirmed(r, input_delay))
+            .collect();
+        for msg in in_flight.iter().filter(|m| m.to == idx && m.arrival <= now) {
+            let newer = peer.remote[msg.from]
+                .map(|(frame, _, _)| msg.frame > frame)
+                .unwrap_or(true);
+            if newer {
+                peer.remote[msg.from] = Some((msg.frame, msg.latency, msg.arrival));
+            }
+            let rtt = msg.latency * 2.;
+            peer.rtt = Some(peer.rtt.map_or(rtt, |r| r + (rtt - r) * RTT_SMOOTHING));
+        }
+        in_flight.retain(|m| m.to != idx || m.arrival > now);
+        let remotes = (0..peer_count).filter(|r| *r != idx);
+        // re-simulate from the oldest frame whose remote inputs were predicted and have arrived since
+        let rollback_from = remotes
+            .clone()
+            .filter(|r| peer.confirmed(*r, input_delay) > confirmed_before[*r])
+            .map(|r| confirmed_before[r] + 1)
+            .min();
+        let rollback_frames = rollback_from
+            .map(|from| (peer.frame - from + 1).max(0))
+            .unwrap_or(0);
+        let oldest_confirmed = remotes
+            .map(|r| peer.confirmed(r, input_delay))
+            .min()
+            .unwrap_or(peer.frame);
+        let stalled = peer.frame + 1 > oldest_confirmed + settings.max_prediction as i64;
+        if !stalled {
+            peer.frame += 1;
+            let (min_latency, max_latency) = latency_at(settings, now);
+            for to in (0..peer_count).filter(|to| *to != idx) {
+                let mut latency = 0.;
+                let mut lost = false;
+                for hop in route(settings, idx, to, min_latency) {
+                    // later messages repeat all unacknowledged inputs, so a lost message only delays them
+                    lost |= rng.gen::<f32>() < settings.loss_percentage;
+                    latency += hop;
+                    if max_latency > min_latency {
+                        latency += rng.gen_range(0., max_latency - min_latency);
+                    }
+                }
+                if lost {
+                    continue;
+                }
+                let latency = latency / 1000.;
+                in_flight.push(InputMessage {
+                    from: idx,
+                    to,
+                    arrival: now + latency,
+                    latency,
+                    frame: peer.frame,
+                });
+            }
+        }
+        metrics.record(
+            &format!("frame advantage {}", idx),
+            now,
+            peer.frame_advantage(idx, now, fps),
+        );
+        metrics.record(
+            &format!("rollback frames {}", idx),
+            now,
+            rollback_frames as f32,
+        );
+        metrics.record(
+            &format!("stall frames {}", idx),
+            now,
+            if stalled { 1. } else { 0. },
+        );
+    }
+    Ok(())
+}
@@ -0,0 +1,421 @@
+//! Client pipelines composed from reusable stages, so combinations of buffering, prediction,
+//! reconciliation, smoothing and filtering are configured in the settings rather than each
+//! needing its own `SimulationBehaviour`
+use crate::{
+    codec::SyncCodec,
+    filter::{new_filter, FilterKind, PositionFilter},
+    metrics::Metrics,
+    scenario::{events_label, frame_events, TimedEvent},
+    sequence::{Delivery, SequencedReceiver},
+    sim::{
+        DeterministicSimulation, LocalClock, Sample, SimSettings, SimSide, SimulationBehaviour,
+        SimulationResult, SimulationState,
+    },
+    snapshot_buffer::{Kernel, Key, SnapshotBuffer},
+    truth::{GroundTruth, ServerTruth},
+};
+use amethyst::core::{math::Vector3, Time};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt, time::Duration};
+
+/// Suffix of the display names of composed behaviours
+const COMPOSED_SUFFIX: &str = " (Composed Pipeline)";
+
+/// One stage of a composed client pipeline. State stages pass on a simulation state with the
+/// server time it's for, position stages the rendered position, so every state stage has to
+/// come before the first position stage
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum PipelineStage {
+    /// Interpolates the received states `delay` ms behind the clock synced to the server
+    Buffer { delay: f32 },
+    /// Simulates the state forward from its time to the present server time
+    Predict,
+    /// Keeps simulating its own state, only taking on the incoming one when the two are more
+    /// than `deadband` apart
+    Reconcile { deadband: f32 },
+    /// Averages the positions of the last `window` ms
+    Smooth { window: f32 },
+    /// Filters the positions with the filter of `kind`, parameterized by the filter settings
+    Filter { kind: FilterKind },
+}
+impl PipelineStage {
+    /// Whether the stage passes on a position rather than a state
+    pub fn is_position_stage(&self) -> bool {
+        match self {
+            PipelineStage::Smooth { .. } | PipelineStage::Filter { .. } => true,
+            _ => false,
+        }
+    }
+    /// Kind of the stage, naming its metric channels
+    pub fn name(&self) -> &'static str {
+        match self {
+            PipelineStage::Buffer { .. } => "buffer",
+            PipelineStage::Predict => "predict",
+            PipelineStage::Reconcile { .. } => "reconcile",
+            PipelineStage::Smooth { .. } => "smooth",
+            PipelineStage::Filter { .. } => "filter",
+        }
+    }
+}
+impl fmt::Display for PipelineStage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PipelineStage::Buffer { delay } => write!(f, "buffer {} ms", delay),
+            PipelineStage::Predict => write!(f, "predict"),
+            PipelineStage::Reconcile { deadband } => write!(f, "reconcile {}", deadband),
+            PipelineStage::Smooth { window } => write!(f, "smooth {} ms", window),
+            PipelineStage::Filter { kind } => write!(f, "filter {}", kind),
+        }
+    }
+}
+/// A stage of each kind with its default parameters, as offered by the GUI
+pub const PIPELINE_STAGES: [PipelineStage; 5] = [
+    PipelineStage::Buffer { delay: 100. },
+    PipelineStage::Predict,
+    PipelineStage::Reconcile { deadband: 0.01 },
+    PipelineStage::Smooth { window: 50. },
+    PipelineStage::Filter {
+        kind: FilterKind::Exponential,
+    },
+];
+/// Label of a pipeline, its stages in order
+pub fn pipeline_label(stages: &[PipelineStage]) -> String {
+    if stages.is_empty() {
+        return "latest state".to_owned();
+    }
+    stages
+        .iter()
+        .map(|s| s.to_string())
+        .collect::<Vec<_>>()
+        .join(" → ")
+}
+/// Whether `behaviour` runs the client pipeline of `SimSettings::pipeline`
+pub fn is_composed(behaviour: &dyn SimulationBehaviour) -> bool {
+    behaviour.to_string().ends_with(COMPOSED_SUFFIX)
+}
+
+/// Runs the server simulation as usual and the client as the stack of stages of
+/// `SimSettings::pipeline`, starting from the latest received state
+#[derive(Default)]
+pub struct ComposedPipeline<T> {
+    _marker: std::marker::PhantomData<T>,
+}
+impl<T: fmt::Display + Default> fmt::Display for ComposedPipeline<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}{}", T::default(), COMPOSED_SUFFIX)
+    }
+}
+impl<T: DeterministicSimulation + fmt::Display> SimulationBehaviour for ComposedPipeline<T> {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        let first_position = settings
+            .pipeline
+            .iter()
+            .position(|s| s.is_position_stage())
+            .unwrap_or(settings.pipeline.len());
+        let stages = settings
+            .pipeline
+            .iter()
+            .enumerate()
+            .filter(|(idx, stage)| {
+                // a state stage can't follow a position stage, the state is gone by then
+                let misplaced = *idx > first_position && !stage.is_position_stage();
+                if misplaced {
+                    log::warn!("skipping pipeline stage {} after a position stage", stage);
+                }
+                !misplaced
+            })
+            .collect::<Vec<_>>();
+        // the input and every stage is named by its position in the configured pipeline
+        let stage_outputs = std::iter::once("stage 0 input".to_owned())
+            .chain(
+                stages
+                    .iter()
+                    .map(|(idx, stage)| format!("stage {} {}", idx + 1, stage.name())),
+            )
+            .map(|name| (name, Vec::new()))
+            .collect();
+        let stages = stages
+            .into_iter()
+            .map(|(_, stage)| match *stage {
+                PipelineStage::Buffer { delay } => Stage::Buffer { delay },
+                PipelineStage::Predict => Stage::Predict,
+                PipelineStage::Reconcile { deadband } => Stage::Reconcile {
+                    deadband,
+                    own: None,
+                },
+                PipelineStage::Smooth { window } => Stage::Smooth {
+                    window,
+                    history: VecDeque::new(),
+                },
+                PipelineStage::Filter { kind } => Stage::Filter(new_filter(&SimSettings {
+                    filter: kind,
+                    ..settings.clone()
+                })),
+            })
+            .collect();
+        Box::new(ComposedPipelineState {
+            server: T::initial(settings),
+            template: T::initial(settings),
+            received: SnapshotBuffer::new(),
+            latest: None,
+            clock: None,
+            sequence: SequencedReceiver::new(),
+            stages,
+            server_fps: settings.server_fps,
+            events: settings.events.clone(),
+            server_label: None,
+            last_correction: None,
+            stage_outputs,
+            settings: settings.clone(),
+        })
+    }
+    fn description(&self) -> String {
+        format!(
+            "{}\n\n\
+            In this variant the client runs the pipeline of stages configured in the settings \
+            on the latest received state: buffering interpolates the received states a delay in \
+            the past, prediction simulates the state forward to the present, reconciliation \
+            keeps simulating its own state until it drifts beyond a dead-band from the incoming \
+            one, smoothing averages the positions over a window and filtering runs a position \
+            filter. Expect the artifacts of each stage to combine.\n\n\
+            Relevant settings: the pipeline, server fps, sync rate, min and max latency ms and \
+            the filter parameters.",
+            T::default().description()
+        )
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        T::ground_truth(settings)
+    }
+}
+
+/// Instantiated `PipelineStage` with the state it keeps between frames
+enum Stage<T: DeterministicSimulation> {
+    Buffer {
+        delay: f32,
+    },
+    Predict,
+    Reconcile {
+        deadband: f32,
+        /// Server time and state of the stage's own simulation
+        own: Option<(f32, T)>,
+    },
+    Smooth {
+        window: f32,
+        history: VecDeque<(f32, Vector3<f32>)>,
+    },
+    Filter(Box<dyn PositionFilter>),
+}
+
+/// Value passed from one stage to the next
+enum Estimate<T: DeterministicSimulation> {
+    /// State at a server time
+    State(f32, T::SyncType),
+    Position(Vector3<f32>),
+}
+
+pub struct ComposedPipelineState<T: DeterministicSimulation> {
+    server: T,
+    /// Simulation as configured by the settings, cloned for every client simulation
+    template: T,
+    /// Received states keyed by server time
+    received: SnapshotBuffer<T::SyncType>,
+    /// Server time and state of the newest received state
+    latest: Option<(f32, T::SyncType)>,
+    clock: Option<LocalClock>,
+    sequence: SequencedReceiver,
+    stages: Vec<Stage<T>>,
+    server_fps: u32,
+    events: Vec<TimedEvent>,
+    server_label: Option<String>,
+    /// Local time, size and whether it was taken on of the latest reconciliation
+    last_correction: Option<(f32, f32, bool)>,
+    /// Metric name and the rendered positions by local time of the pipeline's input, the latest
+    /// received state, and of the output of each stage
+    stage_outputs: Vec<(String, Vec<(f32, Vector3<f32>)>)>,
+    /// Settings of the run, for the ground truth the stage outputs are measured from
+    settings: SimSettings,
+}
+impl<T: DeterministicSimulation> ComposedPipelineState<T> {
+    /// Simulates `sim` forward from server time `from` in server ticks while a whole tick fits
+    /// before `to`, returning the time it reached
+    fn simulate(&self, sim: &mut T, from: f32, to: f32) -> f32 {
+        let dt = 1. / self.server_fps as f32;
+        let mut t = from;
+        while t + dt <= to {
+            t += dt;
+            sim.update(Duration::from_secs_f32(t), Duration::from_secs_f32(dt));
+        }
+        t
+    }
+}
+impl<T: DeterministicSimulation> SimulationState for ComposedPipelineState<T> {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(self.server.send_state())
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        // states are keyed by server time, so late ones still land in the right place
+        let delivery = self.sequence.recv(seq);
+        if delivery == Delivery::Duplicate {
+            return;
+        }
+        if self.clock.is_none() {
+            self.clock = Some(LocalClock::synced_to_server(
+                time,
+                server_time,
+                server_frame,
+                self.server_fps,
+            ));
+        }
+        let state: T::SyncType = codec.decode(msg);
+        let t = server_time.as_secs_f32();
+        if delivery == Delivery::Latest {
+            self.latest = Some((t, state.clone()));
+        }
+        self.received.add(Key::new(t, state, Kernel::Linear));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        let clock = self.clock?;
+        let render_time = time.absolute_time().as_secs_f32();
+        // the present server time, `playback_time` being a tick in the past
+        let now = clock.playback_time(time, 0.) + 1. / self.server_fps as f32;
+        let (latest_t, latest) = self.latest.clone()?;
+        let mut estimate = Estimate::<T>::State(latest_t, latest);
+        let input = self.position(&estimate);
+        self.stage_outputs[0].1.push((render_time, input));
+        let mut stages = std::mem::replace(&mut self.stages, Vec::new());
+        let mut oldest_needed = latest_t;
+        for (idx, stage) in stages.iter_mut().enumerate() {
+            estimate = match (stage, estimate) {
+                (Stage::Buffer { delay }, Estimate::State(t, state)) => {
+                    let at = now - *delay / 1000.;
+                    oldest_needed = oldest_needed.min(at);
+                    match self.received.clamped_sample(at) {
+                        Some(buffered) => Estimate::State(at, buffered),
+                        None => Estimate::State(t, state),
+                    }
+                }
+                (Stage::Predict, Estimate::State(t, state)) => {
+                    let mut sim = self.template.clone();
+                    sim.recv_state(state);
+                    let t = self.simulate(&mut sim, t, now);
+                    Estimate::State(t, sim.send_state().clone())
+                }
+                (Stage::Reconcile { deadband, own }, Estimate::State(t, state)) => {
+                    let (own_t, mut sim) = match own.take() {
+                        Some(own) => own,
+                        None => {
+                            let mut sim = self.template.clone();
+                            sim.recv_state(state.clone());
+                            (t, sim)
+                        }
+                    };
+                    let own_t = self.simulate(&mut sim, own_t, t);
+                    let error =
+                        (sim.pos_sample(sim.send_state()).pos - sim.pos_sample(&state).pos).norm();
+                    let take_on = error > *deadband;
+                    if error > 0. {
+                        self.last_correction = Some((render_time, error, take_on));
+                    }
+                    let (own_t, sim) = if take_on {
+                        sim.recv_state(state);
+                        (t, sim)
+                    } else {
+                        (own_t, sim)
+                    };
+                    let estimate = Estimate::State(own_t, sim.send_state().clone());
+                    *own = Some((own_t, sim));
+                    estimate
+                }
+                (Stage::Smooth { window, history }, estimate) => {
+                    let pos = self.position(&estimate);
+                    history.push_back((render_time, pos));
+                    while history
+                        .front()
+                        .map_or(false, |(t, _)| *t < render_time - *window / 1000.)
+                    {
+                        history.pop_front();
+                    }
+                    let sum = history.iter().fold(Vector3::zeros(), |sum, (_, p)| sum + p);
+                    Estimate::Position(sum / history.len() as f32)
+                }
+                (Stage::Filter(filter), estimate) => {
+                    let pos = self.position(&estimate);
+                    Estimate::Position(filter.filter(render_time, pos))
+                }
+                // state stages after position stages are dropped in `new_state`
+                (_, estimate) => estimate,
+            };
+            let output = self.position(&estimate);
+            self.stage_outputs[idx + 1].1.push((render_time, output));
+        }
+        self.stages = stages;
+        self.received.prune_before(oldest_needed);
+        let pos = self.position(&estimate);
+        Some(Sample { pos, alpha: 1. })
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.received.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        for event in frame_events(&self.events, time) {
+            self.server.apply_event(event);
+        }
+        self.server_label = events_label(frame_events(&self.events, time));
+        self.server.update(time.absolute_time(), time.delta_time());
+        self.server.pos_sample(self.server.send_state())
+    }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        match side {
+            SimSide::Server => self.server_label.clone(),
+            SimSide::Client => None,
+        }
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        if let (SimSide::Client, Some((t, error, taken_on))) = (side, self.last_correction) {
+            if t == now {
+                let name = if taken_on {
+                    "correction"
+                } else {
+                    "ignored correction"
+                };
+                metrics.record(name, now, error);
+            }
+        }
+    }
+    /// Records the error and the frame-to-frame jump of the input and of the output of each stage
+    /// as `stage <n> <kind> error` and `pop`, so the contribution of each stage can be told apart
+    fn finalize(&mut self, side: SimSide, result: &mut SimulationResult<Sample>) {
+        if side != SimSide::Client {
+            return;
+        }
+        let truth = ServerTruth::new(&result.frames, T::ground_truth(&self.settings));
+        for (name, outputs) in self.stage_outputs.iter() {
+            let (error, pop) = (format!("{} error", name), format!("{} pop", name));
+            for (t, pos) in outputs.iter() {
+                if let Some(server) = truth.at(None, *t, self.settings.error_reference) {
+                    result.record(&error, *t, (pos - server).norm());
+                }
+            }
+            for w in outputs.windows(2) {
+                result.record(&pop, w[1].0, (w[1].1 - w[0].1).norm());
+            }
+        }
+    }
+}
+impl<T: DeterministicSimulation> ComposedPipelineState<T> {
+    fn position(&self, estimate: &Estimate<T>) -> Vector3<f32> {
+        match estimate {
+            Estimate::State(_, state) => self.template.pos_sample(state).pos,
+            Estimate::Position(pos) => *pos,
+        }
+    }
+}
@@ -0,0 +1,261 @@
+//! Render plugin drawing frame markers as instanced quads from one vertex buffer, so results
+//! with millions of frames don't have to go through debug lines
+use amethyst::{
+    ecs::{DispatcherBuilder, World, WorldExt},
+    renderer::{
+        bundle::{RenderOrder, RenderPlan, RenderPlugin, Target},
+        pipeline::{PipelineDescBuilder, PipelinesBuilder},
+        rendy::{
+            command::{QueueId, RenderPassEncoder},
+            factory::Factory,
+            graph::{
+                render::{PrepareResult, RenderGroup, RenderGroupDesc},
+                GraphContext, NodeBuffer, NodeImage,
+            },
+            hal::{self, device::Device, format::Format, pso},
+            mesh::{AsVertex, VertexFormat},
+            shader::{PathBufShaderInfo, Shader, ShaderKind, SourceLanguage, SpirvShader},
+        },
+        submodules::{DynamicVertexBuffer, FlatEnvironmentSub},
+        types::Backend,
+        util::{self, ChangeDetection},
+    },
+    Error,
+};
+use lazy_static::*;
+use std::path::PathBuf;
+
+lazy_static! {
+    static ref VERTEX: SpirvShader = PathBufShaderInfo::new(
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/points.vert")),
+        ShaderKind::Vertex,
+        SourceLanguage::GLSL,
+        "main",
+    )
+    .precompile()
+    .expect("shaders/points.vert doesn't compile");
+    static ref FRAGMENT: SpirvShader = PathBufShaderInfo::new(
+        PathBuf::from(concat!(env!("CARGO_MANIFEST_DIR"), "/shaders/points.frag")),
+        ShaderKind::Fragment,
+        SourceLanguage::GLSL,
+        "main",
+    )
+    .precompile()
+    .expect("shaders/points.frag doesn't compile");
+}
+
+/// One ring shaped marker
+#[derive(Clone, Copy, Debug, PartialEq)]
+#[repr(C)]
+pub struct PointArgs {
+    /// Center in screen pixels
+    pub pos: [f32; 2],
+    /// Outer radius in pixels
+    pub radius: f32,
+    pub color: [f32; 4],
+}
+impl AsVertex for PointArgs {
+    fn vertex() -> VertexFormat {
+        VertexFormat::new((
+            (Format::Rg32Sfloat, "pos"),
+            (Format::R32Sfloat, "radius"),
+            (Format::Rgba32Sfloat, "color"),
+        ))
+    }
+}
+
+/// Markers drawn by `RenderPoints`, replaced all at once
+#[derive(Default)]
+pub struct Points {
+    points: Vec<PointArgs>,
+    /// Bumped with every replacement, so the points are only uploaded when they change
+    version: u64,
+}
+impl Points {
+    pub fn set(&mut self, points: Vec<PointArgs>) {
+        if points.is_empty() && self.points.is_empty() {
+            return;
+        }
+        self.points = points;
+        self.version += 1;
+    }
+}
+
+#[derive(Clone, Debug, Default)]
+struct DrawPointsDesc;
+impl<B: Backend> RenderGroupDesc<B, World> for DrawPointsDesc {
+    fn build(
+        self,
+        _ctx: &GraphContext<B>,
+        factory: &mut Factory<B>,
+        _queue: QueueId,
+        _world: &World,
+        framebuffer_width: u32,
+        framebuffer_height: u32,
+        subpass: hal::pass::Subpass<'_, B>,
+        _buffers: Vec<NodeBuffer>,
+        _images: Vec<NodeImage>,
+    ) -> Result<Box<dyn RenderGroup<B, World>>, failure::Error> {
+        let env = FlatEnvironmentSub::new(factory)?;
+        let (pipeline, pipeline_layout) = build_points_pipeline(
+            factory,
+            subpass,
+            framebuffer_width,
+            framebuffer_height,
+            vec![env.raw_layout()],
+        )?;
+        Ok(Box::new(DrawPoints::<B> {
+            pipeline,
+            pipeline_layout,
+            env,
+            points: DynamicVertexBuffer::new(),
+            count: 0,
+            versions: Vec::new(),
+            change: ChangeDetection::default(),
+        }))
+    }
+}
+
+#[derive(Debug)]
+struct DrawPoints<B: Backend> {
+    pipeline: B::GraphicsPipeline,
+    pipeline_layout: B::PipelineLayout,
+    env: FlatEnvironmentSub<B>,
+    points: DynamicVertexBuffer<B, PointArgs>,
+    count: usize,
+    /// `Points::version` in the vertex buffer of each frame in flight
+    versions: Vec<Option<u64>>,
+    change: ChangeDetection,
+}
+impl<B: Backend> RenderGroup<B, World> for DrawPoints<B> {
+    fn prepare(
+        &mut self,
+        factory: &Factory<B>,
+        _queue: QueueId,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        world: &World,
+    ) -> PrepareResult {
+        let env_changed = self.env.process(factory, index, world);
+        let points = world.read_resource::<Points>();
+        if self.versions.len() <= index {
+            self.versions.resize(index + 1, None);
+        }
+        let changed = self.versions[index] != Some(points.version);
+        if changed {
+            if !points.points.is_empty() {
+                self.points.write(
+                    factory,
+                    index,
+                    points.points.len() as u64,
+                    Some(&points.points[..]),
+                );
+            }
+            self.versions[index] = Some(points.version);
+        }
+        self.count = points.points.len();
+        self.change.prepare_result(index, changed || env_changed)
+    }
+
+    fn draw_inline(
+        &mut self,
+        mut encoder: RenderPassEncoder<'_, B>,
+        index: usize,
+        _subpass: hal::pass::Subpass<'_, B>,
+        _world: &World,
+    ) {
+        if self.count == 0 {
+            return;
+        }
+        encoder.bind_graphics_pipeline(&self.pipeline);
+        self.env.bind(index, &self.pipeline_layout, 0, &mut encoder);
+        self.points.bind(index, 0, 0, &mut encoder);
+        // the vertex shader places the 4 corners of each point's quad
+        unsafe {
+            encoder.draw(0..4, 0..self.count as u32);
+        }
+    }
+
+    fn dispose(self: Box<Self>, factory: &mut Factory<B>, _world: &World) {
+        unsafe {
+            factory.device().destroy_graphics_pipeline(self.pipeline);
+            factory
+                .device()
+                .destroy_pipeline_layout(self.pipeline_layout);
+        }
+    }
+}
+
+fn build_points_pipeline<B: Backend>(
+    factory: &Factory<B>,
+    subpass: hal::pass::Subpass<'_, B>,
+    framebuffer_width: u32,
+    framebuffer_height: u32,
+    layouts: Vec<&B::DescriptorSetLayout>,
+) -> Result<(B::GraphicsPipeline, B::PipelineLayout), failure::Error> {
+    let pipeline_layout = unsafe {
+        factory
+            .device()
+            .create_pipeline_layout(layouts, None as Option<(_, _)>)
+    }?;
+    let shader_vertex = unsafe { VERTEX.module(factory).unwrap() };
+    let shader_fragment = unsafe { FRAGMENT.module(factory).unwrap() };
+    let pipes = PipelinesBuilder::new()
+        .with_pipeline(
+            PipelineDescBuilder::new()
+                .with_vertex_desc(&[(PointArgs::vertex(), pso::VertexInputRate::Instance(1))])
+                .with_input_assembler(pso::InputAssemblerDesc::new(hal::Primitive::TriangleStrip))
+                .with_shaders(util::simple_shader_set(
+                    &shader_vertex,
+                    Some(&shader_fragment),
+                ))
+                .with_layout(&pipeline_layout)
+                .with_subpass(subpass)
+                .with_framebuffer_size(framebuffer_width, framebuffer_height)
+                .with_blend_targets(vec![pso::ColorBlendDesc {
+                    mask: pso::ColorMask::ALL,
+                    blend: Some(pso::BlendState::ALPHA),
+                }]),
+        )
+        .build(factory, None);
+    unsafe {
+        factory.destroy_shader_module(shader_vertex);
+        factory.destroy_shader_module(shader_fragment);
+    }
+    match pipes {
+        Err(e) => {
+            unsafe {
+                factory.device().destroy_pipeline_layout(pipeline_layout);
+            }
+            Err(e)
+        }
+        Ok(mut pipes) => Ok((pipes.remove(0), pipeline_layout)),
+    }
+}
+
+/// Draws the markers of the `Points` resource
+#[derive(Default, Debug)]
+pub struct RenderPoints;
+impl<B: Backend> RenderPlugin<B> for RenderPoints {
+    fn on_build<'a, 'b>(
+        &mut self,
+        world: &mut World,
+        _builder: &mut DispatcherBuilder<'a, 'b>,
+    ) -> Result<(), Error> {
+        world.insert(Points::default());
+        Ok(())
+    }
+
+    fn on_plan(
+        &mut self,
+        plan: &mut RenderPlan<B>,
+        _factory: &mut Factory<B>,
+        _world: &World,
+    ) -> Result<(), Error> {
+        plan.extend_target(Target::Main, |ctx| {
+            ctx.add(RenderOrder::Transparent, DrawPointsDesc.builder())?;
+            Ok(())
+        });
+        Ok(())
+    }
+}
@@ -0,0 +1,803 @@
+//! Protocol Buffers wire format for serde types, with the Rust type as the schema.
+//!
+//! Struct fields and tuple elements are numbered from 1 in declaration order, the way a `.proto`
+//! file mirroring the type would number them. Integers are varints, signed ones zigzag encoded
+//! like `sint` fields, floats are `fixed32`/`double`, and nested structs, strings and bytes are
+//! length delimited. Sequences are unpacked repeated fields, or a nested message with a repeated
+//! field 1 where they can't be repeated directly, e.g. inside another sequence. Enums with data
+//! are a `oneof` numbered by variant index + 1, unit enums a varint. Like proto3, zero values are
+//! left out, except in options and sequences where their presence matters.
+use serde::{
+    de::{self, IntoDeserializer},
+    ser, Deserialize, Serialize,
+};
+use std::fmt;
+
+const VARINT: u8 = 0;
+const FIXED64: u8 = 1;
+const LEN: u8 = 2;
+const FIXED32: u8 = 5;
+
+#[derive(Debug)]
+pub struct Error(pub(crate) String);
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0)
+    }
+}
+impl std::error::Error for Error {}
+impl ser::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+impl de::Error for Error {
+    fn custom<T: fmt::Display>(msg: T) -> Self {
+        Error(msg.to_string())
+    }
+}
+pub type Result<T> = std::result::Result<T, Error>;
+
+pub fn write_varint(out: &mut Vec<u8>, mut v: u64) {
+    while v >= 0x80 {
+        out.push(v as u8 | 0x80);
+        v >>= 7;
+    }
+    out.push(v as u8);
+}
+pub fn read_varint(buf: &mut &[u8]) -> Result<u64> {
+    let mut v = 0;
+    let mut shift = 0;
+    loop {
+        let (b, rest) = buf
+            .split_first()
+            .ok_or_else(|| Error("unexpected end of varint".to_owned()))?;
+        *buf = rest;
+        if shift >= 64 {
+            return Err(Error("varint is too long".to_owned()));
+        }
+        v |= ((b & 0x7f) as u64) << shift;
+        if b & 0x80 == 0 {
+            return Ok(v);
+        }
+        shift += 7;
+    }
+}
+/// Maps signed integers to unsigned ones so that small magnitudes have short varints
+pub fn zigzag(v: i64) -> u64 {
+    ((v << 1) ^ (v >> 63)) as u64
+}
+pub fn unzigzag(v: u64) -> i64 {
+    (v >> 1) as i64 ^ -((v & 1) as i64)
+}
+
+fn write_key(out: &mut Vec<u8>, num: u32, wire_type: u8) {
+    write_varint(out, (num as u64) << 3 | wire_type as u64);
+}
+fn write_len(out: &mut Vec<u8>, num: u32, bytes: &[u8]) {
+    write_key(out, num, LEN);
+    write_varint(out, bytes.len() as u64);
+    out.extend_from_slice(bytes);
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut out = Vec::new();
+    value.serialize(FieldSerializer {
+        out: &mut out,
+        num: 1,
+        repeated: true,
+        explicit: false,
+        inline: true,
+    })?;
+    Ok(out)
+}
+
+/// Bytes `value` takes as field `num` of a message
+pub fn field_len<T: Serialize + ?Sized>(num: u32, value: &T) -> Result<usize> {
+    let mut out = Vec::new();
+    value.serialize(FieldSerializer {
+        out: &mut out,
+        num,
+        repeated: true,
+        explicit: false,
+        inline: false,
+    })?;
+    Ok(out.len())
+}
+
+pub fn from_slice<'a, T: Deserialize<'a>>(buf: &'a [u8]) -> Result<T> {
+    T::deserialize(FieldDeserializer {
+        values: vec![Value::Bytes(buf)],
+        repeated: true,
+        inline: true,
+    })
+}
+
+/// Writes a value as field `num` of the message in `out`
+struct FieldSerializer<'a> {
+    out: &'a mut Vec<u8>,
+    num: u32,
+    /// Whether a sequence can be written as a repeated field instead of a nested message
+    repeated: bool,
+    /// Whether zero values have to be written, as their absence would mean something else
+    explicit: bool,
+    /// Whether structs and tuples are the message itself rather than a nested one, for the top
+    /// level value
+    inline: bool,
+}
+impl<'a> FieldSerializer<'a> {
+    fn varint(self, v: u64) -> Result<()> {
+        if v != 0 || self.explicit {
+            write_key(self.out, self.num, VARINT);
+            write_varint(self.out, v);
+        }
+        Ok(())
+    }
+    fn bytes(self, v: &[u8]) -> Result<()> {
+        if !v.is_empty() || self.explicit {
+            write_len(self.out, self.num, v);
+        }
+        Ok(())
+    }
+    fn message(self, variant: Option<u32>, repeat: bool, inline: bool) -> Compound<'a> {
+        Compound {
+            out: self.out,
+            num: self.num,
+            explicit: self.explicit,
+            inline,
+            variant,
+            repeat,
+            buf: Vec::new(),
+            next: 0,
+        }
+    }
+}
+
+/// Nested message, written to the parent message once complete to prefix it with its length
+struct Compound<'a> {
+    out: &'a mut Vec<u8>,
+    num: u32,
+    explicit: bool,
+    inline: bool,
+    /// Variant index of an enum variant, which wraps the message in the variant's field
+    variant: Option<u32>,
+    /// Whether all elements are repeated field 1, for a sequence nested in a sequence
+    repeat: bool,
+    buf: Vec<u8>,
+    next: u32,
+}
+impl<'a> Compound<'a> {
+    fn element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        let num = if self.repeat {
+            1
+        } else {
+            self.next += 1;
+            self.next
+        };
+        value.serialize(FieldSerializer {
+            out: &mut self.buf,
+            num,
+            repeated: !self.repeat,
+            explicit: self.repeat,
+            inline: false,
+        })
+    }
+    fn finish(self) -> Result<()> {
+        let mut msg = self.buf;
+        if let Some(variant) = self.variant {
+            let mut wrapped = Vec::new();
+            write_len(&mut wrapped, variant + 1, &msg);
+            msg = wrapped;
+        }
+        if self.inline {
+            self.out.extend_from_slice(&msg);
+        } else if !msg.is_empty() || self.explicit {
+            write_len(self.out, self.num, &msg);
+        }
+        Ok(())
+    }
+}
+
+/// Sequence written as a repeated field of the parent message
+struct Repeated<'a> {
+    out: &'a mut Vec<u8>,
+    num: u32,
+}
+
+macro_rules! serialize_unsigned {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<()> {
+            self.varint(v as u64)
+        })*
+    };
+}
+macro_rules! serialize_signed {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<()> {
+            self.varint(zigzag(v as i64))
+        })*
+    };
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Sequence<'a>;
+    type SerializeTuple = Compound<'a>;
+    type SerializeTupleStruct = Compound<'a>;
+    type SerializeTupleVariant = Compound<'a>;
+    type SerializeMap = ser::Impossible<(), Error>;
+    type SerializeStruct = Compound<'a>;
+    type SerializeStructVariant = Compound<'a>;
+
+    serialize_unsigned!(serialize_u8: u8, serialize_u16: u16, serialize_u32: u32, serialize_u64: u64);
+    serialize_signed!(serialize_i8: i8, serialize_i16: i16, serialize_i32: i32, serialize_i64: i64);
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.varint(v as u64)
+    }
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.varint(v as u64)
+    }
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        if v.to_bits() != 0 || self.explicit {
+            write_key(self.out, self.num, FIXED32);
+            self.out.extend_from_slice(&v.to_le_bytes());
+        }
+        Ok(())
+    }
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        if v.to_bits() != 0 || self.explicit {
+            write_key(self.out, self.num, FIXED64);
+            self.out.extend_from_slice(&v.to_le_bytes());
+        }
+        Ok(())
+    }
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.bytes(v.as_bytes())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.bytes(v)
+    }
+    fn serialize_none(self) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        // an empty sequence has to be a present nested message to tell it apart from none
+        value.serialize(FieldSerializer {
+            repeated: false,
+            explicit: true,
+            ..self
+        })
+    }
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.varint(variant_index as u64)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        let mut msg = Vec::new();
+        value.serialize(FieldSerializer {
+            out: &mut msg,
+            num: variant_index + 1,
+            repeated: false,
+            explicit: true,
+            inline: false,
+        })?;
+        write_len(self.out, self.num, &msg);
+        Ok(())
+    }
+    fn serialize_seq(self, _len: Option<usize>) -> Result<Sequence<'a>> {
+        if self.repeated {
+            Ok(Sequence::Repeated(Repeated {
+                out: self.out,
+                num: self.num,
+            }))
+        } else {
+            Ok(Sequence::Nested(self.message(None, true, false)))
+        }
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Compound<'a>> {
+        let inline = self.inline;
+        Ok(self.message(None, false, inline))
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        let inline = self.inline;
+        Ok(self.message(None, false, inline))
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        Ok(self.message(Some(variant_index), false, false))
+    }
+    fn serialize_map(self, _len: Option<usize>) -> Result<Self::SerializeMap> {
+        Err(Error("maps are not supported".to_owned()))
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Compound<'a>> {
+        let inline = self.inline;
+        Ok(self.message(None, false, inline))
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Compound<'a>> {
+        Ok(self.message(Some(variant_index), false, false))
+    }
+}
+
+enum Sequence<'a> {
+    Repeated(Repeated<'a>),
+    Nested(Compound<'a>),
+}
+impl<'a> ser::SerializeSeq for Sequence<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        match self {
+            Sequence::Repeated(repeated) => value.serialize(FieldSerializer {
+                out: repeated.out,
+                num: repeated.num,
+                repeated: false,
+                explicit: true,
+                inline: false,
+            }),
+            Sequence::Nested(nested) => nested.element(value),
+        }
+    }
+    fn end(self) -> Result<()> {
+        match self {
+            Sequence::Repeated(_) => Ok(()),
+            Sequence::Nested(nested) => nested.finish(),
+        }
+    }
+}
+impl<'a> ser::SerializeTuple for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeTupleStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeTupleVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        self.element(value)
+    }
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeStruct for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+impl<'a> ser::SerializeStructVariant for Compound<'a> {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.element(value)
+    }
+    fn end(self) -> Result<()> {
+        self.finish()
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Value<'a> {
+    Varint(u64),
+    Fixed64(u64),
+    Bytes(&'a [u8]),
+    Fixed32(u32),
+}
+
+fn take<'a>(buf: &mut &'a [u8], len: usize) -> Result<&'a [u8]> {
+    if buf.len() < len {
+        return Err(Error("unexpected end of message".to_owned()));
+    }
+    let (taken, rest) = buf.split_at(len);
+    *buf = rest;
+    Ok(taken)
+}
+
+fn parse_message<'a>(mut buf: &'a [u8]) -> Result<Vec<(u32, Value<'a>)>> {
+    let mut fields = Vec::new();
+    while !buf.is_empty() {
+        let key = read_varint(&mut buf)?;
+        let value = match (key & 7) as u8 {
+            VARINT => Value::Varint(read_varint(&mut buf)?),
+            FIXED64 => {
+                let mut bytes = [0; 8];
+                bytes.copy_from_slice(take(&mut buf, 8)?);
+                Value::Fixed64(u64::from_le_bytes(bytes))
+            }
+            LEN => {
+                let len = read_varint(&mut buf)? as usize;
+                Value::Bytes(take(&mut buf, len)?)
+            }
+            FIXED32 => {
+                let mut bytes = [0; 4];
+                bytes.copy_from_slice(take(&mut buf, 4)?);
+                Value::Fixed32(u32::from_le_bytes(bytes))
+            }
+            wire_type => return Err(Error(format!("unknown wire type {}", wire_type))),
+        };
+        fields.push(((key >> 3) as u32, value));
+    }
+    Ok(fields)
+}
+
+/// Every occurrence of field `num`, in order
+fn field<'a>(fields: &[(u32, Value<'a>)], num: u32) -> Vec<Value<'a>> {
+    fields
+        .iter()
+        .filter(|(n, _)| *n == num)
+        .map(|(_, value)| *value)
+        .collect()
+}
+
+/// Reads a value from the occurrences of its field, mirroring `FieldSerializer`
+struct FieldDeserializer<'a> {
+    values: Vec<Value<'a>>,
+    repeated: bool,
+    inline: bool,
+}
+impl<'a> FieldDeserializer<'a> {
+    fn element(value: Value<'a>) -> Self {
+        Self {
+            values: vec![value],
+            repeated: false,
+            inline: false,
+        }
+    }
+    /// Field 1 of the top level message, for values that aren't a message themselves
+    fn root_field(self) -> Result<Self> {
+        if !self.inline {
+            return Ok(self);
+        }
+        Ok(Self {
+            values: field(&parse_message(self.bytes()?)?, 1),
+            repeated: true,
+            inline: false,
+        })
+    }
+    fn wire_type_error(&self) -> Error {
+        Error("unexpected wire type".to_owned())
+    }
+    // the last occurrence of a scalar field wins, a missing one is zero
+    fn varint(&self) -> Result<u64> {
+        match self.values.last() {
+            None => Ok(0),
+            Some(Value::Varint(v)) => Ok(*v),
+            Some(_) => Err(self.wire_type_error()),
+        }
+    }
+    fn fixed32(&self) -> Result<u32> {
+        match self.values.last() {
+            None => Ok(0),
+            Some(Value::Fixed32(v)) => Ok(*v),
+            Some(_) => Err(self.wire_type_error()),
+        }
+    }
+    fn fixed64(&self) -> Result<u64> {
+        match self.values.last() {
+            None => Ok(0),
+            Some(Value::Fixed64(v)) => Ok(*v),
+            Some(_) => Err(self.wire_type_error()),
+        }
+    }
+    fn bytes(&self) -> Result<&'a [u8]> {
+        match self.values.last() {
+            None => Ok(&[]),
+            Some(Value::Bytes(v)) => Ok(v),
+            Some(_) => Err(self.wire_type_error()),
+        }
+    }
+    fn message<V: de::Visitor<'a>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Fields {
+            fields: parse_message(self.bytes()?)?,
+            next: 0,
+            len: len as u32,
+        })
+    }
+}
+
+macro_rules! deserialize_unsigned {
+    ($($method:ident: $visit:ident $ty:ty),*) => {
+        $(fn $method<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(self.root_field()?.varint()? as $ty)
+        })*
+    };
+}
+macro_rules! deserialize_signed {
+    ($($method:ident: $visit:ident $ty:ty),*) => {
+        $(fn $method<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(unzigzag(self.root_field()?.varint()?) as $ty)
+        })*
+    };
+}
+
+impl<'a> de::Deserializer<'a> for FieldDeserializer<'a> {
+    type Error = Error;
+
+    deserialize_unsigned!(
+        deserialize_u8: visit_u8 u8,
+        deserialize_u16: visit_u16 u16,
+        deserialize_u32: visit_u32 u32,
+        deserialize_u64: visit_u64 u64
+    );
+    deserialize_signed!(
+        deserialize_i8: visit_i8 i8,
+        deserialize_i16: visit_i16 i16,
+        deserialize_i32: visit_i32 i32,
+        deserialize_i64: visit_i64 i64
+    );
+    fn deserialize_any<V: de::Visitor<'a>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error(
+            "the wire format needs the type to be known".to_owned(),
+        ))
+    }
+    fn deserialize_bool<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.root_field()?.varint()? != 0)
+    }
+    fn deserialize_char<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let v = self.root_field()?.varint()?;
+        let c = std::char::from_u32(v as u32).ok_or_else(|| Error("invalid char".to_owned()))?;
+        visitor.visit_char(c)
+    }
+    fn deserialize_f32<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(f32::from_bits(self.root_field()?.fixed32()?))
+    }
+    fn deserialize_f64<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(f64::from_bits(self.root_field()?.fixed64()?))
+    }
+    fn deserialize_str<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let bytes = self.root_field()?.bytes()?;
+        let s = std::str::from_utf8(bytes).map_err(|e| Error(e.to_string()))?;
+        visitor.visit_borrowed_str(s)
+    }
+    fn deserialize_string<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+    fn deserialize_bytes<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_borrowed_bytes(self.root_field()?.bytes()?)
+    }
+    fn deserialize_byte_buf<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+    fn deserialize_option<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let field = self.root_field()?;
+        if field.values.is_empty() {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(FieldDeserializer {
+                repeated: false,
+                ..field
+            })
+        }
+    }
+    fn deserialize_unit<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    fn deserialize_unit_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let field = self.root_field()?;
+        let elements = if field.repeated {
+            field.values
+        } else {
+            self::field(&parse_message(field.bytes()?)?, 1)
+        };
+        visitor.visit_seq(Elements {
+            elements: elements.into_iter(),
+        })
+    }
+    fn deserialize_tuple<V: de::Visitor<'a>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.message(len, visitor)
+    }
+    fn deserialize_tuple_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.message(len, visitor)
+    }
+    fn deserialize_map<V: de::Visitor<'a>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error("maps are not supported".to_owned()))
+    }
+    fn deserialize_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.message(fields.len(), visitor)
+    }
+    fn deserialize_enum<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        let field = self.root_field()?;
+        let (variant, content) = match field.values.last() {
+            None => (0, Vec::new()),
+            Some(Value::Varint(v)) => (*v as u32, Vec::new()),
+            Some(Value::Bytes(msg)) => {
+                let fields = parse_message(msg)?;
+                let num = fields
+                    .first()
+                    .map(|(num, _)| *num)
+                    .ok_or_else(|| Error("missing enum variant".to_owned()))?;
+                (num - 1, self::field(&fields, num))
+            }
+            Some(_) => return Err(field.wire_type_error()),
+        };
+        visitor.visit_enum(Variant {
+            variant,
+            content: FieldDeserializer {
+                values: content,
+                repeated: false,
+                inline: false,
+            },
+        })
+    }
+    fn deserialize_identifier<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_u32(visitor)
+    }
+    fn deserialize_ignored_any<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+}
+
+/// Fields 1 to `len` of a message
+struct Fields<'a> {
+    fields: Vec<(u32, Value<'a>)>,
+    next: u32,
+    len: u32,
+}
+impl<'a> de::SeqAccess<'a> for Fields<'a> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'a>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        if self.next == self.len {
+            return Ok(None);
+        }
+        self.next += 1;
+        seed.deserialize(FieldDeserializer {
+            values: field(&self.fields, self.next),
+            repeated: true,
+            inline: false,
+        })
+        .map(Some)
+    }
+}
+
+struct Elements<'a> {
+    elements: std::vec::IntoIter<Value<'a>>,
+}
+impl<'a> de::SeqAccess<'a> for Elements<'a> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'a>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        match self.elements.next() {
+            Some(value) => seed
+                .deserialize(FieldDeserializer::element(value))
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.elements.len())
+    }
+}
+
+struct Variant<'a> {
+    variant: u32,
+    content: FieldDeserializer<'a>,
+}
+impl<'a> de::EnumAccess<'a> for Variant<'a> {
+    type Error = Error;
+    type Variant = FieldDeserializer<'a>;
+    fn variant_seed<V: de::DeserializeSeed<'a>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, FieldDeserializer<'a>)> {
+        let variant: de::value::U32Deserializer<Error> = self.variant.into_deserializer();
+        Ok((seed.deserialize(variant)?, self.content))
+    }
+}
+impl<'a> de::VariantAccess<'a> for FieldDeserializer<'a> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'a>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(FieldDeserializer {
+            repeated: false,
+            ..self
+        })
+    }
+    fn tuple_variant<V: de::Visitor<'a>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        self.message(len, visitor)
+    }
+    fn struct_variant<V: de::Visitor<'a>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.message(fields.len(), visitor)
+    }
+}
@@ -0,0 +1,180 @@
+use crate::sequence::sequence_greater_than;
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, fmt};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum RetransmitPolicy {
+    /// Messages are sent once and never acked
+    None,
+    /// Resend a message as soon as an ack shows that it was skipped
+    ResendOnNack,
+    /// Resend a message when it hasn't been acked within the retransmission timeout
+    ResendAfterRto,
+}
+impl fmt::Display for RetransmitPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            RetransmitPolicy::None => write!(f, "None"),
+            RetransmitPolicy::ResendOnNack => write!(f, "Resend on NACK"),
+            RetransmitPolicy::ResendAfterRto => write!(f, "Resend after RTO"),
+        }
+    }
+}
+pub const RETRANSMIT_POLICIES: [RetransmitPolicy; 3] = [
+    RetransmitPolicy::None,
+    RetransmitPolicy::ResendOnNack,
+    RetransmitPolicy::ResendAfterRto,
+];
+
+/// Acknowledges `ack` and the 32 sequence numbers before it, bit n of `ack_bits` being `ack - 1 - n`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct AckMessage {
+    pub ack: u16,
+    pub ack_bits: u32,
+}
+impl AckMessage {
+    /// Distance behind `ack`, if `seq` is inside the ack window
+    fn window_distance(&self, seq: u16) -> Option<u32> {
+        if !sequence_greater_than(self.ack, seq) {
+            return None;
+        }
+        let distance = self.ack.wrapping_sub(seq) as u32;
+        if distance <= 32 {
+            Some(distance)
+        } else {
+            None
+        }
+    }
+    pub fn acks(&self, seq: u16) -> bool {
+        seq == self.ack
+            || self
+                .window_distance(seq)
+                .map(|d| self.ack_bits & (1 << (d - 1)) != 0)
+                .unwrap_or(false)
+    }
+    /// A message is negatively acknowledged when a newer one was received but it wasn't.
+    pub fn nacks(&self, seq: u16) -> bool {
+        self.window_distance(seq)
+            .map(|d| self.ack_bits & (1 << (d - 1)) == 0)
+            .unwrap_or(false)
+    }
+    /// Messages too far behind `ack` can no longer be acknowledged.
+    pub fn expired(&self, seq: u16) -> bool {
+        sequence_greater_than(self.ack, seq) && self.window_distance(seq).is_none()
+    }
+}
+
+struct PendingMessage {
+    seq: u16,
+    sent_at: f32,
+    nacked: bool,
+    payload: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct ReliabilityStats {
+    pub acked: u32,
+    pub retransmissions: u32,
+    /// Messages that fell out of the ack window before being acked
+    pub expired: u32,
+}
+
+/// Keeps sent messages until they are acked and decides when to retransmit them.
+/// Retransmissions keep their original sequence number, so receivers see them as late deliveries.
+pub struct ReliableSender {
+    policy: RetransmitPolicy,
+    /// Retransmission timeout in seconds
+    rto: f32,
+    pending: VecDeque<PendingMessage>,
+    stats: ReliabilityStats,
+}
+impl ReliableSender {
+    pub fn new(policy: RetransmitPolicy, rto: f32) -> Self {
+        Self {
+            policy,
+            rto,
+            pending: VecDeque::new(),
+            stats: ReliabilityStats::default(),
+        }
+    }
+    pub fn stats(&self) -> &ReliabilityStats {
+        &self.stats
+    }
+    pub fn sent(&mut self, seq: u16, time: f32, payload: Vec<u8>) {
+        if self.policy == RetransmitPolicy::None {
+            return;
+        }
+        self.pending.push_back(PendingMessage {
+            seq,
+            sent_at: time,
+            nacked: false,
+            payload,
+        });
+    }
+    pub fn recv_ack(&mut self, ack: &AckMessage) {
+        let stats = &mut self.stats;
+        self.pending.retain(|msg| {
+            if ack.acks(msg.seq) {
+                stats.acked += 1;
+                false
+            } else if ack.expired(msg.seq) {
+                stats.expired += 1;
+                false
+            } else {
+                true
+            }
+        });
+        for msg in self.pending.iter_mut() {
+            msg.nacked |= ack.nacks(msg.seq);
+        }
+    }
+    /// Returns the sequence number and payload of every message due for retransmission at `time`.
+    pub fn retransmissions(&mut self, time: f32) -> Vec<(u16, Vec<u8>)> {
+        let policy = self.policy;
+        let rto = self.rto;
+        let mut resend = Vec::new();
+        for msg in self.pending.iter_mut() {
+            let due = match policy {
+                RetransmitPolicy::None => false,
+                RetransmitPolicy::ResendOnNack => msg.nacked,
+                RetransmitPolicy::ResendAfterRto => time - msg.sent_at >= rto,
+            };
+            if due {
+                msg.nacked = false;
+                msg.sent_at = time;
+                resend.push((msg.seq, msg.payload.clone()));
+            }
+        }
+        self.stats.retransmissions += resend.len() as u32;
+        resend
+    }
+}
+
+/// How the client delivers its per-frame inputs to the server
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InputDelivery {
+    /// The client sends no inputs
+    Off,
+    /// Each input is sent once
+    Unreliable,
+    /// Every message repeats the last `input_redundancy` inputs
+    Redundant,
+    /// Each input is sent once and retransmitted until acked, using the retransmission timeout
+    Reliable,
+}
+impl fmt::Display for InputDelivery {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InputDelivery::Off => write!(f, "Off"),
+            InputDelivery::Unreliable => write!(f, "Unreliable"),
+            InputDelivery::Redundant => write!(f, "Redundant"),
+            InputDelivery::Reliable => write!(f, "Reliable"),
+        }
+    }
+}
+pub const INPUT_DELIVERIES: [InputDelivery; 4] = [
+    InputDelivery::Off,
+    InputDelivery::Unreliable,
+    InputDelivery::Redundant,
+    InputDelivery::Reliable,
+];
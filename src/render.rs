@@ -1,68 +1,409 @@
-use crate::sim::{Sample, SimSettings, SimSide, SimulationResult, WorldFrame};
+use crate::{
+    points::{PointArgs, Points},
+    session::Bookmarks,
+};
+use network_sim::{
+    sim::{CurveVisibility, Sample, SimSettings, SimSide, SimulationResult, WorldFrame},
+    style::{srgba, Style},
+    view::{Panel, ScaleMode, Viewport},
+};
 
 use amethyst::{
-    core::math::{Point3, Vector2},
-    ecs::{ReadExpect, Write, WriteExpect},
-    renderer::{debug_drawing::DebugLines, palette::Srgba},
-    window::ScreenDimensions,
+    core::math::{Point3, Vector2, Vector3},
+    ecs::{Entities, Entity, Read, ReadExpect, Write, WriteExpect, WriteStorage},
+    renderer::{
+        debug_drawing::{DebugLinesComponent, DebugLinesParams},
+        palette::Srgba,
+    },
 };
 use std::{
+    collections::{BTreeMap, BTreeSet},
     fmt::Debug,
     sync::{Arc, Mutex},
 };
-fn sim_bounding_box_render<M: Debug + Clone>(
+const WARM_UP_ALPHA: f32 = 0.3;
+/// Lines across each direction of the 3d view's ground grid
+const GRID_LINES: usize = 10;
+/// Frame intervals after which an entity's latest frame no longer shows on the minimap
+const MINIMAP_STALE_FRAMES: f32 = 2.;
+/// Pixels, scaled with the markers, a frame marker has to be from the last one drawn of its
+/// curve to be drawn with level of detail on
+const LOD_SPACING: f32 = 3.;
+
+pub fn sim_bounding_box_render<M: Debug + Clone>(
     sim: &SimulationResult<M>,
-    map_fn: fn(&WorldFrame<M>) -> Vector2<f32>,
-) -> (Vector2<f32>, Vector2<f32>) {
-    let min_pos_x = sim
-        .frames
-        .iter()
-        .map(map_fn)
-        .map(|pos| pos.x)
-        .fold(std::f32::INFINITY, f32::min);
-    let mut max_pos_x = sim
-        .frames
-        .iter()
-        .map(map_fn)
-        .map(|pos| pos.x)
-        .fold(std::f32::NEG_INFINITY, f32::max);
-    let mut max_pos_y = sim
-        .frames
-        .iter()
-        .map(map_fn)
-        .map(|pos| pos.y)
-        .fold(std::f32::NEG_INFINITY, f32::max);
-    let min_pos_y = sim
+    map_fn: fn(&WorldFrame<M>) -> Vector3<f32>,
+) -> (Vector3<f32>, Vector3<f32>) {
+    let mut min_pos = Vector3::repeat(std::f32::INFINITY);
+    let mut max_pos = Vector3::repeat(std::f32::NEG_INFINITY);
+    for pos in sim.frames.iter().map(map_fn) {
+        for axis in 0..3 {
+            min_pos[axis] = min_pos[axis].min(pos[axis]);
+            max_pos[axis] = max_pos[axis].max(pos[axis]);
+        }
+    }
+    non_empty(min_pos, max_pos)
+}
+/// Extents with every empty or inverted axis widened to 1
+fn non_empty(min_pos: Vector3<f32>, mut max_pos: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    for axis in 0..3 {
+        if max_pos[axis] <= min_pos[axis] {
+            max_pos[axis] = min_pos[axis] + 1.;
+        }
+    }
+    (min_pos, max_pos)
+}
+/// Maps world positions into a panel of `size` pixels
+struct View {
+    min: Vector3<f32>,
+    max: Vector3<f32>,
+    size: Vector2<f32>,
+    /// Yaw and pitch in radians of the orbit camera, `None` for the flat view from above
+    orbit: Option<(f32, f32)>,
+    /// Scale X and Y of the flat view alike, centering the extents in the panel
+    equal_scale: bool,
+}
+impl View {
+    fn project(&self, pos: Vector3<f32>) -> Vector2<f32> {
+        match self.orbit {
+            None => {
+                let extents = (self.max - self.min).xy();
+                let mut scale = self.size.component_div(&extents);
+                if self.equal_scale {
+                    scale = Vector2::repeat(scale.x.min(scale.y));
+                }
+                let margin = (self.size - extents.component_mul(&scale)) * 0.5;
+                (pos - self.min).xy().component_mul(&scale) + margin
+            }
+            Some((yaw, pitch)) => {
+                // orthographic, scaled uniformly so the bounding box fits at any angle
+                let rel = pos - (self.min + self.max) * 0.5;
+                let (sin_yaw, cos_yaw) = yaw.sin_cos();
+                let (sin_pitch, cos_pitch) = pitch.sin_cos();
+                let x = rel.x * cos_yaw - rel.y * sin_yaw;
+                let depth = rel.x * sin_yaw + rel.y * cos_yaw;
+                let y = depth * sin_pitch + rel.z * cos_pitch;
+                let scale = self.size.x.min(self.size.y) / (self.max - self.min).norm();
+                self.size * 0.5 + Vector2::new(x, y) * scale
+            }
+        }
+    }
+    /// Grid on the ground plane and the axes from its corner, x red, y green and z blue
+    fn draw_grid(&self, lines: &mut DebugLinesComponent, offset: Vector2<f32>) {
+        let line = |lines: &mut DebugLinesComponent, a: Vector3<f32>, b: Vector3<f32>, color| {
+            let (a, b) = (self.project(a) + offset, self.project(b) + offset);
+            lines.add_line(Point3::new(a.x, a.y, 0.), Point3::new(b.x, b.y, 0.), color);
+        };
+        let (min, max) = (self.min, self.max);
+        let grid_color = Srgba::new(0.4, 0.4, 0.4, 0.3);
+        for i in 0..=GRID_LINES {
+            let f = i as f32 / GRID_LINES as f32;
+            let x = min.x + (max.x - min.x) * f;
+            let y = min.y + (max.y - min.y) * f;
+            line(
+                lines,
+                Vector3::new(x, min.y, min.z),
+                Vector3::new(x, max.y, min.z),
+                grid_color,
+            );
+            line(
+                lines,
+                Vector3::new(min.x, y, min.z),
+                Vector3::new(max.x, y, min.z),
+                grid_color,
+            );
+        }
+        let axis_colors = [
+            Srgba::new(1.0, 0.3, 0.3, 1.0),
+            Srgba::new(0.3, 1.0, 0.3, 1.0),
+            Srgba::new(0.3, 0.3, 1.0, 1.0),
+        ];
+        for (axis, color) in axis_colors.iter().enumerate() {
+            let mut end = min;
+            end[axis] = max[axis];
+            line(lines, min, end, *color);
+        }
+    }
+}
+/// Every entity's server and client position at the playhead from above in one viewport, with
+/// a line from each client position to the server's
+fn draw_minimap(
+    lines: &mut DebugLinesComponent,
+    sim: &SimulationResult<Sample>,
+    settings: &SimSettings,
+    (min, max): (Vector3<f32>, Vector3<f32>),
+    panel: Panel,
+) {
+    let view = View {
+        min,
+        max,
+        size: panel.size,
+        orbit: None,
+        equal_scale: settings.equal_axis_scale,
+    };
+    let offset = panel.offset;
+    let corner =
+        |x: f32, y: f32| Point3::new(offset.x + x * panel.size.x, offset.y + y * panel.size.y, 0.);
+    let frame_color = Srgba::new(0.6, 0.6, 0.6, 1.0);
+    for (a, b) in [
+        ((0., 0.), (1., 0.)),
+        ((1., 0.), (1., 1.)),
+        ((1., 1.), (0., 1.)),
+        ((0., 1.), (0., 0.)),
+    ]
+    .iter()
+    {
+        lines.add_line(corner(a.0, a.1), corner(b.0, b.1), frame_color);
+    }
+    // latest (server, client) frame at or before the playhead of every entity
+    let mut latest: BTreeMap<
+        Option<u32>,
+        (Option<&WorldFrame<Sample>>, Option<&WorldFrame<Sample>>),
+    > = BTreeMap::new();
+    for frame in sim
         .frames
         .iter()
-        .map(map_fn)
-        .map(|pos| pos.y)
-        .fold(std::f32::INFINITY, f32::min);
-    if max_pos_x == min_pos_x {
-        max_pos_x = min_pos_x + 1.;
+        .filter(|f| f.render_time <= settings.curr_time)
+    {
+        if frame
+            .entity
+            .map(|e| settings.hidden_entities.contains(&e))
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        let entry = latest.entry(frame.entity).or_insert((None, None));
+        let slot = match frame.side {
+            SimSide::Server => &mut entry.0,
+            SimSide::Client => &mut entry.1,
+        };
+        if slot
+            .map(|f| f.render_time <= frame.render_time)
+            .unwrap_or(true)
+        {
+            *slot = Some(frame);
+        }
+    }
+    // entities that stopped producing frames before the playhead are gone
+    let server_stale = MINIMAP_STALE_FRAMES / settings.server_fps as f32;
+    let client_stale = MINIMAP_STALE_FRAMES / settings.render_fps as f32;
+    let style = &settings.style;
+    let project = |frame: &WorldFrame<Sample>| {
+        let pos = view.project(frame.sample.pos) + offset;
+        Point3::new(pos.x, pos.y, 0.)
+    };
+    for (entity, (server, client)) in latest.iter() {
+        let server = server
+            .filter(|_| settings.curves.server)
+            .filter(|f| f.render_time + server_stale >= settings.curr_time);
+        let client = client
+            .filter(|_| settings.curves.client)
+            .filter(|f| f.render_time + client_stale >= settings.curr_time);
+        if let Some(frame) = server {
+            let mut color = srgba(style.entity(style.server, *entity));
+            color.alpha *= frame.sample.alpha;
+            lines.add_circle_2d(project(frame), 5.0 * style.marker_scale, 10, color);
+        }
+        if let Some(frame) = client {
+            let mut color = srgba(style.entity(style.client, *entity));
+            color.alpha *= frame.sample.alpha;
+            lines.add_circle_2d(project(frame), 3.0 * style.marker_scale, 10, color);
+        }
+        if let (Some(server), Some(client)) = (server, client) {
+            lines.add_line(
+                project(client),
+                project(server),
+                Srgba::new(1.0, 0.3, 0.3, 1.0),
+            );
+        }
     }
-    if max_pos_y == min_pos_y {
-        max_pos_y = min_pos_y + 1.;
+}
+fn draw_playhead(lines: &mut DebugLinesComponent, pos: Point3<f32>, color: Srgba, scale: f32) {
+    lines.add_circle_2d(pos, 5.0 * scale, 10, color);
+    lines.add_circle_2d(pos, 10.0 * scale, 10, color);
+    lines.add_circle_2d(pos, 20.0 * scale, 10, color);
+    lines.add_circle_2d(pos, 30.0 * scale, 20, color);
+}
+fn draw_second_playhead(
+    lines: &mut DebugLinesComponent,
+    pos: Point3<f32>,
+    color: Srgba,
+    scale: f32,
+) {
+    let size = 25. * scale;
+    lines.add_line(
+        Point3::new(pos.x - size, pos.y - size, pos.z),
+        Point3::new(pos.x + size, pos.y + size, pos.z),
+        color,
+    );
+    lines.add_line(
+        Point3::new(pos.x - size, pos.y + size, pos.z),
+        Point3::new(pos.x + size, pos.y - size, pos.z),
+        color,
+    );
+    lines.add_circle_2d(pos, 15.0 * scale, 10, color);
+}
+fn point(pos: Point3<f32>, radius: f32, color: Srgba) -> PointArgs {
+    PointArgs {
+        pos: [pos.x, pos.y],
+        radius,
+        color: [color.red, color.green, color.blue, color.alpha],
     }
-    (
-        Vector2::new(min_pos_x, min_pos_y),
-        Vector2::new(max_pos_x, max_pos_y),
-    )
 }
-pub struct SimRenderSystem;
+/// Lines kept on `entity`, creating the entity the first time
+fn lines_of<'a>(
+    entity: &mut Option<Entity>,
+    entities: &Entities,
+    storage: &'a mut WriteStorage<DebugLinesComponent>,
+) -> &'a mut DebugLinesComponent {
+    let entity = *entity.get_or_insert_with(|| entities.create());
+    storage
+        .entry(entity)
+        .expect("line entities are never deleted")
+        .or_insert_with(DebugLinesComponent::new)
+}
+/// Text next to a playhead, drawn by the GUI
+pub struct PlayheadLabel {
+    /// Position as a fraction of the screen size from the bottom left
+    pub pos: [f32; 2],
+    pub color: [f32; 4],
+    pub text: String,
+}
+/// Labels of the playheads rendered in the last frame
+#[derive(Default)]
+pub struct PlayheadLabels(pub Vec<PlayheadLabel>);
+
+/// What the cached curves depend on
+#[derive(Clone, PartialEq)]
+struct CurvesKey {
+    run: u64,
+    /// Generation of the viewport laid out for
+    viewport: u64,
+    hidden_entities: BTreeSet<u32>,
+    curves: CurveVisibility,
+    style: Style,
+    orbit: Option<(f32, f32)>,
+    playing: bool,
+    lod: bool,
+    point_cloud: bool,
+    scale: (ScaleMode, [f32; 3], [f32; 3]),
+    equal_axis_scale: bool,
+    y_range: Option<(f32, f32)>,
+}
+/// What the cached playheads, bookmarks and minimap depend on besides the curves
+#[derive(Clone, PartialEq)]
+struct OverlayKey {
+    curves: CurvesKey,
+    curr_time: f32,
+    second_playhead: bool,
+    playhead_offset: f32,
+    bookmarks: Vec<f32>,
+    minimap: bool,
+    playhead_labels: bool,
+}
+
+/// Draws the frames of the current run into line components kept between frames: the curves,
+/// rebuilt when the run, the view or the curve settings change, and the overlay of playheads,
+/// bookmarks and minimap, also rebuilt when the playhead moves. Frames where nothing changed
+/// submit the same lines without doing any work
+#[derive(Default)]
+pub struct SimRenderSystem {
+    curves: Option<Entity>,
+    overlay: Option<Entity>,
+    curves_key: Option<CurvesKey>,
+    overlay_key: Option<OverlayKey>,
+    /// Bounding box of the run numbered by the first element
+    bounds: Option<(u64, (Vector3<f32>, Vector3<f32>))>,
+}
 impl<'s> amethyst::ecs::System<'s> for SimRenderSystem {
     type SystemData = (
-        ReadExpect<'s, ScreenDimensions>,
-        Write<'s, DebugLines>,
+        Read<'s, Viewport>,
+        WriteExpect<'s, DebugLinesParams>,
         WriteExpect<'s, Arc<Mutex<SimulationResult<Sample>>>>,
         WriteExpect<'s, SimSettings>,
+        ReadExpect<'s, Bookmarks>,
+        Write<'s, PlayheadLabels>,
+        Entities<'s>,
+        WriteStorage<'s, DebugLinesComponent>,
+        Write<'s, Points>,
     );
-    fn run(&mut self, (screen_dimensions, mut lines, sim, settings): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            viewport,
+            mut line_params,
+            sim,
+            settings,
+            bookmarks,
+            mut labels,
+            entities,
+            mut line_components,
+            mut points,
+        ): Self::SystemData,
+    ) {
         let sim = sim.lock().unwrap();
-        let screen_w = screen_dimensions.width();
-        let screen_h = screen_dimensions.height();
+        let style = &settings.style;
+        line_params.line_width = style.line_width;
+        let (screen_w, screen_h) = (viewport.size.x, viewport.size.y);
+        let orbit = if settings.view_3d {
+            Some((
+                settings.orbit_yaw.to_radians(),
+                settings.orbit_pitch.to_radians(),
+            ))
+        } else {
+            None
+        };
+        let curves_key = CurvesKey {
+            run: sim.run,
+            viewport: viewport.generation,
+            hidden_entities: settings.hidden_entities.clone(),
+            curves: settings.curves.clone(),
+            style: style.clone(),
+            orbit,
+            playing: settings.playing,
+            lod: settings.lod,
+            point_cloud: settings.point_cloud,
+            scale: (settings.scale_mode, settings.scale_min, settings.scale_max),
+            equal_axis_scale: settings.equal_axis_scale,
+            y_range: if settings.lock_y {
+                Some((settings.y_min, settings.y_max))
+            } else {
+                None
+            },
+        };
+        let overlay_key = OverlayKey {
+            curves: curves_key.clone(),
+            curr_time: settings.curr_time,
+            second_playhead: settings.second_playhead,
+            playhead_offset: settings.playhead_offset,
+            bookmarks: bookmarks.0.iter().map(|b| b.time).collect(),
+            minimap: settings.minimap,
+            playhead_labels: settings.playhead_labels,
+        };
+        let rebuild_curves = self.curves_key.as_ref() != Some(&curves_key);
+        if !rebuild_curves && self.overlay_key.as_ref() == Some(&overlay_key) {
+            return;
+        }
 
-        let (min_pos, max_pos) = sim_bounding_box_render(&sim, |x| x.sample.pos);
+        let (min_pos, max_pos) = match (settings.scale_mode, self.bounds) {
+            (ScaleMode::Auto, Some((run, bounds))) if run == sim.run => bounds,
+            (ScaleMode::Auto, _) => {
+                let bounds = sim_bounding_box_render(&sim, |x| x.sample.pos);
+                self.bounds = Some((sim.run, bounds));
+                bounds
+            }
+            (ScaleMode::Fixed, _) | (ScaleMode::Baseline, _) => non_empty(
+                Vector3::from(settings.scale_min),
+                Vector3::from(settings.scale_max),
+            ),
+        };
+        let (min_pos, max_pos) = match curves_key.y_range {
+            Some((y_min, y_max)) => non_empty(
+                Vector3::new(min_pos.x, y_min, min_pos.z),
+                Vector3::new(max_pos.x, y_max, max_pos.z),
+            ),
+            None => (min_pos, max_pos),
+        };
         // lines.draw_line(
         //     Point3::new(screen_w * 0.5, screen_h * 0.5, 0.),
         //     Point3::new(screen_w, screen_h, 0.),
@@ -88,52 +429,232 @@ impl<'s> amethyst::ecs::System<'s> for SimRenderSystem {
         //     Point3::new(screen_w * 0.25, screen_h * 0.4, 0.),
         //     Srgba::new(0.3, 0.3, 1.0, 1.0),
         // );
-        let render_size = Vector2::new(screen_w * 0.45, screen_h * 0.85);
-        let mut server_pos_color = None;
-        let mut client_pos_color = None;
-        for frame in sim.frames.iter() {
-            let pos = (frame.sample.pos - min_pos)
-                .component_div(&(max_pos - min_pos))
-                .component_mul(&render_size);
+        let view = View {
+            min: min_pos,
+            max: max_pos,
+            size: viewport.server.size,
+            orbit,
+            equal_scale: settings.equal_axis_scale,
+        };
+        let server_offset = viewport.server.offset;
+        let client_offset = viewport.client.offset;
+        let lifetimes = sim.server_lifetimes();
+        let ghost_slack = settings.ghost_slack();
+        // screen position and colors of a frame, `None` for frames not shown
+        let place = |frame: &WorldFrame<Sample>| {
+            if frame
+                .entity
+                .map(|e| settings.hidden_entities.contains(&e))
+                .unwrap_or(false)
+            {
+                return None;
+            }
+            let visible = match frame.side {
+                SimSide::Server => settings.curves.server,
+                SimSide::Client => settings.curves.client,
+            };
+            if !visible {
+                return None;
+            }
+            let pos = view.project(frame.sample.pos);
             let (pos, color) = match frame.side {
-                SimSide::Server => (
-                    Point3::new(pos.x + screen_w * 0.02, pos.y + screen_h * 0.02, 0.0),
-                    Srgba::new(0.3, 0.3, 1.0, 1.0),
-                ),
-                SimSide::Client => (
-                    Point3::new(pos.x + screen_w * 0.5, pos.y + screen_h * 0.02, 0.0),
-                    Srgba::new(0.5, 1.0, 0.5, 1.0),
-                ),
+                SimSide::Server => (pos + server_offset, style.server),
+                SimSide::Client => (pos + client_offset, style.client),
             };
+            let color = style.entity(color, frame.entity);
+            let pos = Point3::new(pos.x, pos.y, 0.0);
+            // client entities still rendered after the server despawned them
+            let ghost = frame.side == SimSide::Client
+                && frame
+                    .entity
+                    .and_then(|e| lifetimes.get(&e))
+                    .map(|(_, last)| last + ghost_slack < frame.render_time)
+                    .unwrap_or(false);
+            if ghost && !settings.curves.ghosts {
+                return None;
+            }
+            let color = srgba(if ghost { style.ghost } else { color });
             let mut line_color = color;
             if settings.playing {
                 line_color.alpha = 0.15;
             }
-            lines.draw_circle(pos, 15.0, 30, line_color);
-            lines.draw_circle(pos, 10.0, 20, line_color);
-            lines.draw_circle(pos, 5.0, 10, line_color);
+            line_color.alpha *= frame.sample.alpha;
+            // frames excluded from the metrics
+            if !sim.in_window(frame.render_time) {
+                line_color.alpha *= WARM_UP_ALPHA;
+            }
+            Some((pos, color, line_color))
+        };
+
+        if rebuild_curves {
+            let curves = lines_of(&mut self.curves, &entities, &mut line_components);
+            curves.clear();
+            if settings.view_3d {
+                view.draw_grid(curves, server_offset);
+                view.draw_grid(curves, client_offset);
+            }
+            let mut cloud = Vec::new();
+            // last marker drawn of each side and entity
+            let mut last_drawn: BTreeMap<(bool, Option<u32>), Point3<f32>> = BTreeMap::new();
+            for frame in sim.frames.iter() {
+                let (pos, _, line_color) = match place(frame) {
+                    Some(placed) => placed,
+                    None => continue,
+                };
+                let scale = style.marker_scale;
+                // markers closer than a few pixels to the last one overlap it, so long runs and
+                // zoomed out views draw a fraction of their frames; labels are always drawn
+                let curve = (frame.side == SimSide::Server, frame.entity);
+                if let (true, None, Some(last)) =
+                    (settings.lod, &frame.label, last_drawn.get(&curve))
+                {
+                    if (pos - last).norm() < LOD_SPACING * scale {
+                        continue;
+                    }
+                }
+                last_drawn.insert(curve, pos);
+                let unfiltered = frame
+                    .unfiltered
+                    .as_ref()
+                    .filter(|_| settings.curves.unfiltered)
+                    .map(|unfiltered| {
+                        let raw = view.project(unfiltered.pos) + client_offset;
+                        let mut raw_color = srgba(style.unfiltered);
+                        raw_color.alpha *= line_color.alpha;
+                        (Point3::new(raw.x, raw.y, 0.0), raw_color)
+                    });
+                if settings.point_cloud {
+                    cloud.push(point(pos, 15.0 * scale, line_color));
+                    if let Some((raw, raw_color)) = unfiltered {
+                        cloud.push(point(raw, 5.0 * scale, raw_color));
+                    }
+                } else {
+                    curves.add_circle_2d(pos, 15.0 * scale, 30, line_color);
+                    curves.add_circle_2d(pos, 10.0 * scale, 20, line_color);
+                    curves.add_circle_2d(pos, 5.0 * scale, 10, line_color);
+                    if let Some((raw, raw_color)) = unfiltered {
+                        curves.add_circle_2d(raw, 5.0 * scale, 4, raw_color);
+                        curves.add_line(raw, pos, raw_color);
+                    }
+                }
+                if frame.label.is_some() {
+                    curves.add_circle_2d(pos, 20.0 * scale, 3, srgba(style.label));
+                }
+            }
+            points.set(cloud);
+            self.curves_key = Some(curves_key);
+        }
+
+        let lines = lines_of(&mut self.overlay, &entities, &mut line_components);
+        lines.clear();
+        if settings.minimap {
+            draw_minimap(lines, &sim, &settings, (min_pos, max_pos), viewport.minimap);
+        }
+
+        let mut server_pos_color = None;
+        let mut client_pos_color = None;
+        let second_time = settings.curr_time + settings.playhead_offset / 1000.;
+        let mut second_server_pos_color = None;
+        let mut second_client_pos_color = None;
+        // latest (server, client) position at or before each bookmark
+        let mut bookmark_pos = vec![(None, None); bookmarks.0.len()];
+        // playheads and bookmarks follow the primary entity
+        for frame in sim.frames.iter().filter(|f| f.entity.is_none()) {
+            let (pos, color, _) = match place(frame) {
+                Some(placed) => placed,
+                None => continue,
+            };
             if frame.render_time <= settings.curr_time {
                 match frame.side {
-                    SimSide::Server => server_pos_color = Some((pos, color)),
-                    SimSide::Client => client_pos_color = Some((pos, color)),
+                    SimSide::Server => server_pos_color = Some((pos, color, frame.sample.pos)),
+                    SimSide::Client => client_pos_color = Some((pos, color, frame.sample.pos)),
+                }
+            }
+            for (bookmark, pos_pair) in bookmarks.0.iter().zip(bookmark_pos.iter_mut()) {
+                if frame.render_time <= bookmark.time {
+                    match frame.side {
+                        SimSide::Server => pos_pair.0 = Some(pos),
+                        SimSide::Client => pos_pair.1 = Some(pos),
+                    }
                 }
             }
+            if frame.render_time <= second_time {
+                match frame.side {
+                    SimSide::Server => {
+                        second_server_pos_color = Some((pos, color, frame.sample.pos))
+                    }
+                    SimSide::Client => {
+                        second_client_pos_color = Some((pos, color, frame.sample.pos))
+                    }
+                }
+            }
+        }
+        let bookmark_color = srgba(style.bookmark);
+        for (server_pos, client_pos) in bookmark_pos {
+            for pos in server_pos.into_iter().chain(client_pos) {
+                lines.add_circle_2d(pos, 20.0 * style.marker_scale, 4, bookmark_color);
+            }
         }
+        labels.0.clear();
+        let mut label = |pos: Point3<f32>, color: Srgba, text: String| {
+            if settings.playhead_labels {
+                labels.0.push(PlayheadLabel {
+                    pos: [
+                        (pos.x + 32. * style.marker_scale) / screen_w,
+                        pos.y / screen_h,
+                    ],
+                    color: [color.red, color.green, color.blue, color.alpha],
+                    text,
+                });
+            }
+        };
+        // distance from the client's rendered position to the server's at the same time
+        let error = |server: Option<(Point3<f32>, Srgba, Vector3<f32>)>,
+                     client: Option<(Point3<f32>, Srgba, Vector3<f32>)>| {
+            match (server, client) {
+                (Some(server), Some(client)) => {
+                    format!(" error {:.1}", (client.2 - server.2).norm())
+                }
+                _ => String::new(),
+            }
+        };
         if settings.playing || settings.curr_time != 0. {
             if settings.curr_time <= settings.duration {
-                if let Some((pos, color)) = server_pos_color {
-                    lines.draw_circle(pos, 5.0, 10, color);
-                    lines.draw_circle(pos, 10.0, 10, color);
-                    lines.draw_circle(pos, 20.0, 10, color);
-                    lines.draw_circle(pos, 30.0, 20, color);
+                if let Some((pos, color, _)) = server_pos_color {
+                    draw_playhead(lines, pos, color, style.marker_scale);
+                    label(pos, color, "server".to_owned());
                 }
             }
-            if let Some((pos, color)) = client_pos_color {
-                lines.draw_circle(pos, 5.0, 10, color);
-                lines.draw_circle(pos, 10.0, 10, color);
-                lines.draw_circle(pos, 20.0, 10, color);
-                lines.draw_circle(pos, 30.0, 20, color);
+            if let Some((pos, color, _)) = client_pos_color {
+                draw_playhead(lines, pos, color, style.marker_scale);
+                label(
+                    pos,
+                    color,
+                    format!("client{}", error(server_pos_color, client_pos_color)),
+                );
+            }
+            if settings.second_playhead {
+                let offset = format!("{:+.0} ms", settings.playhead_offset);
+                if second_time <= settings.duration {
+                    if let Some((pos, color, _)) = second_server_pos_color {
+                        draw_second_playhead(lines, pos, color, style.marker_scale);
+                        label(pos, color, format!("server {}", offset));
+                    }
+                }
+                if let Some((pos, color, _)) = second_client_pos_color {
+                    draw_second_playhead(lines, pos, color, style.marker_scale);
+                    label(
+                        pos,
+                        color,
+                        format!(
+                            "client {}{}",
+                            offset,
+                            error(second_server_pos_color, second_client_pos_color)
+                        ),
+                    );
+                }
             }
         }
+        self.overlay_key = Some(overlay_key);
     }
 }
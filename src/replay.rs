@@ -0,0 +1,310 @@
+//! Replays a recorded schedule of sync message deliveries into a client state without the apps
+//! and the network, so behaviour authors can test a client pipeline against a known-bad network
+//! trace quickly and deterministically. A schedule comes from a recorded result, see
+//! `PacketSchedule::from_json_file`, or is built in code:
+//!
+//! ```
+//! use network_sim::{
+//!     replay::PacketSchedule,
+//!     sim::{DeliveryRecord, SimSettings},
+//! };
+//!
+//! let mut settings = SimSettings::default();
+//! settings.render_interpolation_delay = 0.1;
+//! let tick = 1. / settings.server_fps as f32;
+//! // a sync message every tick, each arriving 50 ms after it was sent
+//! let deliveries = (1..=(settings.duration / tick) as u64)
+//!     .map(|n| DeliveryRecord {
+//!         time: n as f32 * tick + 0.05,
+//!         seq: n as u16,
+//!         server_frame: n,
+//!         tick_time: n as f32 * tick,
+//!         send_time: n as f32 * tick,
+//!         key_flags: Default::default(),
+//!     })
+//!     .collect();
+//! let schedule = PacketSchedule {
+//!     deliveries,
+//!     duration: settings.duration,
+//! };
+//! let replay = schedule.replay(&settings)?;
+//! replay.assert_max_error(20.);
+//! replay.assert_no_underruns();
+//! # Ok::<(), amethyst::Error>(())
+//! ```
+//!
+//! The server state is stepped on its own at the server tick rate, only to produce the states
+//! the schedule delivers, and the client frames are rendered at exactly the render fps. Inputs
+//! and upstream messages aren't replayed
+use crate::{
+    filter::{new_filter, FilterKind},
+    metrics::Metrics,
+    sim::{
+        analyze, next_run, DeliveryRecord, Sample, ServerClock, SimSettings, SimSide,
+        SimulationResult, WorldFrame,
+    },
+};
+use amethyst::{core::Time, Result};
+use std::{collections::BTreeMap, path::Path, time::Duration};
+
+/// When each sync message reached the client, as recorded by a run or a capture
+#[derive(Clone, Debug)]
+pub struct PacketSchedule {
+    /// Deliveries in arrival order
+    pub deliveries: Vec<DeliveryRecord>,
+    /// Seconds the schedule covers
+    pub duration: f32,
+}
+impl PacketSchedule {
+    /// Schedule of the sync messages the client received in `result`
+    pub fn from_result<M: std::fmt::Debug + Clone>(result: &SimulationResult<M>) -> Self {
+        let last_frame = result
+            .frames
+            .iter()
+            .map(|f| f.render_time)
+            .fold(0., f32::max);
+        PacketSchedule {
+            deliveries: result.deliveries.clone(),
+            duration: last_frame.max(result.window.1),
+        }
+    }
+    /// Schedule of a result exported as JSON, e.g. with `--out`
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        Ok(Self::from_result(
+            &SimulationResult::<Sample>::from_json_file(path)?,
+        ))
+    }
+    /// Runs the client state of the behaviour of `settings` against the schedule and analyzes
+    /// the result with `settings`
+    pub fn replay(&self, settings: &SimSettings) -> Result<Replay> {
+        let mut result = SimulationResult {
+            frames: Vec::new(),
+            packets: Vec::new(),
+            deliveries: Vec::new(),
+            metrics: Metrics::default(),
+            window: (settings.warm_up.min(self.duration), self.duration),
+            log: Vec::new(),
+            debug_states: Vec::new(),
+            run: next_run(),
+        };
+        let tick = 1. / settings.server_fps as f32;
+        let tick_of = |t: f32| (t / tick).round() as u64;
+        // the encoded state of every tick the schedule delivers
+        let mut payloads: BTreeMap<u64, Vec<u8>> = self
+            .deliveries
+            .iter()
+            .map(|d| (tick_of(d.tick_time), Vec::new()))
+            .collect();
+        let mut server = settings.behaviour.new_state(settings);
+        server.init(settings);
+        let mut time = Time::default();
+        for n in 1..=tick_of(self.duration) {
+            time.set_delta_time(Duration::from_secs_f32(tick));
+            time.increment_frame_number();
+            let sample = server.update_server(&time);
+            let t = n as f32 * tick;
+            result.frames.push(server_frame(
+                None,
+                t,
+                sample,
+                server.frame_label(SimSide::Server),
+            ));
+            for (entity, sample) in server.server_entities() {
+                result
+                    .frames
+                    .push(server_frame(Some(entity), t, sample, None));
+            }
+            if let Some(payload) = payloads.get_mut(&n) {
+                *payload = server.send_sync(&time, settings.codec);
+            }
+        }
+        let mut client = settings.behaviour.new_state(settings);
+        client.init(settings);
+        let mut filter = new_filter(settings);
+        let mut deliveries = self.deliveries.iter().peekable();
+        let frame_time = 1. / settings.render_fps as f32;
+        let mut time = Time::default();
+        for _ in 0..(self.duration / frame_time) as u64 {
+            time.set_delta_time(Duration::from_secs_f32(frame_time));
+            time.increment_frame_number();
+            let now = time.absolute_time().as_secs_f32();
+            while let Some(delivery) = deliveries.peek() {
+                if delivery.time > now {
+                    break;
+                }
+                let delivery = deliveries.next().unwrap();
+                let payload = match payloads.get(&tick_of(delivery.tick_time)) {
+                    Some(payload) if !payload.is_empty() => payload,
+                    _ => continue,
+                };
+                client.recv_clock(&ServerClock {
+                    tick_time: Duration::from_secs_f32(delivery.tick_time),
+                    send_time: Duration::from_secs_f32(delivery.send_time),
+                    tick_duration: Duration::from_secs_f32(tick),
+                });
+                client.recv_key_flags(delivery.key_flags);
+                client.recv_sync(
+                    &time,
+                    Duration::from_secs_f32(delivery.tick_time),
+                    delivery.server_frame,
+                    delivery.seq,
+                    payload,
+                    settings.codec,
+                );
+                result.deliveries.push(*delivery);
+            }
+            if let Some(mut sample) = client.update_render(&time) {
+                let raw = sample.pos;
+                sample.pos = filter.filter(now, raw);
+                result.frames.push(WorldFrame {
+                    side: SimSide::Client,
+                    entity: None,
+                    label: client.frame_label(SimSide::Client),
+                    render_time: now,
+                    net_time: now,
+                    sample,
+                    unfiltered: if settings.filter != FilterKind::None {
+                        Some(Sample { pos: raw, ..sample })
+                    } else {
+                        None
+                    },
+                });
+            }
+            for (entity, sample) in client.client_entities() {
+                result.frames.push(WorldFrame {
+                    side: SimSide::Client,
+                    entity: Some(entity),
+                    label: None,
+                    render_time: now,
+                    net_time: now,
+                    sample,
+                    unfiltered: None,
+                });
+            }
+            client.record_metrics(SimSide::Client, &time, &mut result.metrics);
+        }
+        server.finalize(SimSide::Server, &mut result);
+        client.finalize(SimSide::Client, &mut result);
+        let mut settings = settings.clone();
+        settings.peer_to_peer = false;
+        Ok(Replay {
+            result: analyze(&settings, result)?,
+        })
+    }
+}
+
+fn server_frame(
+    entity: Option<u32>,
+    t: f32,
+    sample: Sample,
+    label: Option<String>,
+) -> WorldFrame<Sample> {
+    WorldFrame {
+        side: SimSide::Server,
+        entity,
+        label,
+        render_time: t,
+        net_time: t,
+        sample,
+        unfiltered: None,
+    }
+}
+
+/// Analyzed result of `PacketSchedule::replay`, with assertions on it for tests
+pub struct Replay {
+    pub result: SimulationResult<Sample>,
+}
+impl Replay {
+    /// Largest error of a client frame in the analyzed window
+    pub fn max_error(&self) -> f32 {
+        self.result.metrics.get("error").map_or(0., |c| c.max())
+    }
+    /// Number of underruns in the analyzed window
+    pub fn underruns(&self) -> usize {
+        self.result.metrics.get("underrun").map_or(0, |c| c.len())
+    }
+    /// Panics unless every client frame's error is at most `max`
+    pub fn assert_max_error(&self, max: f32) {
+        let error = self.max_error();
+        assert!(error <= max, "max error {} exceeds {}", error, max);
+    }
+    /// Panics if the client held its position while the server moved
+    pub fn assert_no_underruns(&self) {
+        let underruns = self.underruns();
+        assert!(underruns == 0, "{} underruns", underruns);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings() -> SimSettings {
+        let mut settings = SimSettings::default();
+        settings.duration = 2.;
+        settings.render_interpolation_delay = 0.1;
+        settings
+    }
+
+    /// Schedule of a sync message every server tick of `settings`, each arriving `latency`
+    /// seconds after it was sent unless it's lost
+    fn schedule(
+        settings: &SimSettings,
+        latency: f32,
+        lost: impl Fn(f32) -> bool,
+    ) -> PacketSchedule {
+        let tick = 1. / settings.server_fps as f32;
+        let ticks = (settings.duration / tick) as u64;
+        let deliveries = (1..=ticks)
+            .map(|n| {
+                let t = n as f32 * tick;
+                DeliveryRecord {
+                    time: t + latency,
+                    seq: n as u16,
+                    server_frame: n,
+                    tick_time: t,
+                    send_time: t,
+                    key_flags: Default::default(),
+                }
+            })
+            .filter(|d| !lost(d.tick_time))
+            .collect();
+        PacketSchedule {
+            deliveries,
+            duration: settings.duration,
+        }
+    }
+
+    #[test]
+    fn in_order_delivery_without_underruns() {
+        let settings = settings();
+        let replay = schedule(&settings, 0.05, |_| false)
+            .replay(&settings)
+            .unwrap();
+        replay.assert_no_underruns();
+        replay.assert_max_error(20.);
+    }
+
+    /// Schedule losing every message between 0.8 and 1.3 seconds
+    fn long_gap(settings: &SimSettings) -> PacketSchedule {
+        schedule(settings, 0.05, |t| t > 0.8 && t < 1.3)
+    }
+
+    #[test]
+    fn long_gap_underruns() {
+        let settings = settings();
+        let replay = long_gap(&settings).replay(&settings).unwrap();
+        assert!(replay.underruns() > 0);
+        assert!(replay.max_error() > 20., "{}", replay.max_error());
+    }
+
+    #[test]
+    #[should_panic(expected = "underruns")]
+    fn long_gap_fails_assertion() {
+        let settings = settings();
+        long_gap(&settings)
+            .replay(&settings)
+            .unwrap()
+            .assert_no_underruns();
+    }
+}
@@ -0,0 +1,251 @@
+use crate::{
+    metrics::{ConfidenceInterval, MetricChannel, Metrics},
+    sim::SimSettings,
+};
+use amethyst::Result;
+use std::{collections::BTreeMap, fmt::Write, path::Path};
+
+const PLOT_WIDTH: f32 = 600.;
+const PLOT_HEIGHT: f32 = 120.;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ReportFormat {
+    Markdown,
+    Html,
+}
+impl ReportFormat {
+    /// `.html`/`.htm` files get an HTML report, everything else Markdown
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("html") | Some("htm") => ReportFormat::Html,
+            _ => ReportFormat::Markdown,
+        }
+    }
+}
+
+/// One run in a report, e.g. a single scenario or one point of a sweep
+pub struct ReportRun<'a> {
+    pub title: String,
+    pub settings: &'a SimSettings,
+    pub metrics: &'a Metrics,
+}
+
+/// Metric confidence intervals of one sweep point over all of its repetitions
+pub struct SweepRow {
+    pub label: String,
+    pub intervals: BTreeMap<String, ConfidenceInterval>,
+}
+
+pub struct Report<'a> {
+    pub title: String,
+    pub runs: Vec<ReportRun<'a>>,
+    pub sweep: Vec<SweepRow>,
+}
+
+fn escape_html(s: &str) -> String {
+    s.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Inline SVG line plot of a metric channel, scaled to fit its time and value range
+fn svg_plot(channel: &MetricChannel) -> String {
+    let (min_t, max_t) = channel.times.iter().fold(
+        (std::f32::INFINITY, std::f32::NEG_INFINITY),
+        |(min, max), t| (min.min(*t), max.max(*t)),
+    );
+    let (min_v, max_v) = (channel.min(), channel.max());
+    let t_range = (max_t - min_t).max(std::f32::EPSILON);
+    let v_range = (max_v - min_v).max(std::f32::EPSILON);
+    let mut points = String::new();
+    for (t, v) in channel.times.iter().zip(channel.values.iter()) {
+        let x = (t - min_t) / t_range * PLOT_WIDTH;
+        let y = PLOT_HEIGHT - (v - min_v) / v_range * PLOT_HEIGHT;
+        write!(points, "{:.1},{:.1} ", x, y).unwrap();
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+<rect width=\"{w}\" height=\"{h}\" fill=\"#f8f8f8\"/>\
+<polyline fill=\"none\" stroke=\"#2060c0\" stroke-width=\"1\" points=\"{points}\"/>\
+</svg>",
+        w = PLOT_WIDTH,
+        h = PLOT_HEIGHT,
+        points = points.trim_end()
+    )
+}
+
+/// Inline SVG of each sweep point's mean of `metric` with its confidence interval as error bar
+fn svg_error_bars(sweep: &[SweepRow], metric: &str) -> String {
+    let intervals: Vec<Option<&ConfidenceInterval>> =
+        sweep.iter().map(|row| row.intervals.get(metric)).collect();
+    let (min_v, max_v) = intervals.iter().filter_map(|i| *i).fold(
+        (std::f32::INFINITY, std::f32::NEG_INFINITY),
+        |(min, max), i| (min.min(i.low()), max.max(i.high())),
+    );
+    let v_range = (max_v - min_v).max(std::f32::EPSILON);
+    let y = |v: f32| PLOT_HEIGHT - (v - min_v) / v_range * PLOT_HEIGHT;
+    let step = PLOT_WIDTH / intervals.len().max(1) as f32;
+    let mut shapes = String::new();
+    for (idx, interval) in intervals.iter().enumerate() {
+        if let Some(interval) = interval {
+            let x = (idx as f32 + 0.5) * step;
+            write!(
+                shapes,
+                "<line x1=\"{x:.1}\" y1=\"{lo:.1}\" x2=\"{x:.1}\" y2=\"{hi:.1}\" stroke=\"#c03020\"/>\
+<circle cx=\"{x:.1}\" cy=\"{m:.1}\" r=\"3\" fill=\"#2060c0\"/>",
+                x = x,
+                lo = y(interval.low()),
+                hi = y(interval.high()),
+                m = y(interval.mean)
+            )
+            .unwrap();
+        }
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+<rect width=\"{w}\" height=\"{h}\" fill=\"#f8f8f8\"/>{shapes}</svg>",
+        w = PLOT_WIDTH,
+        h = PLOT_HEIGHT,
+        shapes = shapes
+    )
+}
+
+fn sweep_metric_names(sweep: &[SweepRow]) -> Vec<String> {
+    let mut names: Vec<String> = sweep
+        .iter()
+        .flat_map(|row| row.intervals.keys().cloned())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn format_interval(interval: Option<&ConfidenceInterval>) -> String {
+    interval
+        .map(|i| format!("{:.4} ± {:.4}", i.mean, i.half_width))
+        .unwrap_or_default()
+}
+
+fn render_markdown(report: &Report) -> Result<String> {
+    let mut out = String::new();
+    writeln!(out, "# {}\n", report.title)?;
+    if !report.sweep.is_empty() {
+        let names = sweep_metric_names(&report.sweep);
+        writeln!(out, "## sweep (mean ± 95% CI)\n")?;
+        writeln!(out, "| point | {} |", names.join(" | "))?;
+        writeln!(out, "|---|{}", "---|".repeat(names.len()))?;
+        for row in report.sweep.iter() {
+            let cells: Vec<String> = names
+                .iter()
+                .map(|name| format_interval(row.intervals.get(name)))
+                .collect();
+            writeln!(out, "| {} | {} |", row.label, cells.join(" | "))?;
+        }
+        writeln!(out)?;
+        for name in names.iter() {
+            writeln!(out, "### {}\n", name)?;
+            writeln!(out, "{}\n", svg_error_bars(&report.sweep, name))?;
+        }
+    }
+    for run in report.runs.iter() {
+        writeln!(out, "## {}\n", run.title)?;
+        writeln!(out, "```ron")?;
+        writeln!(
+            out,
+            "{}",
+            ron::ser::to_string_pretty(run.settings, Default::default())?
+        )?;
+        writeln!(out, "```\n")?;
+        writeln!(out, "| metric | samples | mean | min | max |")?;
+        writeln!(out, "|---|---|---|---|---|")?;
+        for (name, summary) in run.metrics.summary() {
+            writeln!(
+                out,
+                "| {} | {} | {:.4} | {:.4} | {:.4} |",
+                name, summary.count, summary.mean, summary.min, summary.max
+            )?;
+        }
+        writeln!(out)?;
+        for (name, channel) in run.metrics.iter() {
+            writeln!(out, "### {}\n", name)?;
+            writeln!(out, "{}\n", svg_plot(channel))?;
+        }
+    }
+    Ok(out)
+}
+
+fn render_html(report: &Report) -> Result<String> {
+    let mut out = String::new();
+    writeln!(
+        out,
+        "<!DOCTYPE html>\n<html><head><meta charset=\"utf-8\"><title>{0}</title></head><body>\n<h1>{0}</h1>",
+        escape_html(&report.title)
+    )?;
+    if !report.sweep.is_empty() {
+        let names = sweep_metric_names(&report.sweep);
+        writeln!(out, "<h2>sweep (mean ± 95% CI)</h2>")?;
+        write!(out, "<table border=\"1\"><tr><th>point</th>")?;
+        for name in names.iter() {
+            write!(out, "<th>{}</th>", escape_html(name))?;
+        }
+        writeln!(out, "</tr>")?;
+        for row in report.sweep.iter() {
+            write!(out, "<tr><td>{}</td>", escape_html(&row.label))?;
+            for name in names.iter() {
+                write!(out, "<td>{}</td>", format_interval(row.intervals.get(name)))?;
+            }
+            writeln!(out, "</tr>")?;
+        }
+        writeln!(out, "</table>")?;
+        for name in names.iter() {
+            writeln!(
+                out,
+                "<h3>{}</h3>\n{}",
+                escape_html(name),
+                svg_error_bars(&report.sweep, name)
+            )?;
+        }
+    }
+    for run in report.runs.iter() {
+        writeln!(out, "<h2>{}</h2>", escape_html(&run.title))?;
+        writeln!(
+            out,
+            "<pre>{}</pre>",
+            escape_html(&ron::ser::to_string_pretty(
+                run.settings,
+                Default::default()
+            )?)
+        )?;
+        writeln!(
+            out,
+            "<table border=\"1\"><tr><th>metric</th><th>samples</th><th>mean</th><th>min</th><th>max</th></tr>"
+        )?;
+        for (name, summary) in run.metrics.summary() {
+            writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{:.4}</td><td>{:.4}</td><td>{:.4}</td></tr>",
+                escape_html(&name),
+                summary.count,
+                summary.mean,
+                summary.min,
+                summary.max
+            )?;
+        }
+        writeln!(out, "</table>")?;
+        for (name, channel) in run.metrics.iter() {
+            writeln!(out, "<h3>{}</h3>\n{}", escape_html(name), svg_plot(channel))?;
+        }
+    }
+    writeln!(out, "</body></html>")?;
+    Ok(out)
+}
+
+/// Writes the sweep results and the settings, metric summaries and metric plots of every run into a single file
+pub fn write_report(path: &Path, report: &Report) -> Result<()> {
+    let contents = match ReportFormat::from_path(path) {
+        ReportFormat::Markdown => render_markdown(report)?,
+        ReportFormat::Html => render_html(report)?,
+    };
+    std::fs::write(path, contents)?;
+    Ok(())
+}
@@ -0,0 +1,55 @@
+use rand::{rngs::SmallRng, SeedableRng};
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+fn fnv1a(hash: u64, bytes: &[u8]) -> u64 {
+    bytes
+        .iter()
+        .fold(hash, |hash, b| (hash ^ *b as u64).wrapping_mul(FNV_PRIME))
+}
+
+/// Scrambles `x` so that nearby inputs give unrelated outputs
+fn splitmix64(x: u64) -> u64 {
+    let mut z = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^ (z >> 31)
+}
+
+/// Seeded source of reproducible random number streams, inserted into the server and client
+/// worlds of a run.
+///
+/// Every consumer derives its own stream by name, and per entity if needed, so adding a consumer
+/// or drawing more numbers in one stream doesn't change the numbers of any other stream.
+#[derive(Clone, Copy, Debug)]
+pub struct SimRng {
+    seed: u64,
+}
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        Self { seed }
+    }
+    /// Seed of the stream `name`, for generators that take a seed instead of an rng
+    pub fn stream_seed(&self, name: &str) -> [u8; 16] {
+        self.derive_seed(fnv1a(
+            fnv1a(FNV_OFFSET, &self.seed.to_le_bytes()),
+            name.as_bytes(),
+        ))
+    }
+    /// Stream of a consumer, e.g. a system
+    pub fn stream(&self, name: &str) -> SmallRng {
+        SmallRng::from_seed(self.stream_seed(name))
+    }
+    /// Stream of one entity of a consumer, e.g. the spawn position of an entity
+    pub fn entity_stream(&self, name: &str, entity: u32) -> SmallRng {
+        let hash = fnv1a(fnv1a(FNV_OFFSET, &self.seed.to_le_bytes()), name.as_bytes());
+        SmallRng::from_seed(self.derive_seed(fnv1a(hash, &entity.to_le_bytes())))
+    }
+    fn derive_seed(&self, hash: u64) -> [u8; 16] {
+        let mut seed = [0; 16];
+        seed[..8].copy_from_slice(&splitmix64(hash).to_le_bytes());
+        seed[8..].copy_from_slice(&splitmix64(hash ^ FNV_PRIME).to_le_bytes());
+        seed
+    }
+}
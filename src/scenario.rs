@@ -0,0 +1,91 @@
+use amethyst::core::{math::Vector2, Time};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Authoritative discontinuity applied by the server simulation
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum ScenarioEvent {
+    /// Move the entity to a position without passing through the positions in between
+    Teleport { x: f32, y: f32 },
+    /// Multiply the entity's velocity
+    ScaleVelocity(f32),
+}
+impl fmt::Display for ScenarioEvent {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScenarioEvent::Teleport { x, y } => write!(f, "teleport to ({}, {})", x, y),
+            ScenarioEvent::ScaleVelocity(scale) => write!(f, "velocity x{}", scale),
+        }
+    }
+}
+impl ScenarioEvent {
+    pub fn apply(&self, pos: &mut Vector2<f32>, velocity: &mut Vector2<f32>) {
+        match *self {
+            ScenarioEvent::Teleport { x, y } => *pos = Vector2::new(x, y),
+            ScenarioEvent::ScaleVelocity(scale) => *velocity *= scale,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct TimedEvent {
+    /// Server time in seconds
+    pub time: f32,
+    pub event: ScenarioEvent,
+}
+
+/// Events scheduled after `from` and at or before `to`
+pub fn events_between(
+    events: &[TimedEvent],
+    from: f32,
+    to: f32,
+) -> impl Iterator<Item = &ScenarioEvent> {
+    events
+        .iter()
+        .filter(move |e| e.time > from && e.time <= to)
+        .map(|e| &e.event)
+}
+
+/// Frame label describing the events applied in a frame, None if there were none
+pub fn events_label<'a>(events: impl Iterator<Item = &'a ScenarioEvent>) -> Option<String> {
+    let labels: Vec<String> = events.map(|e| e.to_string()).collect();
+    if labels.is_empty() {
+        None
+    } else {
+        Some(labels.join(", "))
+    }
+}
+
+/// Events scheduled during the frame that ended at `time`
+pub fn frame_events<'a>(
+    events: &'a [TimedEvent],
+    time: &Time,
+) -> impl Iterator<Item = &'a ScenarioEvent> {
+    let to = time.absolute_time().as_secs_f32();
+    events_between(events, to - time.delta_seconds(), to)
+}
+
+/// How a client presents a teleport flagged in the sync messages
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TeleportPolicy {
+    /// Hold the old position until the teleport snapshot, then jump
+    Snap,
+    /// Interpolate through the teleport like any other movement
+    Smooth,
+    /// Fade out at the old position and fade in at the new one
+    Fade,
+}
+impl fmt::Display for TeleportPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TeleportPolicy::Snap => write!(f, "Snap"),
+            TeleportPolicy::Smooth => write!(f, "Smooth"),
+            TeleportPolicy::Fade => write!(f, "Fade"),
+        }
+    }
+}
+pub const TELEPORT_POLICIES: [TeleportPolicy; 3] = [
+    TeleportPolicy::Snap,
+    TeleportPolicy::Smooth,
+    TeleportPolicy::Fade,
+];
@@ -0,0 +1,173 @@
+/// Returns true if sequence number `a` is more recent than `b`, accounting for wraparound.
+pub fn sequence_greater_than(a: u16, b: u16) -> bool {
+    const HALF: u16 = std::u16::MAX / 2 + 1;
+    (a > b && a - b <= HALF) || (a < b && b - a > HALF)
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Delivery {
+    /// Newer than anything received so far
+    Latest,
+    /// Older than the latest received sequence, but not seen before
+    Late,
+    /// Already received
+    Duplicate,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SequenceStats {
+    /// Messages delivered as either latest or late
+    pub received: u32,
+    pub late: u32,
+    pub duplicates: u32,
+    /// Sequence numbers skipped over when a newer message arrived
+    pub gaps: u32,
+    /// Largest distance behind the latest sequence that a late message arrived at
+    pub max_lateness: u16,
+}
+
+/// Classifies incoming sequence numbers of an unreliable message stream as latest, late or
+/// duplicate, keeping a 32 message history window for duplicate detection.
+#[derive(Clone, Debug, Default)]
+pub struct SequencedReceiver {
+    latest: Option<u16>,
+    /// Bit n is set if `latest - 1 - n` has been received
+    history: u32,
+    stats: SequenceStats,
+}
+
+impl SequencedReceiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn latest(&self) -> Option<u16> {
+        self.latest
+    }
+    /// Received sequence numbers before `latest`, bit n being `latest - 1 - n`
+    pub fn history(&self) -> u32 {
+        self.history
+    }
+    pub fn stats(&self) -> &SequenceStats {
+        &self.stats
+    }
+    /// Sequence numbers skipped over that haven't arrived late since
+    pub fn missing(&self) -> u32 {
+        self.stats.gaps.saturating_sub(self.stats.late)
+    }
+    pub fn recv(&mut self, seq: u16) -> Delivery {
+        let latest = match self.latest {
+            Some(latest) => latest,
+            None => {
+                self.latest = Some(seq);
+                self.stats.received += 1;
+                return Delivery::Latest;
+            }
+        };
+        if seq == latest {
+            self.stats.duplicates += 1;
+            Delivery::Duplicate
+        } else if sequence_greater_than(seq, latest) {
+            let distance = seq.wrapping_sub(latest) as u32;
+            // the previous latest moves into the history at bit (distance - 1)
+            self.history = self.history.checked_shl(distance).unwrap_or(0)
+                | 1u32.checked_shl(distance - 1).unwrap_or(0);
+            self.latest = Some(seq);
+            self.stats.received += 1;
+            self.stats.gaps += distance - 1;
+            Delivery::Latest
+        } else {
+            let distance = latest.wrapping_sub(seq);
+            let bit = 1u32.checked_shl(distance as u32 - 1).unwrap_or(0);
+            if self.history & bit != 0 {
+                self.stats.duplicates += 1;
+                return Delivery::Duplicate;
+            }
+            // messages older than the history window can't be checked for duplication
+            self.history |= bit;
+            self.stats.received += 1;
+            self.stats.late += 1;
+            self.stats.max_lateness = self.stats.max_lateness.max(distance);
+            Delivery::Late
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn greater_than_across_wraparound() {
+        assert!(sequence_greater_than(0, 65535));
+        assert!(sequence_greater_than(5, 65530));
+        assert!(!sequence_greater_than(65535, 0));
+        assert!(sequence_greater_than(1, 0));
+        assert!(!sequence_greater_than(0, 0));
+        // half the sequence space apart is still newer, more than half is older
+        assert!(sequence_greater_than(32768, 0));
+        assert!(!sequence_greater_than(32769, 0));
+    }
+
+    #[test]
+    fn latest_across_wraparound() {
+        let mut receiver = SequencedReceiver::new();
+        assert_eq!(receiver.recv(65534), Delivery::Latest);
+        assert_eq!(receiver.recv(65535), Delivery::Latest);
+        assert_eq!(receiver.recv(0), Delivery::Latest);
+        assert_eq!(receiver.recv(1), Delivery::Latest);
+        assert_eq!(receiver.latest(), Some(1));
+        assert_eq!(receiver.history & 0b111, 0b111);
+        assert_eq!(receiver.stats().gaps, 0);
+    }
+
+    #[test]
+    fn rejects_duplicates() {
+        let mut receiver = SequencedReceiver::new();
+        assert_eq!(receiver.recv(10), Delivery::Latest);
+        assert_eq!(receiver.recv(10), Delivery::Duplicate);
+        assert_eq!(receiver.recv(12), Delivery::Latest);
+        assert_eq!(receiver.recv(10), Delivery::Duplicate);
+        assert_eq!(receiver.recv(11), Delivery::Late);
+        assert_eq!(receiver.recv(11), Delivery::Duplicate);
+        let stats = receiver.stats();
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.duplicates, 3);
+    }
+
+    #[test]
+    fn late_delivery_stats_with_gaps() {
+        let mut receiver = SequencedReceiver::new();
+        for seq in [65533, 65534, 3, 65535, 0, 7].iter() {
+            receiver.recv(*seq);
+        }
+        let stats = receiver.stats();
+        assert_eq!(stats.received, 6);
+        assert_eq!(stats.late, 2);
+        assert_eq!(stats.duplicates, 0);
+        // 65535 to 2 were skipped for 3 and 4 to 6 for 7, late arrivals don't fill gaps
+        assert_eq!(stats.gaps, 7);
+        // 65535 arrived 4 behind 3
+        assert_eq!(stats.max_lateness, 4);
+        assert_eq!(receiver.recv(2), Delivery::Late);
+        assert_eq!(receiver.stats().max_lateness, 5);
+    }
+
+    #[test]
+    fn missing_over_long_runs() {
+        let mut receiver = SequencedReceiver::new();
+        assert_eq!(receiver.missing(), 0);
+        // every tenth message lost over more than the sequence space, well past the history
+        let mut lost = 0;
+        for n in 1..70_000u32 {
+            if n % 10 == 0 {
+                lost += 1;
+            } else {
+                receiver.recv(n as u16);
+            }
+        }
+        assert_eq!(receiver.missing(), lost);
+        // late arrivals fill their gaps
+        receiver.recv(69_990u32 as u16);
+        assert_eq!(receiver.missing(), lost - 1);
+    }
+}
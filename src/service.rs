@@ -0,0 +1,367 @@
+//! Headless service running simulations on request, for sweep farms driving the simulator over
+//! HTTP:
+//!
+//! - `POST /run` with the RON settings of a scenario as the body runs it and responds with the
+//!   summary of its metrics as JSON
+//! - `POST /jobs` with the same body queues the run in the durable `JobQueue` and responds with
+//!   the job's id, `GET /jobs/<id>` with its status and summary and `GET /jobs/<id>/result` with
+//!   its full result
+//! - `POST /validate` with the same body responds with the issues `validation::validate` finds
+//!   in the settings as JSON. `POST /run` rejects settings with errors with the same response
+//! - `GET /metrics` responds with the aggregates of the runs so far in the Prometheus text format
+use crate::{
+    jobs::{JobQueue, JobStatus},
+    metrics::MetricSummary,
+    sim::{run_simulation, Sample, SimSettings, SimulationResult},
+    validation::{has_errors, validate},
+};
+use amethyst::Result;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    fmt::Write as _,
+    io::{self, BufRead, BufReader, Read, Write},
+    net::{TcpListener, TcpStream, ToSocketAddrs},
+    panic::{self, AssertUnwindSafe},
+    path::Path,
+    sync::{Arc, Mutex},
+    thread,
+    time::Instant,
+};
+
+/// Upper bounds in seconds of the run duration histogram buckets
+const DURATION_BUCKETS: [f64; 8] = [0.1, 0.5, 1., 2., 5., 10., 30., 60.];
+/// Largest request body accepted, settings are a few KiB
+const MAX_BODY: usize = 1 << 20;
+
+/// Counts of observations at or below each bound, the Prometheus histogram type
+struct Histogram {
+    bounds: &'static [f64],
+    counts: Vec<u64>,
+    sum: f64,
+    count: u64,
+}
+impl Histogram {
+    fn new(bounds: &'static [f64]) -> Self {
+        Histogram {
+            bounds,
+            counts: vec![0; bounds.len()],
+            sum: 0.,
+            count: 0,
+        }
+    }
+    fn observe(&mut self, value: f64) {
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter_mut()) {
+            if value <= *bound {
+                *count += 1;
+            }
+        }
+        self.sum += value;
+        self.count += 1;
+    }
+    fn write(&self, out: &mut String, name: &str, help: &str) {
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} histogram", name);
+        for (bound, count) in self.bounds.iter().zip(self.counts.iter()) {
+            let _ = writeln!(out, "{}_bucket{{le=\"{}\"}} {}", name, bound, count);
+        }
+        let _ = writeln!(out, "{}_bucket{{le=\"+Inf\"}} {}", name, self.count);
+        let _ = writeln!(out, "{}_sum {}", name, self.sum);
+        let _ = writeln!(out, "{}_count {}", name, self.count);
+    }
+}
+
+/// Aggregates of the runs the service executed
+pub struct ServiceMetrics {
+    runs: u64,
+    failed_runs: u64,
+    simulated_seconds: f64,
+    durations: Histogram,
+    /// Sum and count of the mean of each metric channel over the runs recording it
+    means: BTreeMap<String, (f64, u64)>,
+}
+impl Default for ServiceMetrics {
+    fn default() -> Self {
+        ServiceMetrics {
+            runs: 0,
+            failed_runs: 0,
+            simulated_seconds: 0.,
+            durations: Histogram::new(&DURATION_BUCKETS),
+            means: BTreeMap::new(),
+        }
+    }
+}
+impl ServiceMetrics {
+    fn record_run(
+        &mut self,
+        settings: &SimSettings,
+        seconds: f64,
+        metrics: &BTreeMap<String, MetricSummary>,
+    ) {
+        self.runs += 1;
+        self.simulated_seconds += settings.run_duration() as f64;
+        self.durations.observe(seconds);
+        for (name, summary) in metrics.iter().filter(|(_, s)| s.count > 0) {
+            let mean = self.means.entry(name.clone()).or_insert((0., 0));
+            mean.0 += summary.mean as f64;
+            mean.1 += 1;
+        }
+    }
+    /// The aggregates and the number of jobs waiting in the queue in the Prometheus text
+    /// exposition format
+    pub fn render(&self, pending_jobs: usize) -> String {
+        let mut out = String::new();
+        let _ = writeln!(
+            out,
+            "# HELP network_sim_pending_jobs Jobs waiting in the queue"
+        );
+        let _ = writeln!(out, "# TYPE network_sim_pending_jobs gauge");
+        let _ = writeln!(out, "network_sim_pending_jobs {}", pending_jobs);
+        let mut counter = |name: &str, help: &str, value: f64| {
+            let _ = writeln!(out, "# HELP {} {}", name, help);
+            let _ = writeln!(out, "# TYPE {} counter", name);
+            let _ = writeln!(out, "{} {}", name, value);
+        };
+        counter(
+            "network_sim_runs_total",
+            "Runs executed successfully",
+            self.runs as f64,
+        );
+        counter(
+            "network_sim_failed_runs_total",
+            "Runs that failed or had invalid settings",
+            self.failed_runs as f64,
+        );
+        counter(
+            "network_sim_simulated_seconds_total",
+            "Virtual seconds simulated by the successful runs",
+            self.simulated_seconds,
+        );
+        self.durations.write(
+            &mut out,
+            "network_sim_run_duration_seconds",
+            "Wall-clock time of each run",
+        );
+        let name = "network_sim_metric_mean";
+        let _ = writeln!(
+            out,
+            "# HELP {} Sum and count of the mean of each metric channel per run",
+            name
+        );
+        let _ = writeln!(out, "# TYPE {} summary", name);
+        for (metric, (sum, count)) in self.means.iter() {
+            let label = metric.replace('\\', "\\\\").replace('"', "\\\"");
+            let _ = writeln!(out, "{}_sum{{metric=\"{}\"}} {}", name, label, sum);
+            let _ = writeln!(out, "{}_count{{metric=\"{}\"}} {}", name, label, count);
+        }
+        out
+    }
+}
+
+/// Response body of `POST /run`
+#[derive(Serialize)]
+struct RunResponse {
+    window: (f32, f32),
+    metrics: BTreeMap<String, MetricSummary>,
+}
+
+/// Serves requests on `addr` until the listener fails, each connection on its own thread, and
+/// runs the jobs queued in `jobs_dir` one at a time
+pub fn serve(addr: impl ToSocketAddrs, jobs_dir: &Path) -> Result<()> {
+    let listener = TcpListener::bind(addr)?;
+    let metrics = Arc::new(Mutex::new(ServiceMetrics::default()));
+    let queue = Arc::new(JobQueue::open(jobs_dir)?);
+    {
+        let (metrics, queue) = (metrics.clone(), queue.clone());
+        thread::spawn(move || loop {
+            let (id, settings) = queue.next();
+            // a panicking run fails its job rather than ending the worker
+            let outcome = settings.and_then(|settings| {
+                panic::catch_unwind(AssertUnwindSafe(|| run(&settings, &metrics))).unwrap_or_else(
+                    |_| Err(io::Error::new(io::ErrorKind::Other, "the run panicked").into()),
+                )
+            });
+            if outcome.is_err() {
+                metrics.lock().unwrap().failed_runs += 1;
+            }
+            if let Err(e) = queue.finish(id, outcome) {
+                log::error!("failed to store the outcome of job {}: {}", id, e);
+            }
+        });
+    }
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let (metrics, queue) = (metrics.clone(), queue.clone());
+        thread::spawn(move || {
+            if let Err(e) = handle(stream, &metrics, &queue) {
+                log::warn!("failed to handle request: {}", e);
+            }
+        });
+    }
+    Ok(())
+}
+
+fn handle(stream: TcpStream, metrics: &Mutex<ServiceMetrics>, queue: &JobQueue) -> io::Result<()> {
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line)?;
+    let mut parts = request_line.split_whitespace();
+    let (method, path) = (parts.next().unwrap_or(""), parts.next().unwrap_or(""));
+    let mut content_length = 0;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header)? == 0 || header.trim().is_empty() {
+            break;
+        }
+        if let Some(colon) = header.find(':') {
+            if header[..colon]
+                .trim()
+                .eq_ignore_ascii_case("content-length")
+            {
+                content_length = header[colon + 1..].trim().parse().unwrap_or(0);
+            }
+        }
+    }
+    let (status, content_type, body) = match (method, path) {
+        ("GET", "/metrics") => (
+            "200 OK",
+            "text/plain; version=0.0.4",
+            metrics.lock().unwrap().render(queue.pending()),
+        ),
+        ("POST", "/run") | ("POST", "/jobs") | ("POST", "/validate")
+            if content_length > MAX_BODY =>
+        {
+            (
+                "413 Payload Too Large",
+                "text/plain",
+                "settings too large\n".to_owned(),
+            )
+        }
+        ("POST", "/run") => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            let settings: Result<SimSettings> = std::str::from_utf8(&body)
+                .map_err(Into::into)
+                .and_then(|body| Ok(ron::de::from_str(body)?));
+            let issues = settings.as_ref().map(validate).unwrap_or_default();
+            if has_errors(&issues) {
+                metrics.lock().unwrap().failed_runs += 1;
+                (
+                    "400 Bad Request",
+                    "application/json",
+                    serde_json::to_string(&issues).unwrap_or_default(),
+                )
+            } else {
+                // a panicking run fails the request rather than dropping the connection
+                let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+                    settings.and_then(|settings| run(&settings, metrics))
+                }));
+                match outcome {
+                    Ok(Ok((summary, _))) => ("200 OK", "application/json", summary),
+                    Ok(Err(e)) => {
+                        metrics.lock().unwrap().failed_runs += 1;
+                        ("400 Bad Request", "text/plain", format!("{}\n", e))
+                    }
+                    Err(_) => {
+                        metrics.lock().unwrap().failed_runs += 1;
+                        (
+                            "500 Internal Server Error",
+                            "text/plain",
+                            "the run panicked\n".to_owned(),
+                        )
+                    }
+                }
+            }
+        }
+        ("POST", "/validate") => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            let settings: Result<SimSettings> = std::str::from_utf8(&body)
+                .map_err(Into::into)
+                .and_then(|body| Ok(ron::de::from_str(body)?));
+            match settings {
+                Ok(settings) => (
+                    "200 OK",
+                    "application/json",
+                    serde_json::to_string(&validate(&settings)).unwrap_or_default(),
+                ),
+                Err(e) => ("400 Bad Request", "text/plain", format!("{}\n", e)),
+            }
+        }
+        ("POST", "/jobs") => {
+            let mut body = vec![0; content_length];
+            reader.read_exact(&mut body)?;
+            match queue.submit(&String::from_utf8_lossy(&body)) {
+                Ok(id) => (
+                    "202 Accepted",
+                    "application/json",
+                    serde_json::json!({ "id": id }).to_string(),
+                ),
+                Err(e) => ("400 Bad Request", "text/plain", format!("{}\n", e)),
+            }
+        }
+        ("GET", path) if path.starts_with("/jobs/") => {
+            let mut parts = path["/jobs/".len()..].splitn(2, '/');
+            let id = parts.next().and_then(|id| id.parse::<u64>().ok());
+            match (id, parts.next()) {
+                (Some(id), None) => match queue.status(id) {
+                    Some(status) => ("200 OK", "application/json", job_status(id, status)),
+                    None => ("404 Not Found", "text/plain", "no such job\n".to_owned()),
+                },
+                (Some(id), Some("result")) => match queue.result(id) {
+                    Some(result) => ("200 OK", "application/json", result),
+                    None => ("404 Not Found", "text/plain", "no result\n".to_owned()),
+                },
+                _ => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+            }
+        }
+        _ => ("404 Not Found", "text/plain", "not found\n".to_owned()),
+    };
+    let mut stream = stream;
+    write!(
+        stream,
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        status,
+        content_type,
+        body.len(),
+        body
+    )?;
+    stream.flush()
+}
+
+/// Runs a scenario, returning the JSON summary of its metrics and its result
+fn run(
+    settings: &SimSettings,
+    metrics: &Mutex<ServiceMetrics>,
+) -> Result<(String, SimulationResult<Sample>)> {
+    let started = Instant::now();
+    let result = run_simulation(settings)?;
+    let summary = result.metrics.summary();
+    metrics
+        .lock()
+        .unwrap()
+        .record_run(settings, started.elapsed().as_secs_f64(), &summary);
+    let response = serde_json::to_string(&RunResponse {
+        window: result.window,
+        metrics: summary,
+    })?;
+    Ok((response, result))
+}
+
+/// JSON status of job `id`, with its summary once it's done
+fn job_status(id: u64, status: JobStatus) -> String {
+    match status {
+        JobStatus::Pending => serde_json::json!({ "id": id, "status": "pending" }),
+        JobStatus::Running => serde_json::json!({ "id": id, "status": "running" }),
+        JobStatus::Done(summary) => serde_json::json!({
+            "id": id,
+            "status": "done",
+            "summary": serde_json::from_str::<serde_json::Value>(&summary)
+                .unwrap_or(serde_json::Value::Null),
+        }),
+        JobStatus::Failed(error) => {
+            serde_json::json!({ "id": id, "status": "failed", "error": error })
+        }
+    }
+    .to_string()
+}
@@ -0,0 +1,42 @@
+use amethyst::Result;
+use network_sim::sim::SimSettings;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Bookmark {
+    pub time: f32,
+    pub name: String,
+}
+
+#[derive(Default, Clone, Debug)]
+pub struct Bookmarks(pub Vec<Bookmark>);
+impl Bookmarks {
+    pub fn add(&mut self, time: f32, name: String) {
+        let idx = self
+            .0
+            .iter()
+            .position(|b| b.time > time)
+            .unwrap_or(self.0.len());
+        self.0.insert(idx, Bookmark { time, name });
+    }
+}
+
+/// Everything needed to reproduce and annotate a run, saved as RON.
+#[derive(Serialize, Deserialize)]
+pub struct Session {
+    pub settings: SimSettings,
+    #[serde(default)]
+    pub bookmarks: Vec<Bookmark>,
+}
+impl Session {
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let contents = ron::ser::to_string_pretty(self, Default::default())?;
+        std::fs::write(path, contents)?;
+        Ok(())
+    }
+    pub fn load(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(ron::de::from_str(&contents)?)
+    }
+}
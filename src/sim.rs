@@ -1,76 +1,643 @@
 use std::time::Duration;
 
+#[cfg(target_arch = "wasm32")]
+use crate::stepping::step_in_turn;
+#[cfg(not(target_arch = "wasm32"))]
+use crate::stepping::SideClocks;
+use crate::{
+    codec::{self, DeltaDecoder, DeltaEncoder, SyncCodec},
+    filter::{new_filter, DeadBand, FilterKind, PositionFilter},
+    latency::{self, LatencyModel},
+    metrics::{merge_by_time, Metrics},
+    monkey::SimMonkey,
+    p2p::{InputDelayPolicy, LatencyChange, Topology},
+    pipeline::{pipeline_label, PipelineStage},
+    reliability::{AckMessage, InputDelivery, ReliableSender, RetransmitPolicy},
+    rng::SimRng,
+    scenario::{events_label, frame_events, ScenarioEvent, TeleportPolicy, TimedEvent},
+    sequence::{Delivery, SequencedReceiver},
+    sim_behaviours::{AnimationPolicy, InteractionPrediction},
+    snapshot_buffer::{Interpolate, Kernel, Key, KeyFlags, SnapshotBuffer},
+    stepping::Stepper,
+    stopwatch::Stopwatch,
+    style::Style,
+    truth::{ErrorReference, GroundTruth, ServerTruth},
+    validation::SettingsLink,
+    view::ScaleMode,
+};
 use amethyst::{
     core::{
-        math::{self, Vector2},
+        math::{self, Vector2, Vector3},
         SystemDesc, Time,
     },
     ecs::{Read, ReadExpect, System, World, Write, WriteExpect},
-    network::simulation::{
-        memory::{channel as memory_channel, MemoryNetworkBundle},
-        NetworkSimulationEvent, NetworkSimulationTime, TransportResource,
-    },
+    network::simulation::{NetworkSimulationTime, NetworkSimulationTimeSystem},
     prelude::*,
-    shrev::{EventChannel, ReaderId},
     utils::application_root_dir,
     Result,
 };
-use serde::{Deserialize, Serialize};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
 use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
     fmt::{self, Debug},
-    sync::{Arc, Mutex},
+    fs::File,
+    io::{BufReader, BufWriter},
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+};
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::mpsc,
+    thread,
+    time::Instant,
 };
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
 pub enum SimSide {
     Client,
     Server,
 }
-#[derive(Debug)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WorldFrame<M: Debug + Clone> {
     pub side: SimSide,
+    /// Entity the frame belongs to, None for the behaviour's primary entity
+    #[serde(default)]
+    pub entity: Option<u32>,
+    /// Free-form annotation, e.g. the scenario event that happened in this frame
+    #[serde(default)]
+    pub label: Option<String>,
     pub render_time: f32,
     pub net_time: f32,
     pub sample: M,
+    /// Client sample before the position filter, if one was applied
+    #[serde(default = "Option::default")]
+    pub unfiltered: Option<M>,
 }
-#[derive(Clone)]
+/// How a run accounts for the client rendering the server's last frames only after the
+/// interpolation delay and latency
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum CompensationPolicy {
+    /// Run and analyze both sides for the duration plus the compensation
+    Extend,
+    /// Run and analyze only the duration, cutting off the client's last frames
+    Truncate,
+    /// Run for the duration plus the compensation so messages sent during the duration arrive,
+    /// but analyze only the duration
+    PadAndTrim,
+}
+impl fmt::Display for CompensationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CompensationPolicy::Extend => write!(f, "Extend"),
+            CompensationPolicy::Truncate => write!(f, "Truncate"),
+            CompensationPolicy::PadAndTrim => write!(f, "Pad and Trim"),
+        }
+    }
+}
+pub const COMPENSATION_POLICIES: [CompensationPolicy; 3] = [
+    CompensationPolicy::Extend,
+    CompensationPolicy::Truncate,
+    CompensationPolicy::PadAndTrim,
+];
+
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
 pub struct SimSettings {
     pub curr_time: f32,
+    pub second_playhead: bool,
+    /// Offset of the second playhead from `curr_time` in ms
+    pub playhead_offset: f32,
     pub sim_time_scale: f32,
+    /// Advance playback by exactly one recorded client frame per viewer frame
+    pub step_frames: bool,
+    /// Advance playback by the wall-clock time between viewer frames instead of the frame
+    /// time, so a second of playback is a second of simulated time at any viewer frame rate
+    pub wall_clock_playback: bool,
+    /// Pause at the end of the run instead of starting over
+    pub pause_at_end: bool,
+    /// Click when playback crosses a pop over `pop_cue_threshold` or an underrun
+    pub audio_cues: bool,
+    pub pop_cue_threshold: f32,
     pub server_fps: u32,
     pub sync_rate: u32,
     pub render_fps: u32,
     pub render_time_variance: f32,
+    /// Factor the server and client steps are lengthened by to finish long runs sooner, at the
+    /// cost of fewer frames per second. Limited by `step_scale`
+    pub fast_forward: f32,
     pub duration: f32,
     pub render_interpolation_delay: f32,
     pub min_latency: f32,
     pub max_latency: f32,
+    /// Distribution the latency of each packet is drawn from
+    pub latency_model: LatencyModel,
+    /// Mean latency in ms of `LatencyModel::Normal`
+    pub latency_mean: f32,
+    /// Standard deviation in ms of `LatencyModel::Normal`
+    pub latency_stddev: f32,
+    /// Smallest latency in ms of `LatencyModel::Pareto`, its scale
+    pub latency_pareto_scale: f32,
+    /// Shape of `LatencyModel::Pareto`
+    pub latency_pareto_shape: f32,
+    /// Latency in ms the tail of `LatencyModel::Pareto` is cut at, as small shapes draw
+    /// latencies without bound
+    pub latency_pareto_max: f32,
     pub loss_percentage: f32,
+    /// Lose packets in bursts by the Gilbert–Elliott model of the `burst_` fields instead of
+    /// uniformly by `loss_percentage`, see `loss::GilbertElliott`
+    pub burst_loss: bool,
+    /// Probability per packet of the link turning from good to bad
+    pub burst_good_to_bad: f32,
+    /// Probability per packet of the link recovering from bad to good
+    pub burst_bad_to_good: f32,
+    /// Loss probability in the good state
+    pub burst_good_loss: f32,
+    /// Loss probability in the bad state
+    pub burst_bad_loss: f32,
+    /// Probability that the server skips a scheduled sync message, as when its send thread is
+    /// starved. Unlike loss the message is never sent, so it takes no sequence number
+    pub send_skip_percentage: f32,
+    pub retransmit_policy: RetransmitPolicy,
+    pub retransmit_timeout: f32,
+    /// Fields kept at a multiple of others as those are edited in the GUI or swept
+    pub links: Vec<SettingsLink>,
+    pub input_delivery: InputDelivery,
+    /// Number of most recent inputs in every message with redundant input delivery
+    pub input_redundancy: u32,
+    /// Serialization of the behaviours' sync messages
+    pub codec: SyncCodec,
+    /// Sync messages from one keyframe to the next with the delta encoding varint codec, 1 for
+    /// keyframes only
+    pub keyframe_interval: u32,
+    /// Huffman code the deltas of the delta encoding varint codec, see `codec::DeltaEncoder`
+    pub entropy_coding: bool,
+    /// Entities alive at once in the spawning entities behaviour
+    pub entity_count: u32,
+    /// Client CPU time in ms per frame for reconciliation work, beyond which the frame takes
+    /// longer, 0 for no limit
+    pub client_cpu_budget: f32,
+    /// Client CPU time in ms to re-simulate one frame after a rollback
+    pub resimulation_cost: f32,
+    /// What the door interaction client shows while waiting for the server
+    pub interaction_prediction: InteractionPrediction,
+    /// How the animation state machine client switches to states it learns about late
+    pub animation_policy: AnimationPolicy,
+    /// Milliseconds a crossfade between animation states takes
+    pub crossfade_window: f32,
+    /// Seconds at the start of a run excluded from the metrics, to skip connection establishment
+    pub warm_up: f32,
+    pub compensation: CompensationPolicy,
+    pub error_reference: ErrorReference,
+    /// Seeds all randomness of a run, see `SimRng`
+    pub seed: u64,
+    /// Scripted discontinuities applied by the server simulation
+    pub events: Vec<TimedEvent>,
+    pub teleport_policy: TeleportPolicy,
+    /// Client stages of the composed pipeline behaviours, in order
+    pub pipeline: Vec<PipelineStage>,
+    /// Filter applied to the client's rendered primary entity position
+    pub filter: FilterKind,
+    /// Smoothing factor of the exponential filters, 1 disables smoothing
+    pub filter_alpha: f32,
+    /// Trend smoothing factor of the double exponential filter
+    pub filter_beta: f32,
+    pub kalman_process_noise: f32,
+    pub kalman_measurement_noise: f32,
+    /// 1€ filter cutoff frequency in Hz at zero speed
+    pub one_euro_min_cutoff: f32,
+    /// 1€ filter cutoff frequency increase per unit of speed
+    pub one_euro_beta: f32,
+    /// Server corrections smaller than this are ignored by predicting clients
+    pub correction_deadband: f32,
+    /// Fraction of the dead-band corrections keep being applied below, once one exceeded it
+    pub correction_hysteresis: f32,
+    /// Also run the peer-to-peer rollback model, see `p2p::simulate`
+    pub peer_to_peer: bool,
+    /// Frames between a peer sampling an input and the frame it is applied in
+    pub input_delay: u32,
+    pub input_delay_policy: InputDelayPolicy,
+    /// Seconds between input delay negotiations
+    pub renegotiation_interval: f32,
+    /// Scripted latency changes of the peer-to-peer links
+    pub latency_changes: Vec<LatencyChange>,
+    pub topology: Topology,
+    /// One-way latency in ms from each peer (row) to each other peer (column), with one row per
+    /// peer. Empty for two peers with the configured latency range
+    pub latency_matrix: Vec<Vec<f32>>,
+    /// One-way latency in ms between each peer and the relay server
+    pub relay_latencies: Vec<f32>,
+    /// Frames a peer may simulate ahead of the last confirmed remote inputs before stalling
+    pub max_prediction: u32,
+    /// Start time of each peer after the previous one in ms
+    pub peer_start_offset: f32,
+    /// Non-primary entities the renderer skips
+    pub hidden_entities: BTreeSet<u32>,
+    pub curves: CurveVisibility,
+    pub style: Style,
+    /// Render the positions in 3D, seen from the orbit angles, instead of from above
+    pub view_3d: bool,
+    /// Orbit camera angle around the vertical axis in degrees
+    pub orbit_yaw: f32,
+    /// Orbit camera angle above the ground plane in degrees, 90 looks straight down
+    pub orbit_pitch: f32,
+    /// Also render every entity's positions at the playhead from above in a small viewport
+    pub minimap: bool,
+    /// Name the playheads' side and the client's error next to them
+    pub playhead_labels: bool,
+    /// Plot the viewer's own frame times, to tell viewer slowdowns from simulated ones
+    pub viewer_frame_times: bool,
+    /// Skip frame markers crowded onto the markers before them on screen
+    pub lod: bool,
+    /// Draw the frame markers as points on the GPU instead of debug lines, for very long runs
+    pub point_cloud: bool,
+    pub scale_mode: ScaleMode,
+    /// Lower corner of the world extents drawn with the fixed and baseline scale modes
+    pub scale_min: [f32; 3],
+    /// Upper corner of the world extents drawn with the fixed and baseline scale modes
+    pub scale_max: [f32; 3],
+    /// Draw the same number of world units per pixel on X and Y instead of stretching each
+    /// axis to its panel
+    pub equal_axis_scale: bool,
+    /// Draw Y from `y_min` to `y_max` whatever the scale mode
+    pub lock_y: bool,
+    pub y_min: f32,
+    pub y_max: f32,
     pub playing: bool,
+    #[serde(with = "behaviour_name")]
     pub behaviour: Arc<dyn SimulationBehaviour>,
 }
+/// Which kinds of frames the renderer draws
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CurveVisibility {
+    pub server: bool,
+    pub client: bool,
+    /// Client entities still rendered after the server despawned them
+    pub ghosts: bool,
+    /// Client positions before the position filter
+    pub unfiltered: bool,
+}
+impl Default for CurveVisibility {
+    fn default() -> Self {
+        Self {
+            server: true,
+            client: true,
+            ghosts: true,
+            unfiltered: true,
+        }
+    }
+}
+
+/// Serializes behaviours by their registered display name
+mod behaviour_name {
+    use super::SimulationBehaviour;
+    use serde::{de::Error, Deserialize, Deserializer, Serializer};
+    use std::sync::Arc;
+    pub fn serialize<S: Serializer>(
+        behaviour: &Arc<dyn SimulationBehaviour>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&behaviour.to_string())
+    }
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Arc<dyn SimulationBehaviour>, D::Error> {
+        let name = String::deserialize(deserializer)?;
+        crate::sim_behaviours::behaviour_by_name(&name)
+            .ok_or_else(|| D::Error::custom(format!("unknown behaviour \"{}\"", name)))
+    }
+}
 impl Default for SimSettings {
     fn default() -> Self {
         Self {
             curr_time: 0.0,
+            second_playhead: false,
+            playhead_offset: 0.,
             sim_time_scale: 1.0,
+            step_frames: false,
+            wall_clock_playback: false,
+            pause_at_end: false,
+            audio_cues: false,
+            pop_cue_threshold: 10.,
             render_fps: 60,
             sync_rate: 30,
             server_fps: 30,
+            fast_forward: 1.,
             duration: 0.5,
             render_interpolation_delay: 0.,
             render_time_variance: 0.,
             min_latency: 0.,
             max_latency: 0.,
+            latency_model: LatencyModel::Uniform,
+            latency_mean: 50.,
+            latency_stddev: 10.,
+            latency_pareto_scale: 20.,
+            latency_pareto_shape: 3.,
+            latency_pareto_max: 1000.,
             loss_percentage: 0.,
+            burst_loss: false,
+            burst_good_to_bad: 0.01,
+            burst_bad_to_good: 0.25,
+            burst_good_loss: 0.,
+            burst_bad_loss: 0.5,
+            send_skip_percentage: 0.,
+            retransmit_policy: RetransmitPolicy::None,
+            retransmit_timeout: 100.,
+            links: Vec::new(),
+            input_delivery: InputDelivery::Off,
+            input_redundancy: 3,
+            codec: SyncCodec::Bincode,
+            keyframe_interval: 1,
+            entropy_coding: false,
+            entity_count: 3,
+            client_cpu_budget: 0.,
+            resimulation_cost: 1.,
+            interaction_prediction: InteractionPrediction::Strict,
+            animation_policy: AnimationPolicy::Snap,
+            crossfade_window: 150.,
+            warm_up: 0.,
+            compensation: CompensationPolicy::Extend,
+            error_reference: ErrorReference::Interpolated,
+            seed: 0,
+            events: Vec::new(),
+            teleport_policy: TeleportPolicy::Smooth,
+            pipeline: vec![PipelineStage::Buffer { delay: 100. }],
+            filter: FilterKind::None,
+            filter_alpha: 0.5,
+            filter_beta: 0.3,
+            kalman_process_noise: 10000.,
+            kalman_measurement_noise: 25.,
+            one_euro_min_cutoff: 1.,
+            one_euro_beta: 0.007,
+            correction_deadband: 0.,
+            correction_hysteresis: 0.5,
+            peer_to_peer: false,
+            input_delay: 2,
+            input_delay_policy: InputDelayPolicy::Fixed,
+            renegotiation_interval: 1.,
+            latency_changes: Vec::new(),
+            topology: Topology::Mesh,
+            latency_matrix: Vec::new(),
+            relay_latencies: Vec::new(),
+            max_prediction: 8,
+            peer_start_offset: 50.,
+            hidden_entities: BTreeSet::new(),
+            curves: CurveVisibility::default(),
+            style: Style::default(),
+            view_3d: false,
+            orbit_yaw: 30.,
+            orbit_pitch: 30.,
+            minimap: false,
+            playhead_labels: true,
+            viewer_frame_times: false,
+            lod: true,
+            point_cloud: false,
+            scale_mode: ScaleMode::Auto,
+            scale_min: [-1.; 3],
+            scale_max: [1.; 3],
+            equal_axis_scale: false,
+            lock_y: false,
+            y_min: -1.,
+            y_max: 1.,
             playing: false,
             behaviour: Arc::new(crate::sim_behaviours::SineWaveThinClientCreator::default()),
         }
     }
 }
 
+impl SimSettings {
+    /// Sets a numeric setting by field name, returning false if there is no such setting
+    pub fn set_field(&mut self, name: &str, value: f64) -> bool {
+        match name {
+            "server_fps" => self.server_fps = value as u32,
+            "sync_rate" => self.sync_rate = value as u32,
+            "render_fps" => self.render_fps = value as u32,
+            "render_time_variance" => self.render_time_variance = value as f32,
+            "fast_forward" => self.fast_forward = value as f32,
+            "render_interpolation_delay" => self.render_interpolation_delay = value as f32,
+            "duration" => self.duration = value as f32,
+            "min_latency" => self.min_latency = value as f32,
+            "max_latency" => self.max_latency = value as f32,
+            "latency_mean" => self.latency_mean = value as f32,
+            "latency_stddev" => self.latency_stddev = value as f32,
+            "latency_pareto_scale" => self.latency_pareto_scale = value as f32,
+            "latency_pareto_shape" => self.latency_pareto_shape = value as f32,
+            "latency_pareto_max" => self.latency_pareto_max = value as f32,
+            "loss_percentage" => self.loss_percentage = value as f32,
+            "burst_good_to_bad" => self.burst_good_to_bad = value as f32,
+            "burst_bad_to_good" => self.burst_bad_to_good = value as f32,
+            "burst_good_loss" => self.burst_good_loss = value as f32,
+            "burst_bad_loss" => self.burst_bad_loss = value as f32,
+            "send_skip_percentage" => self.send_skip_percentage = value as f32,
+            "retransmit_timeout" => self.retransmit_timeout = value as f32,
+            "input_redundancy" => self.input_redundancy = value as u32,
+            "keyframe_interval" => self.keyframe_interval = value as u32,
+            "entity_count" => self.entity_count = value as u32,
+            "client_cpu_budget" => self.client_cpu_budget = value as f32,
+            "resimulation_cost" => self.resimulation_cost = value as f32,
+            "crossfade_window" => self.crossfade_window = value as f32,
+            "warm_up" => self.warm_up = value as f32,
+            "seed" => self.seed = value as u64,
+            "filter_alpha" => self.filter_alpha = value as f32,
+            "filter_beta" => self.filter_beta = value as f32,
+            "kalman_process_noise" => self.kalman_process_noise = value as f32,
+            "kalman_measurement_noise" => self.kalman_measurement_noise = value as f32,
+            "one_euro_min_cutoff" => self.one_euro_min_cutoff = value as f32,
+            "one_euro_beta" => self.one_euro_beta = value as f32,
+            "correction_deadband" => self.correction_deadband = value as f32,
+            "correction_hysteresis" => self.correction_hysteresis = value as f32,
+            "input_delay" => self.input_delay = value as u32,
+            "renegotiation_interval" => self.renegotiation_interval = value as f32,
+            "max_prediction" => self.max_prediction = value as u32,
+            "peer_start_offset" => self.peer_start_offset = value as f32,
+            _ => return false,
+        }
+        true
+    }
+    /// Factor the steps of a run are lengthened by, `fast_forward` limited so both sides still
+    /// step at least once per sync tick and don't skip network frames
+    pub fn step_scale(&self) -> f32 {
+        let max = self.server_fps.min(self.render_fps) as f32 / self.sync_rate.max(1) as f32;
+        self.fast_forward.min(max).max(1.)
+    }
+    /// Base latency in ms of the latency model, which few packets arrive faster than, and its
+    /// 95th percentile, which few arrive slower than. The uniform model's are its bounds
+    pub fn latency_range(&self) -> (f32, f32) {
+        // quantile of the standard normal distribution's 95th percentile
+        const Z_95: f32 = 1.645;
+        match self.latency_model {
+            LatencyModel::Uniform => (self.min_latency, self.max_latency.max(self.min_latency)),
+            LatencyModel::Normal => {
+                let spread = self.latency_stddev.max(0.) * Z_95;
+                (
+                    (self.latency_mean - spread).max(0.),
+                    (self.latency_mean + spread).max(0.),
+                )
+            }
+            LatencyModel::Pareto => {
+                let shape = self.latency_pareto_shape.max(std::f32::EPSILON);
+                let p95 = self.latency_pareto_scale * 0.05f32.powf(-1. / shape);
+                (
+                    self.latency_pareto_scale,
+                    p95.min(self.latency_pareto_max)
+                        .max(self.latency_pareto_scale),
+                )
+            }
+        }
+    }
+    /// Moves the latency model's base latency to `base` ms. The uniform and normal models keep
+    /// their spread, the Pareto model's tail scales with the base
+    pub fn set_base_latency(&mut self, base: f32) {
+        let shift = base - self.latency_range().0;
+        match self.latency_model {
+            LatencyModel::Uniform => {
+                self.min_latency += shift;
+                self.max_latency += shift;
+            }
+            LatencyModel::Normal => self.latency_mean += shift,
+            LatencyModel::Pareto => {
+                self.latency_pareto_scale += shift;
+                self.latency_pareto_max += shift;
+            }
+        }
+    }
+    /// Time the client needs to render what the server simulated at the end of the run
+    pub fn client_compensation(&self) -> f32 {
+        (self.render_interpolation_delay + self.latency_range().0) / 1000.
+    }
+    /// Seconds both sides are simulated for
+    pub fn run_duration(&self) -> f32 {
+        match self.compensation {
+            CompensationPolicy::Extend | CompensationPolicy::PadAndTrim => {
+                self.duration + self.client_compensation()
+            }
+            CompensationPolicy::Truncate => self.duration,
+        }
+    }
+    /// Start and end time in seconds of the part of a run the metrics cover
+    pub fn analyzed_window(&self) -> (f32, f32) {
+        match self.compensation {
+            CompensationPolicy::Extend => (self.warm_up, self.run_duration()),
+            CompensationPolicy::Truncate | CompensationPolicy::PadAndTrim => {
+                (self.warm_up, self.duration)
+            }
+        }
+    }
+    /// How long after its last server frame a client may still render an entity before it
+    /// counts as a ghost
+    pub fn ghost_slack(&self) -> f32 {
+        (self.render_interpolation_delay + self.latency_range().1) / 1000.
+            + 1. / self.sync_rate as f32
+    }
+    /// Describes the settings affecting the simulation run that differ from `prev`
+    pub fn diff(&self, prev: &SimSettings) -> Vec<String> {
+        let mut diff = Vec::new();
+        macro_rules! diff_field {
+            ($field: ident, $label: expr, $unit: expr) => {
+                if self.$field != prev.$field {
+                    diff.push(format!(
+                        "{} {}→{}{}",
+                        $label, prev.$field, self.$field, $unit
+                    ));
+                }
+            };
+        }
+        diff_field!(server_fps, "server fps", "");
+        diff_field!(render_fps, "client fps", "");
+        diff_field!(sync_rate, "sync rate", "");
+        diff_field!(render_interpolation_delay, "interpolation delay", " ms");
+        diff_field!(render_time_variance, "render time variance", " ms");
+        diff_field!(fast_forward, "fast-forward", "x");
+        diff_field!(min_latency, "min latency", " ms");
+        diff_field!(max_latency, "max latency", " ms");
+        diff_field!(latency_model, "latency model", "");
+        diff_field!(latency_mean, "mean latency", " ms");
+        diff_field!(latency_stddev, "latency stddev", " ms");
+        diff_field!(latency_pareto_scale, "pareto scale", " ms");
+        diff_field!(latency_pareto_shape, "pareto shape", "");
+        diff_field!(latency_pareto_max, "pareto max latency", " ms");
+        diff_field!(loss_percentage, "loss", "");
+        diff_field!(burst_loss, "burst loss", "");
+        diff_field!(burst_good_to_bad, "good→bad", "");
+        diff_field!(burst_bad_to_good, "bad→good", "");
+        diff_field!(burst_good_loss, "good state loss", "");
+        diff_field!(burst_bad_loss, "bad state loss", "");
+        diff_field!(send_skip_percentage, "skipped sends", "");
+        diff_field!(retransmit_policy, "retransmit", "");
+        diff_field!(retransmit_timeout, "retransmit timeout", " ms");
+        diff_field!(warm_up, "warm-up", " s");
+        diff_field!(compensation, "compensation", "");
+        diff_field!(error_reference, "error reference", "");
+        diff_field!(input_delivery, "inputs", "");
+        diff_field!(input_redundancy, "input redundancy", "");
+        diff_field!(codec, "codec", "");
+        diff_field!(keyframe_interval, "keyframe interval", "");
+        diff_field!(entropy_coding, "entropy coding", "");
+        diff_field!(entity_count, "entities", "");
+        diff_field!(client_cpu_budget, "client cpu budget", " ms");
+        diff_field!(resimulation_cost, "resimulation cost", " ms");
+        diff_field!(interaction_prediction, "interaction", "");
+        diff_field!(animation_policy, "animation switch", "");
+        diff_field!(crossfade_window, "crossfade window", " ms");
+        diff_field!(duration, "duration", " s");
+        diff_field!(seed, "seed", "");
+        diff_field!(teleport_policy, "teleport", "");
+        diff_field!(filter, "filter", "");
+        diff_field!(filter_alpha, "filter alpha", "");
+        diff_field!(filter_beta, "filter beta", "");
+        diff_field!(kalman_process_noise, "kalman process noise", "");
+        diff_field!(kalman_measurement_noise, "kalman measurement noise", "");
+        diff_field!(one_euro_min_cutoff, "1€ min cutoff", " Hz");
+        diff_field!(one_euro_beta, "1€ beta", "");
+        diff_field!(correction_deadband, "dead-band", "");
+        diff_field!(correction_hysteresis, "hysteresis", "");
+        diff_field!(peer_to_peer, "peer to peer", "");
+        diff_field!(input_delay, "input delay", " frames");
+        diff_field!(input_delay_policy, "input delay policy", "");
+        diff_field!(renegotiation_interval, "renegotiation interval", " s");
+        if self.latency_changes != prev.latency_changes {
+            diff.push(format!(
+                "latency changes {}→{}",
+                prev.latency_changes.len(),
+                self.latency_changes.len()
+            ));
+        }
+        diff_field!(topology, "topology", "");
+        if self.latency_matrix != prev.latency_matrix {
+            diff.push("latency matrix".to_owned());
+        }
+        if self.relay_latencies != prev.relay_latencies {
+            diff.push("relay latencies".to_owned());
+        }
+        diff_field!(max_prediction, "max prediction", " frames");
+        diff_field!(peer_start_offset, "peer start offset", " ms");
+        if self.events != prev.events {
+            diff.push(format!(
+                "events {}→{}",
+                prev.events.len(),
+                self.events.len()
+            ));
+        }
+        if self.pipeline != prev.pipeline {
+            diff.push(format!(
+                "pipeline {}→{}",
+                pipeline_label(&prev.pipeline),
+                pipeline_label(&self.pipeline)
+            ));
+        }
+        if self.behaviour.to_string() != prev.behaviour.to_string() {
+            diff.push(format!("mode {}→{}", prev.behaviour, self.behaviour));
+        }
+        diff
+    }
+}
+
 #[derive(Clone, Copy, Debug)]
-struct LocalClock {
+pub(crate) struct LocalClock {
     /// Clock offset's second part for constructing a Duration
     clock_offset_secs: i64,
     /// Clock offset's sub-second part for constructing a Duration
@@ -121,6 +688,45 @@ impl LocalClock {
             ..Default::default()
         }
     }
+    /// Clock ticking at `server_fps` that was started `server_time` before the local `time`
+    pub(crate) fn synced_to_server(
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        server_fps: u32,
+    ) -> Self {
+        let diff = time.absolute_time() - server_time;
+        let offset_secs = -(diff.as_secs() as i64);
+        let offset_nanos = -(diff.as_nanos() as i32);
+        let mut clock = LocalClock::new(
+            offset_secs,
+            offset_nanos,
+            None,
+            Some(Duration::from_secs_f32(1 as f32 / server_fps as f32)),
+        );
+        clock.frame_number = server_frame;
+        clock.absolute_time = server_time;
+        clock
+    }
+    /// Local render time in server time, one tick and `delay` ms in the past
+    pub(crate) fn playback_time(&self, time: &Time, delay: f32) -> f32 {
+        (time.absolute_time()
+            - Duration::new(
+                (-self.clock_offset_secs) as u64,
+                (-self.clock_offset_nanos) as u32,
+            ))
+        .as_secs_f32()
+            - self.time_per_frame.unwrap().as_secs_f32()
+            - (delay / 1000.)
+    }
+    /// Time of each frame ticked by the latest `tick`
+    fn ticked_frames(&self) -> impl Iterator<Item = Duration> {
+        let time_per_frame = self.time_per_frame.unwrap();
+        let frame_number = self.frame_number;
+        let frames_since_tick = self.frames_since_tick;
+        (1..=frames_since_tick)
+            .map(move |i| time_per_frame.mul_f32((frame_number - (frames_since_tick - i)) as f32))
+    }
     fn tick(&mut self, time: &Time) {
         let abs_time = if self.clock_offset_secs < 0 || self.clock_offset_nanos < 0 {
             time.absolute_time().checked_sub(Duration::new(
@@ -176,59 +782,284 @@ impl LocalClock {
     }
 }
 
+/// Version of the `ServerMessage` layout. Later versions may only append fields, which older
+/// decoders ignore, unless they also raise `MIN_WIRE_VERSION` to the new version.
+const WIRE_VERSION: u16 = 2;
+/// Oldest version the current layout can decode. Version 1 messages end before the key flags,
+/// see `ServerMessageV1`
+const MIN_WIRE_VERSION: u16 = 1;
+
+fn encode_server_message(msg: &ServerMessage) -> Vec<u8> {
+    bincode::serialize(&(WIRE_VERSION, msg)).unwrap()
+}
+/// Decodes a `ServerMessage` prefixed by its wire version
+fn decode_server_message(payload: &[u8]) -> bincode::Result<ServerMessage> {
+    let version: u16 = bincode::deserialize(payload)?;
+    match version {
+        version if version < MIN_WIRE_VERSION => Err(Box::new(bincode::ErrorKind::Custom(
+            format!("unsupported wire version {}", version),
+        ))),
+        1 => {
+            let (_, msg): (u16, ServerMessageV1) = bincode::deserialize(payload)?;
+            Ok(msg.into())
+        }
+        // fields appended by newer versions are trailing bytes, which bincode ignores
+        _ => {
+            let (_, msg): (u16, ServerMessage) = bincode::deserialize(payload)?;
+            Ok(msg)
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize)]
 struct ServerMessage {
-    // contents of Duration
-    server_secs: u64,
-    server_nanos: u32,
+    // contents of the Duration of the simulation tick the state is from
+    tick_secs: u64,
+    tick_nanos: u32,
+    // contents of the Duration of the network frame the message was sent in
+    send_secs: u64,
+    send_nanos: u32,
+    // length of the simulation tick, sub-second
+    tick_delta_nanos: u32,
     server_frame: u64,
+    // wrapping per-message sequence number
+    seq: u16,
+    // acks the client's inputs when they are delivered reliably
+    input_ack: Option<AckMessage>,
+    // SimulationState::sync_version of the behaviour that encoded msg
+    payload_version: u16,
     msg: Vec<u8>,
+    // SimulationState::key_flags of the ticks since the previous message
+    key_flags: KeyFlags,
+}
+
+/// `ServerMessage` of wire version 1, from before the key flags were appended
+#[derive(Serialize, Deserialize)]
+struct ServerMessageV1 {
+    tick_secs: u64,
+    tick_nanos: u32,
+    send_secs: u64,
+    send_nanos: u32,
+    tick_delta_nanos: u32,
+    server_frame: u64,
+    seq: u16,
+    input_ack: Option<AckMessage>,
+    payload_version: u16,
+    msg: Vec<u8>,
+}
+impl From<ServerMessageV1> for ServerMessage {
+    fn from(msg: ServerMessageV1) -> Self {
+        ServerMessage {
+            tick_secs: msg.tick_secs,
+            tick_nanos: msg.tick_nanos,
+            send_secs: msg.send_secs,
+            send_nanos: msg.send_nanos,
+            tick_delta_nanos: msg.tick_delta_nanos,
+            server_frame: msg.server_frame,
+            seq: msg.seq,
+            input_ack: msg.input_ack,
+            payload_version: msg.payload_version,
+            msg: msg.msg,
+            // version 1 servers sent no flags, so every key was interpolated through
+            key_flags: KeyFlags::default(),
+        }
+    }
+}
+
+/// Server timing of a sync message
+#[derive(Clone, Copy, Debug)]
+pub struct ServerClock {
+    /// Server time of the simulation tick the synced state is from
+    pub tick_time: Duration,
+    /// Server time of the network frame the message was sent in
+    pub send_time: Duration,
+    /// Length of that simulation tick
+    pub tick_duration: Duration,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct PlayerInput {
+    seq: u16,
+    /// Client time the input was sampled at
+    time: f32,
+    dir: Vector2<f32>,
+}
+#[derive(Serialize, Deserialize)]
+enum ClientMessage {
+    Ack(AckMessage),
+    Inputs(Vec<PlayerInput>),
+    /// Behaviour specific state, see `SimulationState::send_upstream`
+    Upstream(Vec<u8>),
 }
 
 pub trait SimulationBehaviour: fmt::Display + Send + Sync + std::any::Any {
     fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState>;
+    /// What the behaviour models, the artifacts to expect and the settings affecting them,
+    /// shown in the GUI next to the selected mode
+    fn description(&self) -> String;
+    /// Closed-form position of the primary entity at any server time, for behaviours whose
+    /// motion has one, see `ErrorReference::GroundTruth`
+    fn ground_truth(&self, _settings: &SimSettings) -> Option<GroundTruth> {
+        None
+    }
 }
 pub trait AsymmetricSimulationState {
     type SyncType: Serialize + for<'de> Deserialize<'de>;
+    fn init(&mut self, _settings: &SimSettings) {}
     fn update_server(&mut self, time: &Time) -> Sample;
     fn send_state(&self) -> &Self::SyncType;
     fn recv_state(&mut self, val: Self::SyncType, time: &Time);
-    fn send_sync(&self, _time: &Time) -> Vec<u8> {
-        bincode::serialize(&self.send_state()).unwrap()
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.send_state())
     }
     fn recv_sync(
         &mut self,
         time: &Time,
         _server_time: Duration,
         _server_frame: u64,
+        _seq: u16,
         msg: &Vec<u8>,
+        codec: SyncCodec,
     ) {
-        self.recv_state(bincode::deserialize(msg).unwrap(), time);
+        self.recv_state(codec.decode(msg), time);
     }
     fn update_render(&mut self, time: &Time) -> Option<Sample>;
+    fn record_metrics(&self, _side: SimSide, _time: &Time, _metrics: &mut Metrics) {}
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        None
+    }
+    fn finalize(&mut self, _side: SimSide, _result: &mut SimulationResult<Sample>) {}
+    fn debug_state(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 impl<T: AsymmetricSimulationState + Send + Sync + 'static> SimulationState for T {
+    fn init(&mut self, settings: &SimSettings) {
+        <Self as AsymmetricSimulationState>::init(self, settings)
+    }
     fn update_server(&mut self, time: &Time) -> Sample {
         <Self as AsymmetricSimulationState>::update_server(self, time)
     }
-    fn send_sync(&self, time: &Time) -> Vec<u8> {
-        <Self as AsymmetricSimulationState>::send_sync(self, time)
+    fn send_sync(&self, time: &Time, codec: SyncCodec) -> Vec<u8> {
+        <Self as AsymmetricSimulationState>::send_sync(self, time, codec)
     }
-    fn recv_sync(&mut self, time: &Time, server_time: Duration, server_frame: u64, msg: &Vec<u8>) {
-        <Self as AsymmetricSimulationState>::recv_sync(self, time, server_time, server_frame, msg)
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        <Self as AsymmetricSimulationState>::recv_sync(
+            self,
+            time,
+            server_time,
+            server_frame,
+            seq,
+            msg,
+            codec,
+        )
     }
     fn update_render(&mut self, time: &Time) -> Option<Sample> {
         <Self as AsymmetricSimulationState>::update_render(self, time)
     }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        <Self as AsymmetricSimulationState>::record_metrics(self, side, time, metrics)
+    }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        <Self as AsymmetricSimulationState>::frame_label(self, side)
+    }
+    fn finalize(&mut self, side: SimSide, result: &mut SimulationResult<Sample>) {
+        <Self as AsymmetricSimulationState>::finalize(self, side, result)
+    }
+    fn debug_state(&self) -> Option<serde_json::Value> {
+        <Self as AsymmetricSimulationState>::debug_state(self)
+    }
 }
 
 pub trait SimulationState: Send + Sync + std::any::Any {
+    /// Called once for every run before the first update, on both the server and the client state
+    fn init(&mut self, _settings: &SimSettings) {}
     fn update_server(&mut self, time: &Time) -> Sample;
-    fn send_sync(&self, time: &Time) -> Vec<u8>;
-    fn recv_sync(&mut self, time: &Time, server_time: Duration, server_frame: u64, msg: &Vec<u8>);
+    /// Encodes the state sent to the client with `codec`
+    fn send_sync(&self, time: &Time, codec: SyncCodec) -> Vec<u8>;
+    /// Receives a message of `send_sync` encoded with `codec`
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    );
     fn update_render(&mut self, time: &Time) -> Option<Sample>;
+    /// Called every frame after the simulation update, to let behaviours record their own metrics
+    fn record_metrics(&self, _side: SimSide, _time: &Time, _metrics: &mut Metrics) {}
+    /// Label for the primary entity's frame produced by the latest update on `side`
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        None
+    }
+    /// Entities besides the primary one as of the last `update_server`, keyed by entity id
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        Vec::new()
+    }
+    /// Entities besides the primary one as of the last `update_render`, keyed by entity id
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        Vec::new()
+    }
+    /// Version of the `send_sync` payload layout. A behaviour raising it must keep decoding the
+    /// payloads of older versions in `recv_sync`, newer ones are skipped before reaching it.
+    fn sync_version(&self) -> u16 {
+        0
+    }
+    /// Called with the timing of every sync message, before its `recv_sync`
+    fn recv_clock(&mut self, _clock: &ServerClock) {}
+    /// Flags of the key produced by the latest `update_server`, e.g. `KeyFlags::DISCONTINUITY`
+    /// after a teleport. The flags of every update since the previous sync message are sent
+    /// with the next one
+    fn key_flags(&self) -> KeyFlags {
+        KeyFlags::default()
+    }
+    /// Called with the key flags of every sync message, before its `recv_sync`, so the client
+    /// can choose how to interpolate into the key
+    fn recv_key_flags(&mut self, _flags: KeyFlags) {}
+    /// Message for the server after each `update_render`, e.g. the state of entities the client
+    /// has authority over
+    fn send_upstream(&self, _time: &Time) -> Option<Vec<u8>> {
+        None
+    }
+    /// Receives the messages of the client's `send_upstream` on the server
+    fn recv_upstream(&mut self, _time: &Time, _msg: &[u8]) {}
+    /// Bytes held by the state's interpolation and snapshot buffers, recorded after every
+    /// client frame as `buffer bytes`
+    fn buffered_bytes(&self) -> usize {
+        0
+    }
+    /// Frames re-simulated after rollbacks during the last `update_render`, the reconciliation
+    /// work charged against `SimSettings::client_cpu_budget`
+    fn resimulated_frames(&self) -> u64 {
+        0
+    }
+    /// Called once after the run, before the result is analyzed and cut to the analyzed window,
+    /// to let behaviours record metrics derived from their state over the whole run
+    fn finalize(&mut self, _side: SimSide, _result: &mut SimulationResult<Sample>) {}
+    /// Internal variables of the client state after the last `update_render`, shown in the
+    /// GUI's inspector at the playhead. Sampled at most every `DEBUG_STATE_INTERVAL`
+    fn debug_state(&self) -> Option<serde_json::Value> {
+        None
+    }
 }
 
+/// Shortest time in seconds between two recorded `SimulationState::debug_state`s
+const DEBUG_STATE_INTERVAL: f32 = 1. / 30.;
+/// Largest distance between a predicted and the authoritative position that counts as a match
+const MISPREDICTION_EPSILON: f32 = 0.001;
+/// Longest lag in seconds `SimulationResult::record_display_lag` looks for
+const MAX_DISPLAY_LAG: f32 = 1.;
+
 #[derive(Default)]
 pub struct ServerRateSimulation<T> {
     _marker: std::marker::PhantomData<T>,
@@ -242,82 +1073,120 @@ impl<T: DeterministicSimulation + fmt::Display> SimulationBehaviour for ServerRa
     fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
         Box::new(ServerRateSimulationState {
             clock: None,
-            interpolation_buffer: splines::Spline::from_vec(vec![]),
+            interpolation_buffer: SnapshotBuffer::new(),
             server_fps: settings.server_fps,
             prev_pos: math::zero(),
             client_sim: T::default(),
+            sequence: SequencedReceiver::new(),
             last_server_frame: None,
+            deadband: DeadBand::new(settings.correction_deadband, settings.correction_hysteresis),
+            last_correction: None,
+            rollback_frames: 0,
+            resimulated_frames: 0,
+            longest_rollback: 0,
             render_delay: settings.render_interpolation_delay,
             server: T::initial(settings),
+            events: settings.events.clone(),
+            server_label: None,
         })
     }
+    fn description(&self) -> String {
+        format!(
+            "{}\n\n\
+            The client predicts ahead of the server by running the same simulation at the \
+            server's tick rate on a clock synced to the server. When a state arrives for a frame \
+            it already simulated and differs from its prediction by at least the correction \
+            dead-band, it rolls back to that state and resimulates up to the present. Expect \
+            corrections wherever the prediction went wrong, growing with the latency.\n\n\
+            Relevant settings: server fps, sync rate, min and max latency ms, correction \
+            dead-band, dead-band hysteresis, render interpolation delay ms.",
+            T::default().description()
+        )
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        T::ground_truth(settings)
+    }
 }
 #[derive(Clone)]
 pub struct ServerRateSimulationState<T: DeterministicSimulation> {
-    interpolation_buffer: splines::Spline<f32, T::SyncType>,
+    interpolation_buffer: SnapshotBuffer<T::SyncType>,
     prev_pos: Vector2<f32>,
     clock: Option<LocalClock>,
     client_sim: T,
     server: T,
+    sequence: SequencedReceiver,
     last_server_frame: Option<u64>,
+    deadband: DeadBand,
+    /// Local time, size and whether it was applied of the latest server correction
+    last_correction: Option<(f32, f32, bool)>,
+    /// Frames rolled back since the last render update, to be re-simulated
+    rollback_frames: u64,
+    /// Frames re-simulated during the last render update
+    resimulated_frames: u64,
+    /// Most frames a single correction rolled back
+    longest_rollback: u64,
     render_delay: f32,
     server_fps: u32,
+    events: Vec<TimedEvent>,
+    server_label: Option<String>,
 }
 impl<T: DeterministicSimulation> SimulationState for ServerRateSimulationState<T> {
-    fn send_sync(&self, _time: &Time) -> Vec<u8> {
-        bincode::serialize(self.server.send_state()).unwrap()
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(self.server.send_state())
     }
-    fn recv_sync(&mut self, time: &Time, server_time: Duration, server_frame: u64, msg: &Vec<u8>) {
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        // only the newest snapshot is of any use, reordered and duplicated messages are ignored
+        let newer_snapshot = self.sequence.recv(seq) == Delivery::Latest;
         // start a new local clock that started server_time in the past
         if let None = self.clock {
-            self.server.recv_state(bincode::deserialize(msg).unwrap());
-            let diff = time.absolute_time() - server_time;
-            let offset_secs = -(diff.as_secs() as i64);
-            let offset_nanos = -(diff.as_nanos() as i32);
-            let mut clock = LocalClock::new(
-                offset_secs,
-                offset_nanos,
-                None,
-                Some(Duration::from_secs_f32(1 as f32 / self.server_fps as f32)),
-            );
-            clock.frame_number = server_frame;
-            clock.absolute_time = server_time;
+            self.server.recv_state(codec.decode(msg));
+            let clock =
+                LocalClock::synced_to_server(time, server_time, server_frame, self.server_fps);
             // add the first keyframe for the simulation
             let t = clock.absolute_time.as_secs_f32();
-            self.interpolation_buffer.add(splines::Key::new(
+            self.interpolation_buffer.add(Key::new(
                 t,
                 self.server.send_state().clone(),
-                splines::Interpolation::Linear,
+                Kernel::Linear,
             ));
             self.clock = Some(clock);
             self.client_sim.clone_from(&self.server);
         } else if let Some(clock) = self.clock.as_mut() {
-            // check if the incoming packet happened after our last received packet
-            let newer_snapshot = self
-                .last_server_frame
-                .map(|f| f < server_frame)
-                .unwrap_or(true);
-
             if newer_snapshot {
                 if server_frame < clock.frame_number {
-                    self.last_server_frame = None;
-                    clock.frame_number = server_frame;
-                    clock.absolute_time = server_time;
-                    self.client_sim
-                        .recv_state(bincode::deserialize(msg).unwrap());
-                    for i in (0..self.interpolation_buffer.len()).rev() {
-                        if self
-                            .interpolation_buffer
-                            .get(i)
-                            .map(|k| k.t >= clock.absolute_time.as_secs_f32())
-                            .unwrap_or(false)
-                        {
-                            self.interpolation_buffer.remove(i);
-                        }
+                    let state: T::SyncType = codec.decode(msg);
+                    let predicted = self.interpolation_buffer.sample(server_time.as_secs_f32());
+                    let client_sim = &self.client_sim;
+                    let error = predicted
+                        .map(|p| {
+                            (client_sim.pos_sample(&state).pos - client_sim.pos_sample(&p).pos)
+                                .norm()
+                        })
+                        .unwrap_or(std::f32::INFINITY);
+                    let apply = self.deadband.accept(error);
+                    self.last_correction = Some((time.absolute_time().as_secs_f32(), error, apply));
+                    if apply {
+                        self.last_server_frame = None;
+                        let rollback = clock.frame_number - server_frame;
+                        self.rollback_frames += rollback;
+                        self.longest_rollback = self.longest_rollback.max(rollback);
+                        clock.frame_number = server_frame;
+                        clock.absolute_time = server_time;
+                        self.client_sim.recv_state(state);
+                        self.interpolation_buffer
+                            .truncate_from(clock.absolute_time.as_secs_f32());
                     }
                 } else {
                     self.last_server_frame = Some(server_frame);
-                    self.server.recv_state(bincode::deserialize(msg).unwrap());
+                    self.server.recv_state(codec.decode(msg));
                 }
             } else {
                 // ignore reordered message
@@ -327,6 +1196,8 @@ impl<T: DeterministicSimulation> SimulationState for ServerRateSimulationState<T
     fn update_render(&mut self, time: &Time) -> Option<Sample> {
         if let Some(clock) = self.clock.as_mut() {
             clock.tick(time);
+            self.resimulated_frames = self.rollback_frames.min(clock.frames_since_tick);
+            self.rollback_frames = 0;
             for i in 1..=clock.frames_since_tick {
                 let frame_time = clock
                     .time_per_frame
@@ -341,53 +1212,247 @@ impl<T: DeterministicSimulation> SimulationState for ServerRateSimulationState<T
                     .unwrap_or(false)
                 {
                     self.last_server_frame = None;
-                    self.client_sim.clone_from(&self.server);
+                    let mut predicted = self.client_sim.clone();
+                    predicted.update(frame_time, clock.delta_time);
+                    let error = (self.server.pos_sample(self.server.send_state()).pos
+                        - predicted.pos_sample(predicted.send_state()).pos)
+                        .norm();
+                    let apply = self.deadband.accept(error);
+                    self.last_correction = Some((time.absolute_time().as_secs_f32(), error, apply));
+                    if apply {
+                        self.client_sim.clone_from(&self.server);
+                    } else {
+                        self.client_sim.clone_from(&predicted);
+                    }
                 } else {
                     self.client_sim.update(frame_time, clock.delta_time);
                 }
                 let t = frame_time.as_secs_f32();
-                self.interpolation_buffer.add(splines::Key::new(
+                self.interpolation_buffer.add(Key::new(
                     t,
                     self.client_sim.send_state().clone(),
-                    splines::Interpolation::Linear,
+                    Kernel::Linear,
                 ));
             }
             // sample the simulation at (now - time_per_frame), while offsetting render time into local time
-            let t = (time.absolute_time()
-                - Duration::new(
-                    (-clock.clock_offset_secs) as u64,
-                    (-clock.clock_offset_nanos) as u32,
-                ))
-            .as_secs_f32()
-                - clock.time_per_frame.unwrap().as_secs_f32()
-                - (self.render_delay / 1000.);
+            let t = clock.playback_time(time, self.render_delay);
             let pos = self
                 .interpolation_buffer
                 .sample(t)
                 .map(|x| self.client_sim.pos_sample(&x));
+            self.interpolation_buffer.prune_before(t);
             pos
         } else {
             None
         }
     }
+    fn buffered_bytes(&self) -> usize {
+        self.interpolation_buffer.memory_bytes()
+    }
     fn update_server(&mut self, time: &Time) -> Sample {
+        for event in frame_events(&self.events, time) {
+            self.server.apply_event(event);
+        }
+        self.server_label = events_label(frame_events(&self.events, time));
         self.server.update(time.absolute_time(), time.delta_time());
         self.server.pos_sample(self.server.send_state())
     }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        match side {
+            SimSide::Server => self.server_label.clone(),
+            SimSide::Client => None,
+        }
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        match (side, self.last_correction) {
+            (SimSide::Client, Some((t, error, applied))) if t == now => {
+                let name = if applied {
+                    "correction"
+                } else {
+                    "ignored correction"
+                };
+                metrics.record(name, now, error);
+                // 1 if the prediction diverged from the server state, the mean being the rate
+                let mispredicted = error > MISPREDICTION_EPSILON;
+                metrics.record("misprediction", now, if mispredicted { 1. } else { 0. });
+            }
+            _ => {}
+        }
+        if side == SimSide::Client && self.clock.is_some() {
+            metrics.record("resimulated frames", now, self.resimulated_frames as f32);
+        }
+    }
+    fn resimulated_frames(&self) -> u64 {
+        self.resimulated_frames
+    }
+    fn debug_state(&self) -> Option<serde_json::Value> {
+        let clock = self.clock.as_ref()?;
+        let predicted = self.client_sim.pos_sample(self.client_sim.send_state()).pos;
+        let server = self.server.pos_sample(self.server.send_state()).pos;
+        Some(serde_json::json!({
+            "frame": clock.frame_number,
+            "predicted": [predicted.x, predicted.y, predicted.z],
+            "latest server": [server.x, server.y, server.z],
+            "last correction": self.last_correction.map(|(t, error, applied)| {
+                serde_json::json!({ "time": t, "error": error, "applied": applied })
+            }),
+            "resimulated frames": self.resimulated_frames,
+            "buffered keys": self.interpolation_buffer.len(),
+        }))
+    }
+    fn finalize(&mut self, side: SimSide, result: &mut SimulationResult<Sample>) {
+        if side == SimSide::Client && self.clock.is_some() {
+            let end = result.window.1;
+            result.record("longest rollback", end, self.longest_rollback as f32);
+        }
+    }
+}
+
+/// Asymmetric state whose sync state can be played back by `ServerRatePlayback`
+pub trait PlaybackSimulation: AsymmetricSimulationState {
+    fn pos_sample(state: &Self::SyncType) -> Sample;
+    /// What the simulation models, shown in the GUI before how the client plays it back
+    fn description(&self) -> &'static str;
+    /// See `SimulationBehaviour::ground_truth`
+    fn ground_truth(_settings: &SimSettings) -> Option<GroundTruth> {
+        None
+    }
+}
+
+/// Runs an asymmetric behaviour's server side as usual, but plays received states back on the
+/// client at the server's tick rate instead of running the client simulation.
+#[derive(Default)]
+pub struct ServerRatePlayback<T> {
+    _marker: std::marker::PhantomData<T>,
+}
+impl<T: fmt::Display + Default> fmt::Display for ServerRatePlayback<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{} (Server-Rate Playback)", T::default())
+    }
+}
+impl<T> SimulationBehaviour for ServerRatePlayback<T>
+where
+    T: PlaybackSimulation + fmt::Display + Default + Send + Sync + 'static,
+    T::SyncType: Interpolate + Send + Sync,
+{
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(ServerRatePlaybackState::<T> {
+            inner: T::default(),
+            received: SnapshotBuffer::new(),
+            playback: SnapshotBuffer::new(),
+            clock: None,
+            sequence: SequencedReceiver::new(),
+            server_fps: settings.server_fps,
+            render_delay: settings.render_interpolation_delay,
+        })
+    }
+    fn description(&self) -> String {
+        format!(
+            "{}\n\n\
+            In this variant the client doesn't simulate. It resamples the received states, \
+            keyed by server time, at every server-rate tick of a clock synced to the server and \
+            interpolates between the ticks the render interpolation delay in the past. The \
+            motion is smooth as long as the delay covers the latency spread.",
+            T::default().description()
+        )
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        T::ground_truth(settings)
+    }
+}
+pub struct ServerRatePlaybackState<T: AsymmetricSimulationState> {
+    inner: T,
+    /// Received states keyed by server time
+    received: SnapshotBuffer<T::SyncType>,
+    /// States sampled from `received` at every local server-rate tick
+    playback: SnapshotBuffer<T::SyncType>,
+    clock: Option<LocalClock>,
+    sequence: SequencedReceiver,
+    server_fps: u32,
+    render_delay: f32,
+}
+impl<T> SimulationState for ServerRatePlaybackState<T>
+where
+    T: PlaybackSimulation + Send + Sync + 'static,
+    T::SyncType: Interpolate + Send + Sync,
+{
+    fn init(&mut self, settings: &SimSettings) {
+        self.inner.init(settings);
+    }
+    fn send_sync(&self, time: &Time, codec: SyncCodec) -> Vec<u8> {
+        self.inner.send_sync(time, codec)
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        // states are keyed by server time, so late ones still land in the right place
+        if self.sequence.recv(seq) == Delivery::Duplicate {
+            return;
+        }
+        if let None = self.clock {
+            self.clock = Some(LocalClock::synced_to_server(
+                time,
+                server_time,
+                server_frame,
+                self.server_fps,
+            ));
+        }
+        let state: T::SyncType = codec.decode(msg);
+        self.received
+            .add(Key::new(server_time.as_secs_f32(), state, Kernel::Linear));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        let clock = self.clock.as_mut()?;
+        clock.tick(time);
+        for frame_time in clock.ticked_frames() {
+            let t = frame_time.as_secs_f32();
+            if let Some(state) = self.received.clamped_sample(t) {
+                self.playback.add(Key::new(t, state, Kernel::Linear));
+            }
+            self.received.prune_before(t);
+        }
+        let t = clock.playback_time(time, self.render_delay);
+        let sample = self.playback.sample(t).map(|s| T::pos_sample(&s));
+        self.playback.prune_before(t);
+        sample
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.received.memory_bytes() + self.playback.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        AsymmetricSimulationState::update_server(&mut self.inner, time)
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        AsymmetricSimulationState::record_metrics(&self.inner, side, time, metrics)
+    }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        AsymmetricSimulationState::frame_label(&self.inner, side)
+    }
 }
 
 pub trait DeterministicSimulation: fmt::Debug + Default + Send + Sync + Clone + 'static {
-    type SyncType: Serialize
-        + for<'de> Deserialize<'de>
-        + splines::Interpolate<f32>
-        + Send
-        + Sync
-        + Clone;
+    type SyncType: Serialize + for<'de> Deserialize<'de> + Interpolate + Send + Sync + Clone;
     fn send_state(&self) -> &Self::SyncType;
     fn recv_state(&mut self, val: Self::SyncType);
     fn update(&mut self, abs_time: Duration, delta_time: Duration);
     fn pos_sample(&self, val: &Self::SyncType) -> Sample;
     fn initial(settings: &SimSettings) -> Self;
+    /// Applies a scripted event to the authoritative server simulation
+    fn apply_event(&mut self, _event: &ScenarioEvent) {}
+    /// What the simulation models and the artifacts to expect, shown in the GUI before how the
+    /// client runs it
+    fn description(&self) -> &'static str;
+    /// See `SimulationBehaviour::ground_truth`
+    fn ground_truth(_settings: &SimSettings) -> Option<GroundTruth> {
+        None
+    }
 }
 
 pub fn behaviour_data<T: SimulationBehaviour + Default + std::fmt::Display>(
@@ -409,169 +1474,1138 @@ impl<M: Debug + Clone + fmt::Display> fmt::Display for WorldFrame<M> {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum PacketKind {
+    Sync,
+    Retransmit,
+    Ack,
+    Input,
+    Upstream,
+}
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PacketRecord {
+    pub side: SimSide,
+    pub kind: PacketKind,
+    pub time: f32,
+    pub seq: u16,
+    pub size: usize,
+}
+/// Sync message the client received and passed on to its state, see `replay::PacketSchedule`
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub struct DeliveryRecord {
+    /// Client time in seconds the message was received at
+    pub time: f32,
+    pub seq: u16,
+    pub server_frame: u64,
+    /// Server time in seconds of the tick the synced state is from
+    pub tick_time: f32,
+    /// Server time in seconds the message was sent at
+    pub send_time: f32,
+    #[serde(default)]
+    pub key_flags: KeyFlags,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SimulationResult<M: Debug + Clone> {
+    /// Frames of both sides, in render time order once analyzed
     pub frames: Vec<WorldFrame<M>>,
+    /// Every packet sent by either side, in send order
+    pub packets: Vec<PacketRecord>,
+    /// Every sync message the client's state received, in arrival order
+    #[serde(default)]
+    pub deliveries: Vec<DeliveryRecord>,
+    pub metrics: Metrics,
+    /// Start and end time in seconds of the part of the run the metrics cover
+    #[serde(default)]
+    pub window: (f32, f32),
+    /// Diagnostics of the run, in time order
+    #[serde(default)]
+    pub log: Vec<LogEntry>,
+    /// Behaviour-internal client state, in time order
+    #[serde(default)]
+    pub debug_states: Vec<DebugState>,
+    /// Number of the run, unique within the process, so views of it can be cached
+    #[serde(skip)]
+    pub run: u64,
+}
+/// Results run or loaded so far, numbering `SimulationResult::run`
+static RUNS: AtomicU64 = AtomicU64::new(0);
+pub(crate) fn next_run() -> u64 {
+    RUNS.fetch_add(1, Ordering::Relaxed) + 1
+}
+/// Diagnostic message of a run, like a dropped message, shown in the GUI's log console
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LogEntry {
+    /// Time in seconds on the side's clock
+    pub time: f32,
+    pub side: SimSide,
+    pub message: String,
+}
+/// Client state of a behaviour at a client frame, see `SimulationState::debug_state`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DebugState {
+    pub time: f32,
+    pub state: serde_json::Value,
+}
+impl<M: Debug + Clone> SimulationResult<M> {
+    pub fn in_window(&self, t: f32) -> bool {
+        t >= self.window.0 && t <= self.window.1
+    }
+    pub fn record(&mut self, name: &str, t: f32, v: f32) {
+        self.metrics.record(name, t, v);
+    }
+    pub fn warn(&mut self, side: SimSide, time: f32, message: String) {
+        self.log.push(LogEntry {
+            time,
+            side,
+            message,
+        });
+    }
+    /// Result of a run from the results its server and its client recorded in their own
+    /// worlds. The records of both are merged by time, the server's first at equal times, so
+    /// the result doesn't depend on how the steps of the sides interleaved
+    fn merge_sides(server: Self, client: Self) -> Self {
+        let mut metrics = server.metrics;
+        metrics.merge(client.metrics);
+        Self {
+            frames: merge_by_time(server.frames, client.frames, |f| f.render_time),
+            packets: merge_by_time(server.packets, client.packets, |p| p.time),
+            deliveries: merge_by_time(server.deliveries, client.deliveries, |d| d.time),
+            metrics,
+            window: server.window,
+            log: merge_by_time(server.log, client.log, |e| e.time),
+            debug_states: merge_by_time(server.debug_states, client.debug_states, |d| d.time),
+            run: server.run,
+        }
+    }
+    /// Sorts the frames by render time, keeping the frames of each step together. Runs merge
+    /// the frames of the sides by time, but results saved by older versions have them in step
+    /// order, which interleaves the two sides' clocks
+    pub fn sort_frames(&mut self) {
+        self.frames
+            .sort_by(|a, b| a.render_time.partial_cmp(&b.render_time).unwrap());
+    }
+    /// Approximate heap bytes of the result, the peak of a run as results only grow
+    pub fn memory_bytes(&self) -> usize {
+        use std::mem::size_of;
+        self.frames.capacity() * size_of::<WorldFrame<M>>()
+            + self
+                .frames
+                .iter()
+                .filter_map(|f| f.label.as_ref())
+                .map(|l| l.capacity())
+                .sum::<usize>()
+            + self.packets.capacity() * size_of::<PacketRecord>()
+            + self.deliveries.capacity() * size_of::<DeliveryRecord>()
+            + self.metrics.memory_bytes()
+            + self
+                .log
+                .iter()
+                .map(|e| size_of::<LogEntry>() + e.message.capacity())
+                .sum::<usize>()
+            + self
+                .debug_states
+                .iter()
+                .map(|d| size_of::<DebugState>() + d.state.to_string().len())
+                .sum::<usize>()
+    }
+    pub fn packet_count(&self, side: SimSide, kind: PacketKind) -> usize {
+        self.packets
+            .iter()
+            .filter(|p| p.side == side && p.kind == kind)
+            .count()
+    }
+    /// The latest debug state recorded at or before `t`
+    pub fn debug_state_at(&self, t: f32) -> Option<&DebugState> {
+        self.debug_states[..count_until(&self.debug_states, t, |d| d.time)].last()
+    }
+    /// Where the delay of what the client shows at `t` comes from, None before the first sync
+    /// message arrived
+    pub fn latency_budget_at(&self, settings: &SimSettings, t: f32) -> Option<LatencyBudget> {
+        let delivery = self.deliveries[..count_until(&self.deliveries, t, |d| d.time)].last()?;
+        let mut client_frames = self.frames[..count_until(&self.frames, t, |f| f.render_time)]
+            .iter()
+            .rev()
+            .filter(|f| f.side == SimSide::Client && f.entity.is_none())
+            .map(|f| f.render_time);
+        let render_frame = match (client_frames.next(), client_frames.next()) {
+            (Some(frame), Some(prev)) => frame - prev,
+            _ => 1. / settings.render_fps.max(1) as f32,
+        };
+        Some(LatencyBudget {
+            network: (delivery.time - delivery.send_time) * 1000.,
+            sync_quantization: 500. / settings.sync_rate.max(1) as f32
+                + (delivery.send_time - delivery.tick_time) * 1000.,
+            interpolation: settings.render_interpolation_delay,
+            render_frame: render_frame * 1000.,
+        })
+    }
+}
+
+/// Number of `items`, in time order, at or before `t`
+fn count_until<T>(items: &[T], t: f32, time: impl Fn(&T) -> f32) -> usize {
+    match items.binary_search_by(|item| {
+        if time(item) <= t {
+            std::cmp::Ordering::Less
+        } else {
+            std::cmp::Ordering::Greater
+        }
+    }) {
+        Ok(idx) | Err(idx) => idx,
+    }
+}
+
+/// Terms of the delay in ms between a change on the server and the client showing it
+#[derive(Clone, Copy, Debug, Default)]
+pub struct LatencyBudget {
+    /// One-way delay of the latest sync message
+    pub network: f32,
+    /// Mean wait of a change for the next sync message, half the sync interval, plus the time
+    /// the synced state waited to be sent
+    pub sync_quantization: f32,
+    /// How far behind the newest state the client renders
+    pub interpolation: f32,
+    /// Time until the frame showing the state is presented, the last client frame time
+    pub render_frame: f32,
+}
+impl LatencyBudget {
+    pub fn terms(&self) -> [(&'static str, f32); 4] {
+        [
+            ("network one-way", self.network),
+            ("sync quantization", self.sync_quantization),
+            ("interpolation delay", self.interpolation),
+            ("render frame", self.render_frame),
+        ]
+    }
+    pub fn total(&self) -> f32 {
+        self.terms().iter().map(|(_, ms)| ms).sum()
+    }
+}
+impl<M: Debug + Clone + Serialize> SimulationResult<M> {
+    pub fn to_json_file(&self, path: &Path) -> Result<()> {
+        serde_json::to_writer(BufWriter::new(File::create(path)?), self)?;
+        Ok(())
+    }
+}
+impl<M: Debug + Clone + DeserializeOwned> SimulationResult<M> {
+    pub fn from_json_file(path: &Path) -> Result<Self> {
+        let mut result: Self = serde_json::from_reader(BufReader::new(File::open(path)?))?;
+        // results saved before analyzing sorted them kept the frames in step order
+        result.sort_frames();
+        result.run = next_run();
+        Ok(result)
+    }
+}
+impl SimulationResult<Sample> {
+    /// Records the distance between each client frame and the server position at its render
+    /// time, see `ErrorReference`
+    fn record_position_error(
+        &mut self,
+        reference: ErrorReference,
+        ground_truth: Option<GroundTruth>,
+    ) {
+        let truth = ServerTruth::new(&self.frames, ground_truth);
+        let mut errors = Vec::new();
+        for frame in self.frames.iter().filter(|f| f.side == SimSide::Client) {
+            if let Some(server) = truth.at(frame.entity, frame.render_time, reference) {
+                errors.push((frame.render_time, (frame.sample.pos - server).norm()));
+            }
+        }
+        for (t, error) in errors {
+            self.record("error", t, error);
+        }
+    }
+    /// Records how far the client entity visibly jumps between consecutive client frames,
+    /// discounted by how transparent it is while jumping
+    fn record_pop(&mut self) {
+        let mut client: Vec<(f32, Sample)> = self
+            .frames
+            .iter()
+            .filter(|f| f.side == SimSide::Client && f.entity.is_none())
+            .map(|f| (f.render_time, f.sample))
+            .collect();
+        client.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        let pops: Vec<(f32, f32)> = client
+            .windows(2)
+            .map(|w| {
+                let visibility = w[0].1.alpha.min(w[1].1.alpha);
+                (w[1].0, (w[1].1.pos - w[0].1.pos).norm() * visibility)
+            })
+            .collect();
+        for (t, pop) in pops {
+            self.record("pop", t, pop);
+        }
+    }
+    /// Records every stretch of client frames in which the primary entity held its rendered
+    /// position while the server's kept moving, as its length in seconds at its start
+    fn record_underruns(&mut self) {
+        let client = self.primary_positions(SimSide::Client);
+        let server = self.primary_positions(SimSide::Server);
+        let server_pos = |t: f32| server.iter().rev().find(|(st, _)| *st <= t).map(|f| f.1);
+        let mut underruns = Vec::new();
+        let mut start = 0;
+        for i in 1..=client.len() {
+            if i < client.len() && client[i].1 == client[start].1 {
+                continue;
+            }
+            let (from, to) = (client[start].0, client[i - 1].0);
+            if to > from && server_pos(from) != server_pos(to) {
+                underruns.push((from, to - from));
+            }
+            start = i;
+        }
+        for (t, duration) in underruns {
+            self.record("underrun", t, duration);
+            self.warn(
+                SimSide::Client,
+                t,
+                format!("underrun, held position for {:.0} ms", duration * 1000.),
+            );
+        }
+    }
+    /// Records how many ms behind the server each client frame appears, as the time since the
+    /// server frame closest to its position within the last `MAX_DISPLAY_LAG` seconds
+    fn record_display_lag(&mut self) {
+        let client = self.primary_positions(SimSide::Client);
+        let server = self.primary_positions(SimSide::Server);
+        let mut lags = Vec::new();
+        for (t, pos) in client {
+            // the latest of equally close frames, so a resting entity isn't shown as lagging
+            let closest = server
+                .iter()
+                .rev()
+                .skip_while(|(st, _)| *st > t)
+                .take_while(|(st, _)| *st >= t - MAX_DISPLAY_LAG)
+                .fold(None, |closest: Option<(f32, f32)>, (st, server_pos)| {
+                    let distance = (server_pos - pos).norm();
+                    match closest {
+                        Some((_, d)) if d <= distance => closest,
+                        _ => Some((*st, distance)),
+                    }
+                });
+            if let Some((st, _)) = closest {
+                lags.push((t, (t - st) * 1000.));
+            }
+        }
+        for (t, lag) in lags {
+            self.record("display lag", t, lag);
+        }
+    }
+    /// Render time and position of every frame of the primary entity on `side`, in time order
+    fn primary_positions(&self, side: SimSide) -> Vec<(f32, Vector3<f32>)> {
+        let mut frames: Vec<(f32, Vector3<f32>)> = self
+            .frames
+            .iter()
+            .filter(|f| f.side == side && f.entity.is_none())
+            .map(|f| (f.render_time, f.sample.pos))
+            .collect();
+        frames.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        frames
+    }
+    /// First and last server frame time of every non-primary entity
+    pub fn server_lifetimes(&self) -> BTreeMap<u32, (f32, f32)> {
+        let mut lifetimes = BTreeMap::new();
+        for frame in self.frames.iter().filter(|f| f.side == SimSide::Server) {
+            if let Some(entity) = frame.entity {
+                let lifetime = lifetimes
+                    .entry(entity)
+                    .or_insert((frame.render_time, frame.render_time));
+                lifetime.0 = lifetime.0.min(frame.render_time);
+                lifetime.1 = lifetime.1.max(frame.render_time);
+            }
+        }
+        lifetimes
+    }
+    /// Records client entities rendered after the server despawned them, and server entities
+    /// the client never rendered
+    fn record_entity_lifecycle(&mut self, ghost_slack: f32) {
+        let lifetimes = self.server_lifetimes();
+        if lifetimes.is_empty() {
+            return;
+        }
+        let mut ghosts: BTreeMap<u32, u32> = BTreeMap::new();
+        let mut rendered = BTreeSet::new();
+        for frame in self.frames.iter().filter(|f| f.side == SimSide::Client) {
+            if let Some(entity) = frame.entity {
+                rendered.insert(entity);
+                let despawned = lifetimes
+                    .get(&entity)
+                    .map(|(_, last)| last + ghost_slack < frame.render_time)
+                    .unwrap_or(false);
+                let ghost_count = ghosts.entry(frame.render_time.to_bits()).or_insert(0);
+                if despawned {
+                    *ghost_count += 1;
+                }
+            }
+        }
+        for (t, count) in ghosts {
+            self.record("ghost entities", f32::from_bits(t), count as f32);
+        }
+        let end = self.frames.iter().map(|f| f.render_time).fold(0., f32::max);
+        let missed = lifetimes.keys().filter(|e| !rendered.contains(e)).count();
+        self.record("missed spawns", end, missed as f32);
+    }
 }
 
 pub fn run_simulation(settings: &SimSettings) -> Result<SimulationResult<Sample>> {
-    let (client_tx, server_rx) = memory_channel();
-    let (server_tx, client_rx) = memory_channel();
-    let server_data = GameDataBuilder::default()
-        .with_bundle(MemoryNetworkBundle::new(server_tx, server_rx))?
-        .with_system_desc(ServerSimulationSystem, "server_sim", &[]);
-    let client_data = GameDataBuilder::default()
-        .with_bundle(MemoryNetworkBundle::new(client_tx, client_rx))?
-        .with_system_desc(ClientSimulationSystemDesc, "client_sim", &[]);
+    analyze(settings, simulate(settings)?)
+}
+
+/// Runs the server and client apps with `settings`, without analyzing the result. Keeping it
+/// lets `refilter` try other client filters without running the apps again
+pub fn simulate(settings: &SimSettings) -> Result<SimulationResult<Sample>> {
+    SimWorldPool::default().simulate(settings)
+}
+
+/// App of a side of a `SimWorldPool`
+struct SimApp(Application<'static, GameData<'static, 'static>>);
+
+/// Builds the app of `side` on the calling thread
+fn build_app(side: SimSide) -> Result<SimApp> {
+    // steps the sync ticks of `NetworkSimulationTime`, the simulated network is `SimMonkey`
+    let data = GameDataBuilder::default().with(NetworkSimulationTimeSystem, "net_time", &[]);
     let assets_dir = application_root_dir()?.join("./");
-    let mut client_monkey = amethyst::network::simulation::NetworkMonkey::new([0; 16]);
-    let mut server_monkey = amethyst::network::simulation::NetworkMonkey::new([0; 16]);
-    client_monkey.set_min_latency(Some(settings.min_latency / 1000.));
-    client_monkey.set_max_latency(Some(settings.max_latency / 1000.));
-    client_monkey.set_loss_percentage(Some(settings.loss_percentage));
-    server_monkey.set_min_latency(Some(settings.min_latency / 1000.));
-    server_monkey.set_max_latency(Some(settings.max_latency / 1000.));
-    server_monkey.set_loss_percentage(Some(settings.loss_percentage));
-    let sim_result = Arc::new(Mutex::new(SimulationResult { frames: Vec::new() }));
-    {
-        let mut server_app =
-            Application::build(assets_dir.clone(), ServerState::default())?.build(server_data)?;
-        let mut client_app =
-            Application::build(assets_dir.clone(), ClientState::default())?.build(client_data)?;
-        server_app.initialize();
-        client_app.initialize();
-        server_app.world.insert(settings.clone());
-        client_app.world.insert(settings.clone());
-        server_app
-            .world
-            .insert(settings.behaviour.new_state(&settings));
-        client_app
-            .world
-            .insert(settings.behaviour.new_state(&settings));
-        server_app.world.insert(sim_result.clone());
-        client_app.world.insert(sim_result.clone());
-        server_app
-            .world
-            .get_mut::<NetworkSimulationTime>()
-            .unwrap()
-            .set_sim_frame_rate(settings.sync_rate as u32);
-        client_app
-            .world
-            .get_mut::<NetworkSimulationTime>()
-            .unwrap()
-            .set_sim_frame_rate(settings.sync_rate as u32);
-        client_app
-            .world
-            .get_mut::<TransportResource>()
-            .unwrap()
-            .set_monkey(Some(client_monkey));
-        server_app
-            .world
-            .get_mut::<TransportResource>()
-            .unwrap()
-            .set_monkey(Some(server_monkey));
-        use rand::{Rng, SeedableRng};
-        let mut rng = rand::rngs::SmallRng::from_seed([0; 16]);
-        let extended_client_duration =
-            (settings.render_interpolation_delay + settings.min_latency) / 1000.;
-        let mut server_time = settings.duration + extended_client_duration;
-        let mut client_time = settings.duration + extended_client_duration;
-        while server_time > 0. || client_time > 0. {
-            if server_time >= client_time && server_time > 0. {
-                let server_delta = 1 as f32 / settings.server_fps as f32;
-                server_time -= server_delta;
-                server_app.step(Duration::from_secs_f32(server_delta));
-            } else if client_time > 0. {
-                let render_time_variance = {
-                    let deviation = (settings.render_time_variance / 1000.) * 0.5;
-                    rng.sample(rand::distributions::Normal::new(0., deviation as f64)) as f32
-                };
-                let mut client_delta = 1 as f32 / settings.render_fps as f32;
-                client_delta += render_time_variance;
-                client_time -= client_delta;
-                client_app.step(Duration::from_secs_f32(client_delta));
+    let mut app = match side {
+        SimSide::Server => Application::build(assets_dir, ServerState::default())?.build(
+            data.with_system_desc(ServerSimulationSystemDesc, "server_sim", &["net_time"]),
+        )?,
+        SimSide::Client => Application::build(assets_dir, ClientState::default())?.build(
+            data.with_system_desc(ClientSimulationSystemDesc, "client_sim", &["net_time"]),
+        )?,
+    };
+    app.initialize();
+    Ok(SimApp(app))
+}
+
+/// Job run on the thread of an app, false if it panicked and left the app unusable
+#[cfg(not(target_arch = "wasm32"))]
+type AppJob = Box<dyn FnOnce(&mut SimApp) -> bool + Send>;
+
+/// Thread the app of a side is built on and runs the jobs of the runs on. The app never leaves
+/// it, as its dispatcher and state machine can't be sent to another thread
+#[cfg(not(target_arch = "wasm32"))]
+struct AppThread {
+    jobs: Option<mpsc::Sender<AppJob>>,
+    thread: Option<thread::JoinHandle<()>>,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl AppThread {
+    fn spawn(side: SimSide) -> Result<Self> {
+        let (jobs, queue) = mpsc::channel::<AppJob>();
+        let (built_sender, built) = mpsc::channel();
+        let thread = thread::spawn(move || {
+            let mut app = match build_app(side) {
+                Ok(app) => app,
+                Err(e) => return built_sender.send(Err(e)).unwrap_or(()),
+            };
+            built_sender.send(Ok(())).ok();
+            for job in queue {
+                if !job(&mut app) {
+                    return;
+                }
             }
+            app.0.shutdown();
+        });
+        match built.recv() {
+            Ok(built) => built?,
+            Err(_) => panic::resume_unwind(thread.join().unwrap_err()),
+        }
+        Ok(Self {
+            jobs: Some(jobs),
+            thread: Some(thread),
+        })
+    }
+    /// Runs `job` on the app's thread
+    fn run<R: Send + 'static>(
+        &mut self,
+        job: impl FnOnce(&mut SimApp) -> R + Send + 'static,
+    ) -> AppResult<R> {
+        let (sender, result) = mpsc::channel();
+        let job: AppJob = Box::new(move |app| {
+            let outcome = panic::catch_unwind(AssertUnwindSafe(|| job(app)));
+            let usable = outcome.is_ok();
+            sender.send(outcome).ok();
+            usable
+        });
+        // only fails once a panic ended the thread, which the panicking job's result resumed
+        self.jobs.as_ref().unwrap().send(job).ok();
+        AppResult(result)
+    }
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for AppThread {
+    fn drop(&mut self) {
+        // the thread shuts the app down once it runs out of jobs
+        self.jobs.take();
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+    }
+}
+/// Result of a job on the thread of an app
+#[cfg(not(target_arch = "wasm32"))]
+struct AppResult<R>(mpsc::Receiver<thread::Result<R>>);
+#[cfg(not(target_arch = "wasm32"))]
+impl<R> AppResult<R> {
+    /// Waits for the job, resuming its panic on the calling thread
+    fn wait(self) -> R {
+        match self.0.recv().expect("the app's thread ended in a panic") {
+            Ok(result) => result,
+            Err(panic) => panic::resume_unwind(panic),
         }
-        server_app.shutdown();
-        client_app.shutdown();
     }
-    Ok(Arc::try_unwrap(sim_result).unwrap().into_inner().unwrap())
+}
+
+/// App of a side, run on the calling thread as wasm has no threads to spare
+#[cfg(target_arch = "wasm32")]
+struct AppThread(SimApp);
+#[cfg(target_arch = "wasm32")]
+impl AppThread {
+    fn spawn(side: SimSide) -> Result<Self> {
+        Ok(AppThread(build_app(side)?))
+    }
+    fn run<R>(&mut self, job: impl FnOnce(&mut SimApp) -> R) -> AppResult<R> {
+        AppResult(job(&mut self.0))
+    }
+}
+#[cfg(target_arch = "wasm32")]
+impl Drop for AppThread {
+    fn drop(&mut self) {
+        (self.0).0.shutdown();
+    }
+}
+#[cfg(target_arch = "wasm32")]
+struct AppResult<R>(R);
+#[cfg(target_arch = "wasm32")]
+impl<R> AppResult<R> {
+    fn wait(self) -> R {
+        self.0
+    }
+}
+
+/// Whether the apps of a `SimWorldPool` are between runs. The simulation systems then only
+/// reset themselves
+#[derive(Default)]
+pub struct BetweenRuns(pub bool);
+
+/// Lengths of the steps of a side's app
+enum StepLengths {
+    /// The server's fixed step in seconds
+    Server(f32),
+    /// The client's frame time in seconds, varied randomly and delayed by overrun frames
+    Client {
+        rng: rand::rngs::SmallRng,
+        frame_time: f32,
+        render_time_variance: f32,
+    },
+}
+
+/// Steps an app through a run
+struct AppStepper<'a> {
+    app: &'a mut SimApp,
+    lengths: StepLengths,
+    run_duration: f32,
+    /// Virtual time the app is at
+    elapsed: Duration,
+    /// Length of the next step
+    next: Duration,
+}
+impl<'a> AppStepper<'a> {
+    fn new(app: &'a mut SimApp, lengths: StepLengths, run_duration: f32) -> Self {
+        let mut stepper = Self {
+            app,
+            lengths,
+            run_duration,
+            elapsed: Duration::from_secs(0),
+            next: Duration::from_secs(0),
+        };
+        stepper.next = stepper.next_length();
+        stepper
+    }
+    fn next_length(&mut self) -> Duration {
+        let secs = match &mut self.lengths {
+            StepLengths::Server(delta) => *delta,
+            StepLengths::Client {
+                rng,
+                frame_time,
+                render_time_variance,
+            } => {
+                use rand::Rng;
+                let deviation = (*render_time_variance / 1000.) * 0.5;
+                let variance =
+                    rng.sample(rand::distributions::Normal::new(0., deviation as f64)) as f32;
+                // seconds the frame just stepped ran over its CPU budget
+                let overrun = self.app.0.world.fetch::<FrameOverrun>().0;
+                *frame_time + variance + overrun
+            }
+        };
+        Duration::from_secs_f32(secs)
+    }
+}
+impl Stepper for AppStepper<'_> {
+    fn next_end(&self) -> Option<f32> {
+        if self.elapsed.as_secs_f32() < self.run_duration {
+            Some((self.elapsed + self.next).as_secs_f32())
+        } else {
+            None
+        }
+    }
+    fn step(&mut self) {
+        self.elapsed += self.next;
+        let now = self.elapsed.as_secs_f32();
+        self.app.0.world.fetch_mut::<SimMonkey>().set_now(now);
+        self.app.0.step(self.next);
+        self.next = self.next_length();
+    }
+}
+
+/// How far along a run of the apps is
+#[derive(Clone, Copy, Debug)]
+pub struct RunProgress {
+    /// Fraction of the run's virtual time both apps have stepped through
+    pub done: f32,
+    /// Wall-clock time the rest of the run takes at the pace so far
+    pub eta: Duration,
+}
+/// Wall-clock time between two reports of `SimWorldPool::simulate_with_progress`
+#[cfg(not(target_arch = "wasm32"))]
+const PROGRESS_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Steps the apps for `run_duration` on their threads, concurrently as far as the network's
+/// `min_latency` allows or one at a time if `in_turn`
+#[cfg(not(target_arch = "wasm32"))]
+fn step_apps(
+    (server, client): (&mut AppThread, &mut AppThread),
+    (server_lengths, client_lengths): (StepLengths, StepLengths),
+    run_duration: f32,
+    min_latency: f32,
+    in_turn: bool,
+    progress: &mut impl FnMut(RunProgress),
+) {
+    let clocks = Arc::new(SideClocks::new(min_latency, in_turn));
+    let server = server.run({
+        let clocks = clocks.clone();
+        move |app| {
+            let mut stepper = AppStepper::new(app, server_lengths, run_duration);
+            clocks.run(SimSide::Server, &mut stepper);
+        }
+    });
+    let client = client.run({
+        let clocks = clocks.clone();
+        move |app| {
+            let mut stepper = AppStepper::new(app, client_lengths, run_duration);
+            clocks.run(SimSide::Client, &mut stepper);
+        }
+    });
+    let started = Instant::now();
+    let mut reported = started;
+    while let Some((server_time, client_time)) = clocks.progress(PROGRESS_INTERVAL) {
+        if reported.elapsed() < PROGRESS_INTERVAL {
+            continue;
+        }
+        reported = Instant::now();
+        let stepped = server_time.min(run_duration) + client_time.min(run_duration);
+        let done = stepped / (2. * run_duration).max(std::f32::EPSILON);
+        let elapsed = started.elapsed().as_secs_f32();
+        let eta = if done > 0. {
+            Duration::from_secs_f32(elapsed * (1. - done) / done)
+        } else {
+            Duration::from_secs(0)
+        };
+        progress(RunProgress { done, eta });
+    }
+    server.wait();
+    client.wait();
+}
+/// Steps the apps for `run_duration` one at a time on the calling thread. Without a clock to
+/// estimate how long the rest takes no progress is reported
+#[cfg(target_arch = "wasm32")]
+fn step_apps(
+    (server, client): (&mut AppThread, &mut AppThread),
+    (server_lengths, client_lengths): (StepLengths, StepLengths),
+    run_duration: f32,
+    min_latency: f32,
+    _in_turn: bool,
+    _progress: &mut impl FnMut(RunProgress),
+) {
+    step_in_turn(
+        &mut AppStepper::new(&mut server.0, server_lengths, run_duration),
+        &mut AppStepper::new(&mut client.0, client_lengths, run_duration),
+        min_latency,
+    );
+}
+
+/// Server and client apps kept between runs, each on a thread of its own, so repeated runs
+/// don't pay for building their worlds and dispatchers. The resources of a run are replaced at
+/// its start
+#[derive(Default)]
+pub struct SimWorldPool {
+    apps: Option<(AppThread, AppThread)>,
+}
+impl SimWorldPool {
+    /// See `simulate`
+    pub fn simulate(&mut self, settings: &SimSettings) -> Result<SimulationResult<Sample>> {
+        self.simulate_with_progress(settings, |_| {})
+    }
+    /// `simulate`, reporting the progress of the run every `PROGRESS_INTERVAL`
+    pub fn simulate_with_progress(
+        &mut self,
+        settings: &SimSettings,
+        progress: impl FnMut(RunProgress),
+    ) -> Result<SimulationResult<Sample>> {
+        self.run(settings, progress, false)
+    }
+    /// Runs the apps, stepping them one at a time if `in_turn`
+    fn run(
+        &mut self,
+        settings: &SimSettings,
+        mut progress: impl FnMut(RunProgress),
+        in_turn: bool,
+    ) -> Result<SimulationResult<Sample>> {
+        if self.apps.is_none() {
+            self.apps = Some((
+                AppThread::spawn(SimSide::Server)?,
+                AppThread::spawn(SimSide::Client)?,
+            ));
+        }
+        let (mut server_app, mut client_app) = self.apps.take().unwrap();
+        let sim_rng = SimRng::new(settings.seed);
+        let (server_monkey, client_monkey) = SimMonkey::pair(settings, &sim_rng);
+        for (app, monkey) in vec![
+            (&mut server_app, server_monkey),
+            (&mut client_app, client_monkey),
+        ] {
+            let settings = settings.clone();
+            app.run(move |app| {
+                let world = &mut app.0.world;
+                world.insert(monkey);
+                world.insert(BetweenRuns(false));
+                world.insert(Time::default());
+                world.insert(NetworkSimulationTime::default());
+                world.insert(FrameOverrun::default());
+                world.insert(sim_rng);
+                let mut state = settings.behaviour.new_state(&settings);
+                state.init(&settings);
+                world.insert(state);
+                // each side records its own result, merged once the run is done
+                world.insert(SimulationResult::<Sample> {
+                    frames: Vec::new(),
+                    packets: Vec::new(),
+                    deliveries: Vec::new(),
+                    metrics: Metrics::default(),
+                    window: (0., 0.),
+                    log: Vec::new(),
+                    debug_states: Vec::new(),
+                    run: 0,
+                });
+                world
+                    .get_mut::<NetworkSimulationTime>()
+                    .unwrap()
+                    .set_sim_frame_rate(settings.sync_rate as u32);
+                world.insert(settings);
+            })
+            .wait();
+        }
+        let run_duration = settings.run_duration();
+        let step_scale = settings.step_scale();
+        let lengths = (
+            StepLengths::Server(step_scale / settings.server_fps as f32),
+            StepLengths::Client {
+                rng: sim_rng.stream("render time"),
+                frame_time: step_scale / settings.render_fps as f32,
+                render_time_variance: settings.render_time_variance,
+            },
+        );
+        step_apps(
+            (&mut server_app, &mut client_app),
+            lengths,
+            run_duration,
+            latency::min_latency(settings),
+            in_turn,
+            &mut progress,
+        );
+        // lets the simulation systems reset themselves for the next run
+        let finish = |app: &mut AppThread| {
+            app.run(|app| {
+                app.0.world.insert(BetweenRuns(true));
+                app.0.step(Duration::from_secs(0));
+                let world = &mut app.0.world;
+                let state = world.remove::<Box<dyn SimulationState>>();
+                (state, world.remove::<SimulationResult<Sample>>().unwrap())
+            })
+            .wait()
+        };
+        let (server_state, server_result) = finish(&mut server_app);
+        let (client_state, client_result) = finish(&mut client_app);
+        self.apps = Some((server_app, client_app));
+        let mut sim_result = SimulationResult::merge_sides(server_result, client_result);
+        sim_result.run = next_run();
+        if settings.fast_forward > step_scale {
+            sim_result.warn(
+                SimSide::Server,
+                0.,
+                format!(
+                    "fast-forward limited to {:.2}x to keep both sides stepping every sync tick",
+                    step_scale
+                ),
+            );
+        }
+        sim_result.window = settings.analyzed_window();
+        if let (Some(mut server_state), Some(mut client_state)) = (server_state, client_state) {
+            server_state.finalize(SimSide::Server, &mut sim_result);
+            client_state.finalize(SimSide::Client, &mut sim_result);
+        }
+        // at the end of the window, so they survive the analysis cutting the metrics to it
+        let end = sim_result.window.1;
+        if let Some(peak) = sim_result.metrics.get("buffer bytes").map(|c| c.max()) {
+            sim_result.record("peak buffer bytes", end, peak);
+        }
+        let bytes = sim_result.memory_bytes();
+        sim_result.record("result bytes", end, bytes as f32);
+        Ok(sim_result)
+    }
+}
+
+/// Derives the metrics of a result of `simulate` from its frames and cuts the metrics to the
+/// analyzed window
+pub fn analyze(
+    settings: &SimSettings,
+    mut sim_result: SimulationResult<Sample>,
+) -> Result<SimulationResult<Sample>> {
+    sim_result.sort_frames();
+    sim_result.record_position_error(
+        settings.error_reference,
+        settings.behaviour.ground_truth(settings),
+    );
+    sim_result.record_pop();
+    sim_result.record_underruns();
+    sim_result.record_display_lag();
+    sim_result.record_entity_lifecycle(settings.ghost_slack());
+    sim_result
+        .log
+        .sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    if settings.peer_to_peer {
+        crate::p2p::simulate(settings, &mut sim_result.metrics)?;
+    }
+    let (from, to) = sim_result.window;
+    sim_result.metrics.retain_window(from, to);
+    Ok(sim_result)
+}
+
+/// Filters the unfiltered client positions of `raw`, a result of `simulate`, again with the
+/// filter of `settings` and analyzes the result. The filter is the client's last stage, so as
+/// long as `settings` only differ from the settings of `raw` in the filter this is the result
+/// a full run would give, at a fraction of the cost
+pub fn refilter(
+    settings: &SimSettings,
+    raw: &SimulationResult<Sample>,
+) -> Result<SimulationResult<Sample>> {
+    let mut sim_result = raw.clone();
+    sim_result.run = next_run();
+    sim_result.metrics.remove("filter lag");
+    let mut filter = new_filter(settings);
+    let mut lags = Vec::new();
+    let client = sim_result
+        .frames
+        .iter_mut()
+        .filter(|f| f.side == SimSide::Client && f.entity.is_none());
+    for frame in client {
+        let raw = frame.unfiltered.take().unwrap_or(frame.sample);
+        frame.sample.pos = filter.filter(frame.render_time, raw.pos);
+        if settings.filter != FilterKind::None {
+            lags.push((frame.render_time, (frame.sample.pos - raw.pos).norm()));
+            frame.unfiltered = Some(raw);
+        }
+    }
+    for (t, lag) in lags {
+        sim_result.record("filter lag", t, lag);
+    }
+    analyze(settings, sim_result)
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 pub struct Sample {
-    pub pos: Vector2<f32>,
+    /// Position with z as height, 2D behaviours' positions lie on the ground plane
+    #[serde(deserialize_with = "deserialize_pos")]
+    pub pos: Vector3<f32>,
+    /// Opacity the entity is rendered with
+    #[serde(default = "opaque")]
+    pub alpha: f32,
+}
+fn opaque() -> f32 {
+    1.
+}
+/// Reads positions of results saved before samples had a height as well
+fn deserialize_pos<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> std::result::Result<Vector3<f32>, D::Error> {
+    let pos = Vec::<f32>::deserialize(deserializer)?;
+    match pos.as_slice() {
+        [x, y] => Ok(Vector3::new(*x, *y, 0.)),
+        [x, y, z] => Ok(Vector3::new(*x, *y, *z)),
+        _ => Err(serde::de::Error::invalid_length(
+            pos.len(),
+            &"2 or 3 coordinates",
+        )),
+    }
+}
+impl Sample {
+    /// Sample of a 2D behaviour, on the ground plane
+    pub fn planar(pos: Vector2<f32>, alpha: f32) -> Self {
+        Self {
+            pos: Vector3::new(pos.x, pos.y, 0.),
+            alpha,
+        }
+    }
 }
 
 impl fmt::Display for Sample {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "pos: [ x: {}, y: {} ] ", self.pos.x, self.pos.y,)
+        write!(
+            f,
+            "pos: [ x: {}, y: {}, z: {} ] ",
+            self.pos.x, self.pos.y, self.pos.z
+        )
     }
 }
 
-#[derive(Default, Debug)]
-pub struct ServerSimulationSystem;
+pub struct ServerSimulationSystem {
+    sequence: u16,
+    // created on the first run, once SimSettings is available
+    reliable: Option<ReliableSender>,
+    // client input sequence numbers received so far
+    inputs: SequencedReceiver,
+    // created on the first run, once SimSettings is available
+    deltas: Option<DeltaEncoder>,
+    // key flags of the updates since the last sync message, sent with the next one
+    key_flags: KeyFlags,
+    // created on the first run, once SimRng is available
+    skip_rng: Option<rand::rngs::SmallRng>,
+}
+pub struct ServerSimulationSystemDesc;
 
-impl<'a, 'b> SystemDesc<'a, 'b, ServerSimulationSystem> for ServerSimulationSystem {
+impl<'a, 'b> SystemDesc<'a, 'b, ServerSimulationSystem> for ServerSimulationSystemDesc {
     fn build(self, world: &mut World) -> ServerSimulationSystem {
-        world.insert(Sample { pos: math::zero() });
-        ServerSimulationSystem
+        world.insert(Sample {
+            pos: math::zero(),
+            alpha: 1.,
+        });
+        ServerSimulationSystem {
+            sequence: 0,
+            reliable: None,
+            inputs: SequencedReceiver::new(),
+            deltas: None,
+            key_flags: KeyFlags::default(),
+            skip_rng: None,
+        }
     }
 }
 impl<'a> System<'a> for ServerSimulationSystem {
     type SystemData = (
         Read<'a, NetworkSimulationTime>,
         Read<'a, Time>,
-        Write<'a, TransportResource>,
         WriteExpect<'a, Box<dyn SimulationState>>,
-        WriteExpect<'a, Arc<Mutex<SimulationResult<Sample>>>>,
+        WriteExpect<'a, SimulationResult<Sample>>,
         ReadExpect<'a, SimSettings>,
+        Read<'a, BetweenRuns>,
+        ReadExpect<'a, SimRng>,
+        WriteExpect<'a, SimMonkey>,
     );
-    fn run(&mut self, (net_time, time, mut transport, mut obj, sim, settings): Self::SystemData) {
+    fn run(
+        &mut self,
+        (
+            net_time,
+            time,
+            mut obj,
+            mut sim,
+            settings,
+            between_runs,
+            sim_rng,
+            mut monkey,
+        ): Self::SystemData,
+    ) {
+        use rand::Rng;
+        if between_runs.0 {
+            self.sequence = 0;
+            self.reliable = None;
+            self.inputs = SequencedReceiver::new();
+            self.deltas = None;
+            self.key_flags = KeyFlags::default();
+            self.skip_rng = None;
+            return;
+        }
+        let now = time.absolute_time().as_secs_f32();
+        let reliable = self.reliable.get_or_insert_with(|| {
+            ReliableSender::new(
+                settings.retransmit_policy,
+                settings.retransmit_timeout / 1000.,
+            )
+        });
+        let deltas = self.deltas.get_or_insert_with(|| {
+            DeltaEncoder::new(settings.keyframe_interval, settings.entropy_coding)
+        });
+        let skip_rng = self
+            .skip_rng
+            .get_or_insert_with(|| sim_rng.stream("send skips"));
+        let inputs = &mut self.inputs;
+        for payload in monkey.receive() {
+            match bincode::deserialize(&payload).unwrap() {
+                ClientMessage::Ack(ack) => reliable.recv_ack(&ack),
+                ClientMessage::Inputs(msg) => {
+                    for input in msg {
+                        if inputs.recv(input.seq) != Delivery::Duplicate {
+                            sim.record("input latency", now, now - input.time);
+                        }
+                    }
+                }
+                ClientMessage::Upstream(msg) => obj.recv_upstream(&time, &msg),
+            }
+        }
+        if inputs.latest().is_some() {
+            // inputs of the run so far the server never applied
+            sim.record("input gaps", now, inputs.missing() as f32);
+        }
+        let input_ack = match settings.input_delivery {
+            InputDelivery::Reliable => inputs.latest().map(|ack| AckMessage {
+                ack,
+                ack_bits: inputs.history(),
+            }),
+            _ => None,
+        };
         let obj = &mut *obj;
         let sample = obj.update_server(&time);
-        for _ in net_time.sim_frames_to_run() {
-            let buf = obj.send_sync(&time);
+        self.key_flags = self.key_flags | obj.key_flags();
+        for net_frame in net_time.sim_frames_to_run() {
+            if settings.send_skip_percentage > 0.
+                && skip_rng.gen::<f32>() < settings.send_skip_percentage
+            {
+                // the updates' key flags go out with the next message sent
+                sim.record("skipped sends", now, 1.);
+                continue;
+            }
+            let encode_start = Stopwatch::start();
+            let (mut buf, field_sizes) =
+                codec::measure_fields(|| obj.send_sync(&time, settings.codec));
+            if settings.codec == SyncCodec::Varint {
+                let fields = codec::field_count(&buf);
+                buf = deltas.encode(self.sequence, &buf);
+                if fields > 0 {
+                    sim.record(
+                        "bits per field",
+                        now,
+                        (buf.len() * 8) as f32 / fields as f32,
+                    );
+                }
+            }
+            if let Some(micros) = encode_start.elapsed_micros() {
+                sim.record("encode time", now, micros);
+            }
+            sim.record("payload bytes", now, buf.len() as f32);
+            for (path, size) in field_sizes {
+                sim.record(&format!("field bytes {}", path), now, size as f32);
+            }
+            // reference for the size of the other codecs
+            let bincode_len = obj.send_sync(&time, SyncCodec::Bincode).len();
+            sim.record("bincode payload bytes", now, bincode_len as f32);
+            let send_time =
+                Duration::from_secs_f64(net_frame as f64 / net_time.sim_frame_rate() as f64);
             let server_msg = ServerMessage {
-                server_secs: time.absolute_time().as_secs(),
-                server_nanos: time.absolute_time().subsec_nanos(),
+                tick_secs: time.absolute_time().as_secs(),
+                tick_nanos: time.absolute_time().subsec_nanos(),
+                send_secs: send_time.as_secs(),
+                send_nanos: send_time.subsec_nanos(),
+                tick_delta_nanos: time.delta_time().subsec_nanos(),
                 server_frame: time.frame_number(),
+                seq: self.sequence,
+                input_ack,
+                payload_version: obj.sync_version(),
                 msg: buf,
+                key_flags: self.key_flags,
             };
-            transport.send(
-                std::net::SocketAddr::new("0.0.0.0".parse().unwrap(), 0),
-                &bincode::serialize(&server_msg).unwrap(),
-            );
+            self.key_flags = KeyFlags::default();
+            let payload = encode_server_message(&server_msg);
+            monkey.send(&payload);
+            sim.packets.push(PacketRecord {
+                side: SimSide::Server,
+                kind: PacketKind::Sync,
+                time: now,
+                seq: self.sequence,
+                size: payload.len(),
+            });
+            sim.record("sync bytes", now, payload.len() as f32);
+            reliable.sent(self.sequence, now, payload);
+            self.sequence = self.sequence.wrapping_add(1);
         }
-        transport.update_monkey(&*time);
+        for (seq, payload) in reliable.retransmissions(now) {
+            monkey.send(&payload);
+            sim.packets.push(PacketRecord {
+                side: SimSide::Server,
+                kind: PacketKind::Retransmit,
+                time: now,
+                seq,
+                size: payload.len(),
+            });
+        }
+        obj.record_metrics(SimSide::Server, &time, &mut sim.metrics);
         if time.absolute_time().as_secs_f32() <= settings.duration {
-            let mut sim = sim.lock().unwrap();
+            let render_time = time.absolute_time().as_secs_f32();
+            let net_time = (time.absolute_time() + net_time.elapsed_duration()).as_secs_f32();
             sim.frames.push(WorldFrame {
                 side: SimSide::Server,
-                render_time: time.absolute_time().as_secs_f32(),
-                net_time: (time.absolute_time() + net_time.elapsed_duration()).as_secs_f32(),
+                entity: None,
+                label: obj.frame_label(SimSide::Server),
+                render_time,
+                net_time,
                 sample,
+                unfiltered: None,
             });
+            for (entity, sample) in obj.server_entities() {
+                sim.frames.push(WorldFrame {
+                    side: SimSide::Server,
+                    entity: Some(entity),
+                    label: None,
+                    render_time,
+                    net_time,
+                    sample,
+                    unfiltered: None,
+                });
+            }
         }
     }
 }
+/// Seconds the latest client frame's reconciliation work exceeded
+/// `SimSettings::client_cpu_budget` by, which the next client frame is delayed by
+#[derive(Default)]
+pub struct FrameOverrun(pub f32);
+
 pub struct ClientSimulationSystem {
-    reader: ReaderId<NetworkSimulationEvent>,
+    // sequence numbers received from the server, for acking
+    acks: SequencedReceiver,
+    filter: Option<Box<dyn PositionFilter>>,
+    input_seq: u16,
+    // most recent inputs, oldest first, for redundant delivery
+    recent_inputs: VecDeque<PlayerInput>,
+    // created on the first run, once SimSettings is available
+    input_sender: Option<ReliableSender>,
+    deltas: Option<DeltaDecoder>,
 }
 pub struct ClientSimulationSystemDesc;
 
 impl<'a, 'b> SystemDesc<'a, 'b, ClientSimulationSystem> for ClientSimulationSystemDesc {
     fn build(self, world: &mut World) -> ClientSimulationSystem {
-        world.insert(Sample { pos: math::zero() });
-        let has_chan = world
-            .try_fetch_mut::<EventChannel<NetworkSimulationEvent>>()
-            .is_some();
-        if !has_chan {
-            world.insert(EventChannel::<NetworkSimulationEvent>::default());
+        world.insert(Sample {
+            pos: math::zero(),
+            alpha: 1.,
+        });
+        world.insert(FrameOverrun::default());
+        ClientSimulationSystem {
+            acks: SequencedReceiver::new(),
+            filter: None,
+            input_seq: 0,
+            recent_inputs: VecDeque::new(),
+            input_sender: None,
+            deltas: None,
         }
-        let mut chan = world.fetch_mut::<EventChannel<NetworkSimulationEvent>>();
-        let reader = chan.register_reader();
-        ClientSimulationSystem { reader }
     }
 }
 impl<'a> System<'a> for ClientSimulationSystem {
@@ -579,34 +2613,280 @@ impl<'a> System<'a> for ClientSimulationSystem {
         Read<'a, NetworkSimulationTime>,
         Read<'a, Time>,
         WriteExpect<'a, Box<dyn SimulationState>>,
-        Read<'a, EventChannel<NetworkSimulationEvent>>,
-        WriteExpect<'a, Arc<Mutex<SimulationResult<Sample>>>>,
+        WriteExpect<'a, SimulationResult<Sample>>,
+        ReadExpect<'a, SimSettings>,
+        Write<'a, FrameOverrun>,
+        Read<'a, BetweenRuns>,
+        WriteExpect<'a, SimMonkey>,
     );
-    fn run(&mut self, (net_time, time, mut obj, channel, sim): Self::SystemData) {
-        let mut sim = sim.lock().unwrap();
+    fn run(
+        &mut self,
+        (
+            net_time,
+            time,
+            mut obj,
+            mut sim,
+            settings,
+            mut overrun,
+            between_runs,
+            mut monkey,
+        ): Self::SystemData,
+    ) {
+        if between_runs.0 {
+            self.acks = SequencedReceiver::new();
+            self.filter = None;
+            self.input_seq = 0;
+            self.recent_inputs.clear();
+            self.input_sender = None;
+            self.deltas = None;
+            return;
+        }
+        let frame_start = Stopwatch::start();
         let obj = &mut *obj;
-        for event in channel.read(&mut self.reader) {
-            match event {
-                NetworkSimulationEvent::Message(_, payload) => {
-                    let server_msg: ServerMessage = bincode::deserialize(&payload).unwrap();
-                    obj.recv_sync(
-                        &time,
-                        Duration::new(server_msg.server_secs, server_msg.server_nanos),
-                        server_msg.server_frame,
-                        &server_msg.msg,
+        let now = time.absolute_time().as_secs_f32();
+        let input_sender = self.input_sender.get_or_insert_with(|| {
+            ReliableSender::new(
+                RetransmitPolicy::ResendAfterRto,
+                settings.retransmit_timeout / 1000.,
+            )
+        });
+        let deltas = self
+            .deltas
+            .get_or_insert_with(|| DeltaDecoder::new(settings.entropy_coding));
+        let mut received = 0;
+        for payload in monkey.receive() {
+            received += 1;
+            let server_msg = match decode_server_message(&payload) {
+                Ok(server_msg) => server_msg,
+                Err(e) => {
+                    sim.warn(
+                        SimSide::Client,
+                        now,
+                        format!("dropped undecodable server message: {}", e),
                     );
+                    sim.record("undecodable messages", now, 1.);
+                    continue;
+                }
+            };
+            let latest = self.acks.latest();
+            match self.acks.recv(server_msg.seq) {
+                Delivery::Late => sim.warn(
+                    SimSide::Client,
+                    now,
+                    format!(
+                        "sync message {} arrived late, after {}",
+                        server_msg.seq,
+                        latest.unwrap_or(0)
+                    ),
+                ),
+                Delivery::Duplicate => sim.warn(
+                    SimSide::Client,
+                    now,
+                    format!("duplicate sync message {}", server_msg.seq),
+                ),
+                Delivery::Latest => {}
+            }
+            if let Some(input_ack) = server_msg.input_ack {
+                input_sender.recv_ack(&input_ack);
+            }
+            if server_msg.payload_version > obj.sync_version() {
+                sim.warn(
+                    SimSide::Client,
+                    now,
+                    format!(
+                        "skipped sync message {} with payload version {}, newer than {}",
+                        server_msg.seq,
+                        server_msg.payload_version,
+                        obj.sync_version()
+                    ),
+                );
+                sim.record("skipped payloads", now, 1.);
+                continue;
+            }
+            let clock = ServerClock {
+                tick_time: Duration::new(server_msg.tick_secs, server_msg.tick_nanos),
+                send_time: Duration::new(server_msg.send_secs, server_msg.send_nanos),
+                tick_duration: Duration::new(0, server_msg.tick_delta_nanos),
+            };
+            // how far into the next tick the message was sent, in ticks
+            let tick = clock.tick_duration.as_secs_f32().max(std::f32::EPSILON);
+            sim.record(
+                "send phase",
+                now,
+                (clock.send_time.as_secs_f32() - clock.tick_time.as_secs_f32()) / tick,
+            );
+            obj.recv_clock(&clock);
+            obj.recv_key_flags(server_msg.key_flags);
+            let decode_start = Stopwatch::start();
+            let msg = if settings.codec == SyncCodec::Varint {
+                match deltas.decode(server_msg.seq, &server_msg.msg) {
+                    Some(msg) => msg,
+                    None => {
+                        sim.warn(
+                            SimSide::Client,
+                            now,
+                            format!(
+                                "dropped delta sync message {} without its keyframe",
+                                server_msg.seq
+                            ),
+                        );
+                        sim.record("missing keyframes", now, 1.);
+                        continue;
+                    }
+                }
+            } else {
+                server_msg.msg
+            };
+            sim.deliveries.push(DeliveryRecord {
+                time: now,
+                seq: server_msg.seq,
+                server_frame: server_msg.server_frame,
+                tick_time: clock.tick_time.as_secs_f32(),
+                send_time: clock.send_time.as_secs_f32(),
+                key_flags: server_msg.key_flags,
+            });
+            obj.recv_sync(
+                &time,
+                clock.tick_time,
+                server_msg.server_frame,
+                server_msg.seq,
+                &msg,
+                settings.codec,
+            );
+            if let Some(micros) = decode_start.elapsed_micros() {
+                sim.record("decode time", now, micros);
+            }
+        }
+        if settings.retransmit_policy != RetransmitPolicy::None {
+            if let Some(ack) = self.acks.latest() {
+                for _ in net_time.sim_frames_to_run() {
+                    let payload = bincode::serialize(&ClientMessage::Ack(AckMessage {
+                        ack,
+                        ack_bits: self.acks.history(),
+                    }))
+                    .unwrap();
+                    monkey.send(&payload);
+                    sim.packets.push(PacketRecord {
+                        side: SimSide::Client,
+                        kind: PacketKind::Ack,
+                        time: time.absolute_time().as_secs_f32(),
+                        seq: ack,
+                        size: payload.len(),
+                    });
                 }
-                _ => {}
             }
         }
-        if let Some(sample) = obj.update_render(&time) {
+        if settings.input_delivery != InputDelivery::Off {
+            let input = PlayerInput {
+                seq: self.input_seq,
+                time: now,
+                dir: crate::sim_behaviours::PLAYER_INPUT_DIR
+                    .clamped_sample(now)
+                    .unwrap(),
+            };
+            self.input_seq = self.input_seq.wrapping_add(1);
+            sim.record("inputs sent", now, 1.);
+            self.recent_inputs.push_back(input);
+            while self.recent_inputs.len() > settings.input_redundancy.max(1) as usize {
+                self.recent_inputs.pop_front();
+            }
+            let inputs = match settings.input_delivery {
+                InputDelivery::Redundant => self.recent_inputs.iter().cloned().collect(),
+                _ => vec![input],
+            };
+            let payload = bincode::serialize(&ClientMessage::Inputs(inputs)).unwrap();
+            let mut messages = vec![(input.seq, payload.clone())];
+            if settings.input_delivery == InputDelivery::Reliable {
+                input_sender.sent(input.seq, now, payload);
+                messages.extend(input_sender.retransmissions(now));
+            }
+            for (seq, payload) in messages {
+                monkey.send(&payload);
+                sim.packets.push(PacketRecord {
+                    side: SimSide::Client,
+                    kind: PacketKind::Input,
+                    time: now,
+                    seq,
+                    size: payload.len(),
+                });
+                sim.record("input bytes", now, payload.len() as f32);
+            }
+        }
+        sim.record("messages received", now, received as f32);
+        let filter = self.filter.get_or_insert_with(|| new_filter(&settings));
+        let mut unfiltered = None;
+        let sample = obj.update_render(&time).map(|mut sample| {
+            let raw = sample.pos;
+            sample.pos = filter.filter(now, raw);
+            if settings.filter != FilterKind::None {
+                sim.record("filter lag", now, (sample.pos - raw).norm());
+                unfiltered = Some(Sample { pos: raw, ..sample });
+            }
+            sample
+        });
+        if settings.client_cpu_budget > 0. {
+            let cost = obj.resimulated_frames() as f32 * settings.resimulation_cost;
+            let over_budget = (cost - settings.client_cpu_budget).max(0.);
+            sim.record("reconciliation cost", now, cost);
+            sim.record("frame overrun", now, over_budget);
+            sim.record("frame interval", now, time.delta_seconds() * 1000.);
+            overrun.0 = over_budget / 1000.;
+        }
+        let upstream = obj.send_upstream(&time);
+        if let Some(msg) = &upstream {
+            let payload = bincode::serialize(&ClientMessage::Upstream(msg.clone())).unwrap();
+            monkey.send(&payload);
+            sim.packets.push(PacketRecord {
+                side: SimSide::Client,
+                kind: PacketKind::Upstream,
+                time: now,
+                seq: 0,
+                size: payload.len(),
+            });
+            sim.record("upstream bytes", now, payload.len() as f32);
+        }
+        obj.record_metrics(SimSide::Client, &time, &mut sim.metrics);
+        let buffered = obj.buffered_bytes();
+        if buffered > 0 {
+            sim.record("buffer bytes", now, buffered as f32);
+        }
+        let due = sim
+            .debug_states
+            .last()
+            .map(|d| now - d.time >= DEBUG_STATE_INTERVAL)
+            .unwrap_or(true);
+        if due {
+            if let Some(state) = obj.debug_state() {
+                sim.debug_states.push(DebugState { time: now, state });
+            }
+        }
+        let render_time = time.absolute_time().as_secs_f32();
+        let net_time = (time.absolute_time() + net_time.elapsed_duration()).as_secs_f32();
+        if let Some(sample) = sample {
+            sim.frames.push(WorldFrame {
+                side: SimSide::Client,
+                entity: None,
+                label: obj.frame_label(SimSide::Client),
+                render_time,
+                net_time,
+                sample,
+                unfiltered,
+            });
+        }
+        for (entity, sample) in obj.client_entities() {
             sim.frames.push(WorldFrame {
                 side: SimSide::Client,
-                render_time: time.absolute_time().as_secs_f32(),
-                net_time: (time.absolute_time() + net_time.elapsed_duration()).as_secs_f32(),
+                entity: Some(entity),
+                label: None,
+                render_time,
+                net_time,
                 sample,
+                unfiltered: None,
             });
         }
+        if let Some(micros) = frame_start.elapsed_micros() {
+            sim.record("client frame time", now, micros);
+        }
     }
 }
 
@@ -631,3 +2911,160 @@ impl Default for ClientState {
 }
 
 impl SimpleState for ClientState {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Result of a short run as JSON, without the metrics timed on the wall clock
+    fn deterministic_result(in_turn: bool) -> serde_json::Value {
+        let mut settings = SimSettings::default();
+        settings.duration = 3.;
+        settings.latency_model = LatencyModel::Uniform;
+        settings.min_latency = 40.;
+        settings.max_latency = 80.;
+        settings.loss_percentage = 0.05;
+        let mut result = SimWorldPool::default()
+            .run(&settings, |_| {}, in_turn)
+            .unwrap();
+        result.run = 0;
+        let mut result = serde_json::to_value(&result).unwrap();
+        let channels = result["metrics"]["channels"].as_object_mut().unwrap();
+        for name in &["encode time", "decode time", "client frame time"] {
+            channels.remove(*name);
+        }
+        result
+    }
+
+    #[test]
+    fn concurrent_run_matches_stepping_in_turn() {
+        assert_eq!(deterministic_result(false), deterministic_result(true));
+    }
+
+    #[test]
+    fn decodes_version_1_messages() {
+        let v1 = ServerMessageV1 {
+            tick_secs: 3,
+            tick_nanos: 500,
+            send_secs: 3,
+            send_nanos: 700,
+            tick_delta_nanos: 16_666_667,
+            server_frame: 180,
+            seq: 42,
+            input_ack: Some(AckMessage {
+                ack: 7,
+                ack_bits: 0b101,
+            }),
+            payload_version: 1,
+            msg: vec![1, 2, 3],
+        };
+        let payload = bincode::serialize(&(1u16, &v1)).unwrap();
+        let msg = decode_server_message(&payload).unwrap();
+        assert_eq!((msg.tick_secs, msg.tick_nanos), (3, 500));
+        assert_eq!((msg.send_secs, msg.send_nanos), (3, 700));
+        assert_eq!(msg.tick_delta_nanos, 16_666_667);
+        assert_eq!((msg.server_frame, msg.seq), (180, 42));
+        assert_eq!(msg.input_ack.map(|a| (a.ack, a.ack_bits)), Some((7, 0b101)));
+        assert_eq!(msg.payload_version, 1);
+        assert_eq!(msg.msg, vec![1, 2, 3]);
+        assert_eq!(msg.key_flags, KeyFlags::default());
+    }
+
+    #[test]
+    fn decodes_current_messages() {
+        let mut msg: ServerMessage = ServerMessageV1 {
+            tick_secs: 0,
+            tick_nanos: 0,
+            send_secs: 0,
+            send_nanos: 0,
+            tick_delta_nanos: 0,
+            server_frame: 1,
+            seq: 2,
+            input_ack: None,
+            payload_version: 1,
+            msg: vec![4],
+        }
+        .into();
+        msg.key_flags = KeyFlags::DISCONTINUITY;
+        let decoded = decode_server_message(&encode_server_message(&msg)).unwrap();
+        assert_eq!(decoded.key_flags, KeyFlags::DISCONTINUITY);
+        assert_eq!(decoded.msg, vec![4]);
+    }
+
+    fn frame(side: SimSide, entity: Option<u32>, render_time: f32) -> WorldFrame<Sample> {
+        WorldFrame {
+            side,
+            entity,
+            label: None,
+            render_time,
+            net_time: render_time,
+            sample: Sample::planar(math::zero(), 1.),
+            unfiltered: None,
+        }
+    }
+
+    #[test]
+    fn latency_budget_at_latest_delivery_and_frames() {
+        let delivery = |time: f32, seq: u16| DeliveryRecord {
+            time,
+            seq,
+            server_frame: seq as u64,
+            tick_time: time - 0.06,
+            send_time: time - 0.05,
+            key_flags: KeyFlags::default(),
+        };
+        let mut result = SimulationResult {
+            frames: vec![
+                frame(SimSide::Client, None, 0.02),
+                frame(SimSide::Server, None, 0.03),
+                frame(SimSide::Client, None, 0.01),
+                frame(SimSide::Client, Some(1), 0.02),
+                frame(SimSide::Client, None, 0.05),
+                frame(SimSide::Client, Some(1), 0.05),
+                frame(SimSide::Client, None, 0.06),
+            ],
+            packets: Vec::new(),
+            deliveries: vec![delivery(0.1, 0), delivery(0.2, 1), delivery(0.3, 2)],
+            metrics: Metrics::default(),
+            window: (0., 1.),
+            log: Vec::new(),
+            debug_states: Vec::new(),
+            run: 0,
+        };
+        result.sort_frames();
+        let settings = SimSettings::default();
+        assert!(result.latency_budget_at(&settings, 0.05).is_none());
+        let budget = result.latency_budget_at(&settings, 0.25).unwrap();
+        assert!((budget.network - 50.).abs() < 1e-3);
+        // the client's primary frames before 0.25 are at 0.05 and 0.06
+        assert!((budget.render_frame - 10.).abs() < 1e-3);
+        assert!(result.latency_budget_at(&settings, 0.055).is_none());
+        result.deliveries.insert(0, delivery(0.04, 9));
+        let budget = result.latency_budget_at(&settings, 0.055).unwrap();
+        // the primary frames at or before 0.055 are at 0.02 and 0.05
+        assert!((budget.render_frame - 30.).abs() < 1e-3);
+    }
+
+    #[test]
+    fn base_latency_moves_each_model() {
+        for model in crate::latency::LATENCY_MODELS.iter() {
+            let mut settings = SimSettings::default();
+            settings.latency_model = *model;
+            settings.max_latency = 30.;
+            let (base, high) = settings.latency_range();
+            assert!(high >= base, "{}: {} below {}", model, high, base);
+            settings.set_base_latency(base + 40.);
+            let (shifted_base, shifted_high) = settings.latency_range();
+            assert!((shifted_base - base - 40.).abs() < 1e-3, "{}", model);
+            if *model != LatencyModel::Pareto {
+                assert!((shifted_high - high - 40.).abs() < 1e-3, "{}", model);
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_unversioned_messages() {
+        let payload = bincode::serialize(&(0u16, vec![0u8; 64])).unwrap();
+        assert!(decode_server_message(&payload).is_err());
+    }
+}
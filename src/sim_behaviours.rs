@@ -1,37 +1,83 @@
-use crate::sim::{
-    behaviour_data, AsymmetricSimulationState, DeterministicSimulation, Sample,
-    ServerRateSimulation, SimSettings, SimulationBehaviour, SimulationState,
+use crate::{
+    codec::SyncCodec,
+    metrics::Metrics,
+    pipeline::ComposedPipeline,
+    rng::SimRng,
+    scenario::{events_label, frame_events, ScenarioEvent, TeleportPolicy, TimedEvent},
+    sequence::{Delivery, SequencedReceiver},
+    sim::{
+        behaviour_data, AsymmetricSimulationState, DeterministicSimulation, PlaybackSimulation,
+        Sample, ServerRatePlayback, ServerRateSimulation, SimSettings, SimSide,
+        SimulationBehaviour, SimulationState,
+    },
+    snapshot_buffer::{Kernel, Key, KeyFlags, SnapshotBuffer},
+    sync_interpolate,
+    truth::GroundTruth,
 };
 use amethyst::core::{
-    math::{self, Vector2},
+    math::{self, Vector2, Vector3},
     Time,
 };
 use lazy_static::*;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use std::{fmt, sync::Arc, time::Duration};
+use std::{
+    collections::{BTreeMap, BTreeSet, VecDeque},
+    fmt,
+    sync::Arc,
+    time::Duration,
+};
 
-#[derive(Copy, Clone, Serialize, Deserialize, Debug)]
-pub struct PosVel {
-    pos: Vector2<f32>,
-    velocity: Vector2<f32>,
+sync_interpolate! {
+    #[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+    pub struct PosVel {
+        #[lerp] pos: Vector2<f32>,
+        #[hold] velocity: Vector2<f32>,
+        // incremented by every teleport, so a teleport is noticed even if the message carrying it is lost
+        #[hold] teleports: u8,
+    }
 }
 impl Default for PosVel {
     fn default() -> Self {
         Self {
             pos: math::zero(),
             velocity: Vector2::new(100., 0.),
+            teleports: 0,
         }
     }
 }
-#[derive(Copy, Default, Clone, Debug)]
+impl PosVel {
+    fn apply_event(&mut self, event: &ScenarioEvent) {
+        event.apply(&mut self.pos, &mut self.velocity);
+        if let ScenarioEvent::Teleport { .. } = event {
+            self.teleports = self.teleports.wrapping_add(1);
+        }
+    }
+}
+#[derive(Default, Clone, Debug)]
 struct SineWaveClientSim {
     state: PosVel,
     start_time: Option<Duration>,
+    events: Vec<TimedEvent>,
+    server_label: Option<String>,
 }
 impl SimulationBehaviour for SineWaveClientSim {
     fn new_state(&self, _settings: &SimSettings) -> Box<dyn SimulationState> {
         Box::new(Self::default())
     }
+    fn description(&self) -> String {
+        "An object moving along a sine wave. The client runs the same \
+simulation as the server at its own frame rate, snapping to every state it receives and \
+simulating on from it until the next one arrives.\n\n\
+Received states are already a latency old, so the client lags the server and jumps at every \
+sync, further the lower the sync rate. Between syncs the client drifts from the server when \
+client fps differs from server fps, since the result depends on the step size.\n\n\
+Relevant settings: sync rate, server fps, client fps, min and max latency ms."
+            .into()
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        Some(sine_wave_truth(&settings.events))
+    }
 }
 impl fmt::Display for SineWaveClientSim {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -40,6 +86,9 @@ impl fmt::Display for SineWaveClientSim {
 }
 impl AsymmetricSimulationState for SineWaveClientSim {
     type SyncType = PosVel;
+    fn init(&mut self, settings: &SimSettings) {
+        self.events = settings.events.clone();
+    }
     fn send_state(&self) -> &Self::SyncType {
         &self.state
     }
@@ -53,19 +102,36 @@ impl AsymmetricSimulationState for SineWaveClientSim {
         self.start_time.map(|t| {
             self.state.pos += self.state.velocity * time.delta_seconds();
             self.state.velocity += sine_wave(time.delta_time(), time.absolute_time() - t);
-            Sample {
-                pos: self.state.pos,
-            }
+            Sample::planar(self.state.pos, 1.)
         })
     }
     fn update_server(&mut self, time: &Time) -> Sample {
+        for event in frame_events(&self.events, time) {
+            self.state.apply_event(event);
+        }
+        self.server_label = events_label(frame_events(&self.events, time));
         self.state.pos += self.state.velocity * time.delta_seconds();
         self.state.velocity += sine_wave(time.delta_time(), time.absolute_time());
-        Sample {
-            pos: self.state.pos,
+        Sample::planar(self.state.pos, 1.)
+    }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        match side {
+            SimSide::Server => self.server_label.clone(),
+            SimSide::Client => None,
         }
     }
 }
+impl PlaybackSimulation for SineWaveClientSim {
+    fn pos_sample(state: &PosVel) -> Sample {
+        Sample::planar(state.pos, 1.)
+    }
+    fn description(&self) -> &'static str {
+        "An object moving along a sine wave."
+    }
+    fn ground_truth(settings: &SimSettings) -> Option<GroundTruth> {
+        Some(sine_wave_truth(&settings.events))
+    }
+}
 #[derive(Copy, Default, Serialize, Deserialize, Clone, Debug)]
 struct SineWaveDeterministicSim {
     state: PosVel,
@@ -77,6 +143,10 @@ impl fmt::Display for SineWaveDeterministicSim {
 }
 impl DeterministicSimulation for SineWaveDeterministicSim {
     type SyncType = PosVel;
+    fn description(&self) -> &'static str {
+        "An object moving along a sine wave without any input, so \
+the client's prediction only goes wrong after scripted scenario events such as teleports."
+    }
     fn send_state(&self) -> &Self::SyncType {
         &self.state
     }
@@ -88,11 +158,17 @@ impl DeterministicSimulation for SineWaveDeterministicSim {
         self.state.velocity += sine_wave(delta_time, abs_time);
     }
     fn pos_sample(&self, state: &Self::SyncType) -> Sample {
-        Sample { pos: state.pos }
+        Sample::planar(state.pos, 1.)
     }
     fn initial(_settings: &SimSettings) -> Self {
         Self::default()
     }
+    fn apply_event(&mut self, event: &ScenarioEvent) {
+        self.state.apply_event(event);
+    }
+    fn ground_truth(settings: &SimSettings) -> Option<GroundTruth> {
+        Some(sine_wave_truth(&settings.events))
+    }
 }
 
 #[derive(Default)]
@@ -106,12 +182,32 @@ impl SimulationBehaviour for SineWaveThinClientCreator {
     fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
         Box::new(SineWaveThinClient {
             sim_state: Default::default(),
-            sample_buffer: splines::Spline::from_vec(vec![]),
+            sample_buffer: SnapshotBuffer::new(),
             delay: settings.render_interpolation_delay,
             start_time: None,
+            sequence: SequencedReceiver::new(),
             recv_sample_server_time: false,
+            events: settings.events.clone(),
+            teleport_policy: settings.teleport_policy,
+            server_label: None,
+            key_flags: KeyFlags::default(),
         })
     }
+    fn description(&self) -> String {
+        "An object moving along a sine wave, rendered by a thin \
+client that doesn't simulate at all: it buffers the received states keyed by the local time \
+they arrived at and interpolates between them, rendering the render interpolation delay in \
+the past.\n\n\
+Jitter changes the spacing of the buffered states, so the motion speeds up and slows down, \
+and late states are dropped. A delay shorter than the time between syncs plus the jitter \
+runs out of states and holds the newest one until the next arrives.\n\n\
+Relevant settings: render interpolation delay ms, sync rate, min and max latency ms, loss \
+percentage, teleport policy."
+            .into()
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        Some(sine_wave_truth(&settings.events))
+    }
 }
 
 #[derive(Default)]
@@ -125,29 +221,89 @@ impl SimulationBehaviour for SineWaveThinClientServerTime {
     fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
         Box::new(SineWaveThinClient {
             sim_state: Default::default(),
-            sample_buffer: splines::Spline::from_vec(vec![]),
+            sample_buffer: SnapshotBuffer::new(),
             delay: settings.render_interpolation_delay,
             start_time: None,
+            sequence: SequencedReceiver::new(),
             recv_sample_server_time: true,
+            events: settings.events.clone(),
+            teleport_policy: settings.teleport_policy,
+            server_label: None,
+            key_flags: KeyFlags::default(),
         })
     }
+    fn description(&self) -> String {
+        "The thin client sine wave, with received states keyed \
+by the server time they were sent at instead of their arrival time.\n\n\
+The spacing of the states matches the server's, so jitter no longer distorts the motion and \
+late states are still placed correctly. Compare with Thin Client Sine Wave under jitter.\n\n\
+Relevant settings: render interpolation delay ms, sync rate, min and max latency ms, loss \
+percentage, teleport policy."
+            .into()
+    }
+    fn ground_truth(&self, settings: &SimSettings) -> Option<GroundTruth> {
+        Some(sine_wave_truth(&settings.events))
+    }
 }
 
 #[derive(Clone, Debug)]
 pub struct SineWaveThinClient {
     sim_state: PosVel,
-    sample_buffer: splines::Spline<f32, PosVel>,
+    sample_buffer: SnapshotBuffer<PosVel>,
     delay: f32,
     start_time: Option<f32>,
+    sequence: SequencedReceiver,
     recv_sample_server_time: bool,
+    events: Vec<TimedEvent>,
+    teleport_policy: TeleportPolicy,
+    server_label: Option<String>,
+    /// On the server the key flags of the latest update, on the client those of the message
+    /// being received
+    key_flags: KeyFlags,
+}
+impl SineWaveThinClient {
+    /// Samples the buffer at `t`, presenting teleports according to the teleport policy. Keys
+    /// after a teleport are flagged as discontinuities unless the policy is smooth, so the
+    /// buffer itself holds the position until the teleport with the snap policy
+    fn sample(&self, t: f32) -> Option<Sample> {
+        let teleport = self
+            .sample_buffer
+            .segment(t)
+            .filter(|(_, b, _)| b.flags.contains(KeyFlags::DISCONTINUITY));
+        match (teleport, self.teleport_policy) {
+            (Some((a, b, alpha)), TeleportPolicy::Fade) => Some(if alpha < 0.5 {
+                Sample::planar(a.value.pos, 1. - alpha * 2.)
+            } else {
+                Sample::planar(b.value.pos, alpha * 2. - 1.)
+            }),
+            _ => self
+                .sample_buffer
+                .clamped_sample(t)
+                .map(|p| Sample::planar(p.pos, 1.)),
+        }
+    }
 }
 
 impl SimulationState for SineWaveThinClient {
-    fn send_sync(&self, _time: &Time) -> Vec<u8> {
-        bincode::serialize(&self.sim_state).unwrap()
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.sim_state)
     }
-    fn recv_sync(&mut self, time: &Time, server_time: Duration, _server_frame: u64, msg: &Vec<u8>) {
-        let sample = bincode::deserialize(msg).unwrap();
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        match self.sequence.recv(seq) {
+            Delivery::Duplicate => return,
+            // late samples can only be placed correctly when keyed by server time
+            Delivery::Late if !self.recv_sample_server_time => return,
+            _ => {}
+        }
+        let sample: PosVel = codec.decode(msg);
         if let None = self.start_time {
             self.start_time = Some(time.absolute_time().as_secs_f32());
         }
@@ -156,28 +312,57 @@ impl SimulationState for SineWaveThinClient {
         } else {
             time.absolute_time().as_secs_f32()
         };
-        self.sample_buffer.add(splines::Key::new(
-            time,
-            sample,
-            splines::Interpolation::Linear,
-        ));
+        // the teleport count catches teleports whose flagged message was lost
+        let teleported = self.key_flags.contains(KeyFlags::DISCONTINUITY)
+            || self
+                .sample_buffer
+                .last()
+                .map_or(false, |k| k.value.teleports != sample.teleports);
+        let flags = if teleported && self.teleport_policy != TeleportPolicy::Smooth {
+            KeyFlags::DISCONTINUITY
+        } else {
+            KeyFlags::default()
+        };
+        self.sample_buffer
+            .add(Key::new(time, sample, Kernel::Linear).with_flags(flags));
     }
     fn update_render(&mut self, time: &Time) -> Option<Sample> {
-        self.start_time
-            .and_then(|start_time| {
-                let t = time.absolute_time().as_secs_f32() - (self.delay / 1000.);
-                if t < start_time {
-                    return None;
-                }
-                self.sample_buffer.clamped_sample(t)
-            })
-            .map(|p| Sample { pos: p.pos })
+        self.start_time.and_then(|start_time| {
+            let t = time.absolute_time().as_secs_f32() - (self.delay / 1000.);
+            if t < start_time {
+                return None;
+            }
+            let sample = self.sample(t);
+            self.sample_buffer.prune_before(t);
+            sample
+        })
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.sample_buffer.memory_bytes()
     }
     fn update_server(&mut self, time: &Time) -> Sample {
+        self.key_flags = KeyFlags::default();
+        for event in frame_events(&self.events, time) {
+            self.sim_state.apply_event(event);
+            if let ScenarioEvent::Teleport { .. } = event {
+                self.key_flags = KeyFlags::DISCONTINUITY;
+            }
+        }
+        self.server_label = events_label(frame_events(&self.events, time));
         self.sim_state.pos += self.sim_state.velocity * time.delta_seconds();
         self.sim_state.velocity += sine_wave(time.delta_time(), time.absolute_time());
-        Sample {
-            pos: self.sim_state.pos,
+        Sample::planar(self.sim_state.pos, 1.)
+    }
+    fn key_flags(&self) -> KeyFlags {
+        self.key_flags
+    }
+    fn recv_key_flags(&mut self, flags: KeyFlags) {
+        self.key_flags = flags;
+    }
+    fn frame_label(&self, side: SimSide) -> Option<String> {
+        match side {
+            SimSide::Server => self.server_label.clone(),
+            SimSide::Client => None,
         }
     }
 }
@@ -195,6 +380,24 @@ impl SimulationBehaviour for SineWavePureFunctionCreator {
             start_time: None,
         })
     }
+    fn description(&self) -> String {
+        "An object whose position is a pure function of time. \
+The client only uses the first received message to start its clock and computes every \
+position itself, so nothing but the start time is replicated.\n\n\
+The client is offset by the latency of the first message and never corrects it, but its \
+motion is perfectly smooth regardless of jitter and loss.\n\n\
+Relevant settings: min and max latency ms, client fps."
+            .into()
+    }
+    fn ground_truth(&self, _settings: &SimSettings) -> Option<GroundTruth> {
+        Some(Box::new(|t| {
+            let pos = sine_wave(
+                Duration::from_secs_f32(1.),
+                Duration::from_secs_f32(t.max(0.)),
+            ) + t * Vector2::new(2000., 2000.);
+            Vector3::new(pos.x, pos.y, 0.)
+        }))
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -204,15 +407,17 @@ pub struct SineWavePureFunction {
 }
 
 impl SimulationState for SineWavePureFunction {
-    fn send_sync(&self, time: &Time) -> Vec<u8> {
-        bincode::serialize(&self.sim_state).unwrap()
+    fn send_sync(&self, time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.sim_state)
     }
     fn recv_sync(
         &mut self,
         time: &Time,
         _server_time: Duration,
         _server_frame: u64,
+        _seq: u16,
         _msg: &Vec<u8>,
+        _codec: SyncCodec,
     ) {
         if let None = self.start_time {
             self.start_time = Some(time.absolute_time().as_secs_f32());
@@ -224,180 +429,2466 @@ impl SimulationState for SineWavePureFunction {
             if t < 0. {
                 return None;
             }
-            Some(Sample {
-                pos: sine_wave(Duration::from_secs_f32(1.), Duration::from_secs_f32(t))
+            Some(Sample::planar(
+                sine_wave(Duration::from_secs_f32(1.), Duration::from_secs_f32(t))
                     + time.absolute_time_seconds() as f32 * Vector2::new(2000., 2000.),
-            })
+                1.,
+            ))
         })
     }
     fn update_server(&mut self, time: &Time) -> Sample {
-        Sample {
-            pos: sine_wave(Duration::from_secs_f32(1.), time.absolute_time())
+        Sample::planar(
+            sine_wave(Duration::from_secs_f32(1.), time.absolute_time())
                 + time.absolute_time_seconds() as f32 * Vector2::new(2000., 2000.),
-        }
+            1.,
+        )
     }
 }
 
-#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
-pub struct InputPosVel {
-    input_dir: Vector2<f32>,
-    pos: Vector2<f32>,
-    velocity: Vector2<f32>,
+/// Seconds a spawned entity lives before it is despawned, spawns are spaced so that
+/// `SimSettings::entity_count` entities are alive at once
+const ENTITY_LIFETIME: f32 = 0.4;
+
+#[derive(Serialize, Deserialize)]
+struct EntitiesMessage {
+    primary: PosVel,
+    /// Entities spawned and despawned during the sync interval ending with this message
+    spawned: Vec<u32>,
+    despawned: Vec<u32>,
+    positions: Vec<(u32, Vector2<f32>)>,
 }
-impl Default for InputPosVel {
-    fn default() -> Self {
-        Self {
-            input_dir: math::zero(),
-            pos: math::zero(),
-            velocity: math::zero(),
-        }
+
+/// Name of the behaviour whose entity count is `SimSettings::entity_count`
+pub const SPAWNING_ENTITIES: &str = "Spawning Entities Thin Client";
+
+#[derive(Default)]
+struct SpawningEntitiesCreator;
+impl fmt::Display for SpawningEntitiesCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", SPAWNING_ENTITIES)
+    }
+}
+impl SimulationBehaviour for SpawningEntitiesCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(SpawningEntities {
+            primary: Default::default(),
+            server_time: 0.,
+            sync_interval: 1. / settings.sync_rate as f32,
+            spawn_interval: ENTITY_LIFETIME / settings.entity_count.max(1) as f32,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            primary_buffer: SnapshotBuffer::new(),
+            known: BTreeMap::new(),
+            despawned: BTreeSet::new(),
+            rendered: Vec::new(),
+            rng: SimRng::new(settings.seed),
+        })
+    }
+    fn description(&self) -> String {
+        "A primary entity plus a stream of short-lived entities, \
+spawned so that entity count of them are alive at once. Spawns and despawns are only sent \
+with the sync following them.\n\n\
+Under loss the client misses spawns, or keeps rendering ghosts of entities the server \
+already despawned. A retransmit policy makes spawns and despawns reliable. Large entity \
+counts show the bandwidth cost of the sync codec.\n\n\
+Relevant settings: entity count, loss percentage, retransmit policy, sync codec, \
+bandwidth."
+            .into()
     }
 }
 
-#[derive(Debug, Default)]
-pub struct PlayerCharacterDeterministic {
-    state: InputPosVel,
-    server: bool,
+/// Thin client replicating a stream of short-lived entities next to the primary one.
+///
+/// Spawns and despawns are only sent with the sync following them, so under loss the client
+/// misses spawns or keeps rendering ghosts of despawned entities. Enabling a retransmit policy
+/// makes them reliable, since spawns and despawns are applied from late messages too.
+#[derive(Clone, Debug)]
+pub struct SpawningEntities {
+    primary: PosVel,
+    server_time: f32,
+    sync_interval: f32,
+    /// Seconds between entity spawns
+    spawn_interval: f32,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    primary_buffer: SnapshotBuffer<PosVel>,
+    known: BTreeMap<u32, SnapshotBuffer<Vector2<f32>>>,
+    despawned: BTreeSet<u32>,
+    rendered: Vec<(u32, Sample)>,
+    rng: SimRng,
 }
-impl Clone for PlayerCharacterDeterministic {
-    fn clone(&self) -> Self {
-        Self {
-            state: self.state,
-            server: self.server,
-        }
+impl SpawningEntities {
+    fn spawn_time(&self, id: u32) -> f32 {
+        id as f32 * self.spawn_interval
     }
-    fn clone_from(&mut self, source: &Self) {
-        self.state = source.state;
+    fn entity_pos(&self, id: u32, t: f32) -> Vector2<f32> {
+        let age = t - self.spawn_time(id);
+        let lane: f32 = self.rng.entity_stream("spawn lane", id).gen_range(0., 160.);
+        Vector2::new(age * 150., lane + (age * 10. + id as f32).sin() * 15.)
     }
-}
-impl fmt::Display for PlayerCharacterDeterministic {
-    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "Player Character Server-Rate")
+    /// Entities alive at server time `t`
+    fn alive<'a>(&'a self, t: f32) -> impl Iterator<Item = u32> + 'a {
+        let newest = (t / self.spawn_interval).floor().max(0.) as u32;
+        let oldest = ((t - ENTITY_LIFETIME) / self.spawn_interval)
+            .floor()
+            .max(0.) as u32;
+        (oldest..=newest).filter(move |id| {
+            let spawn = self.spawn_time(*id);
+            spawn <= t && t < spawn + ENTITY_LIFETIME
+        })
+    }
+    /// Entities spawned and despawned between server times `from` and `to`
+    fn lifecycle(&self, from: f32, to: f32) -> (Vec<u32>, Vec<u32>) {
+        let before: BTreeSet<u32> = self.alive(from).collect();
+        let after: BTreeSet<u32> = self.alive(to).collect();
+        let spawned = after.difference(&before).cloned().collect();
+        let despawned = before.difference(&after).cloned().collect();
+        (spawned, despawned)
     }
 }
-
-impl DeterministicSimulation for PlayerCharacterDeterministic {
-    type SyncType = InputPosVel;
-    fn send_state(&self) -> &Self::SyncType {
-        &self.state
+impl SimulationState for SpawningEntities {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        let (spawned, despawned) =
+            self.lifecycle(self.server_time - self.sync_interval, self.server_time);
+        codec.encode(&EntitiesMessage {
+            primary: self.primary,
+            spawned,
+            despawned,
+            positions: self
+                .alive(self.server_time)
+                .map(|id| (id, self.entity_pos(id, self.server_time)))
+                .collect(),
+        })
     }
-    fn recv_state(&mut self, val: Self::SyncType) {
-        self.state = val;
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        let delivery = self.sequence.recv(seq);
+        if delivery == Delivery::Duplicate {
+            return;
+        }
+        let msg: EntitiesMessage = codec.decode(msg);
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        // spawns and despawns are idempotent, so they are applied even from late messages
+        for id in msg.despawned {
+            self.known.remove(&id);
+            self.despawned.insert(id);
+        }
+        for id in msg.spawned {
+            if !self.despawned.contains(&id) {
+                self.known.entry(id).or_default();
+            }
+        }
+        let t = server_time.as_secs_f32();
+        self.primary_buffer
+            .add(Key::new(t, msg.primary, Kernel::Linear));
+        for (id, pos) in msg.positions {
+            if let Some(buffer) = self.known.get_mut(&id) {
+                buffer.add(Key::new(t, pos, Kernel::Linear));
+            }
+        }
     }
-    fn update(&mut self, abs_time: Duration, delta_time: Duration) {
-        if self.server {
-            self.state.input_dir = PLAYER_INPUT_DIR
-                .clamped_sample(abs_time.as_secs_f32())
-                .unwrap();
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        let start_time = self.start_time?;
+        let t = time.absolute_time().as_secs_f32() - (self.delay / 1000.);
+        self.rendered.clear();
+        if t < start_time {
+            return None;
         }
-        self.state.velocity = self.state.input_dir * 100.;
-        self.state.pos += self.state.velocity * delta_time.as_secs_f32();
+        for (id, buffer) in self.known.iter_mut() {
+            if let Some(pos) = buffer.clamped_sample(t) {
+                self.rendered.push((*id, Sample::planar(pos, 1.)));
+            }
+            buffer.prune_before(t);
+        }
+        let sample = self.primary_buffer.clamped_sample(t);
+        self.primary_buffer.prune_before(t);
+        sample.map(|p| Sample::planar(p.pos, 1.))
     }
-    fn pos_sample(&self, state: &Self::SyncType) -> Sample {
-        Sample { pos: state.pos }
+    fn buffered_bytes(&self) -> usize {
+        self.primary_buffer.memory_bytes()
+            + self.known.values().map(|b| b.memory_bytes()).sum::<usize>()
     }
-    fn initial(_settings: &SimSettings) -> Self {
-        Self {
-            server: true,
-            ..Default::default()
-        }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.server_time = time.absolute_time().as_secs_f32();
+        self.primary.pos += self.primary.velocity * time.delta_seconds();
+        self.primary.velocity += sine_wave(time.delta_time(), time.absolute_time());
+        Sample::planar(self.primary.pos, 1.)
+    }
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        self.alive(self.server_time)
+            .map(|id| {
+                let sample = Sample::planar(self.entity_pos(id, self.server_time), 1.);
+                (id, sample)
+            })
+            .collect()
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        self.rendered.clone()
     }
 }
 
-fn sine_wave(delta_time: Duration, abs_time: Duration) -> Vector2<f32> {
-    Vector2::new(0., 1.)
-        * (abs_time.as_secs_f32() * 20.).sin()
-        * 300 as f32
-        * delta_time.as_secs_f32()
+/// Server time in seconds at which the server hands authority over the box to the client
+const OWNERSHIP_TRANSFER_TIME: f32 = 1.;
+/// Entity id of the box
+const BOX_ENTITY: u32 = 0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum Owner {
+    Server,
+    Client,
 }
 
-macro_rules! spline_key {
-    ( $time: expr => $x: expr , $y: expr ) => {{
-        splines::Key::new($time, Vector2::new($x, $y), splines::Interpolation::Linear)
-    }};
+#[derive(Serialize, Deserialize)]
+struct OwnershipMessage {
+    primary: PosVel,
+    owner: Owner,
+    /// Authoritative box position, or the latest one received from the client once it owns it
+    box_pos: Vector2<f32>,
 }
 
-lazy_static! {
-    pub static ref PLAYER_INPUT_DIR: splines::Spline<f32, Vector2<f32>> =
-        splines::Spline::from_vec(vec![
-            spline_key!(0. => 0., 0.),
-            spline_key!(0.3 => 1., 0.),
-            spline_key!(0.5 => 0., 0.),
-            spline_key!(0.7 => 0., 1.),
-            spline_key!(1. => 0., 0.),
-            spline_key!(1.5 => -1., 0.),
-            spline_key!(2.0 => 0., 0.),
-        ]);
+#[derive(Serialize, Deserialize)]
+struct BoxMessage {
+    time: f32,
+    pos: Vector2<f32>,
 }
 
-lazy_static! {
-    pub static ref SIM_BEHAVIOURS: Vec<(Arc<dyn SimulationBehaviour>, std::ffi::CString)> = vec![
-        behaviour_data::<SineWaveClientSim>(),
-        behaviour_data::<ServerRateSimulation<SineWaveDeterministicSim>>(),
-        behaviour_data::<SineWaveThinClientCreator>(),
-        behaviour_data::<SineWaveThinClientServerTime>(),
-        behaviour_data::<SineWavePureFunctionCreator>(),
-        behaviour_data::<ServerRateSimulation<PlayerCharacterDeterministic>>(),
-    ];
+#[derive(Default)]
+struct OwnershipTransferCreator;
+impl fmt::Display for OwnershipTransferCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Ownership Transfer Thin Client")
+    }
+}
+impl SimulationBehaviour for OwnershipTransferCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(OwnershipTransfer {
+            primary: Default::default(),
+            server_time: 0.,
+            owner: Owner::Server,
+            box_pos: OwnershipTransfer::box_path(0.),
+            box_time: None,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            primary_buffer: SnapshotBuffer::new(),
+            box_buffer: SnapshotBuffer::new(),
+            rendered_box: None,
+            took_ownership: false,
+            stale_updates: 0,
+            label: None,
+            metrics: Vec::new(),
+        })
+    }
+    fn description(&self) -> String {
+        "A thin client next to a box the server simulates until \
+it hands authority over to the client, like a physics object being picked up.\n\n\
+The client renders the server owned box in the interpolated past but its own box in the \
+present, so the box jumps forward when the client takes over. Until the client's first \
+state reaches the server nobody updates the box there, and server owned states still in \
+flight are stale.\n\n\
+Relevant settings: render interpolation delay ms, min and max latency ms."
+            .into()
+    }
 }
 
-impl splines::Interpolate<f32> for InputPosVel {
-    /// Linear interpolation.
-    fn lerp(a: Self, b: Self, t: f32) -> Self {
-        Self {
-            input_dir: a.input_dir,
-            pos: <Vector2<f32> as splines::Interpolate<f32>>::lerp(a.pos, b.pos, t),
-            velocity: a.velocity,
+/// Thin client next to a box that the server simulates until `OWNERSHIP_TRANSFER_TIME`, when
+/// it hands authority over to the client, like a physics object being picked up.
+///
+/// The client renders the server owned box in the interpolated past, but its own box in the
+/// present, so the box jumps when the client takes over. Until the first state from the client
+/// arrives, nobody updates the box on the server, and server owned states still in flight when
+/// the client takes over are stale.
+#[derive(Clone, Debug)]
+pub struct OwnershipTransfer {
+    primary: PosVel,
+    server_time: f32,
+    /// Owner of the box as known to this side
+    owner: Owner,
+    box_pos: Vector2<f32>,
+    /// Time of the latest box state from the client, on the server
+    box_time: Option<f32>,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    primary_buffer: SnapshotBuffer<PosVel>,
+    box_buffer: SnapshotBuffer<Vector2<f32>>,
+    rendered_box: Option<Vector2<f32>>,
+    /// Set when the client received the ownership, until the next `update_render`
+    took_ownership: bool,
+    /// Server owned box states received since the last `update_render` after the client took over
+    stale_updates: u32,
+    label: Option<String>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+}
+impl OwnershipTransfer {
+    /// Position of the box at time `t` of whichever side owns it
+    fn box_path(t: f32) -> Vector2<f32> {
+        Vector2::new(t * 120., 150. + (t * 3.).sin() * 30.)
+    }
+}
+impl SimulationState for OwnershipTransfer {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&OwnershipMessage {
+            primary: self.primary,
+            owner: self.owner,
+            box_pos: self.box_pos,
+        })
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) == Delivery::Duplicate {
+            return;
+        }
+        let msg: OwnershipMessage = codec.decode(msg);
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        let t = server_time.as_secs_f32();
+        self.primary_buffer
+            .add(Key::new(t, msg.primary, Kernel::Linear));
+        match (self.owner, msg.owner) {
+            (Owner::Server, Owner::Server) => {
+                self.box_buffer
+                    .add(Key::new(t, msg.box_pos, Kernel::Linear))
+            }
+            (Owner::Server, Owner::Client) => {
+                self.owner = Owner::Client;
+                self.took_ownership = true;
+            }
+            (Owner::Client, Owner::Server) => self.stale_updates += 1,
+            (Owner::Client, Owner::Client) => {}
         }
     }
-
-    fn cubic_hermite(
-        _: (Self, f32),
-        _: (Self, f32),
-        _: (Self, f32),
-        _: (Self, f32),
-        _: f32,
-    ) -> Self {
-        unimplemented!()
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        let start_time = self.start_time?;
+        let now = time.absolute_time().as_secs_f32();
+        let t = now - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        self.metrics.clear();
+        if self.stale_updates > 0 {
+            self.metrics
+                .push(("stale box updates", self.stale_updates as f32));
+            self.stale_updates = 0;
+        }
+        let box_pos = match self.owner {
+            Owner::Server => {
+                let pos = self.box_buffer.clamped_sample(t);
+                self.box_buffer.prune_before(t);
+                pos
+            }
+            // the owner simulates the box in the present
+            Owner::Client => Some(Self::box_path(now)),
+        };
+        self.label = None;
+        if self.took_ownership {
+            self.took_ownership = false;
+            self.label = Some("took box ownership".to_owned());
+            if let (Some(prev), Some(pos)) = (self.rendered_box, box_pos) {
+                self.metrics.push(("handover jump", (pos - prev).norm()));
+            }
+        }
+        self.rendered_box = box_pos.or(self.rendered_box);
+        let sample = self.primary_buffer.clamped_sample(t);
+        self.primary_buffer.prune_before(t);
+        sample.map(|p| Sample::planar(p.pos, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.primary_buffer.memory_bytes() + self.box_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.server_time = time.absolute_time().as_secs_f32();
+        self.metrics.clear();
+        self.label = None;
+        self.primary.pos += self.primary.velocity * time.delta_seconds();
+        self.primary.velocity += sine_wave(time.delta_time(), time.absolute_time());
+        if self.owner == Owner::Server {
+            if self.server_time >= OWNERSHIP_TRANSFER_TIME {
+                self.owner = Owner::Client;
+                self.label = Some("box ownership → client".to_owned());
+            } else {
+                self.box_pos = Self::box_path(self.server_time);
+            }
+        }
+        // between the handover and the client's first state the box has no authority
+        if self.owner == Owner::Client && self.box_time.is_none() {
+            self.metrics.push(("unowned box frames", 1.));
+        }
+        Sample::planar(self.primary.pos, 1.)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, _side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        for (name, value) in self.metrics.iter() {
+            metrics.record(name, now, *value);
+        }
+    }
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        vec![(BOX_ENTITY, Sample::planar(self.box_pos, 1.))]
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        self.rendered_box
+            .map(|pos| (BOX_ENTITY, Sample::planar(pos, 1.)))
+            .into_iter()
+            .collect()
     }
+    fn send_upstream(&self, time: &Time) -> Option<Vec<u8>> {
+        match (self.owner, self.rendered_box) {
+            (Owner::Client, Some(pos)) => Some(
+                bincode::serialize(&BoxMessage {
+                    time: time.absolute_time().as_secs_f32(),
+                    pos,
+                })
+                .unwrap(),
+            ),
+            _ => None,
+        }
+    }
+    fn recv_upstream(&mut self, _time: &Time, msg: &[u8]) {
+        let msg: BoxMessage = bincode::deserialize(msg).unwrap();
+        // ignore reordered states
+        if self.box_time.map(|t| msg.time > t).unwrap_or(true) {
+            self.box_time = Some(msg.time);
+            self.box_pos = msg.pos;
+        }
+    }
+}
+
+/// Radius of the colliding balls
+const BALL_RADIUS: f32 = 20.;
+/// Distance of the walls the balls bounce off from the origin
+const WALL_DISTANCE: f32 = 250.;
+const BALL_SPEED: f32 = 150.;
+/// Entity id of the client owned ball
+const CLIENT_BALL: u32 = 1;
+
+#[derive(Serialize, Deserialize)]
+struct BallMessage {
+    time: f32,
+    ball: PosVel,
+}
 
-    /// Quadratic Bézier interpolation.
-    fn quadratic_bezier(_: Self, _: Self, _: Self, _: f32) -> Self {
-        unimplemented!()
+/// Moves `own` by `dt` seconds, bouncing it off the walls and off `other` as an elastic
+/// collision of equal masses. Returns true if it bounced off `other`.
+fn step_ball(own: &mut PosVel, other: &PosVel, dt: f32) -> bool {
+    own.pos += own.velocity * dt;
+    if own.pos.x.abs() > WALL_DISTANCE && own.pos.x * own.velocity.x > 0. {
+        own.velocity.x = -own.velocity.x;
     }
+    let offset = other.pos.x - own.pos.x;
+    let approaching = offset * (own.velocity.x - other.velocity.x) > 0.;
+    if offset.abs() < BALL_RADIUS * 2. && approaching {
+        own.velocity = other.velocity;
+        true
+    } else {
+        false
+    }
+}
+fn overlap(a: &PosVel, b: &PosVel) -> f32 {
+    (BALL_RADIUS * 2. - (a.pos.x - b.pos.x).abs()).max(0.)
+}
 
-    /// Cubic Bézier interpolation.
-    fn cubic_bezier(_: Self, _: Self, _: Self, _: Self, _: f32) -> Self {
-        unimplemented!()
+#[derive(Default)]
+struct SplitAuthorityCreator;
+impl fmt::Display for SplitAuthorityCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Split Authority Collision")
+    }
+}
+impl SimulationBehaviour for SplitAuthorityCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(SplitAuthority {
+            server_ball: SplitAuthority::initial_ball(-1.),
+            client_ball: SplitAuthority::initial_ball(1.),
+            client_ball_time: None,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            server_ball_buffer: SnapshotBuffer::new(),
+            rendered_server_ball: None,
+            label: None,
+            overlap: None,
+        })
+    }
+    fn description(&self) -> String {
+        "Two balls bouncing between walls and off each other, one \
+simulated by the server and the other by the client.\n\n\
+Each side resolves collisions against its outdated view of the other side's ball, so the two \
+authorities disagree about when and where the balls collided: balls bounce early, pass into \
+each other or bounce off nothing. The disagreement grows with latency.\n\n\
+Relevant settings: min and max latency ms, sync rate."
+            .into()
     }
 }
 
-impl splines::Interpolate<f32> for PosVel {
-    /// Linear interpolation.
-    fn lerp(a: Self, b: Self, t: f32) -> Self {
-        Self {
-            pos: <Vector2<f32> as splines::Interpolate<f32>>::lerp(a.pos, b.pos, t),
-            velocity: a.velocity,
+/// Two balls bouncing between walls and off each other, one simulated by the server and the
+/// other by the client. Each side resolves collisions against its outdated view of the other
+/// side's ball, so the two authorities disagree about when and where the balls collided: balls
+/// bounce early, pass into each other or bounce off nothing.
+#[derive(Clone, Debug)]
+pub struct SplitAuthority {
+    server_ball: PosVel,
+    client_ball: PosVel,
+    /// Client time of the latest client ball state, on the server
+    client_ball_time: Option<f32>,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    server_ball_buffer: SnapshotBuffer<PosVel>,
+    rendered_server_ball: Option<PosVel>,
+    label: Option<String>,
+    /// Overlap of the balls as seen by this side in the latest update
+    overlap: Option<f32>,
+}
+impl SplitAuthority {
+    /// Ball starting next to the wall on `side` (-1 or 1), moving toward the other ball
+    fn initial_ball(side: f32) -> PosVel {
+        PosVel {
+            pos: Vector2::new(side * WALL_DISTANCE * 0.8, 0.),
+            velocity: Vector2::new(-side * BALL_SPEED, 0.),
+            teleports: 0,
         }
     }
-
-    fn cubic_hermite(
-        _: (Self, f32),
-        _: (Self, f32),
-        _: (Self, f32),
-        _: (Self, f32),
-        _: f32,
-    ) -> Self {
-        unimplemented!()
+}
+impl SimulationState for SplitAuthority {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.server_ball)
     }
-
-    /// Quadratic Bézier interpolation.
-    fn quadratic_bezier(_: Self, _: Self, _: Self, _: f32) -> Self {
-        unimplemented!()
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) != Delivery::Latest {
+            return;
+        }
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        let ball: PosVel = codec.decode(msg);
+        self.server_ball_buffer
+            .add(Key::new(server_time.as_secs_f32(), ball, Kernel::Linear));
     }
-
-    /// Cubic Bézier interpolation.
-    fn cubic_bezier(_: Self, _: Self, _: Self, _: Self, _: f32) -> Self {
-        unimplemented!()
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.label = None;
+        self.overlap = None;
+        let start_time = self.start_time?;
+        let t = time.absolute_time().as_secs_f32() - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        let server_ball = self.server_ball_buffer.clamped_sample(t)?;
+        self.server_ball_buffer.prune_before(t);
+        // the client collides its own ball in the present with the server's ball in the past
+        if step_ball(&mut self.client_ball, &server_ball, time.delta_seconds()) {
+            self.label = Some("client ball bounced".to_owned());
+        }
+        self.overlap = Some(overlap(&self.client_ball, &server_ball));
+        self.rendered_server_ball = Some(server_ball);
+        Some(Sample::planar(server_ball.pos, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.server_ball_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.label = None;
+        // the server collides its ball with the latest client ball state it received
+        if step_ball(
+            &mut self.server_ball,
+            &self.client_ball,
+            time.delta_seconds(),
+        ) {
+            self.label = Some("server ball bounced".to_owned());
+        }
+        self.overlap = Some(overlap(&self.server_ball, &self.client_ball));
+        Sample::planar(self.server_ball.pos, 1.)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        if let Some(overlap) = self.overlap {
+            let name = match side {
+                SimSide::Server => "server overlap",
+                SimSide::Client => "client overlap",
+            };
+            metrics.record(name, time.absolute_time().as_secs_f32(), overlap);
+        }
     }
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        vec![(CLIENT_BALL, Sample::planar(self.client_ball.pos, 1.))]
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        match self.start_time {
+            Some(_) => vec![(CLIENT_BALL, Sample::planar(self.client_ball.pos, 1.))],
+            None => Vec::new(),
+        }
+    }
+    fn send_upstream(&self, time: &Time) -> Option<Vec<u8>> {
+        self.rendered_server_ball.map(|_| {
+            bincode::serialize(&BallMessage {
+                time: time.absolute_time().as_secs_f32(),
+                ball: self.client_ball,
+            })
+            .unwrap()
+        })
+    }
+    fn recv_upstream(&mut self, _time: &Time, msg: &[u8]) {
+        let msg: BallMessage = bincode::deserialize(msg).unwrap();
+        // ignore reordered states
+        if self.client_ball_time.map(|t| msg.time > t).unwrap_or(true) {
+            self.client_ball_time = Some(msg.time);
+            self.client_ball = msg.ball;
+        }
+    }
+}
+
+/// Name of the behaviour adjudicating shots under every `HitCompensation`
+pub const HIT_REGISTRATION: &str = "Hit Registration Thin Client";
+/// Radius of the target the client shoots at
+const TARGET_RADIUS: f32 = 15.;
+/// Largest distance of a shot from the rendered target center along either axis
+const AIM_SPREAD: f32 = 15.;
+/// Seconds between the client's shots
+const SHOT_INTERVAL: f32 = 0.1;
+/// Seconds of target positions the server keeps to rewind to
+const TARGET_HISTORY: f32 = 1.;
+
+/// How the server decides whether a shot hit the target
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HitCompensation {
+    /// Tests the shot against the target's position when the shot arrives
+    None,
+    /// Tests the shot against the target's position at the server time the client rendered
+    Rewind,
+    /// Trusts the client's test against the target it rendered
+    ClientSide,
+}
+impl fmt::Display for HitCompensation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            HitCompensation::None => write!(f, "No Compensation"),
+            HitCompensation::Rewind => write!(f, "Server Rewind"),
+            HitCompensation::ClientSide => write!(f, "Client Side"),
+        }
+    }
+}
+pub const HIT_COMPENSATIONS: [HitCompensation; 3] = [
+    HitCompensation::None,
+    HitCompensation::Rewind,
+    HitCompensation::ClientSide,
+];
+impl HitCompensation {
+    /// Metric with 1 for every hit and 0 for every miss, the mean being the hit rate
+    pub fn metric(&self) -> String {
+        format!("hit rate {}", self.to_string().to_lowercase())
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct ShotMessage {
+    aim: Vector2<f32>,
+    /// Server time of the target state the client rendered when it shot
+    view_time: f32,
+    /// Whether the shot hit the rendered target
+    client_hit: bool,
+}
+
+#[derive(Default)]
+struct HitRegistrationCreator;
+impl fmt::Display for HitRegistrationCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", HIT_REGISTRATION)
+    }
+}
+impl SimulationBehaviour for HitRegistrationCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(HitRegistration {
+            target: HitRegistration::target_path(0.),
+            history: SnapshotBuffer::new(),
+            shots: Vec::new(),
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            target_buffer: SnapshotBuffer::new(),
+            shot_count: 0,
+            next_shot: 0.,
+            shot: None,
+            label: None,
+            metrics: Vec::new(),
+            rng: SimRng::new(settings.seed),
+        })
+    }
+    fn description(&self) -> String {
+        "A thin client shooting at a fast moving target, aiming \
+at the target it renders. The server adjudicates every shot with no compensation, by \
+rewinding the target to the time the client saw, and by trusting the client, recording the \
+hit rate of each in the metrics.\n\n\
+Without compensation, shots that hit on the client miss on the server once the latency plus \
+the render interpolation delay moves the target further than its radius. Rewinding restores \
+the hits as long as the server keeps enough target history.\n\n\
+Relevant settings: render interpolation delay ms, min and max latency ms, loss percentage."
+            .into()
+    }
+}
+
+/// Thin client shooting at a fast moving target, aiming at the target it renders with a
+/// scripted spread. The server adjudicates every shot under each `HitCompensation`, recording
+/// their hit rates, so the strategies can be compared on the same shots. Shots lost on the way
+/// to the server are not adjudicated.
+#[derive(Clone, Debug)]
+pub struct HitRegistration {
+    target: Vector2<f32>,
+    /// Recent target positions on the server, to rewind to
+    history: SnapshotBuffer<Vector2<f32>>,
+    /// Shots received since the last `update_server`
+    shots: Vec<ShotMessage>,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    target_buffer: SnapshotBuffer<Vector2<f32>>,
+    shot_count: u32,
+    /// Client time of the next shot
+    next_shot: f32,
+    /// Shot fired in the latest `update_render`, sent upstream
+    shot: Option<ShotMessage>,
+    label: Option<String>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(String, f32)>,
+    rng: SimRng,
+}
+impl HitRegistration {
+    fn target_path(t: f32) -> Vector2<f32> {
+        Vector2::new(300. + (t * 2.).sin() * 250., 200. + (t * 5.).cos() * 60.)
+    }
+}
+impl SimulationState for HitRegistration {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.target)
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) != Delivery::Latest {
+            return;
+        }
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+            self.next_shot = self.start_time.unwrap();
+        }
+        let target: Vector2<f32> = codec.decode(msg);
+        self.target_buffer
+            .add(Key::new(server_time.as_secs_f32(), target, Kernel::Linear));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.shot = None;
+        self.label = None;
+        let start_time = self.start_time?;
+        let now = time.absolute_time().as_secs_f32();
+        let t = now - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        let target = self.target_buffer.clamped_sample(t)?;
+        // the rendered state is clamped to the received ones
+        let view_time = match (self.target_buffer.first(), self.target_buffer.last()) {
+            (Some(first), Some(last)) => t.max(first.t).min(last.t),
+            _ => t,
+        };
+        self.target_buffer.prune_before(t);
+        if now >= self.next_shot {
+            self.next_shot += SHOT_INTERVAL;
+            let mut rng = self.rng.entity_stream("aim", self.shot_count);
+            self.shot_count += 1;
+            let aim = target
+                + Vector2::new(
+                    rng.gen_range(-AIM_SPREAD, AIM_SPREAD),
+                    rng.gen_range(-AIM_SPREAD, AIM_SPREAD),
+                );
+            self.shot = Some(ShotMessage {
+                aim,
+                view_time,
+                client_hit: (aim - target).norm() <= TARGET_RADIUS,
+            });
+            self.label = Some(format!("shot {}", self.shot_count));
+        }
+        Some(Sample::planar(target, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.history.memory_bytes() + self.target_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        let now = time.absolute_time().as_secs_f32();
+        self.metrics.clear();
+        self.label = None;
+        if !self.shots.is_empty() {
+            self.label = Some(format!("{} shots adjudicated", self.shots.len()));
+        }
+        self.target = Self::target_path(now);
+        self.history.add(Key::new(now, self.target, Kernel::Linear));
+        self.history.prune_before(now - TARGET_HISTORY);
+        for shot in self.shots.drain(..) {
+            for compensation in HIT_COMPENSATIONS.iter() {
+                let hit = match compensation {
+                    HitCompensation::None => (shot.aim - self.target).norm() <= TARGET_RADIUS,
+                    // shots older than the history are tested against its oldest position
+                    HitCompensation::Rewind => self
+                        .history
+                        .clamped_sample(shot.view_time)
+                        .map(|pos| (shot.aim - pos).norm() <= TARGET_RADIUS)
+                        .unwrap_or(false),
+                    HitCompensation::ClientSide => shot.client_hit,
+                };
+                self.metrics
+                    .push((compensation.metric(), if hit { 1. } else { 0. }));
+            }
+            // how far back the server rewinds for the shot
+            self.metrics
+                .push(("rewind time".to_owned(), (now - shot.view_time) * 1000.));
+        }
+        Sample::planar(self.target, 1.)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        if side == SimSide::Server {
+            let now = time.absolute_time().as_secs_f32();
+            for (name, value) in self.metrics.iter() {
+                metrics.record(name, now, *value);
+            }
+        }
+    }
+    fn send_upstream(&self, _time: &Time) -> Option<Vec<u8>> {
+        self.shot.map(|shot| bincode::serialize(&shot).unwrap())
+    }
+    fn recv_upstream(&mut self, _time: &Time, msg: &[u8]) {
+        self.shots.push(bincode::deserialize(msg).unwrap());
+    }
+}
+
+/// Name of the behaviour whose client shows door interactions with
+/// `SimSettings::interaction_prediction`
+pub const DOOR_INTERACTION: &str = "Door Interaction Thin Client";
+/// Seconds between the client's button presses
+const PRESS_INTERVAL: f32 = 0.6;
+/// Seconds after the door moved during which the server rejects presses
+const DOOR_COOLDOWN: f32 = 1.;
+/// Entity id of the button
+const BUTTON_ENTITY: u32 = 1;
+
+/// What the client shows between pressing the button and the server's response
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum InteractionPrediction {
+    /// The door only moves once the client sees the server moving it
+    Strict,
+    /// The door moves on press, and moves back if the server rejects the press
+    Optimistic,
+}
+impl fmt::Display for InteractionPrediction {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            InteractionPrediction::Strict => write!(f, "Strict Server Authority"),
+            InteractionPrediction::Optimistic => write!(f, "Optimistic Prediction"),
+        }
+    }
+}
+pub const INTERACTION_PREDICTIONS: [InteractionPrediction; 2] = [
+    InteractionPrediction::Strict,
+    InteractionPrediction::Optimistic,
+];
+
+#[derive(Serialize, Deserialize)]
+struct PressMessage {
+    press: u32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct DoorMessage {
+    open: bool,
+    /// Latest press the server handled and whether it moved the door
+    press: Option<(u32, bool)>,
+}
+
+#[derive(Default)]
+struct DoorInteractionCreator;
+impl fmt::Display for DoorInteractionCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", DOOR_INTERACTION)
+    }
+}
+impl SimulationBehaviour for DoorInteractionCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(DoorInteraction {
+            open: false,
+            moved_time: None,
+            presses: Vec::new(),
+            handled: None,
+            prediction: settings.interaction_prediction,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            door_buffer: SnapshotBuffer::new(),
+            responses: VecDeque::new(),
+            last_response: None,
+            next_press: 0,
+            press_time: None,
+            pending: None,
+            predicted: None,
+            label: None,
+            metrics: Vec::new(),
+        })
+    }
+    fn description(&self) -> String {
+        "A thin client pressing a button that opens or closes a \
+door. The server rejects presses shortly after the door last moved, and the client resends a \
+press until the response arrives.\n\n\
+With strict server authority the door only moves once the response arrives, a round trip \
+after the press. Optimistic prediction moves it immediately, but has to undo the move when \
+the server rejects the press. The metrics show the time from press to response.\n\n\
+Relevant settings: interaction, min and max latency ms, loss percentage."
+            .into()
+    }
+}
+
+/// Thin client pressing a button that opens or closes a door. The server rejects presses
+/// within `DOOR_COOLDOWN` of the door's last move. The client resends a press until the
+/// response arrives, and measures the time from press to seeing the server's response, while
+/// showing the door either strictly as replicated or optimistically predicted.
+#[derive(Clone, Debug)]
+pub struct DoorInteraction {
+    open: bool,
+    /// Server time the door last moved
+    moved_time: Option<f32>,
+    /// Presses received since the last `update_server`
+    presses: Vec<u32>,
+    /// Latest press the server handled and whether it moved the door
+    handled: Option<(u32, bool)>,
+    prediction: InteractionPrediction,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    door_buffer: SnapshotBuffer<Vector2<f32>>,
+    /// Server time, press and outcome of the responses not yet rendered
+    responses: VecDeque<(f32, u32, bool)>,
+    /// Latest press with a response in `responses`
+    last_response: Option<u32>,
+    next_press: u32,
+    /// Earliest client time of the next press
+    press_time: Option<f32>,
+    /// Press waiting for its response and its client time
+    pending: Option<(u32, f32)>,
+    /// Door state shown until the pending press's response is rendered
+    predicted: Option<bool>,
+    label: Option<String>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+}
+impl DoorInteraction {
+    fn door_pos(open: bool) -> Vector2<f32> {
+        if open {
+            Vector2::new(300., 320.)
+        } else {
+            Vector2::new(300., 200.)
+        }
+    }
+}
+impl SimulationState for DoorInteraction {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&DoorMessage {
+            open: self.open,
+            press: self.handled,
+        })
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) != Delivery::Latest {
+            return;
+        }
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        let msg: DoorMessage = codec.decode(msg);
+        let t = server_time.as_secs_f32();
+        self.door_buffer
+            .add(Key::new(t, Self::door_pos(msg.open), Kernel::Linear));
+        if let Some((press, moved)) = msg.press {
+            if self.last_response.map(|last| press > last).unwrap_or(true) {
+                self.last_response = Some(press);
+                self.responses.push_back((t, press, moved));
+            }
+        }
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.label = None;
+        self.metrics.clear();
+        let start_time = self.start_time?;
+        let now = time.absolute_time().as_secs_f32();
+        let t = now - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        let door = self.door_buffer.clamped_sample(t)?;
+        let rendered_until = self.door_buffer.last().map(|k| k.t.min(t)).unwrap_or(t);
+        self.door_buffer.prune_before(t);
+        // responses become visible with the door state replicated along with them
+        while let Some((_, press, moved)) = self
+            .responses
+            .front()
+            .cloned()
+            .filter(|(response_time, _, _)| *response_time <= rendered_until)
+        {
+            self.responses.pop_front();
+            if let Some((pending, press_time)) = self.pending {
+                if press == pending {
+                    self.pending = None;
+                    self.predicted = None;
+                    let latency = (now - press_time) * 1000.;
+                    self.metrics.push(("interaction latency", latency));
+                    if self.prediction == InteractionPrediction::Strict {
+                        self.metrics.push(("perceived latency", latency));
+                    } else {
+                        self.metrics
+                            .push(("mispredicted interactions", if moved { 0. } else { 1. }));
+                    }
+                    self.label = Some(format!(
+                        "press {} {} after {:.0} ms",
+                        press,
+                        if moved { "moved door" } else { "rejected" },
+                        latency
+                    ));
+                }
+            }
+        }
+        let press_time = *self.press_time.get_or_insert(now);
+        if now >= press_time && self.pending.is_none() {
+            self.press_time = Some(now + PRESS_INTERVAL);
+            self.next_press += 1;
+            self.pending = Some((self.next_press, now));
+            self.label = Some(format!("pressed {}", self.next_press));
+            if self.prediction == InteractionPrediction::Optimistic {
+                // the door is predicted to move from where it is shown
+                let open =
+                    (door - Self::door_pos(true)).norm() < (door - Self::door_pos(false)).norm();
+                self.predicted = Some(!open);
+                self.metrics.push(("perceived latency", 0.));
+            }
+        }
+        let pos = self.predicted.map(Self::door_pos).unwrap_or(door);
+        Some(Sample::planar(pos, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.door_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.label = None;
+        self.metrics.clear();
+        let now = time.absolute_time().as_secs_f32();
+        for press in std::mem::replace(&mut self.presses, Vec::new()) {
+            // presses are resent until handled, so only the first copy counts
+            if self.handled.map(|(p, _)| press <= p).unwrap_or(false) {
+                continue;
+            }
+            let moved = self
+                .moved_time
+                .map(|t| now - t >= DOOR_COOLDOWN)
+                .unwrap_or(true);
+            if moved {
+                self.open = !self.open;
+                self.moved_time = Some(now);
+            }
+            self.handled = Some((press, moved));
+            self.metrics
+                .push(("rejected presses", if moved { 0. } else { 1. }));
+            self.label = Some(format!(
+                "press {} {}",
+                press,
+                if moved {
+                    "moves door"
+                } else {
+                    "rejected: cooldown"
+                }
+            ));
+        }
+        Sample::planar(Self::door_pos(self.open), 1.)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, _side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        for (name, value) in self.metrics.iter() {
+            metrics.record(name, now, *value);
+        }
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        // the button is lit while waiting for the server
+        let alpha = if self.pending.is_some() { 1. } else { 0.3 };
+        match self.start_time {
+            Some(_) => vec![(
+                BUTTON_ENTITY,
+                Sample::planar(Vector2::new(220., 260.), alpha),
+            )],
+            None => Vec::new(),
+        }
+    }
+    fn send_upstream(&self, _time: &Time) -> Option<Vec<u8>> {
+        self.pending
+            .map(|(press, _)| bincode::serialize(&PressMessage { press }).unwrap())
+    }
+    fn recv_upstream(&mut self, _time: &Time, msg: &[u8]) {
+        let msg: PressMessage = bincode::deserialize(msg).unwrap();
+        self.presses.push(msg.press);
+    }
+}
+
+/// Seconds between projectile launches
+const PROJECTILE_INTERVAL: f32 = 0.2;
+/// Seconds a projectile flies before it is despawned
+const PROJECTILE_LIFETIME: f32 = 0.8;
+const PROJECTILE_SPEED: f32 = 400.;
+/// Downward acceleration of projectiles in flight
+const GRAVITY: f32 = 500.;
+
+/// Everything a projectile's flight follows from
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct ProjectileSpawn {
+    id: u32,
+    /// Server time of the launch
+    time: f32,
+    origin: Vector2<f32>,
+    velocity: Vector2<f32>,
+}
+impl ProjectileSpawn {
+    /// Position at server time `t`, or `None` outside of the flight
+    fn pos(&self, t: f32) -> Option<Vector2<f32>> {
+        let age = t - self.time;
+        if age < 0. || age >= PROJECTILE_LIFETIME {
+            return None;
+        }
+        Some(self.origin + self.velocity * age - Vector2::new(0., GRAVITY * 0.5 * age * age))
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct ProjectileMessage {
+    launcher: PosVel,
+    /// Projectiles launched during the sync interval ending with this message
+    spawns: Vec<ProjectileSpawn>,
+}
+
+#[derive(Default)]
+struct ProjectilesCreator;
+impl fmt::Display for ProjectilesCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Deterministic Projectiles Thin Client")
+    }
+}
+impl SimulationBehaviour for ProjectilesCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(Projectiles {
+            launcher: Default::default(),
+            server_time: 0.,
+            sync_interval: 1. / settings.sync_rate as f32,
+            projectiles: Vec::new(),
+            next_projectile: 0,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            launcher_buffer: SnapshotBuffer::new(),
+            rendered: Vec::new(),
+            shown: BTreeSet::new(),
+            metrics: Vec::new(),
+            rng: SimRng::new(settings.seed),
+        })
+    }
+    fn description(&self) -> String {
+        "A thin client next to a launcher firing projectiles. \
+Only a projectile's spawn parameters are replicated, once, and both sides compute its flight \
+from them.\n\n\
+The client's flights match the server's exactly, but a spawn arriving after its render time \
+pops in mid-flight and a lost spawn is never shown. A retransmit policy recovers lost \
+spawns, late.\n\n\
+Relevant settings: render interpolation delay ms, min and max latency ms, loss percentage, \
+retransmit policy."
+            .into()
+    }
+}
+
+/// Thin client next to a launcher firing projectiles. Only a projectile's spawn parameters are
+/// replicated, once, and both sides compute its flight from them. The client renders a
+/// projectile at its age at the rendered server time, reconciling the spawn backward, so the
+/// flight matches the server's exactly, but a spawn arriving after its render time pops in
+/// mid-flight and a lost spawn is never shown. Enabling a retransmit policy recovers lost spawns.
+#[derive(Clone, Debug)]
+pub struct Projectiles {
+    launcher: PosVel,
+    server_time: f32,
+    sync_interval: f32,
+    /// Projectiles in flight on the server, or received and not yet landed on the client
+    projectiles: Vec<ProjectileSpawn>,
+    /// Id of the next projectile the server launches
+    next_projectile: u32,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    launcher_buffer: SnapshotBuffer<PosVel>,
+    rendered: Vec<(u32, Sample)>,
+    /// Projectiles rendered so far, to detect pop-ins
+    shown: BTreeSet<u32>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+    rng: SimRng,
+}
+impl Projectiles {
+    /// Projectile `id` launched now from the launcher
+    fn spawn(&self, id: u32) -> ProjectileSpawn {
+        let angle: f32 = self
+            .rng
+            .entity_stream("projectile angle", id)
+            .gen_range(0.3, 1.2);
+        ProjectileSpawn {
+            id,
+            time: self.server_time,
+            origin: self.launcher.pos,
+            velocity: Vector2::new(angle.cos(), angle.sin()) * PROJECTILE_SPEED,
+        }
+    }
+}
+impl SimulationState for Projectiles {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        let since = self.server_time - self.sync_interval;
+        codec.encode(&ProjectileMessage {
+            launcher: self.launcher,
+            spawns: self
+                .projectiles
+                .iter()
+                .filter(|p| p.time > since)
+                .cloned()
+                .collect(),
+        })
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        let delivery = self.sequence.recv(seq);
+        if delivery == Delivery::Duplicate {
+            return;
+        }
+        let msg: ProjectileMessage = codec.decode(msg);
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        // spawns are applied even from late messages, they carry their own time
+        for spawn in msg.spawns {
+            if !self.projectiles.iter().any(|p| p.id == spawn.id) {
+                self.projectiles.push(spawn);
+            }
+        }
+        self.launcher_buffer.add(Key::new(
+            server_time.as_secs_f32(),
+            msg.launcher,
+            Kernel::Linear,
+        ));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.rendered.clear();
+        self.metrics.clear();
+        let start_time = self.start_time?;
+        let t = time.absolute_time().as_secs_f32() - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        for projectile in self.projectiles.iter() {
+            if let Some(pos) = projectile.pos(t) {
+                if self.shown.insert(projectile.id) {
+                    let age = t - projectile.time;
+                    // a projectile first rendered later than a frame into its flight popped in
+                    self.metrics.push(("projectile pop-in", age * 1000.));
+                    self.metrics.push((
+                        "projectile pop-in distance",
+                        (pos - projectile.origin).norm(),
+                    ));
+                }
+                self.rendered.push((projectile.id, Sample::planar(pos, 1.)));
+            }
+        }
+        self.projectiles
+            .retain(|p| p.time + PROJECTILE_LIFETIME > t);
+        let sample = self.launcher_buffer.clamped_sample(t);
+        self.launcher_buffer.prune_before(t);
+        sample.map(|p| Sample::planar(p.pos, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.launcher_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.server_time = time.absolute_time().as_secs_f32();
+        self.launcher.pos += self.launcher.velocity * time.delta_seconds();
+        self.launcher.velocity += sine_wave(time.delta_time(), time.absolute_time());
+        // launches due since the previous frame happen at this frame
+        while self.next_projectile as f32 * PROJECTILE_INTERVAL <= self.server_time {
+            let spawn = self.spawn(self.next_projectile);
+            self.projectiles.push(spawn);
+            self.next_projectile += 1;
+        }
+        let t = self.server_time;
+        self.projectiles
+            .retain(|p| p.time + PROJECTILE_LIFETIME > t);
+        Sample::planar(self.launcher.pos, 1.)
+    }
+    fn record_metrics(&self, side: SimSide, time: &Time, metrics: &mut Metrics) {
+        if side == SimSide::Client {
+            let now = time.absolute_time().as_secs_f32();
+            for (name, value) in self.metrics.iter() {
+                metrics.record(name, now, *value);
+            }
+        }
+    }
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        self.projectiles
+            .iter()
+            .filter_map(|p| {
+                p.pos(self.server_time)
+                    .map(|pos| (p.id, Sample::planar(pos, 1.)))
+            })
+            .collect()
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        self.rendered.clone()
+    }
+}
+
+/// Name of the behaviour whose client switches animation states with
+/// `SimSettings::animation_policy`
+pub const ANIMATION_STATES: &str = "Animation State Machine Thin Client";
+/// Seconds after which the scripted animation states repeat
+const ANIMATION_LOOP: f32 = 4.5;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum AnimState {
+    Idle,
+    Walk,
+    Run,
+    Jump,
+}
+impl fmt::Display for AnimState {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+impl AnimState {
+    /// Seconds of one cycle of the animation
+    fn cycle(self) -> f32 {
+        match self {
+            AnimState::Idle => 2.,
+            AnimState::Walk => 1.,
+            AnimState::Run => 0.6,
+            AnimState::Jump => 0.8,
+        }
+    }
+    fn speed(self) -> f32 {
+        match self {
+            AnimState::Idle => 0.,
+            AnimState::Walk => 80.,
+            AnimState::Run | AnimState::Jump => 200.,
+        }
+    }
+    /// Scripted state at server time `t` and the time it started
+    fn scripted(t: f32) -> (AnimState, f32) {
+        const SCRIPT: [(f32, AnimState); 7] = [
+            (0., AnimState::Idle),
+            (0.5, AnimState::Walk),
+            (1.3, AnimState::Run),
+            (2.2, AnimState::Jump),
+            (2.6, AnimState::Run),
+            (3.4, AnimState::Walk),
+            (4., AnimState::Idle),
+        ];
+        let loop_start = (t / ANIMATION_LOOP).floor() * ANIMATION_LOOP;
+        let (start, state) = SCRIPT
+            .iter()
+            .rev()
+            .find(|(start, _)| *start <= t - loop_start)
+            .unwrap_or(&SCRIPT[0]);
+        (*state, loop_start + start)
+    }
+    /// Normalized playback time `seconds` into the animation
+    fn phase(self, seconds: f32) -> f32 {
+        (seconds / self.cycle()).fract()
+    }
+}
+
+/// How the client switches to an animation state it learns about late
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum AnimationPolicy {
+    /// Switches at once to the server's playback time, skipping the state's start
+    Snap,
+    /// Blends from the previous state over `SimSettings::crossfade_window`
+    Crossfade,
+}
+impl fmt::Display for AnimationPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AnimationPolicy::Snap => write!(f, "Snap"),
+            AnimationPolicy::Crossfade => write!(f, "Crossfade"),
+        }
+    }
+}
+pub const ANIMATION_POLICIES: [AnimationPolicy; 2] =
+    [AnimationPolicy::Snap, AnimationPolicy::Crossfade];
+
+#[derive(Serialize, Deserialize)]
+struct AnimationMessage {
+    pos: Vector2<f32>,
+    state: AnimState,
+    /// Normalized playback time of `state`
+    phase: f32,
+}
+
+#[derive(Default)]
+struct AnimationStatesCreator;
+impl fmt::Display for AnimationStatesCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", ANIMATION_STATES)
+    }
+}
+impl SimulationBehaviour for AnimationStatesCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(AnimationStates {
+            pos: math::zero(),
+            state: AnimState::Idle,
+            phase: 0.,
+            policy: settings.animation_policy,
+            crossfade_window: settings.crossfade_window / 1000.,
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            pos_buffer: SnapshotBuffer::new(),
+            snapshots: VecDeque::new(),
+            fade: None,
+            label: None,
+            metrics: Vec::new(),
+        })
+    }
+    fn description(&self) -> String {
+        "A thin client of a character playing scripted animation \
+states, synced as the current state and its playback time next to the position.\n\n\
+A switch arriving after its render time is shown late and starts partway into the new \
+state, which the client either snaps to or crossfades into. The server and client animation \
+state plots show how the client's timeline lags and deviates.\n\n\
+Relevant settings: animation switch, crossfade window ms, render interpolation delay ms, \
+min and max latency ms, loss percentage."
+            .into()
+    }
+}
+
+/// Thin client of a character playing scripted animation states, synced as the current state
+/// and its normalized playback time next to the position. The client plays the state of the
+/// rendered snapshot, so a switch arriving after its render time is shown late and starts
+/// partway into the new state, which the client either snaps to or crossfades into.
+///
+/// Both sides record their state as "server animation state" and "client animation state",
+/// whose plots show how the client's state timeline lags and deviates from the server's.
+#[derive(Clone, Debug)]
+pub struct AnimationStates {
+    pos: Vector2<f32>,
+    /// State shown on this side
+    state: AnimState,
+    phase: f32,
+    policy: AnimationPolicy,
+    /// Seconds of a crossfade
+    crossfade_window: f32,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    pos_buffer: SnapshotBuffer<Vector2<f32>>,
+    /// Server time, state and playback time of received snapshots, oldest first
+    snapshots: VecDeque<(f32, AnimState, f32)>,
+    /// State faded out of and the client time the fade started
+    fade: Option<(AnimState, f32)>,
+    label: Option<String>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+}
+impl SimulationState for AnimationStates {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&AnimationMessage {
+            pos: self.pos,
+            state: self.state,
+            phase: self.phase,
+        })
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) != Delivery::Latest {
+            return;
+        }
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        let msg: AnimationMessage = codec.decode(msg);
+        let t = server_time.as_secs_f32();
+        self.pos_buffer.add(Key::new(t, msg.pos, Kernel::Linear));
+        self.snapshots.push_back((t, msg.state, msg.phase));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.label = None;
+        self.metrics.clear();
+        let start_time = self.start_time?;
+        let now = time.absolute_time().as_secs_f32();
+        let t = now - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        // the latest snapshot at or before the render time, or the oldest one if all are later
+        while self.snapshots.len() > 1 && self.snapshots[1].0 <= t {
+            self.snapshots.pop_front();
+        }
+        let (snapshot_time, state, phase) = *self.snapshots.front()?;
+        // playback continues from the snapshot's playback time
+        let phase = (phase + (t - snapshot_time).max(0.) / state.cycle()).fract();
+        if state != self.state {
+            let late = (t - snapshot_time).max(0.);
+            self.metrics.push(("late animation switch", late * 1000.));
+            self.metrics.push(("switch phase", phase));
+            if self.policy == AnimationPolicy::Crossfade {
+                self.fade = Some((self.state, now));
+            }
+            self.label = Some(format!(
+                "{} → {}{}",
+                self.state,
+                state,
+                if late > 0. {
+                    format!(" {:.0} ms late", late * 1000.)
+                } else {
+                    String::new()
+                }
+            ));
+            self.state = state;
+        }
+        self.phase = phase;
+        // during a crossfade the state with the larger weight is the one seen
+        let mut seen = self.state;
+        if let Some((from, fade_start)) = self.fade {
+            let weight = if self.crossfade_window > 0. {
+                (now - fade_start) / self.crossfade_window
+            } else {
+                1.
+            };
+            if weight >= 1. {
+                self.fade = None;
+            } else {
+                self.metrics.push(("crossfade weight", weight));
+                if weight < 0.5 {
+                    seen = from;
+                }
+            }
+        }
+        let (server_state, _) = AnimState::scripted(t);
+        self.metrics
+            .push(("client animation state", seen as u8 as f32));
+        self.metrics.push((
+            "animation mismatch",
+            if seen == server_state { 0. } else { 1. },
+        ));
+        let pos = self.pos_buffer.clamped_sample(t);
+        self.pos_buffer.prune_before(t);
+        pos.map(|pos| Sample::planar(pos, 1.))
+    }
+    fn buffered_bytes(&self) -> usize {
+        self.pos_buffer.memory_bytes()
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.label = None;
+        self.metrics.clear();
+        let t = time.absolute_time().as_secs_f32();
+        let (state, started) = AnimState::scripted(t);
+        if state != self.state {
+            self.label = Some(format!("{} → {}", self.state, state));
+            self.state = state;
+        }
+        self.phase = state.phase(t - started);
+        self.pos.x += state.speed() * time.delta_seconds();
+        self.metrics
+            .push(("server animation state", state as u8 as f32));
+        Sample::planar(self.pos, 1.)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, _side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        for (name, value) in self.metrics.iter() {
+            metrics.record(name, now, *value);
+        }
+    }
+}
+
+/// Seconds between the server's increments of the counter
+const SERVER_INCREMENT_INTERVAL: f32 = 0.3;
+/// Seconds between the client's increments of the counter
+const CLIENT_INCREMENT_INTERVAL: f32 = 0.45;
+/// Entity id of the last-write-wins counter
+const LWW_ENTITY: u32 = 1;
+
+/// State of both counters, sent by each side to the other in every update
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct CounterMessage {
+    /// Increments of the server and the client, merged by taking the larger count of each side
+    crdt: [u32; 2],
+    /// Value and time of the latest write, a newer write replaces the value
+    lww: (u32, f32),
+}
+
+#[derive(Default)]
+struct CounterCreator;
+impl fmt::Display for CounterCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Counter CRDT vs Last-Write-Wins")
+    }
+}
+impl SimulationBehaviour for CounterCreator {
+    fn new_state(&self, _settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(Counter {
+            side: SimSide::Server,
+            counters: CounterMessage {
+                crdt: [0, 0],
+                lww: (0, 0.),
+            },
+            converged: Vec::new(),
+            metrics: Vec::new(),
+        })
+    }
+    fn description(&self) -> String {
+        "A counter incremented concurrently by the server and the \
+client, kept both as a grow-only counter CRDT and as a last-write-wins register. The primary \
+entity's x position shows the CRDT value and the second entity's the last-write-wins \
+value.\n\n\
+The CRDT converges to the total once messages get through despite loss, while \
+last-write-wins permanently drops one of two increments written before either side saw the \
+other's, more often the higher the latency.\n\n\
+Relevant settings: min and max latency ms, loss percentage."
+            .into()
+    }
+}
+
+/// A counter incremented concurrently by the server and the client, kept both as a grow-only
+/// counter CRDT and as a naive last-write-wins register. Each side sends its state in every
+/// update, so the CRDT converges to the total once messages get through despite loss, while
+/// last-write-wins permanently drops one of two increments written before either side saw the
+/// other's.
+///
+/// The primary entity's x position shows the CRDT value and the second entity's the
+/// last-write-wins value.
+#[derive(Clone, Debug)]
+pub struct Counter {
+    /// Side this state runs on, known from its first update
+    side: SimSide,
+    counters: CounterMessage,
+    /// Seconds the other side's increments took to reach this side, since the last update
+    converged: Vec<f32>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+}
+impl Counter {
+    fn interval(side: SimSide) -> f32 {
+        match side {
+            SimSide::Server => SERVER_INCREMENT_INTERVAL,
+            SimSide::Client => CLIENT_INCREMENT_INTERVAL,
+        }
+    }
+    fn index(side: SimSide) -> usize {
+        match side {
+            SimSide::Server => 0,
+            SimSide::Client => 1,
+        }
+    }
+    /// Increments both sides made up to time `t`
+    fn total(t: f32) -> u32 {
+        (t / SERVER_INCREMENT_INTERVAL).floor() as u32
+            + (t / CLIENT_INCREMENT_INTERVAL).floor() as u32
+    }
+    fn value_pos(value: u32, y: f32) -> Vector2<f32> {
+        Vector2::new(100. + value as f32 * 8., y)
+    }
+    fn merge(&mut self, msg: CounterMessage, now: f32) {
+        let other = match self.side {
+            SimSide::Server => SimSide::Client,
+            SimSide::Client => SimSide::Server,
+        };
+        let idx = Self::index(other);
+        for k in self.counters.crdt[idx] + 1..=msg.crdt[idx] {
+            self.converged.push(now - k as f32 * Self::interval(other));
+        }
+        for (own, received) in self.counters.crdt.iter_mut().zip(msg.crdt.iter()) {
+            *own = (*own).max(*received);
+        }
+        if msg.lww.1 > self.counters.lww.1 {
+            self.counters.lww = msg.lww;
+        }
+    }
+    /// Makes the increments of this side due at time `t`
+    fn update(&mut self, side: SimSide, t: f32) -> Sample {
+        self.side = side;
+        let idx = Self::index(side);
+        let due = (t / Self::interval(side)).floor() as u32;
+        while self.counters.crdt[idx] < due {
+            self.counters.crdt[idx] += 1;
+            self.counters.lww = (self.counters.lww.0 + 1, t);
+        }
+        self.metrics.clear();
+        for seconds in self.converged.drain(..) {
+            self.metrics
+                .push(("crdt convergence time", seconds * 1000.));
+        }
+        let total = Self::total(t) as f32;
+        let crdt: u32 = self.counters.crdt.iter().sum();
+        let (crdt_error, lww_error) = match side {
+            SimSide::Server => ("server crdt error", "server lww error"),
+            SimSide::Client => ("client crdt error", "client lww error"),
+        };
+        self.metrics.push((crdt_error, total - crdt as f32));
+        self.metrics
+            .push((lww_error, total - self.counters.lww.0 as f32));
+        Sample::planar(Self::value_pos(crdt, 200.), 1.)
+    }
+    fn lww_sample(&self) -> Vec<(u32, Sample)> {
+        vec![(
+            LWW_ENTITY,
+            Sample::planar(Self::value_pos(self.counters.lww.0, 260.), 1.),
+        )]
+    }
+}
+impl SimulationState for Counter {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.counters)
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        _server_time: Duration,
+        _server_frame: u64,
+        _seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        // both merges are idempotent, so duplicated and reordered messages need no handling
+        self.side = SimSide::Client;
+        self.merge(codec.decode(msg), time.absolute_time().as_secs_f32());
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        Some(self.update(SimSide::Client, time.absolute_time().as_secs_f32()))
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.update(SimSide::Server, time.absolute_time().as_secs_f32())
+    }
+    fn record_metrics(&self, _side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        for (name, value) in self.metrics.iter() {
+            metrics.record(name, now, *value);
+        }
+    }
+    fn server_entities(&self) -> Vec<(u32, Sample)> {
+        self.lww_sample()
+    }
+    fn client_entities(&self) -> Vec<(u32, Sample)> {
+        self.lww_sample()
+    }
+    fn send_upstream(&self, _time: &Time) -> Option<Vec<u8>> {
+        Some(bincode::serialize(&self.counters).unwrap())
+    }
+    fn recv_upstream(&mut self, time: &Time, msg: &[u8]) {
+        self.side = SimSide::Server;
+        self.merge(
+            bincode::deserialize(msg).unwrap(),
+            time.absolute_time().as_secs_f32(),
+        );
+    }
+}
+
+/// Seconds between the spawns of the contested items
+const PICKUP_ROUND: f32 = 1.;
+/// Seconds into a round the item spawns and is removed
+const ITEM_SPAWN: f32 = 0.2;
+const ITEM_REMOVAL: f32 = 0.9;
+/// Seconds the client takes to react to a rendered item
+const CLIENT_REACTION: f32 = 0.1;
+/// Range of seconds after the spawn the scripted rival's pickup request reaches the server
+const RIVAL_REACTION: (f32, f32) = (0.12, 0.3);
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+enum Picker {
+    Client,
+    Rival,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+struct PickupMessage {
+    round: u32,
+    /// Whether the round's item has spawned and not been removed yet
+    present: bool,
+    owner: Option<Picker>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct PickupRequest {
+    round: u32,
+}
+
+#[derive(Default)]
+struct ContestedPickupCreator;
+impl fmt::Display for ContestedPickupCreator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Contested Pickup Thin Client")
+    }
+}
+impl SimulationBehaviour for ContestedPickupCreator {
+    fn new_state(&self, settings: &SimSettings) -> Box<dyn SimulationState> {
+        Box::new(ContestedPickup {
+            item: PickupMessage {
+                round: 0,
+                present: false,
+                owner: None,
+            },
+            requests: Vec::new(),
+            delay: settings.render_interpolation_delay,
+            start_time: None,
+            sequence: SequencedReceiver::new(),
+            snapshots: VecDeque::new(),
+            press: None,
+            request: None,
+            resolved: None,
+            label: None,
+            metrics: Vec::new(),
+            rng: SimRng::new(settings.seed),
+        })
+    }
+    fn description(&self) -> String {
+        "The client and a scripted rival on the server race to \
+pick up an item spawning every round, and the server gives it to whichever request it \
+handles first.\n\n\
+The client predicts its own pickup as soon as it presses, so when the rival wins the race \
+the client shows the item in its inventory until the server's decision revokes it. The \
+higher the latency, the more races the rival wins and the longer revoked pickups are \
+shown.\n\n\
+Relevant settings: min and max latency ms, render interpolation delay ms."
+            .into()
+    }
+}
+
+/// The client and a scripted rival on the server race to pick up an item spawning every
+/// round. The server gives the item to whichever request it handles first. The client predicts
+/// its own pickup as soon as it presses, so when the rival's request wins the race the client
+/// shows the item in its inventory until the server's decision revokes it. The higher the
+/// latency, the more races the rival wins and the longer revoked pickups are shown.
+#[derive(Clone, Debug)]
+pub struct ContestedPickup {
+    /// Item of the current round as known to this side
+    item: PickupMessage,
+    /// Rounds the client requested since the last `update_server`
+    requests: Vec<u32>,
+    delay: f32,
+    start_time: Option<f32>,
+    sequence: SequencedReceiver,
+    /// Server time and item of received snapshots, oldest first
+    snapshots: VecDeque<(f32, PickupMessage)>,
+    /// Round the client will press pickup for and the client time of the press
+    press: Option<(u32, f32)>,
+    /// Round the client requested and the client time of the press, until resolved
+    request: Option<(u32, f32)>,
+    /// Latest round whose outcome the client has shown
+    resolved: Option<u32>,
+    label: Option<String>,
+    /// Metrics of the latest update, recorded in `record_metrics`
+    metrics: Vec<(&'static str, f32)>,
+    rng: SimRng,
+}
+impl ContestedPickup {
+    fn item_pos(owner: Option<Picker>) -> Vector2<f32> {
+        match owner {
+            None => Vector2::new(300., 200.),
+            Some(Picker::Client) => Vector2::new(100., 100.),
+            Some(Picker::Rival) => Vector2::new(500., 100.),
+        }
+    }
+    fn sample(owner: Option<Picker>, present: bool) -> Sample {
+        Sample::planar(
+            Self::item_pos(owner),
+            if present || owner.is_some() { 1. } else { 0. },
+        )
+    }
+    /// Server time the rival's request for `round` arrives
+    fn rival_time(&self, round: u32) -> f32 {
+        let reaction: f32 = self
+            .rng
+            .entity_stream("rival reaction", round)
+            .gen_range(RIVAL_REACTION.0, RIVAL_REACTION.1);
+        round as f32 * PICKUP_ROUND + ITEM_SPAWN + reaction
+    }
+}
+impl SimulationState for ContestedPickup {
+    fn send_sync(&self, _time: &Time, codec: SyncCodec) -> Vec<u8> {
+        codec.encode(&self.item)
+    }
+    fn recv_sync(
+        &mut self,
+        time: &Time,
+        server_time: Duration,
+        _server_frame: u64,
+        seq: u16,
+        msg: &Vec<u8>,
+        codec: SyncCodec,
+    ) {
+        if self.sequence.recv(seq) != Delivery::Latest {
+            return;
+        }
+        if let None = self.start_time {
+            self.start_time = Some(time.absolute_time().as_secs_f32());
+        }
+        self.snapshots
+            .push_back((server_time.as_secs_f32(), codec.decode(msg)));
+    }
+    fn update_render(&mut self, time: &Time) -> Option<Sample> {
+        self.label = None;
+        self.metrics.clear();
+        let start_time = self.start_time?;
+        let now = time.absolute_time().as_secs_f32();
+        let t = now - (self.delay / 1000.);
+        if t < start_time {
+            return None;
+        }
+        while self.snapshots.len() > 1 && self.snapshots[1].0 <= t {
+            self.snapshots.pop_front();
+        }
+        let (_, item) = *self.snapshots.front()?;
+        // the server's decision becomes visible with the rendered snapshot carrying it
+        if let Some((round, _)) = self.request {
+            // the decision of a request still in flight when its round ended was never seen
+            if item.round > round {
+                self.request = None;
+            }
+        }
+        if let (Some((round, press_time)), Some(owner)) = (self.request, item.owner) {
+            if item.round == round {
+                self.request = None;
+                self.resolved = Some(round);
+                let shown = (now - press_time) * 1000.;
+                if owner == Picker::Client {
+                    self.metrics.push(("pickup revoked", 0.));
+                    self.metrics.push(("pickup confirmation", shown));
+                } else {
+                    self.metrics.push(("pickup revoked", 1.));
+                    self.metrics.push(("revoked pickup shown", shown));
+                    self.label = Some(format!("pickup {} revoked after {:.0} ms", round, shown));
+                }
+            }
+        }
+        // the client reacts to an item it sees on the ground and hasn't gone for yet
+        let pending = |round: u32| {
+            self.resolved.map(|r| r >= round).unwrap_or(false)
+                || self.request.map(|(r, _)| r == round).unwrap_or(false)
+        };
+        if item.present && item.owner.is_none() && !pending(item.round) {
+            match self.press {
+                Some((round, press_time)) if round == item.round => {
+                    if now >= press_time {
+                        self.press = None;
+                        self.request = Some((round, now));
+                        self.label = Some(format!("pressed pickup {}", round));
+                    }
+                }
+                _ => self.press = Some((item.round, now + CLIENT_REACTION)),
+            }
+        }
+        // the client's own pickup is predicted until the server decides
+        let predicted = self
+            .request
+            .filter(|(round, _)| *round == item.round)
+            .map(|_| Picker::Client);
+        Some(Self::sample(predicted.or(item.owner), item.present))
+    }
+    fn update_server(&mut self, time: &Time) -> Sample {
+        self.label = None;
+        self.metrics.clear();
+        let t = time.absolute_time().as_secs_f32();
+        let round = (t / PICKUP_ROUND).floor() as u32;
+        let round_time = t - round as f32 * PICKUP_ROUND;
+        if round != self.item.round {
+            self.item.round = round;
+            self.item.owner = None;
+        }
+        self.item.present = round_time >= ITEM_SPAWN && round_time < ITEM_REMOVAL;
+        if self.item.present && self.item.owner.is_none() {
+            // requests received this frame were handled before the rival's due this frame
+            let client = self.requests.iter().any(|r| *r == round);
+            if client {
+                self.item.owner = Some(Picker::Client);
+            } else if t >= self.rival_time(round) {
+                self.item.owner = Some(Picker::Rival);
+            }
+            if let Some(owner) = self.item.owner {
+                self.metrics
+                    .push(("pickups won", if owner == Picker::Client { 1. } else { 0. }));
+                self.label = Some(format!("item {} to {:?}", round, owner));
+            }
+        }
+        self.requests.clear();
+        Self::sample(self.item.owner, self.item.present)
+    }
+    fn frame_label(&self, _side: SimSide) -> Option<String> {
+        self.label.clone()
+    }
+    fn record_metrics(&self, _side: SimSide, time: &Time, metrics: &mut Metrics) {
+        let now = time.absolute_time().as_secs_f32();
+        for (name, value) in self.metrics.iter() {
+            metrics.record(name, now, *value);
+        }
+    }
+    fn send_upstream(&self, _time: &Time) -> Option<Vec<u8>> {
+        // resent every frame until the decision arrives
+        self.request
+            .map(|(round, _)| bincode::serialize(&PickupRequest { round }).unwrap())
+    }
+    fn recv_upstream(&mut self, _time: &Time, msg: &[u8]) {
+        let msg: PickupRequest = bincode::deserialize(msg).unwrap();
+        self.requests.push(msg.round);
+    }
+}
+
+sync_interpolate! {
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+    pub struct InputPosVel {
+        #[hold] input_dir: Vector2<f32>,
+        #[lerp] pos: Vector2<f32>,
+        #[hold] velocity: Vector2<f32>,
+        #[hold] speed: f32,
+    }
+}
+impl Default for InputPosVel {
+    fn default() -> Self {
+        Self {
+            input_dir: math::zero(),
+            pos: math::zero(),
+            velocity: math::zero(),
+            speed: 100.,
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PlayerCharacterDeterministic {
+    state: InputPosVel,
+    server: bool,
+}
+impl Clone for PlayerCharacterDeterministic {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state,
+            server: self.server,
+        }
+    }
+    fn clone_from(&mut self, source: &Self) {
+        self.state = source.state;
+    }
+}
+impl fmt::Display for PlayerCharacterDeterministic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Player Character Server-Rate")
+    }
+}
+
+impl DeterministicSimulation for PlayerCharacterDeterministic {
+    type SyncType = InputPosVel;
+    fn description(&self) -> &'static str {
+        "A character walking along a scripted path of inputs. \
+The client doesn't know the server's inputs ahead of time and predicts with the last \
+received input held, so it overshoots every change of direction until the correction \
+arrives."
+    }
+    fn send_state(&self) -> &Self::SyncType {
+        &self.state
+    }
+    fn recv_state(&mut self, val: Self::SyncType) {
+        self.state = val;
+    }
+    fn update(&mut self, abs_time: Duration, delta_time: Duration) {
+        if self.server {
+            self.state.input_dir = PLAYER_INPUT_DIR
+                .clamped_sample(abs_time.as_secs_f32())
+                .unwrap();
+        }
+        self.state.velocity = self.state.input_dir * self.state.speed;
+        self.state.pos += self.state.velocity * delta_time.as_secs_f32();
+    }
+    fn pos_sample(&self, state: &Self::SyncType) -> Sample {
+        Sample::planar(state.pos, 1.)
+    }
+    fn initial(_settings: &SimSettings) -> Self {
+        Self {
+            server: true,
+            ..Default::default()
+        }
+    }
+    fn apply_event(&mut self, event: &ScenarioEvent) {
+        match *event {
+            ScenarioEvent::ScaleVelocity(scale) => self.state.speed *= scale,
+            _ => event.apply(&mut self.state.pos, &mut self.state.velocity),
+        }
+    }
+}
+
+/// Acceleration of the vehicle at full throttle
+const VEHICLE_ACCELERATION: f32 = 600.;
+/// Fraction of its speed the vehicle loses per second, limiting its top speed
+const VEHICLE_DRAG: f32 = 0.8;
+/// Turn rate at full steering once the vehicle is at `VEHICLE_TURN_SPEED`
+const VEHICLE_YAW_RATE: f32 = 2.5;
+const VEHICLE_TURN_SPEED: f32 = 300.;
+/// How fast the vehicle's velocity follows its heading, low values drift more
+const VEHICLE_GRIP: f32 = 3.;
+/// Seconds after which the scripted vehicle input repeats
+const VEHICLE_INPUT_LOOP: f32 = 4.;
+
+sync_interpolate! {
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+    pub struct VehicleState {
+        #[hold] throttle: f32,
+        #[hold] steering: f32,
+        #[lerp] pos: Vector2<f32>,
+        #[hold] velocity: Vector2<f32>,
+        #[slerp] heading: f32,
+        #[lerp] speed: f32,
+    }
+}
+impl Default for VehicleState {
+    fn default() -> Self {
+        Self {
+            throttle: 0.,
+            steering: 0.,
+            pos: math::zero(),
+            velocity: math::zero(),
+            heading: 0.,
+            speed: 0.,
+        }
+    }
+}
+
+/// Car driving a scripted loop of throttle and steering inputs. Its speed and momentum make a
+/// client predicting with held inputs overshoot much further than a walking character would,
+/// and corrections snap further.
+#[derive(Debug, Default)]
+pub struct VehicleDeterministic {
+    state: VehicleState,
+    server: bool,
+}
+impl Clone for VehicleDeterministic {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state,
+            server: self.server,
+        }
+    }
+    fn clone_from(&mut self, source: &Self) {
+        self.state = source.state;
+    }
+}
+impl fmt::Display for VehicleDeterministic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Vehicle Server-Rate")
+    }
+}
+
+impl DeterministicSimulation for VehicleDeterministic {
+    type SyncType = VehicleState;
+    fn description(&self) -> &'static str {
+        "A car driving a scripted loop of throttle and steering \
+inputs. Its speed and momentum make a client predicting with held inputs overshoot much \
+further than the walking character does, and corrections snap further."
+    }
+    fn send_state(&self) -> &Self::SyncType {
+        &self.state
+    }
+    fn recv_state(&mut self, val: Self::SyncType) {
+        self.state = val;
+    }
+    fn update(&mut self, abs_time: Duration, delta_time: Duration) {
+        let dt = delta_time.as_secs_f32();
+        let state = &mut self.state;
+        if self.server {
+            let input = VEHICLE_INPUT
+                .clamped_sample(abs_time.as_secs_f32() % VEHICLE_INPUT_LOOP)
+                .unwrap();
+            state.throttle = input.x;
+            state.steering = input.y;
+        }
+        state.speed += (state.throttle * VEHICLE_ACCELERATION - VEHICLE_DRAG * state.speed) * dt;
+        state.heading +=
+            state.steering * VEHICLE_YAW_RATE * (state.speed / VEHICLE_TURN_SPEED).min(1.) * dt;
+        // the velocity lags behind the heading, so the vehicle slides through turns
+        let target = Vector2::new(state.heading.cos(), state.heading.sin()) * state.speed;
+        state.velocity += (target - state.velocity) * (VEHICLE_GRIP * dt).min(1.);
+        state.pos += state.velocity * dt;
+    }
+    fn pos_sample(&self, state: &Self::SyncType) -> Sample {
+        Sample::planar(state.pos, 1.)
+    }
+    fn initial(_settings: &SimSettings) -> Self {
+        Self {
+            server: true,
+            ..Default::default()
+        }
+    }
+    fn apply_event(&mut self, event: &ScenarioEvent) {
+        match *event {
+            ScenarioEvent::ScaleVelocity(scale) => {
+                self.state.speed *= scale;
+                self.state.velocity *= scale;
+            }
+            _ => event.apply(&mut self.state.pos, &mut self.state.velocity),
+        }
+    }
+}
+
+/// Seconds between the starts of two jumps
+const JUMP_INTERVAL: f32 = 1.;
+/// Upward speed at the start of a jump
+const JUMP_SPEED: f32 = 400.;
+const JUMP_GRAVITY: f32 = 1000.;
+/// Ground speed of the jumping character
+const JUMP_RUN_SPEED: f32 = 150.;
+/// Turn rate of the jumping character in radians per second, so it runs in circles
+const JUMP_TURN_RATE: f32 = 1.;
+
+sync_interpolate! {
+    #[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+    pub struct JumpState {
+        #[lerp] pos: Vector3<f32>,
+        #[hold] velocity: Vector3<f32>,
+    }
+}
+impl Default for JumpState {
+    fn default() -> Self {
+        Self {
+            pos: math::zero(),
+            velocity: Vector3::new(JUMP_RUN_SPEED, 0., 0.),
+        }
+    }
+}
+
+/// Character running in circles and jumping every `JUMP_INTERVAL`. Snapshots interpolated
+/// linearly cut the parabola of each jump into straight segments, flattening its apex and
+/// clipping it at the sync rate, which the 3d view shows from the side.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct JumpingDeterministic {
+    state: JumpState,
+}
+impl fmt::Display for JumpingDeterministic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Jumping Server-Rate")
+    }
+}
+impl DeterministicSimulation for JumpingDeterministic {
+    type SyncType = JumpState;
+    fn description(&self) -> &'static str {
+        "A character running in circles and jumping at a fixed \
+interval. Snapshots interpolated linearly cut the parabola of each jump into straight \
+segments, flattening and clipping its apex at the sync rate, which the 3d view shows from \
+the side."
+    }
+    fn send_state(&self) -> &Self::SyncType {
+        &self.state
+    }
+    fn recv_state(&mut self, val: Self::SyncType) {
+        self.state = val;
+    }
+    fn update(&mut self, abs_time: Duration, delta_time: Duration) {
+        let dt = delta_time.as_secs_f32();
+        let t = abs_time.as_secs_f32();
+        let state = &mut self.state;
+        // jumps are scheduled by time, so a predicting client jumps when the server does
+        let jumped = (t / JUMP_INTERVAL).floor() > ((t - dt) / JUMP_INTERVAL).floor();
+        if jumped && state.pos.z <= 0. {
+            state.velocity.z = JUMP_SPEED;
+        }
+        state.velocity.z -= JUMP_GRAVITY * dt;
+        let (sin, cos) = (JUMP_TURN_RATE * dt).sin_cos();
+        let (x, y) = (state.velocity.x, state.velocity.y);
+        state.velocity.x = x * cos - y * sin;
+        state.velocity.y = x * sin + y * cos;
+        state.pos += state.velocity * dt;
+        if state.pos.z <= 0. {
+            state.pos.z = 0.;
+            state.velocity.z = 0.;
+        }
+    }
+    fn pos_sample(&self, state: &Self::SyncType) -> Sample {
+        Sample {
+            pos: state.pos,
+            alpha: 1.,
+        }
+    }
+    fn initial(_settings: &SimSettings) -> Self {
+        Self::default()
+    }
+    fn apply_event(&mut self, event: &ScenarioEvent) {
+        let state = &mut self.state;
+        let mut pos = state.pos.xy();
+        let mut velocity = state.velocity.xy();
+        event.apply(&mut pos, &mut velocity);
+        state.pos = Vector3::new(pos.x, pos.y, state.pos.z);
+        state.velocity = Vector3::new(velocity.x, velocity.y, state.velocity.z);
+    }
+}
+
+fn sine_wave(delta_time: Duration, abs_time: Duration) -> Vector2<f32> {
+    Vector2::new(0., 1.)
+        * (abs_time.as_secs_f32() * 20.).sin()
+        * 300 as f32
+        * delta_time.as_secs_f32()
+}
+/// Closed form of the motion integrated from `sine_wave` accelerations, starting from the
+/// default `PosVel` at time 0, with the scripted events applied at their exact times
+fn sine_wave_truth(events: &[TimedEvent]) -> GroundTruth {
+    // velocity and position gained from the accelerations alone since time 0
+    fn gained_velocity(t: f32) -> Vector2<f32> {
+        Vector2::new(0., 15. * (1. - (t * 20.).cos()))
+    }
+    fn gained_position(t: f32) -> Vector2<f32> {
+        Vector2::new(0., 15. * t - 0.75 * (t * 20.).sin())
+    }
+    fn advance(state: &mut PosVel, from: f32, to: f32) {
+        state.pos += state.velocity * (to - from) + gained_position(to)
+            - gained_position(from)
+            - gained_velocity(from) * (to - from);
+        state.velocity += gained_velocity(to) - gained_velocity(from);
+    }
+    let mut events = events.to_vec();
+    events.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    Box::new(move |t| {
+        let (mut from, mut state) = (0., PosVel::default());
+        for event in events.iter().take_while(|e| e.time <= t) {
+            advance(&mut state, from, event.time);
+            state.apply_event(&event.event);
+            from = event.time;
+        }
+        advance(&mut state, from, t);
+        Vector3::new(state.pos.x, state.pos.y, 0.)
+    })
+}
+
+macro_rules! spline_key {
+    ( $time: expr => $x: expr , $y: expr ) => {{
+        Key::new($time, Vector2::new($x, $y), Kernel::Linear)
+    }};
+}
+
+lazy_static! {
+    pub static ref PLAYER_INPUT_DIR: SnapshotBuffer<Vector2<f32>> = SnapshotBuffer::from_vec(vec![
+        spline_key!(0. => 0., 0.),
+        spline_key!(0.3 => 1., 0.),
+        spline_key!(0.5 => 0., 0.),
+        spline_key!(0.7 => 0., 1.),
+        spline_key!(1. => 0., 0.),
+        spline_key!(1.5 => -1., 0.),
+        spline_key!(2.0 => 0., 0.),
+    ]);
+    /// Throttle (x, negative brakes) and steering (y) of the vehicle
+    static ref VEHICLE_INPUT: SnapshotBuffer<Vector2<f32>> = SnapshotBuffer::from_vec(vec![
+        spline_key!(0. => 1., 0.),
+        spline_key!(1. => 1., 0.),
+        spline_key!(1.2 => 1., 1.),
+        spline_key!(1.8 => 0.5, 1.),
+        spline_key!(2.0 => -1., 0.),
+        spline_key!(2.4 => 1., -1.),
+        spline_key!(3.2 => 1., -1.),
+        spline_key!(3.4 => 1., 0.),
+        spline_key!(4.0 => 1., 0.),
+    ]);
+}
+
+pub fn behaviour_by_name(name: &str) -> Option<Arc<dyn SimulationBehaviour>> {
+    SIM_BEHAVIOURS
+        .iter()
+        .find(|(_, display_name)| display_name.to_str() == Ok(name))
+        .map(|(behaviour, _)| behaviour.clone())
+}
+
+lazy_static! {
+    pub static ref SIM_BEHAVIOURS: Vec<(Arc<dyn SimulationBehaviour>, std::ffi::CString)> = vec![
+        behaviour_data::<SineWaveClientSim>(),
+        behaviour_data::<ServerRatePlayback<SineWaveClientSim>>(),
+        behaviour_data::<ServerRateSimulation<SineWaveDeterministicSim>>(),
+        behaviour_data::<SineWaveThinClientCreator>(),
+        behaviour_data::<SineWaveThinClientServerTime>(),
+        behaviour_data::<SineWavePureFunctionCreator>(),
+        behaviour_data::<ServerRateSimulation<PlayerCharacterDeterministic>>(),
+        behaviour_data::<ServerRateSimulation<VehicleDeterministic>>(),
+        behaviour_data::<ServerRateSimulation<JumpingDeterministic>>(),
+        behaviour_data::<ComposedPipeline<SineWaveDeterministicSim>>(),
+        behaviour_data::<ComposedPipeline<PlayerCharacterDeterministic>>(),
+        behaviour_data::<ComposedPipeline<VehicleDeterministic>>(),
+        behaviour_data::<ComposedPipeline<JumpingDeterministic>>(),
+        behaviour_data::<SpawningEntitiesCreator>(),
+        behaviour_data::<OwnershipTransferCreator>(),
+        behaviour_data::<SplitAuthorityCreator>(),
+        behaviour_data::<HitRegistrationCreator>(),
+        behaviour_data::<DoorInteractionCreator>(),
+        behaviour_data::<ProjectilesCreator>(),
+        behaviour_data::<AnimationStatesCreator>(),
+        behaviour_data::<CounterCreator>(),
+        behaviour_data::<ContestedPickupCreator>(),
+    ];
 }
@@ -0,0 +1,414 @@
+use amethyst::core::math::{UnitQuaternion, Vector2, Vector3};
+use serde::{Deserialize, Serialize};
+use std::{collections::VecDeque, f32::consts::PI, fmt, ops::BitOr};
+
+/// Values that can be blended between two snapshots.
+pub trait Interpolate: Clone {
+    /// Linear interpolation.
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self;
+}
+impl Interpolate for f32 {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        a + (b - a) * t
+    }
+}
+impl Interpolate for Vector2<f32> {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        *a + (*b - *a) * t
+    }
+}
+impl Interpolate for Vector3<f32> {
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        *a + (*b - *a) * t
+    }
+}
+
+/// Spherical/angular interpolation along the shortest arc.
+pub trait Slerp {
+    fn slerp(a: &Self, b: &Self, t: f32) -> Self;
+}
+/// Angles in radians
+impl Slerp for f32 {
+    fn slerp(a: &Self, b: &Self, t: f32) -> Self {
+        let mut delta = (b - a) % (2. * PI);
+        if delta > PI {
+            delta -= 2. * PI;
+        } else if delta < -PI {
+            delta += 2. * PI;
+        }
+        a + delta * t
+    }
+}
+impl Slerp for UnitQuaternion<f32> {
+    fn slerp(a: &Self, b: &Self, t: f32) -> Self {
+        a.slerp(b, t)
+    }
+}
+
+/// Declares a sync state struct and implements `Interpolate` for it from per-field annotations:
+/// `#[lerp]` blends with `Interpolate`, `#[slerp]` blends with `Slerp` and `#[hold]` keeps the
+/// value of the earlier snapshot.
+///
+/// ```ignore
+/// sync_interpolate! {
+///     #[derive(Copy, Clone, Serialize, Deserialize, Debug)]
+///     pub struct PosVel {
+///         #[lerp] pos: Vector2<f32>,
+///         #[hold] velocity: Vector2<f32>,
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! sync_interpolate {
+    (
+        $(#[$meta:meta])*
+        $vis:vis struct $name:ident {
+            $( #[$mode:ident] $field_vis:vis $field:ident : $ty:ty ),* $(,)?
+        }
+    ) => {
+        $(#[$meta])*
+        $vis struct $name {
+            $( $field_vis $field: $ty ),*
+        }
+        impl $crate::snapshot_buffer::Interpolate for $name {
+            fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+                Self {
+                    $( $field: $crate::sync_interpolate!(@field $mode, a.$field, b.$field, t) ),*
+                }
+            }
+        }
+    };
+    (@field lerp, $a:expr, $b:expr, $t:expr) => {
+        $crate::snapshot_buffer::Interpolate::lerp(&$a, &$b, $t)
+    };
+    (@field slerp, $a:expr, $b:expr, $t:expr) => {
+        $crate::snapshot_buffer::Slerp::slerp(&$a, &$b, $t)
+    };
+    (@field hold, $a:expr, $b:expr, $t:expr) => {
+        $a.clone()
+    };
+}
+
+/// How the segment starting at a key is interpolated towards the next key.
+pub enum Kernel<T> {
+    /// Hold the value of the key until the next key.
+    Step,
+    /// Blend linearly using `Interpolate::lerp`.
+    Linear,
+    /// User-provided blend function, called with the normalized segment time.
+    Custom(fn(&T, &T, f32) -> T),
+}
+impl<T> Clone for Kernel<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+impl<T> Copy for Kernel<T> {}
+impl<T> fmt::Debug for Kernel<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Kernel::Step => write!(f, "Step"),
+            Kernel::Linear => write!(f, "Linear"),
+            Kernel::Custom(_) => write!(f, "Custom"),
+        }
+    }
+}
+impl<T: Interpolate> Kernel<T> {
+    fn interpolate(&self, a: &T, b: &T, t: f32) -> T {
+        match self {
+            Kernel::Step => a.clone(),
+            Kernel::Linear => T::lerp(a, b, t),
+            Kernel::Custom(f) => f(a, b, t),
+        }
+    }
+}
+
+/// Metadata of a key sent along with its state, overriding how the segment leading to the key
+/// is interpolated.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyFlags(pub u8);
+impl KeyFlags {
+    /// The key follows a discontinuity such as a teleport, so the segment leading to it holds
+    /// the previous key instead of blending through positions the entity never had.
+    pub const DISCONTINUITY: KeyFlags = KeyFlags(1);
+    pub fn contains(self, flags: KeyFlags) -> bool {
+        self.0 & flags.0 == flags.0
+    }
+    pub fn is_empty(self) -> bool {
+        self.0 == 0
+    }
+}
+impl BitOr for KeyFlags {
+    type Output = KeyFlags;
+    fn bitor(self, other: KeyFlags) -> KeyFlags {
+        KeyFlags(self.0 | other.0)
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Key<T> {
+    pub t: f32,
+    pub value: T,
+    pub kernel: Kernel<T>,
+    pub flags: KeyFlags,
+}
+impl<T> Key<T> {
+    pub fn new(t: f32, value: T, kernel: Kernel<T>) -> Self {
+        Self {
+            t,
+            value,
+            kernel,
+            flags: KeyFlags::default(),
+        }
+    }
+    pub fn with_flags(self, flags: KeyFlags) -> Self {
+        Self { flags, ..self }
+    }
+    /// Kernel of the segment from this key to `next`
+    pub fn kernel_to(&self, next: &Key<T>) -> Kernel<T> {
+        if next.flags.contains(KeyFlags::DISCONTINUITY) {
+            Kernel::Step
+        } else {
+            self.kernel
+        }
+    }
+}
+
+/// Time-ordered ring buffer of snapshots.
+///
+/// Keys are almost always appended in order, which is O(1). Sampling uses a binary search,
+/// and old keys are dropped either by an explicit `prune_before` or, when a capacity is set,
+/// by evicting the oldest key on insert.
+#[derive(Clone, Debug)]
+pub struct SnapshotBuffer<T> {
+    keys: VecDeque<Key<T>>,
+    capacity: Option<usize>,
+}
+impl<T: Interpolate> Default for SnapshotBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+impl<T: Interpolate> SnapshotBuffer<T> {
+    pub fn new() -> Self {
+        Self {
+            keys: VecDeque::new(),
+            capacity: None,
+        }
+    }
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            keys: VecDeque::with_capacity(capacity),
+            capacity: Some(capacity),
+        }
+    }
+    pub fn from_vec(keys: Vec<Key<T>>) -> Self {
+        let mut buffer = Self::new();
+        for key in keys {
+            buffer.add(key);
+        }
+        buffer
+    }
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+    pub fn get(&self, idx: usize) -> Option<&Key<T>> {
+        self.keys.get(idx)
+    }
+    pub fn first(&self) -> Option<&Key<T>> {
+        self.keys.front()
+    }
+    pub fn last(&self) -> Option<&Key<T>> {
+        self.keys.back()
+    }
+    pub fn iter(&self) -> impl Iterator<Item = &Key<T>> {
+        self.keys.iter()
+    }
+    pub fn clear(&mut self) {
+        self.keys.clear();
+    }
+    /// Heap bytes of the keys, not counting memory the values themselves point to.
+    pub fn memory_bytes(&self) -> usize {
+        self.keys.capacity() * std::mem::size_of::<Key<T>>()
+    }
+    /// Index of the first key with a time strictly greater than `t`
+    fn upper_bound(&self, t: f32) -> usize {
+        let (mut lo, mut hi) = (0, self.keys.len());
+        while lo < hi {
+            let mid = (lo + hi) / 2;
+            if self.keys[mid].t <= t {
+                lo = mid + 1;
+            } else {
+                hi = mid;
+            }
+        }
+        lo
+    }
+    pub fn add(&mut self, key: Key<T>) {
+        if let Some(capacity) = self.capacity {
+            if capacity > 0 && self.keys.len() >= capacity {
+                self.keys.pop_front();
+            }
+        }
+        // fast path for the common case of keys arriving in order
+        if self.keys.back().map(|k| k.t <= key.t).unwrap_or(true) {
+            self.keys.push_back(key);
+        } else {
+            let idx = self.upper_bound(key.t);
+            self.keys.insert(idx, key);
+        }
+    }
+    /// The keys surrounding `t` and the normalized time between them, None if `t` is outside
+    /// the buffered range. `t` equal to the last key's time yields the last key twice.
+    pub fn segment(&self, t: f32) -> Option<(&Key<T>, &Key<T>, f32)> {
+        let idx = self.upper_bound(t);
+        if idx == 0 {
+            return None;
+        }
+        let a = &self.keys[idx - 1];
+        if idx == self.keys.len() {
+            return if a.t == t { Some((a, a, 0.)) } else { None };
+        }
+        let b = &self.keys[idx];
+        let span = b.t - a.t;
+        let alpha = if span > 0. { (t - a.t) / span } else { 0. };
+        Some((a, b, alpha))
+    }
+    /// Samples the buffer at `t`, returning None if `t` is outside the buffered range.
+    pub fn sample(&self, t: f32) -> Option<T> {
+        self.segment(t)
+            .map(|(a, b, alpha)| a.kernel_to(b).interpolate(&a.value, &b.value, alpha))
+    }
+    /// Samples the buffer at `t`, holding the first or last key when `t` is out of range.
+    pub fn clamped_sample(&self, t: f32) -> Option<T> {
+        let first = self.keys.front()?;
+        let last = self.keys.back()?;
+        if t <= first.t {
+            Some(first.value.clone())
+        } else if t >= last.t {
+            Some(last.value.clone())
+        } else {
+            self.sample(t)
+        }
+    }
+    /// Drops keys that can no longer contribute to a sample at or after `t`.
+    pub fn prune_before(&mut self, t: f32) {
+        while self.keys.len() >= 2 && self.keys[1].t <= t {
+            self.keys.pop_front();
+        }
+    }
+    /// Drops every key at or after `t`.
+    pub fn truncate_from(&mut self, t: f32) {
+        while self.keys.back().map(|k| k.t >= t).unwrap_or(false) {
+            self.keys.pop_back();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Buffer of linearly blended keys added in the order given
+    fn buffer(keys: &[(f32, f32)]) -> SnapshotBuffer<f32> {
+        let mut buffer = SnapshotBuffer::new();
+        for (t, value) in keys {
+            buffer.add(Key::new(*t, *value, Kernel::Linear));
+        }
+        buffer
+    }
+    fn times(buffer: &SnapshotBuffer<f32>) -> Vec<f32> {
+        buffer.iter().map(|k| k.t).collect()
+    }
+
+    #[test]
+    fn keeps_keys_in_time_order() {
+        let mut buffer = buffer(&[(0., 0.), (2., 20.), (1., 10.), (3., 30.), (-1., -10.)]);
+        assert_eq!(times(&buffer), vec![-1., 0., 1., 2., 3.]);
+        assert_eq!(buffer.upper_bound(-2.), 0);
+        assert_eq!(buffer.upper_bound(1.), 3);
+        assert_eq!(buffer.upper_bound(1.5), 3);
+        assert_eq!(buffer.upper_bound(3.), 5);
+        // a key at an existing time goes after the keys already there
+        buffer.add(Key::new(1., 11., Kernel::Linear));
+        let values: Vec<f32> = buffer.iter().map(|k| k.value).collect();
+        assert_eq!(values, vec![-10., 0., 10., 11., 20., 30.]);
+    }
+
+    #[test]
+    fn evicts_the_oldest_key_at_capacity() {
+        let mut buffer = SnapshotBuffer::with_capacity(3);
+        for t in 0..5 {
+            buffer.add(Key::new(t as f32, t as f32, Kernel::Linear));
+        }
+        assert_eq!(times(&buffer), vec![2., 3., 4.]);
+    }
+
+    #[test]
+    fn samples_at_and_between_keys() {
+        let buffer = buffer(&[(0., 0.), (1., 10.), (2., 30.)]);
+        assert_eq!(buffer.sample(0.), Some(0.));
+        assert_eq!(buffer.sample(0.5), Some(5.));
+        assert_eq!(buffer.sample(1.), Some(10.));
+        assert_eq!(buffer.sample(1.25), Some(15.));
+        // the last key's own time is still inside the buffered range
+        let (a, b, alpha) = buffer.segment(2.).unwrap();
+        assert_eq!((a.t, b.t, alpha), (2., 2., 0.));
+        assert_eq!(buffer.sample(2.), Some(30.));
+        assert_eq!(buffer.sample(-0.1), None);
+        assert_eq!(buffer.sample(2.1), None);
+        assert_eq!(SnapshotBuffer::<f32>::new().sample(0.), None);
+    }
+
+    #[test]
+    fn samples_with_the_kernel_of_each_segment() {
+        let mut buffer = SnapshotBuffer::new();
+        buffer.add(Key::new(0., 0., Kernel::Step));
+        buffer.add(Key::new(
+            1.,
+            10.,
+            Kernel::Custom(|a, b, t| a + (b - a) * t * t),
+        ));
+        buffer.add(Key::new(2., 20., Kernel::Linear));
+        buffer.add(Key::new(3., 100., Kernel::Linear).with_flags(KeyFlags::DISCONTINUITY));
+        assert_eq!(buffer.sample(0.5), Some(0.));
+        assert_eq!(buffer.sample(1.5), Some(12.5));
+        // the segment leading to a discontinuity holds instead of blending
+        assert_eq!(buffer.sample(2.5), Some(20.));
+        assert_eq!(buffer.sample(3.), Some(100.));
+    }
+
+    #[test]
+    fn clamps_samples_to_the_buffered_range() {
+        let buffer = buffer(&[(1., 10.), (2., 20.)]);
+        assert_eq!(buffer.clamped_sample(0.), Some(10.));
+        assert_eq!(buffer.clamped_sample(1.5), Some(15.));
+        assert_eq!(buffer.clamped_sample(5.), Some(20.));
+        assert_eq!(SnapshotBuffer::<f32>::new().clamped_sample(0.), None);
+    }
+
+    #[test]
+    fn prunes_keys_no_sample_needs() {
+        let mut buffer = buffer(&[(0., 0.), (1., 10.), (2., 20.), (3., 30.)]);
+        buffer.prune_before(1.5);
+        // the key before 1.5 is kept to sample between it and the next
+        assert_eq!(times(&buffer), vec![1., 2., 3.]);
+        assert_eq!(buffer.sample(1.5), Some(15.));
+        buffer.prune_before(3.);
+        assert_eq!(times(&buffer), vec![3.]);
+        buffer.prune_before(10.);
+        assert_eq!(times(&buffer), vec![3.]);
+    }
+
+    #[test]
+    fn truncates_keys_from_a_time() {
+        let mut buffer = buffer(&[(0., 0.), (1., 10.), (2., 20.), (3., 30.)]);
+        buffer.truncate_from(2.);
+        assert_eq!(times(&buffer), vec![0., 1.]);
+        buffer.truncate_from(-1.);
+        assert!(buffer.is_empty());
+    }
+}
@@ -0,0 +1,311 @@
+//! Stepping the server and the client of a run by virtual time. A side may take its next step
+//! while the other side is stepping as long as nothing the other side sends from its next step
+//! on can arrive before the step ends, that is up to the end of the other side's next step plus
+//! the smallest latency of the network. Every step then receives the same packets as when the
+//! sides step in turn on one thread, so runs stay deterministic however the steps interleave
+use crate::sim::SimSide;
+#[cfg(not(target_arch = "wasm32"))]
+use std::{
+    sync::{Condvar, Mutex},
+    time::Duration,
+};
+
+/// One side of a run
+pub trait Stepper {
+    /// Virtual time in seconds the side's next step ends at, None once it has run for its time
+    fn next_end(&self) -> Option<f32>;
+    /// Takes the step ending at `next_end`
+    fn step(&mut self);
+}
+
+/// Whether `side` may take its step ending at `end` while the other side's next step ends at
+/// `other_end`, None once the other side is done, with packets taking at least `min_latency`
+/// seconds. When packets can arrive as they're sent and both steps end together the server
+/// goes first
+fn may_step(side: SimSide, end: f32, other_end: Option<f32>, min_latency: f32) -> bool {
+    match other_end {
+        Some(other_end) => {
+            let horizon = other_end + min_latency;
+            end < horizon || (end == horizon && horizon == other_end && side == SimSide::Server)
+        }
+        None => true,
+    }
+}
+
+/// Side stepped next when stepping in turn given the next step ends of the server and the
+/// client, the server whenever it may. None once both have run for their time
+fn turn(server: Option<f32>, client: Option<f32>, min_latency: f32) -> Option<SimSide> {
+    match (server, client) {
+        (Some(end), _) if may_step(SimSide::Server, end, client, min_latency) => {
+            Some(SimSide::Server)
+        }
+        (_, Some(_)) => Some(SimSide::Client),
+        (None, None) => None,
+        // the server may always step once the client is done
+        (Some(_), None) => unreachable!(),
+    }
+}
+
+/// Steps `server` and `client` one at a time on the calling thread
+#[cfg(any(test, target_arch = "wasm32"))]
+pub fn step_in_turn(server: &mut impl Stepper, client: &mut impl Stepper, min_latency: f32) {
+    while let Some(side) = turn(server.next_end(), client.next_end(), min_latency) {
+        match side {
+            SimSide::Server => server.step(),
+            SimSide::Client => client.step(),
+        }
+    }
+}
+
+/// Virtual clock of a side stepping on a thread of its own
+#[cfg(not(target_arch = "wasm32"))]
+#[derive(Clone, Copy)]
+enum Clock {
+    /// The side's thread hasn't started stepping
+    Starting,
+    /// The side is at `now` and its next step ends at `next`, None once it's done
+    At { now: f32, next: Option<f32> },
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl Clock {
+    fn now(self) -> f32 {
+        match self {
+            Clock::Starting => 0.,
+            Clock::At { now, .. } => now,
+        }
+    }
+    fn done(self) -> bool {
+        match self {
+            Clock::Starting => false,
+            Clock::At { next, .. } => next.is_none(),
+        }
+    }
+}
+
+/// Clocks of the server and the client of a run stepping on their own threads
+#[cfg(not(target_arch = "wasm32"))]
+pub struct SideClocks {
+    /// Clocks of the server and the client
+    clocks: Mutex<(Clock, Clock)>,
+    stepped: Condvar,
+    min_latency: f32,
+    /// Whether the sides step one at a time in the order of stepping in turn on one thread
+    in_turn: bool,
+}
+#[cfg(not(target_arch = "wasm32"))]
+impl SideClocks {
+    pub fn new(min_latency: f32, in_turn: bool) -> Self {
+        Self {
+            clocks: Mutex::new((Clock::Starting, Clock::Starting)),
+            stepped: Condvar::new(),
+            min_latency,
+            in_turn,
+        }
+    }
+    /// Takes the steps of `side` on the calling thread until it has run for its time, each once
+    /// the other side's clock allows it
+    pub fn run(&self, side: SimSide, stepper: &mut impl Stepper) {
+        let _finish = FinishOnDrop(self, side);
+        let mut now = 0.;
+        while let Some(end) = stepper.next_end() {
+            let mut clocks = self.clocks.lock().unwrap();
+            *clock_mut(&mut clocks, side) = Clock::At {
+                now,
+                next: Some(end),
+            };
+            self.stepped.notify_all();
+            while !self.may_step(&clocks, side, end) {
+                clocks = self.stepped.wait(clocks).unwrap();
+            }
+            drop(clocks);
+            stepper.step();
+            now = end;
+        }
+    }
+    fn may_step(&self, clocks: &(Clock, Clock), side: SimSide, end: f32) -> bool {
+        let next = |clock: Clock| match clock {
+            Clock::Starting => None,
+            Clock::At { next, .. } => Some(next),
+        };
+        let (server, client) = match (next(clocks.0), next(clocks.1)) {
+            (Some(server), Some(client)) => (server, client),
+            // wait for the other side to publish its first step
+            _ => return false,
+        };
+        if self.in_turn {
+            turn(server, client, self.min_latency) == Some(side)
+        } else {
+            let other_end = match side {
+                SimSide::Server => client,
+                SimSide::Client => server,
+            };
+            may_step(side, end, other_end, self.min_latency)
+        }
+    }
+    /// Virtual times of the server and the client after waiting up to `timeout` for a step,
+    /// None once both have run for their time
+    pub fn progress(&self, timeout: Duration) -> Option<(f32, f32)> {
+        let mut clocks = self.clocks.lock().unwrap();
+        if !(clocks.0.done() && clocks.1.done()) {
+            clocks = self.stepped.wait_timeout(clocks, timeout).unwrap().0;
+        }
+        if clocks.0.done() && clocks.1.done() {
+            None
+        } else {
+            Some((clocks.0.now(), clocks.1.now()))
+        }
+    }
+}
+#[cfg(not(target_arch = "wasm32"))]
+fn clock_mut(clocks: &mut (Clock, Clock), side: SimSide) -> &mut Clock {
+    match side {
+        SimSide::Server => &mut clocks.0,
+        SimSide::Client => &mut clocks.1,
+    }
+}
+/// Ends the time of a side when its thread stops stepping, so a panicking side doesn't leave
+/// the other waiting for it
+#[cfg(not(target_arch = "wasm32"))]
+struct FinishOnDrop<'a>(&'a SideClocks, SimSide);
+#[cfg(not(target_arch = "wasm32"))]
+impl Drop for FinishOnDrop<'_> {
+    fn drop(&mut self) {
+        let mut clocks = self.0.clocks.lock().unwrap_or_else(|e| e.into_inner());
+        let clock = clock_mut(&mut clocks, self.1);
+        *clock = Clock::At {
+            now: clock.now(),
+            next: None,
+        };
+        self.0.stepped.notify_all();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::monkey::InFlight;
+    use rand::{rngs::SmallRng, Rng, SeedableRng};
+    use std::{
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc,
+        },
+        thread,
+    };
+
+    /// Side sending a packet every step and logging the packets it receives
+    struct FakeSide {
+        id: u8,
+        /// Lengths of the steps in seconds
+        deltas: Vec<f32>,
+        steps: usize,
+        now: f32,
+        min_latency: f32,
+        outgoing: InFlight,
+        incoming: InFlight,
+        /// End of every step and the packets received in it
+        log: Vec<(f32, Vec<Vec<u8>>)>,
+        /// Number of sides in a step, shared by both
+        stepping: Arc<AtomicUsize>,
+        /// Whether the other side was in a step when this one started one
+        overlapped: bool,
+    }
+    impl Stepper for FakeSide {
+        fn next_end(&self) -> Option<f32> {
+            self.deltas.get(self.steps).map(|delta| self.now + delta)
+        }
+        fn step(&mut self) {
+            let end = self.next_end().unwrap();
+            self.overlapped |= self.stepping.fetch_add(1, Ordering::SeqCst) > 0;
+            thread::sleep(Duration::from_micros(200));
+            let received = self.incoming.arrived(end);
+            // latencies vary, but never below the smallest one
+            let latency = self.min_latency * (1. + (self.steps % 3) as f32 * 0.5);
+            self.outgoing
+                .push(end + latency, vec![self.id, self.steps as u8]);
+            self.log.push((end, received));
+            self.steps += 1;
+            self.now = end;
+            self.stepping.fetch_sub(1, Ordering::SeqCst);
+        }
+    }
+
+    /// Server with a fixed step and client with a varying frame time, both for a second
+    fn sides(min_latency: f32, client_deltas: impl Fn(usize) -> f32) -> (FakeSide, FakeSide) {
+        let (to_server, to_client) = (InFlight::default(), InFlight::default());
+        let stepping = Arc::new(AtomicUsize::new(0));
+        let side = |id, deltas, outgoing, incoming| FakeSide {
+            id,
+            deltas,
+            steps: 0,
+            now: 0.,
+            min_latency,
+            outgoing,
+            incoming,
+            log: Vec::new(),
+            stepping: stepping.clone(),
+            overlapped: false,
+        };
+        (
+            side(0, vec![1. / 60.; 60], to_client.clone(), to_server.clone()),
+            side(
+                1,
+                (0..60).map(client_deltas).collect(),
+                to_server,
+                to_client,
+            ),
+        )
+    }
+
+    fn run_on_threads(
+        (mut server, mut client): (FakeSide, FakeSide),
+        min_latency: f32,
+        in_turn: bool,
+    ) -> (FakeSide, FakeSide) {
+        let clocks = Arc::new(SideClocks::new(min_latency, in_turn));
+        let server = {
+            let clocks = clocks.clone();
+            thread::spawn(move || {
+                clocks.run(SimSide::Server, &mut server);
+                server
+            })
+        };
+        clocks.run(SimSide::Client, &mut client);
+        (server.join().unwrap(), client)
+    }
+
+    #[test]
+    fn overlaps_and_matches_stepping_in_turn() {
+        let mut rng = SmallRng::seed_from_u64(5);
+        let client_deltas: Vec<f32> = (0..60).map(|_| rng.gen_range(0.012, 0.022)).collect();
+        let client_deltas = |step: usize| client_deltas[step];
+        let (mut server, mut client) = sides(0.05, &client_deltas);
+        step_in_turn(&mut server, &mut client, 0.05);
+        let (concurrent_server, concurrent_client) =
+            run_on_threads(sides(0.05, &client_deltas), 0.05, false);
+        assert!(concurrent_server.overlapped || concurrent_client.overlapped);
+        assert!(client.log.iter().any(|(_, received)| !received.is_empty()));
+        assert_eq!(concurrent_server.log, server.log);
+        assert_eq!(concurrent_client.log, client.log);
+        let (turn_server, turn_client) = run_on_threads(sides(0.05, &client_deltas), 0.05, true);
+        assert!(!turn_server.overlapped && !turn_client.overlapped);
+        assert_eq!(turn_server.log, server.log);
+        assert_eq!(turn_client.log, client.log);
+    }
+
+    #[test]
+    fn steps_ending_together_without_latency() {
+        let (mut server, mut client) = sides(0., |_| 1. / 60.);
+        step_in_turn(&mut server, &mut client, 0.);
+        // the server steps first, so its packets arrive in the client's step ending with it
+        // and the client's only in the server's next step
+        assert!(server.log[0].1.is_empty());
+        assert_eq!(client.log[0].1, vec![vec![0, 0]]);
+        assert_eq!(server.log[1].1, vec![vec![1, 0]]);
+        assert_eq!(client.log[1].1, vec![vec![0, 1]]);
+        let (concurrent_server, concurrent_client) =
+            run_on_threads(sides(0., |_| 1. / 60.), 0., false);
+        assert_eq!(concurrent_server.log, server.log);
+        assert_eq!(concurrent_client.log, client.log);
+    }
+}
@@ -0,0 +1,26 @@
+//! Wall-clock timing of the sim's own work, like encoding and decoding sync messages.
+//! wasm32-unknown-unknown has no clock and `std::time::Instant` panics there, so the stopwatch
+//! reads nothing in the wasm32 build and those metrics aren't recorded
+#[cfg(not(target_arch = "wasm32"))]
+use std::time::Instant;
+
+/// Started at the beginning of the work it times
+pub struct Stopwatch {
+    #[cfg(not(target_arch = "wasm32"))]
+    started: Instant,
+}
+impl Stopwatch {
+    pub fn start() -> Self {
+        Self {
+            #[cfg(not(target_arch = "wasm32"))]
+            started: Instant::now(),
+        }
+    }
+    /// Microseconds since the start, None without a clock
+    pub fn elapsed_micros(&self) -> Option<f32> {
+        #[cfg(not(target_arch = "wasm32"))]
+        return Some(self.started.elapsed().as_secs_f32() * 1e6);
+        #[cfg(target_arch = "wasm32")]
+        None
+    }
+}
@@ -0,0 +1,112 @@
+//! Colors and marker sizes of the rendered frames, saved with the settings of a session
+#[cfg(feature = "viewer")]
+use amethyst::renderer::palette::Srgba;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Red, green, blue and alpha from 0 to 1
+pub type Rgba = [f32; 4];
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Palette {
+    Default,
+    /// Colorblind-safe colors bright enough to tell apart on a projector, with thicker lines
+    HighContrast,
+}
+impl fmt::Display for Palette {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Palette::Default => write!(f, "Default"),
+            Palette::HighContrast => write!(f, "High Contrast"),
+        }
+    }
+}
+pub const PALETTES: [Palette; 2] = [Palette::Default, Palette::HighContrast];
+impl Palette {
+    pub fn style(self) -> Style {
+        match self {
+            Palette::Default => Style {
+                palette: self,
+                server: [0.3, 0.3, 1.0, 1.0],
+                client: [0.5, 1.0, 0.5, 1.0],
+                ghost: [1.0, 0.3, 0.3, 1.0],
+                unfiltered: [1.0, 0.6, 0.2, 1.0],
+                label: [1.0, 1.0, 1.0, 1.0],
+                bookmark: [1.0, 0.8, 0.2, 1.0],
+                entities: vec![
+                    [0.3, 1.0, 1.0, 1.0],
+                    [1.0, 0.3, 1.0, 1.0],
+                    [1.0, 1.0, 0.3, 1.0],
+                    [0.6, 0.6, 0.6, 1.0],
+                ],
+                color_entities: false,
+                marker_scale: 1.,
+                line_width: 1.,
+            },
+            // Okabe-Ito colors
+            Palette::HighContrast => Style {
+                palette: self,
+                server: [0.95, 0.9, 0.25, 1.0],
+                client: [0.35, 0.7, 0.9, 1.0],
+                ghost: [0.84, 0.37, 0.0, 1.0],
+                unfiltered: [0.8, 0.6, 0.7, 1.0],
+                label: [1.0, 1.0, 1.0, 1.0],
+                bookmark: [0.9, 0.6, 0.0, 1.0],
+                entities: vec![
+                    [0.0, 0.62, 0.45, 1.0],
+                    [0.9, 0.6, 0.0, 1.0],
+                    [0.8, 0.6, 0.7, 1.0],
+                    [1.0, 1.0, 1.0, 1.0],
+                ],
+                color_entities: false,
+                marker_scale: 1.2,
+                line_width: 2.,
+            },
+        }
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Style {
+    /// Palette the colors started from
+    pub palette: Palette,
+    pub server: Rgba,
+    pub client: Rgba,
+    /// Client entities still rendered after the server despawned them
+    pub ghost: Rgba,
+    /// Client positions before the position filter
+    pub unfiltered: Rgba,
+    /// Marker of frames with a label
+    pub label: Rgba,
+    pub bookmark: Rgba,
+    /// Colors of the non-primary entities by id, cycled, used instead of the side colors with
+    /// `color_entities`
+    pub entities: Vec<Rgba>,
+    pub color_entities: bool,
+    /// Size of the markers relative to the default size
+    pub marker_scale: f32,
+    /// Width of the rendered lines in pixels
+    pub line_width: f32,
+}
+impl Default for Style {
+    fn default() -> Self {
+        Palette::Default.style()
+    }
+}
+impl Style {
+    /// Color of the frames of `entity` on the side colored `side`
+    pub fn entity(&self, side: Rgba, entity: Option<u32>) -> Rgba {
+        match entity {
+            Some(entity) if self.color_entities && !self.entities.is_empty() => {
+                self.entities[entity as usize % self.entities.len()]
+            }
+            _ => side,
+        }
+    }
+}
+
+#[cfg(feature = "viewer")]
+pub fn srgba(color: Rgba) -> Srgba {
+    Srgba::new(color[0], color[1], color[2], color[3])
+}
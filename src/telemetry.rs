@@ -0,0 +1,293 @@
+//! Import of telemetry captured from a game build, so real-world captures are shown and analyzed
+//! like the results of a run.
+//!
+//! A capture is a JSON Lines file with one record per line, tagged by its `kind`:
+//!
+//! ```text
+//! {"kind":"tick","time":12.016,"tick":721,"pos":[1.0,2.0,0.0]}
+//! {"kind":"snapshot","time":12.070,"tick":721,"size":48}
+//! {"kind":"render","time":12.080,"pos":[0.9,2.0,0.0]}
+//! ```
+//!
+//! - `tick`: the server simulated a tick, with the entity's position after it
+//! - `snapshot`: the client received the server's snapshot of a tick, `size` in bytes optional
+//! - `render`: the client rendered a frame, with the entity's rendered position
+//!
+//! Times are in seconds on one clock shared by the server and the client, e.g. the game's
+//! clock in a local playtest, and are rebased so the capture starts at 0. Positions have z as
+//! height. Ticks and renders take an optional `entity` id, without one they're of the primary
+//! entity, and an optional `label` annotating the frame. Blank lines are skipped.
+//!
+//! A running game can also stream the records to a `LiveCapture` over UDP, each datagram
+//! holding one or more lines of the same format
+use crate::sim::{
+    analyze, next_run, DeliveryRecord, PacketKind, PacketRecord, Sample, SimSettings, SimSide,
+    SimulationResult, WorldFrame,
+};
+use crate::{metrics::Metrics, snapshot_buffer::KeyFlags, truth::ErrorReference};
+use amethyst::{core::math::Vector3, Result};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::{BTreeMap, VecDeque},
+    fs::File,
+    io::{self, BufRead, BufReader},
+    net::UdpSocket,
+    path::Path,
+};
+
+/// Seconds of the latest records a `LiveCapture` keeps, so analyzing it stays fast enough to
+/// follow a long playtest
+pub const LIVE_HISTORY: f32 = 60.;
+
+/// One line of a capture
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TelemetryRecord {
+    Tick {
+        time: f32,
+        tick: u32,
+        #[serde(default)]
+        entity: Option<u32>,
+        pos: [f32; 3],
+        #[serde(default)]
+        label: Option<String>,
+    },
+    Snapshot {
+        time: f32,
+        tick: u32,
+        #[serde(default)]
+        size: Option<usize>,
+    },
+    Render {
+        time: f32,
+        #[serde(default)]
+        entity: Option<u32>,
+        pos: [f32; 3],
+        #[serde(default)]
+        label: Option<String>,
+    },
+}
+impl TelemetryRecord {
+    pub fn time(&self) -> f32 {
+        match self {
+            TelemetryRecord::Tick { time, .. }
+            | TelemetryRecord::Snapshot { time, .. }
+            | TelemetryRecord::Render { time, .. } => *time,
+        }
+    }
+}
+
+/// Reads the records of the capture at `path`
+pub fn read_capture(path: &Path) -> Result<Vec<TelemetryRecord>> {
+    let mut records = Vec::new();
+    for (idx, line) in BufReader::new(File::open(path)?).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record = serde_json::from_str(&line).map_err(|e| {
+            io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} line {}: {}", path.display(), idx + 1, e),
+            )
+        })?;
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Unanalyzed result of a capture, the server's ticks and the client's renders as frames and
+/// the received snapshots as sync packets. Also records the bytes and age in ms of each
+/// snapshot, the age measured from its tick
+pub fn capture_result(records: &[TelemetryRecord]) -> SimulationResult<Sample> {
+    let start = records
+        .iter()
+        .map(|r| r.time())
+        .fold(std::f32::INFINITY, f32::min);
+    let start = if start.is_finite() { start } else { 0. };
+    result_from(records.iter(), start)
+}
+/// Result of the records with times rebased to `start`
+fn result_from<'a>(
+    records: impl Iterator<Item = &'a TelemetryRecord> + Clone,
+    start: f32,
+) -> SimulationResult<Sample> {
+    let frame = |side, entity, time: f32, pos: &[f32; 3], label: &Option<String>| WorldFrame {
+        side,
+        entity,
+        label: label.clone(),
+        render_time: time - start,
+        net_time: time - start,
+        sample: Sample {
+            pos: Vector3::new(pos[0], pos[1], pos[2]),
+            alpha: 1.,
+        },
+        unfiltered: None,
+    };
+    let tick_times: BTreeMap<u32, f32> = records
+        .clone()
+        .filter_map(|r| match r {
+            TelemetryRecord::Tick { time, tick, .. } => Some((*tick, *time)),
+            _ => None,
+        })
+        .collect();
+    let mut frames = Vec::new();
+    let mut packets = Vec::new();
+    let mut deliveries = Vec::new();
+    let mut metrics = Metrics::default();
+    for record in records.clone() {
+        match record {
+            TelemetryRecord::Tick {
+                time,
+                entity,
+                pos,
+                label,
+                ..
+            } => frames.push(frame(SimSide::Server, *entity, *time, pos, label)),
+            TelemetryRecord::Render {
+                time,
+                entity,
+                pos,
+                label,
+            } => frames.push(frame(SimSide::Client, *entity, *time, pos, label)),
+            TelemetryRecord::Snapshot { time, tick, size } => {
+                let t = time - start;
+                let sent = tick_times.get(tick).map_or(t, |sent| sent - start);
+                packets.push(PacketRecord {
+                    side: SimSide::Server,
+                    kind: PacketKind::Sync,
+                    time: sent,
+                    seq: *tick as u16,
+                    size: size.unwrap_or(0),
+                });
+                deliveries.push(DeliveryRecord {
+                    time: t,
+                    seq: *tick as u16,
+                    server_frame: *tick as u64,
+                    tick_time: sent,
+                    send_time: sent,
+                    key_flags: KeyFlags::default(),
+                });
+                if let Some(size) = size {
+                    metrics.record("sync bytes", t, *size as f32);
+                }
+                if let Some(sent) = tick_times.get(tick) {
+                    metrics.record("snapshot age", t, (time - sent) * 1000.);
+                }
+            }
+        }
+    }
+    frames.sort_by(|a, b| a.render_time.partial_cmp(&b.render_time).unwrap());
+    packets.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    deliveries.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+    let from = records
+        .map(|r| r.time() - start)
+        .fold(std::f32::INFINITY, f32::min);
+    let window = match frames.last() {
+        Some(last) => (from.min(last.render_time), last.render_time),
+        None => (0., 0.),
+    };
+    SimulationResult {
+        frames,
+        packets,
+        deliveries,
+        metrics,
+        window,
+        log: Vec::new(),
+        debug_states: Vec::new(),
+        run: next_run(),
+    }
+}
+
+/// Analyzes a capture with the analysis settings of `settings`. A capture has no closed-form
+/// motion, so errors measured from the ground truth are measured from the interpolated server
+/// ticks instead
+pub fn analyze_capture(
+    settings: &SimSettings,
+    raw: SimulationResult<Sample>,
+) -> Result<SimulationResult<Sample>> {
+    let mut settings = settings.clone();
+    if settings.error_reference == ErrorReference::GroundTruth {
+        settings.error_reference = ErrorReference::Interpolated;
+    }
+    settings.peer_to_peer = false;
+    analyze(&settings, raw)
+}
+
+/// Records streamed by a running game to a UDP port, the latest `LIVE_HISTORY` seconds of them
+pub struct LiveCapture {
+    socket: UdpSocket,
+    records: VecDeque<TelemetryRecord>,
+    /// Time of the first record received, the times of the result are rebased to
+    start: Option<f32>,
+    buf: Vec<u8>,
+}
+impl LiveCapture {
+    /// Listens on `port` of every interface, so games on the LAN can stream to it
+    pub fn bind(port: u16) -> Result<Self> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(LiveCapture {
+            socket,
+            records: VecDeque::new(),
+            start: None,
+            buf: vec![0; 65536],
+        })
+    }
+    pub fn port(&self) -> Option<u16> {
+        self.socket.local_addr().ok().map(|a| a.port())
+    }
+    pub fn len(&self) -> usize {
+        self.records.len()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.records.is_empty()
+    }
+    /// Reads the records that arrived since the last poll, true if there were any. Lines that
+    /// aren't records are logged and skipped, so one bad packet doesn't end the capture
+    pub fn poll(&mut self) -> bool {
+        let mut received = false;
+        loop {
+            let len = match self.socket.recv(&mut self.buf) {
+                Ok(len) => len,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                Err(e) => {
+                    log::warn!("live capture failed to receive: {}", e);
+                    break;
+                }
+            };
+            for line in String::from_utf8_lossy(&self.buf[..len]).lines() {
+                if line.trim().is_empty() {
+                    continue;
+                }
+                match serde_json::from_str::<TelemetryRecord>(line) {
+                    Ok(record) => {
+                        self.start.get_or_insert(record.time());
+                        self.records.push_back(record);
+                        received = true;
+                    }
+                    Err(e) => log::warn!("live capture skipped a record: {}", e),
+                }
+            }
+        }
+        if let Some(latest) = self
+            .records
+            .iter()
+            .map(|r| r.time())
+            .fold(None, |max, t| Some(max.map_or(t, |max: f32| max.max(t))))
+        {
+            while self
+                .records
+                .front()
+                .map_or(false, |r| r.time() < latest - LIVE_HISTORY)
+            {
+                self.records.pop_front();
+            }
+        }
+        received
+    }
+    /// Unanalyzed result of the records kept, with times since the first record received
+    pub fn result(&self) -> SimulationResult<Sample> {
+        result_from(self.records.iter(), self.start.unwrap_or(0.))
+    }
+}
@@ -0,0 +1,60 @@
+//! Guided tour stepping through preset scenarios that introduce one networking effect at a time
+use lazy_static::*;
+use network_sim::sim::SimSettings;
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TourStep {
+    pub title: String,
+    /// Explanation of what the step changed and what to look for
+    pub caption: String,
+    /// Settings of the step's run, including its scenario events
+    pub settings: SimSettings,
+}
+impl TourStep {
+    /// The step's settings with the view settings of `current` kept, so the tour doesn't reset
+    /// how the runs are looked at
+    pub fn apply(&self, current: &SimSettings) -> SimSettings {
+        SimSettings {
+            curr_time: 0.,
+            second_playhead: current.second_playhead,
+            playhead_offset: current.playhead_offset,
+            hidden_entities: current.hidden_entities.clone(),
+            curves: current.curves.clone(),
+            style: current.style.clone(),
+            view_3d: current.view_3d,
+            orbit_yaw: current.orbit_yaw,
+            orbit_pitch: current.orbit_pitch,
+            minimap: current.minimap,
+            playhead_labels: current.playhead_labels,
+            viewer_frame_times: current.viewer_frame_times,
+            lod: current.lod,
+            point_cloud: current.point_cloud,
+            scale_mode: current.scale_mode,
+            scale_min: current.scale_min,
+            scale_max: current.scale_max,
+            equal_axis_scale: current.equal_axis_scale,
+            lock_y: current.lock_y,
+            y_min: current.y_min,
+            y_max: current.y_max,
+            sim_time_scale: current.sim_time_scale,
+            wall_clock_playback: current.wall_clock_playback,
+            pause_at_end: current.pause_at_end,
+            audio_cues: current.audio_cues,
+            pop_cue_threshold: current.pop_cue_threshold,
+            playing: true,
+            ..self.settings.clone()
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Tour {
+    pub steps: Vec<TourStep>,
+}
+
+lazy_static! {
+    /// Tour from no delay through latency, jitter, loss and interpolation delay to prediction
+    pub static ref TOUR: Tour = ron::de::from_str(include_str!("../config/tour.ron"))
+        .expect("config/tour.ron is not a valid tour");
+}
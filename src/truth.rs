@@ -0,0 +1,94 @@
+//! Reference positions the client's rendered positions are measured against
+use crate::sim::{Sample, SimSide, WorldFrame};
+use amethyst::core::math::Vector3;
+use serde::{Deserialize, Serialize};
+use std::{cmp::Ordering, collections::BTreeMap, fmt};
+
+/// Server position a client frame's error is measured from
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ErrorReference {
+    /// The latest server frame at or before the client frame, so the error includes how far the
+    /// server moved since its last tick
+    LatestFrame,
+    /// The server frames around the client frame interpolated at its render time
+    Interpolated,
+    /// The behaviour's closed-form motion at the client frame's render time, free of the
+    /// server's tick quantization. Entities and behaviours without one use the interpolated
+    /// server frames
+    GroundTruth,
+}
+impl fmt::Display for ErrorReference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorReference::LatestFrame => write!(f, "Latest Server Frame"),
+            ErrorReference::Interpolated => write!(f, "Interpolated Server Frames"),
+            ErrorReference::GroundTruth => write!(f, "Closed-Form Ground Truth"),
+        }
+    }
+}
+pub const ERROR_REFERENCES: [ErrorReference; 3] = [
+    ErrorReference::LatestFrame,
+    ErrorReference::Interpolated,
+    ErrorReference::GroundTruth,
+];
+
+/// Position of the primary entity at a server time in seconds
+pub type GroundTruth = Box<dyn Fn(f32) -> Vector3<f32>>;
+
+/// Server positions of every entity, evaluated at arbitrary times
+pub struct ServerTruth {
+    /// Time and position of each entity's server frames in time order
+    tracks: BTreeMap<Option<u32>, Vec<(f32, Vector3<f32>)>>,
+    ground_truth: Option<GroundTruth>,
+}
+impl ServerTruth {
+    pub fn new(frames: &[WorldFrame<Sample>], ground_truth: Option<GroundTruth>) -> Self {
+        let mut tracks: BTreeMap<_, Vec<_>> = BTreeMap::new();
+        for frame in frames.iter().filter(|f| f.side == SimSide::Server) {
+            tracks
+                .entry(frame.entity)
+                .or_default()
+                .push((frame.render_time, frame.sample.pos));
+        }
+        for track in tracks.values_mut() {
+            track.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap());
+        }
+        Self {
+            tracks,
+            ground_truth,
+        }
+    }
+    /// Position of `entity` at `t`, `None` before its first server frame. After its last frame
+    /// and with `ErrorReference::LatestFrame` the latest frame at or before `t` is held
+    pub fn at(
+        &self,
+        entity: Option<u32>,
+        t: f32,
+        reference: ErrorReference,
+    ) -> Option<Vector3<f32>> {
+        if let (ErrorReference::GroundTruth, None, Some(ground_truth)) =
+            (reference, entity, &self.ground_truth)
+        {
+            return Some(ground_truth(t));
+        }
+        let track = self.tracks.get(&entity)?;
+        // index of the first frame after t
+        let next = match track.binary_search_by(|(time, _)| {
+            if *time <= t {
+                Ordering::Less
+            } else {
+                Ordering::Greater
+            }
+        }) {
+            Ok(idx) | Err(idx) => idx,
+        };
+        let (t0, p0) = track[next.checked_sub(1)?];
+        match (reference, track.get(next)) {
+            (ErrorReference::Interpolated, Some((t1, p1)))
+            | (ErrorReference::GroundTruth, Some((t1, p1))) => {
+                Some(p0 + (p1 - p0) * ((t - t0) / (t1 - t0).max(std::f32::EPSILON)))
+            }
+            _ => Some(p0),
+        }
+    }
+}
@@ -0,0 +1,416 @@
+//! Constraints between `SimSettings` fields for combinations that make a run fail or mislead.
+//! The GUI enforces the ones that can be fixed as fields are edited and shows the rest, the
+//! headless modes report them as JSON
+use crate::{latency::LatencyModel, reliability::RetransmitPolicy, sim::SimSettings};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Severity {
+    /// The run is possible, but its results likely show an artifact of the settings rather
+    /// than of the behaviour
+    Warning,
+    /// The run can't be done or its results mean nothing
+    Error,
+}
+
+/// A problem with the settings
+#[derive(Clone, Debug, Serialize)]
+pub struct SettingsIssue {
+    pub severity: Severity,
+    /// Fields of the settings file involved
+    pub fields: Vec<&'static str>,
+    pub message: String,
+}
+impl fmt::Display for SettingsIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(
+            f,
+            "{} ({}): {}",
+            severity,
+            self.fields.join(", "),
+            self.message
+        )
+    }
+}
+
+/// A relation between fields of the settings
+pub struct Constraint {
+    pub severity: Severity,
+    /// Fields of the settings file involved
+    pub fields: &'static [&'static str],
+    /// How `settings` violates the constraint, None if they don't
+    violation: fn(&SimSettings) -> Option<String>,
+    /// Changes `settings` to satisfy the constraint, keeping the fields edited since `prev` where
+    /// possible. Constraints without one are only reported
+    fix: Option<fn(&mut SimSettings, &SimSettings)>,
+}
+
+pub static CONSTRAINTS: [Constraint; 12] = [
+    Constraint {
+        severity: Severity::Error,
+        fields: &["server_fps", "render_fps", "sync_rate"],
+        violation: zero_rate,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Error,
+        fields: &["duration"],
+        violation: no_duration,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Error,
+        fields: &["warm_up", "duration"],
+        violation: warm_up_covers_run,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Error,
+        fields: &["min_latency", "max_latency"],
+        violation: latency_order,
+        fix: Some(fix_latency_order),
+    },
+    Constraint {
+        severity: Severity::Error,
+        fields: &[
+            "loss_percentage",
+            "send_skip_percentage",
+            "burst_good_to_bad",
+            "burst_bad_to_good",
+            "burst_good_loss",
+            "burst_bad_loss",
+        ],
+        violation: loss_range,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Error,
+        fields: &[
+            "latency_model",
+            "latency_stddev",
+            "latency_pareto_scale",
+            "latency_pareto_shape",
+            "latency_pareto_max",
+        ],
+        violation: latency_model_parameters,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &["burst_loss", "burst_good_to_bad", "burst_bad_to_good"],
+        violation: endless_burst,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &["sync_rate", "server_fps"],
+        violation: sync_above_server_fps,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &["render_time_variance", "render_fps"],
+        violation: render_variance,
+        fix: Some(fix_render_variance),
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &[
+            "render_interpolation_delay",
+            "sync_rate",
+            "latency_model",
+            "min_latency",
+            "max_latency",
+        ],
+        violation: interpolation_budget,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &["retransmit_timeout", "latency_model", "max_latency"],
+        violation: retransmit_below_round_trip,
+        fix: None,
+    },
+    Constraint {
+        severity: Severity::Warning,
+        fields: &["fast_forward"],
+        violation: fast_forward_limited,
+        fix: None,
+    },
+];
+
+fn zero_rate(settings: &SimSettings) -> Option<String> {
+    let zero: Vec<_> = [
+        ("server_fps", settings.server_fps),
+        ("render_fps", settings.render_fps),
+        ("sync_rate", settings.sync_rate),
+    ]
+    .iter()
+    .filter(|(_, rate)| *rate == 0)
+    .map(|(field, _)| *field)
+    .collect();
+    if zero.is_empty() {
+        None
+    } else {
+        Some(format!("{} must be at least 1", zero.join(", ")))
+    }
+}
+
+fn no_duration(settings: &SimSettings) -> Option<String> {
+    if settings.duration <= 0. {
+        Some("the run has no duration".to_owned())
+    } else {
+        None
+    }
+}
+
+fn warm_up_covers_run(settings: &SimSettings) -> Option<String> {
+    if settings.duration > 0. && settings.warm_up >= settings.analyzed_window().1 {
+        Some(format!(
+            "the warm-up of {} s covers the whole run, nothing is analyzed",
+            settings.warm_up
+        ))
+    } else {
+        None
+    }
+}
+
+fn latency_order(settings: &SimSettings) -> Option<String> {
+    if settings.latency_model == LatencyModel::Uniform
+        && settings.min_latency > settings.max_latency
+    {
+        Some(format!(
+            "min latency {} ms is above max latency {} ms",
+            settings.min_latency, settings.max_latency
+        ))
+    } else {
+        None
+    }
+}
+/// Moves the latency that wasn't edited, so raising the minimum alone runs without jitter
+fn fix_latency_order(settings: &mut SimSettings, prev: &SimSettings) {
+    if settings.min_latency > settings.max_latency {
+        if settings.max_latency != prev.max_latency && settings.min_latency == prev.min_latency {
+            settings.min_latency = settings.max_latency;
+        } else {
+            settings.max_latency = settings.min_latency;
+        }
+    }
+}
+
+fn loss_range(settings: &SimSettings) -> Option<String> {
+    [
+        ("loss percentage", settings.loss_percentage),
+        ("skipped send percentage", settings.send_skip_percentage),
+        ("burst good to bad probability", settings.burst_good_to_bad),
+        ("burst bad to good probability", settings.burst_bad_to_good),
+        ("burst good state loss", settings.burst_good_loss),
+        ("burst bad state loss", settings.burst_bad_loss),
+    ]
+    .iter()
+    .find(|(_, p)| *p < 0. || *p > 1.)
+    .map(|(name, p)| format!("{} {} is outside 0 to 1", name, p))
+}
+
+fn latency_model_parameters(settings: &SimSettings) -> Option<String> {
+    match settings.latency_model {
+        LatencyModel::Normal if settings.latency_stddev < 0. => Some(format!(
+            "latency stddev {} ms is negative",
+            settings.latency_stddev
+        )),
+        LatencyModel::Pareto if settings.latency_pareto_scale <= 0. => Some(format!(
+            "pareto scale {} ms must be above 0, or every packet has no latency",
+            settings.latency_pareto_scale
+        )),
+        LatencyModel::Pareto if settings.latency_pareto_shape <= 0. => Some(format!(
+            "pareto shape {} must be above 0",
+            settings.latency_pareto_shape
+        )),
+        LatencyModel::Pareto if settings.latency_pareto_max < settings.latency_pareto_scale => {
+            Some(format!(
+                "pareto max latency {} ms is below the scale of {} ms",
+                settings.latency_pareto_max, settings.latency_pareto_scale
+            ))
+        }
+        _ => None,
+    }
+}
+
+fn endless_burst(settings: &SimSettings) -> Option<String> {
+    if settings.burst_loss && settings.burst_good_to_bad > 0. && settings.burst_bad_to_good <= 0. {
+        Some(format!(
+            "the link never leaves the bad state once in it, losing {}% of packets from then on",
+            settings.burst_bad_loss * 100.
+        ))
+    } else {
+        None
+    }
+}
+
+fn sync_above_server_fps(settings: &SimSettings) -> Option<String> {
+    if settings.sync_rate > settings.server_fps && settings.server_fps > 0 {
+        Some(format!(
+            "sync rate {} is above the server's {} fps, so ticks are sent more than once",
+            settings.sync_rate, settings.server_fps
+        ))
+    } else {
+        None
+    }
+}
+
+/// Largest render time variance in ms that can't make frames run backwards
+pub fn max_render_time_variance(settings: &SimSettings) -> f32 {
+    (1000. / settings.render_fps.max(1) as f32) * 0.5
+}
+fn render_variance(settings: &SimSettings) -> Option<String> {
+    let max = max_render_time_variance(settings);
+    if settings.render_time_variance > max {
+        Some(format!(
+            "render time variance {} ms is above half the {:.1} ms frame time, so frames may \
+             run backwards",
+            settings.render_time_variance,
+            max * 2.
+        ))
+    } else {
+        None
+    }
+}
+fn fix_render_variance(settings: &mut SimSettings, _prev: &SimSettings) {
+    settings.render_time_variance = settings
+        .render_time_variance
+        .min(max_render_time_variance(settings));
+}
+
+fn interpolation_budget(settings: &SimSettings) -> Option<String> {
+    if settings.render_interpolation_delay <= 0. || settings.sync_rate == 0 {
+        return None;
+    }
+    // the newest state is up to a sync interval plus the jitter older than it could be
+    let (base, high) = settings.latency_range();
+    let budget = 1000. / settings.sync_rate as f32 + high - base;
+    if settings.render_interpolation_delay < budget {
+        Some(format!(
+            "interpolation delay {} ms is below the sync interval plus jitter of {:.1} ms, so \
+             interpolation runs out of states",
+            settings.render_interpolation_delay, budget
+        ))
+    } else {
+        None
+    }
+}
+
+fn retransmit_below_round_trip(settings: &SimSettings) -> Option<String> {
+    let round_trip = settings.latency_range().1 * 2.;
+    if settings.retransmit_policy != RetransmitPolicy::None
+        && settings.retransmit_timeout < round_trip
+    {
+        Some(format!(
+            "retransmit timeout {} ms is below the round trip of up to {} ms, so delivered \
+             messages are retransmitted",
+            settings.retransmit_timeout, round_trip
+        ))
+    } else {
+        None
+    }
+}
+
+fn fast_forward_limited(settings: &SimSettings) -> Option<String> {
+    if settings.fast_forward > settings.step_scale() {
+        Some(format!(
+            "fast-forward is limited to {:.2}x to keep both sides stepping every sync tick",
+            settings.step_scale()
+        ))
+    } else {
+        None
+    }
+}
+
+/// Keeps a field at a multiple of another while the other is edited or swept
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum SettingsLink {
+    /// Interpolation delay is the factor times the sync interval
+    InterpolationDelay(f32),
+    /// Retransmit timeout is the factor times the max round trip
+    RetransmitTimeout(f32),
+}
+impl SettingsLink {
+    pub fn factor_mut(&mut self) -> &mut f32 {
+        match self {
+            SettingsLink::InterpolationDelay(factor) | SettingsLink::RetransmitTimeout(factor) => {
+                factor
+            }
+        }
+    }
+    /// Whether `other` links the same fields, whatever its factor
+    pub fn same_fields(&self, other: &SettingsLink) -> bool {
+        std::mem::discriminant(self) == std::mem::discriminant(other)
+    }
+    fn apply(&self, settings: &mut SimSettings) {
+        match *self {
+            SettingsLink::InterpolationDelay(factor) => {
+                settings.render_interpolation_delay =
+                    factor * 1000. / settings.sync_rate.max(1) as f32;
+            }
+            SettingsLink::RetransmitTimeout(factor) => {
+                settings.retransmit_timeout = factor * settings.latency_range().1 * 2.;
+            }
+        }
+    }
+}
+impl fmt::Display for SettingsLink {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsLink::InterpolationDelay(_) => {
+                write!(f, "interpolation delay = n × sync interval")
+            }
+            SettingsLink::RetransmitTimeout(_) => write!(f, "retransmit timeout = n × round trip"),
+        }
+    }
+}
+/// Every link with its default factor
+pub const SETTINGS_LINKS: [SettingsLink; 2] = [
+    SettingsLink::InterpolationDelay(2.),
+    SettingsLink::RetransmitTimeout(1.5),
+];
+
+/// Applies the links of `settings`, then fixes the constraints they violate that can be fixed,
+/// keeping the fields edited since `prev` where possible
+pub fn enforce(settings: &mut SimSettings, prev: &SimSettings) {
+    for link in settings.links.clone().iter() {
+        link.apply(settings);
+    }
+    for constraint in CONSTRAINTS.iter() {
+        if let Some(fix) = constraint.fix {
+            if (constraint.violation)(settings).is_some() {
+                fix(settings, prev);
+            }
+        }
+    }
+}
+
+/// Whether any of `issues` is an error
+pub fn has_errors(issues: &[SettingsIssue]) -> bool {
+    issues.iter().any(|i| i.severity == Severity::Error)
+}
+
+/// Violated constraints of `settings`, errors first
+pub fn validate(settings: &SimSettings) -> Vec<SettingsIssue> {
+    let mut issues: Vec<_> = CONSTRAINTS
+        .iter()
+        .filter_map(|constraint| {
+            (constraint.violation)(settings).map(|message| SettingsIssue {
+                severity: constraint.severity,
+                fields: constraint.fields.to_vec(),
+                message,
+            })
+        })
+        .collect();
+    issues.sort_by_key(|i| i.severity != Severity::Error);
+    issues
+}
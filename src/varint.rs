@@ -0,0 +1,536 @@
+//! Compact serde format where every value is a varint, laid out like bincode.
+//!
+//! Unsigned integers, lengths and enum variant indices are plain varints, signed integers are
+//! zigzag encoded, and floats are quantized to multiples of `QUANTIZATION_STEP` and zigzag
+//! encoded. Bytes of strings are one varint each. As a message is nothing but a sequence of
+//! varints, it can be delta encoded without knowing its type, see `codec::DeltaEncoder`.
+use crate::protobuf::{read_varint, unzigzag, write_varint, zigzag, Error, Result};
+use serde::{
+    de::{self, IntoDeserializer},
+    ser, Deserialize, Serialize,
+};
+
+/// Resolution of quantized floats in world units
+pub const QUANTIZATION_STEP: f32 = 0.01;
+
+fn quantize(v: f64) -> u64 {
+    zigzag((v / QUANTIZATION_STEP as f64).round() as i64)
+}
+fn dequantize(v: u64) -> f64 {
+    unzigzag(v) as f64 * QUANTIZATION_STEP as f64
+}
+
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>> {
+    let mut serializer = Serializer { out: Vec::new() };
+    value.serialize(&mut serializer)?;
+    Ok(serializer.out)
+}
+
+pub fn from_slice<'a, T: Deserialize<'a>>(buf: &'a [u8]) -> Result<T> {
+    T::deserialize(&mut Deserializer { buf })
+}
+
+struct Serializer {
+    out: Vec<u8>,
+}
+impl Serializer {
+    fn varint(&mut self, v: u64) -> Result<()> {
+        write_varint(&mut self.out, v);
+        Ok(())
+    }
+    fn bytes(&mut self, v: &[u8]) -> Result<()> {
+        self.varint(v.len() as u64)?;
+        for b in v {
+            self.varint(*b as u64)?;
+        }
+        Ok(())
+    }
+}
+
+macro_rules! serialize_unsigned {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<()> {
+            self.varint(v as u64)
+        })*
+    };
+}
+macro_rules! serialize_signed {
+    ($($method:ident: $ty:ty),*) => {
+        $(fn $method(self, v: $ty) -> Result<()> {
+            self.varint(zigzag(v as i64))
+        })*
+    };
+}
+
+impl<'a> ser::Serializer for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Self;
+    type SerializeTupleVariant = Self;
+    type SerializeMap = Self;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Self;
+
+    serialize_unsigned!(serialize_u8: u8, serialize_u16: u16, serialize_u32: u32, serialize_u64: u64);
+    serialize_signed!(serialize_i8: i8, serialize_i16: i16, serialize_i32: i32, serialize_i64: i64);
+    fn serialize_bool(self, v: bool) -> Result<()> {
+        self.varint(v as u64)
+    }
+    fn serialize_char(self, v: char) -> Result<()> {
+        self.varint(v as u64)
+    }
+    fn serialize_f32(self, v: f32) -> Result<()> {
+        self.varint(quantize(v as f64))
+    }
+    fn serialize_f64(self, v: f64) -> Result<()> {
+        self.varint(quantize(v))
+    }
+    fn serialize_str(self, v: &str) -> Result<()> {
+        self.bytes(v.as_bytes())
+    }
+    fn serialize_bytes(self, v: &[u8]) -> Result<()> {
+        self.bytes(v)
+    }
+    fn serialize_none(self) -> Result<()> {
+        self.varint(0)
+    }
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<()> {
+        self.varint(1)?;
+        value.serialize(self)
+    }
+    fn serialize_unit(self) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_struct(self, _name: &'static str) -> Result<()> {
+        Ok(())
+    }
+    fn serialize_unit_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+    ) -> Result<()> {
+        self.varint(variant_index as u64)
+    }
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(self)
+    }
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        self.varint(variant_index as u64)?;
+        value.serialize(self)
+    }
+    fn serialize_seq(self, len: Option<usize>) -> Result<Self> {
+        let len = len.ok_or_else(|| Error("sequences need a known length".to_owned()))?;
+        self.varint(len as u64)?;
+        Ok(self)
+    }
+    fn serialize_tuple(self, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+    fn serialize_tuple_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+    fn serialize_tuple_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.varint(variant_index as u64)?;
+        Ok(self)
+    }
+    fn serialize_map(self, len: Option<usize>) -> Result<Self> {
+        let len = len.ok_or_else(|| Error("maps need a known length".to_owned()))?;
+        self.varint(len as u64)?;
+        Ok(self)
+    }
+    fn serialize_struct(self, _name: &'static str, _len: usize) -> Result<Self> {
+        Ok(self)
+    }
+    fn serialize_struct_variant(
+        self,
+        _name: &'static str,
+        variant_index: u32,
+        _variant: &'static str,
+        _len: usize,
+    ) -> Result<Self> {
+        self.varint(variant_index as u64)?;
+        Ok(self)
+    }
+}
+
+impl<'a> ser::SerializeSeq for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeTuple for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeTupleStruct for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeTupleVariant for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeMap for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<()> {
+        key.serialize(&mut **self)
+    }
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeStruct for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+impl<'a> ser::SerializeStructVariant for &'a mut Serializer {
+    type Ok = ();
+    type Error = Error;
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        _key: &'static str,
+        value: &T,
+    ) -> Result<()> {
+        value.serialize(&mut **self)
+    }
+    fn end(self) -> Result<()> {
+        Ok(())
+    }
+}
+
+struct Deserializer<'a> {
+    buf: &'a [u8],
+}
+impl<'a> Deserializer<'a> {
+    fn varint(&mut self) -> Result<u64> {
+        read_varint(&mut self.buf)
+    }
+    fn len(&mut self) -> Result<usize> {
+        Ok(self.varint()? as usize)
+    }
+    fn bytes(&mut self) -> Result<Vec<u8>> {
+        let len = self.len()?;
+        (0..len).map(|_| Ok(self.varint()? as u8)).collect()
+    }
+}
+
+macro_rules! deserialize_unsigned {
+    ($($method:ident: $visit:ident $ty:ty),*) => {
+        $(fn $method<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(self.varint()? as $ty)
+        })*
+    };
+}
+macro_rules! deserialize_signed {
+    ($($method:ident: $visit:ident $ty:ty),*) => {
+        $(fn $method<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+            visitor.$visit(unzigzag(self.varint()?) as $ty)
+        })*
+    };
+}
+
+impl<'a, 'b> de::Deserializer<'a> for &'b mut Deserializer<'a> {
+    type Error = Error;
+
+    deserialize_unsigned!(
+        deserialize_u8: visit_u8 u8,
+        deserialize_u16: visit_u16 u16,
+        deserialize_u32: visit_u32 u32,
+        deserialize_u64: visit_u64 u64
+    );
+    deserialize_signed!(
+        deserialize_i8: visit_i8 i8,
+        deserialize_i16: visit_i16 i16,
+        deserialize_i32: visit_i32 i32,
+        deserialize_i64: visit_i64 i64
+    );
+    fn deserialize_any<V: de::Visitor<'a>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error("the format needs the type to be known".to_owned()))
+    }
+    fn deserialize_bool<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_bool(self.varint()? != 0)
+    }
+    fn deserialize_char<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let v = self.varint()?;
+        let c = std::char::from_u32(v as u32).ok_or_else(|| Error("invalid char".to_owned()))?;
+        visitor.visit_char(c)
+    }
+    fn deserialize_f32<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f32(dequantize(self.varint()?) as f32)
+    }
+    fn deserialize_f64<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_f64(dequantize(self.varint()?))
+    }
+    fn deserialize_str<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let s = String::from_utf8(self.bytes()?).map_err(|e| Error(e.to_string()))?;
+        visitor.visit_string(s)
+    }
+    fn deserialize_string<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_str(visitor)
+    }
+    fn deserialize_bytes<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_byte_buf(self.bytes()?)
+    }
+    fn deserialize_byte_buf<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_bytes(visitor)
+    }
+    fn deserialize_option<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        if self.varint()? == 0 {
+            visitor.visit_none()
+        } else {
+            visitor.visit_some(self)
+        }
+    }
+    fn deserialize_unit<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    fn deserialize_unit_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_unit()
+    }
+    fn deserialize_newtype_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_newtype_struct(self)
+    }
+    fn deserialize_seq<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.len()?;
+        visitor.visit_seq(Elements { de: self, len })
+    }
+    fn deserialize_tuple<V: de::Visitor<'a>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        visitor.visit_seq(Elements { de: self, len })
+    }
+    fn deserialize_tuple_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        len: usize,
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(len, visitor)
+    }
+    fn deserialize_map<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        let len = self.len()?;
+        visitor.visit_map(Elements { de: self, len })
+    }
+    fn deserialize_struct<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        self.deserialize_tuple(fields.len(), visitor)
+    }
+    fn deserialize_enum<V: de::Visitor<'a>>(
+        self,
+        _name: &'static str,
+        _variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        visitor.visit_enum(self)
+    }
+    fn deserialize_identifier<V: de::Visitor<'a>>(self, visitor: V) -> Result<V::Value> {
+        self.deserialize_u32(visitor)
+    }
+    fn deserialize_ignored_any<V: de::Visitor<'a>>(self, _visitor: V) -> Result<V::Value> {
+        Err(Error("the format needs the type to be known".to_owned()))
+    }
+}
+
+/// The next `len` elements of a sequence, or entries of a map
+struct Elements<'a, 'b> {
+    de: &'b mut Deserializer<'a>,
+    len: usize,
+}
+impl<'a, 'b> de::SeqAccess<'a> for Elements<'a, 'b> {
+    type Error = Error;
+    fn next_element_seed<T: de::DeserializeSeed<'a>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.len)
+    }
+}
+impl<'a, 'b> de::MapAccess<'a> for Elements<'a, 'b> {
+    type Error = Error;
+    fn next_key_seed<K: de::DeserializeSeed<'a>>(&mut self, seed: K) -> Result<Option<K::Value>> {
+        if self.len == 0 {
+            return Ok(None);
+        }
+        self.len -= 1;
+        seed.deserialize(&mut *self.de).map(Some)
+    }
+    fn next_value_seed<V: de::DeserializeSeed<'a>>(&mut self, seed: V) -> Result<V::Value> {
+        seed.deserialize(&mut *self.de)
+    }
+}
+
+impl<'a, 'b> de::EnumAccess<'a> for &'b mut Deserializer<'a> {
+    type Error = Error;
+    type Variant = Self;
+    fn variant_seed<V: de::DeserializeSeed<'a>>(self, seed: V) -> Result<(V::Value, Self)> {
+        let variant: de::value::U32Deserializer<Error> =
+            (self.varint()? as u32).into_deserializer();
+        Ok((seed.deserialize(variant)?, self))
+    }
+}
+impl<'a, 'b> de::VariantAccess<'a> for &'b mut Deserializer<'a> {
+    type Error = Error;
+    fn unit_variant(self) -> Result<()> {
+        Ok(())
+    }
+    fn newtype_variant_seed<T: de::DeserializeSeed<'a>>(self, seed: T) -> Result<T::Value> {
+        seed.deserialize(self)
+    }
+    fn tuple_variant<V: de::Visitor<'a>>(self, len: usize, visitor: V) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, len, visitor)
+    }
+    fn struct_variant<V: de::Visitor<'a>>(
+        self,
+        fields: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value> {
+        de::Deserializer::deserialize_tuple(self, fields.len(), visitor)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sim_behaviours::VehicleState;
+    use serde_json::{json, Value};
+
+    /// Numbers of a value serialized as JSON, in field order
+    fn numbers(value: &Value) -> Vec<f64> {
+        match value {
+            Value::Number(n) => vec![n.as_f64().unwrap()],
+            Value::Array(values) => values.iter().flat_map(numbers).collect(),
+            Value::Object(fields) => fields.values().flat_map(numbers).collect(),
+            _ => Vec::new(),
+        }
+    }
+
+    #[test]
+    fn sync_state_within_quantization_step() {
+        let state: VehicleState = serde_json::from_value(json!({
+            "throttle": 0.734,
+            "steering": -0.25,
+            "pos": [123.456, -78.901],
+            "velocity": [-3.333, 0.004],
+            "heading": 2.718,
+            "speed": 17.5,
+        }))
+        .unwrap();
+        let decoded: VehicleState = from_slice(&to_vec(&state).unwrap()).unwrap();
+        let (sent, received) = (
+            numbers(&serde_json::to_value(state).unwrap()),
+            numbers(&serde_json::to_value(decoded).unwrap()),
+        );
+        assert_eq!(sent.len(), 8);
+        assert_eq!(sent.len(), received.len());
+        for (sent, received) in sent.iter().zip(&received) {
+            // half a step of rounding, plus the error of f32
+            assert!(
+                (sent - received).abs() <= QUANTIZATION_STEP as f64 / 2. + 1e-4,
+                "{} decoded as {}",
+                sent,
+                received
+            );
+        }
+    }
+
+    #[derive(Serialize, Deserialize, Debug, PartialEq)]
+    enum Event {
+        Spawn { id: u32, name: String },
+        Despawn(u32),
+    }
+
+    #[test]
+    fn integers_strings_and_enums_exactly() {
+        let value = (
+            std::u64::MAX,
+            -12_345i32,
+            Some(7u8),
+            None::<u16>,
+            vec![
+                Event::Spawn {
+                    id: 3,
+                    name: "crate".to_owned(),
+                },
+                Event::Despawn(3),
+            ],
+        );
+        let buf = to_vec(&value).unwrap();
+        assert_eq!(
+            from_slice::<(u64, i32, Option<u8>, Option<u16>, Vec<Event>)>(&buf).unwrap(),
+            value
+        );
+    }
+}
@@ -0,0 +1,114 @@
+//! Screen layout of the viewer: the camera and the panels the results are drawn into, both only
+//! updated when the window is resized
+use amethyst::core::math::Vector2;
+#[cfg(feature = "viewer")]
+use amethyst::{
+    core::transform::Transform,
+    ecs::{ReadExpect, System, Write, WriteStorage},
+    renderer::camera::Camera,
+    window::ScreenDimensions,
+};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Side of the minimap as a fraction of the smaller screen dimension
+const MINIMAP_SIZE: f32 = 0.3;
+
+/// Rectangle of the screen in pixels from the bottom left, which is also world space for the
+/// 2d camera
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Panel {
+    pub offset: Vector2<f32>,
+    pub size: Vector2<f32>,
+}
+
+/// Panels of the current window size
+#[derive(Clone, Debug)]
+pub struct Viewport {
+    pub size: Vector2<f32>,
+    pub server: Panel,
+    pub client: Panel,
+    pub minimap: Panel,
+    /// Bumped with every resize, so anything laid out in the panels knows to redo it
+    pub generation: u64,
+}
+impl Default for Viewport {
+    fn default() -> Self {
+        Viewport::new(Vector2::zeros(), 0)
+    }
+}
+impl Viewport {
+    fn new(size: Vector2<f32>, generation: u64) -> Self {
+        let panel = |x: f32, y: f32, w: f32, h: f32| Panel {
+            offset: Vector2::new(size.x * x, size.y * y),
+            size: Vector2::new(size.x * w, size.y * h),
+        };
+        let minimap = size.x.min(size.y) * MINIMAP_SIZE;
+        Viewport {
+            size,
+            server: panel(0.02, 0.02, 0.45, 0.85),
+            client: panel(0.5, 0.02, 0.45, 0.85),
+            minimap: Panel {
+                offset: Vector2::new(size.x * 0.98 - minimap, size.y * 0.02),
+                size: Vector2::new(minimap, minimap),
+            },
+            generation,
+        }
+    }
+}
+
+/// World extents the positions of a run are scaled from to fill the panels
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ScaleMode {
+    /// Each run's own bounding box, so every run fills the panels
+    Auto,
+    /// The extents entered in the settings
+    Fixed,
+    /// The bounding box of the run locked as the baseline, so later runs are drawn to its scale
+    Baseline,
+}
+impl fmt::Display for ScaleMode {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ScaleMode::Auto => write!(f, "Auto"),
+            ScaleMode::Fixed => write!(f, "Fixed Extents"),
+            ScaleMode::Baseline => write!(f, "Locked to Baseline"),
+        }
+    }
+}
+pub const SCALE_MODES: [ScaleMode; 3] = [ScaleMode::Auto, ScaleMode::Fixed, ScaleMode::Baseline];
+
+/// Fits the camera to the window and lays out the `Viewport` when the window size changes
+#[cfg(feature = "viewer")]
+#[derive(Default)]
+pub struct ViewSystem {
+    size: Option<Vector2<f32>>,
+}
+#[cfg(feature = "viewer")]
+impl<'s> System<'s> for ViewSystem {
+    type SystemData = (
+        ReadExpect<'s, ScreenDimensions>,
+        Write<'s, Viewport>,
+        WriteStorage<'s, Camera>,
+        WriteStorage<'s, Transform>,
+    );
+    fn run(
+        &mut self,
+        (screen_dimensions, mut viewport, mut cameras, mut transforms): Self::SystemData,
+    ) {
+        let size = Vector2::new(
+            screen_dimensions.width() * screen_dimensions.hidpi_factor() as f32,
+            screen_dimensions.height() * screen_dimensions.hidpi_factor() as f32,
+        );
+        if self.size == Some(size) {
+            return;
+        }
+        self.size = Some(size);
+        use amethyst::ecs::Join;
+        for (camera, transform) in (&mut cameras, &mut transforms).join() {
+            *camera = Camera::standard_2d(size.x, size.y);
+            transform.set_translation_xyz(size.x / 2., size.y / 2., 10.0);
+        }
+        *viewport = Viewport::new(size, viewport.generation + 1);
+    }
+}
@@ -0,0 +1,228 @@
+//! Entry points of the web viewer in web/, which loads the wasm32 build of the library. The apps
+//! can't run in a browser, so the viewer replays a run recorded natively into the client of a
+//! settings file, see `replay::PacketSchedule`, and hands the page the positions to draw at the
+//! playhead. The page draws them on a 2D canvas in place of the native viewer's renderer and
+//! window
+use crate::{
+    ffi::set_last_error,
+    replay::PacketSchedule,
+    sim::{Sample, SimSettings, SimSide, SimulationResult},
+};
+use amethyst::Result;
+use std::{
+    ffi::CStr,
+    os::raw::c_char,
+    panic::{self, AssertUnwindSafe},
+    ptr,
+};
+
+/// Frames of the primary entity of a side, by render time
+#[derive(Default)]
+struct Track {
+    times: Vec<f32>,
+    /// Positions on the ground plane, x and y interleaved
+    positions: Vec<f32>,
+}
+
+/// Replayed run shown by the web viewer, created with `ns_viewer_new`
+pub struct NsViewer {
+    server: Track,
+    client: Track,
+    /// Smallest x, smallest y, largest x and largest y of any position
+    bounds: [f32; 4],
+    /// Render time of the last frame
+    duration: f32,
+}
+impl NsViewer {
+    /// Viewer of an analyzed result, whose frames are in render time order
+    pub fn new(result: &SimulationResult<Sample>) -> Self {
+        let mut viewer = NsViewer {
+            server: Track::default(),
+            client: Track::default(),
+            bounds: [std::f32::MAX, std::f32::MAX, std::f32::MIN, std::f32::MIN],
+            duration: 0.,
+        };
+        for frame in result.frames.iter().filter(|f| f.entity.is_none()) {
+            let (x, y) = (frame.sample.pos.x, frame.sample.pos.y);
+            let track = match frame.side {
+                SimSide::Server => &mut viewer.server,
+                SimSide::Client => &mut viewer.client,
+            };
+            track.times.push(frame.render_time);
+            track.positions.extend_from_slice(&[x, y]);
+            let bounds = &mut viewer.bounds;
+            *bounds = [
+                bounds[0].min(x),
+                bounds[1].min(y),
+                bounds[2].max(x),
+                bounds[3].max(y),
+            ];
+            viewer.duration = viewer.duration.max(frame.render_time);
+        }
+        viewer
+    }
+    /// Positions of `side` from `trail` seconds before `time` up to `time`, x and y interleaved,
+    /// the last being where it is at `time`
+    pub fn trail(&self, side: SimSide, time: f32, trail: f32) -> &[f32] {
+        let track = match side {
+            SimSide::Server => &self.server,
+            SimSide::Client => &self.client,
+        };
+        let end = track.times.partition_point(|t| *t <= time);
+        let start = track.times[..end].partition_point(|t| *t <= time - trail);
+        &track.positions[start * 2..end * 2]
+    }
+}
+
+/// Viewer of `result_json` replayed into the client of `settings_ron`
+unsafe fn replay(settings_ron: *const c_char, result_json: *const c_char) -> Result<NsViewer> {
+    let settings_ron = CStr::from_ptr(settings_ron).to_string_lossy();
+    let result_json = CStr::from_ptr(result_json).to_string_lossy();
+    let settings: SimSettings = ron::de::from_str(&settings_ron)?;
+    let recorded: SimulationResult<Sample> = serde_json::from_str(&result_json)?;
+    let replay = PacketSchedule::from_result(&recorded).replay(&settings)?;
+    Ok(NsViewer::new(&replay.result))
+}
+
+/// Viewer of the run recorded in `result_json`, a result exported as JSON with `--out`, replayed
+/// into the client of the settings file `settings_ron`, freed with `ns_viewer_free`. Null if
+/// either doesn't parse or the replay fails
+///
+/// # Safety
+/// `settings_ron` and `result_json` must be valid nul-terminated strings
+#[no_mangle]
+pub unsafe extern "C" fn ns_viewer_new(
+    settings_ron: *const c_char,
+    result_json: *const c_char,
+) -> *mut NsViewer {
+    // a panic must not unwind into the page's frames
+    match panic::catch_unwind(AssertUnwindSafe(|| replay(settings_ron, result_json))) {
+        Ok(Ok(viewer)) => Box::into_raw(Box::new(viewer)),
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            ptr::null_mut()
+        }
+        Err(_) => {
+            set_last_error("the replay panicked".to_string());
+            ptr::null_mut()
+        }
+    }
+}
+
+/// # Safety
+/// `viewer` must come from `ns_viewer_new` and not be used after
+#[no_mangle]
+pub unsafe extern "C" fn ns_viewer_free(viewer: *mut NsViewer) {
+    if !viewer.is_null() {
+        drop(Box::from_raw(viewer));
+    }
+}
+
+/// Seconds the playhead runs for
+///
+/// # Safety
+/// `viewer` must be a live viewer
+#[no_mangle]
+pub unsafe extern "C" fn ns_viewer_duration(viewer: *const NsViewer) -> f32 {
+    (&*viewer).duration
+}
+
+/// Writes the smallest x, smallest y, largest x and largest y of the positions to `bounds`
+///
+/// # Safety
+/// `viewer` must be a live viewer and `bounds` writable for 4 floats
+#[no_mangle]
+pub unsafe extern "C" fn ns_viewer_bounds(viewer: *const NsViewer, bounds: *mut f32) {
+    ptr::copy_nonoverlapping((&*viewer).bounds.as_ptr(), bounds, 4);
+}
+
+/// Positions of `side`, 0 for the client and 1 for the server, from `trail` seconds before
+/// `time` up to `time`, x and y interleaved with the last where it is at `time`. Writes their
+/// number to `len`. Valid as long as the viewer
+///
+/// # Safety
+/// `viewer` must be a live viewer and `len` writable
+#[no_mangle]
+pub unsafe extern "C" fn ns_viewer_trail(
+    viewer: *const NsViewer,
+    side: u8,
+    time: f32,
+    trail: f32,
+    len: *mut usize,
+) -> *const f32 {
+    let side = if side == 0 {
+        SimSide::Client
+    } else {
+        SimSide::Server
+    };
+    let positions = (&*viewer).trail(side, time, trail);
+    *len = positions.len() / 2;
+    positions.as_ptr()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{metrics::Metrics, sim::WorldFrame};
+    use amethyst::core::math::Vector3;
+
+    fn frame(side: SimSide, entity: Option<u32>, render_time: f32, x: f32) -> WorldFrame<Sample> {
+        WorldFrame {
+            side,
+            entity,
+            label: None,
+            render_time,
+            net_time: render_time,
+            sample: Sample {
+                pos: Vector3::new(x, -x, 0.),
+                alpha: 1.,
+            },
+            unfiltered: None,
+        }
+    }
+
+    fn viewer() -> NsViewer {
+        let mut frames = Vec::new();
+        for idx in 0..10 {
+            let t = idx as f32 * 0.1;
+            frames.push(frame(SimSide::Server, None, t, idx as f32));
+            frames.push(frame(SimSide::Client, None, t + 0.05, idx as f32 + 0.5));
+            // other entities aren't drawn
+            frames.push(frame(SimSide::Client, Some(1), t, 100.));
+        }
+        NsViewer::new(&SimulationResult {
+            frames,
+            packets: Vec::new(),
+            deliveries: Vec::new(),
+            metrics: Metrics::default(),
+            window: (0., 1.),
+            log: Vec::new(),
+            debug_states: Vec::new(),
+            run: 0,
+        })
+    }
+
+    #[test]
+    fn bounds_of_primary_entities() {
+        let viewer = viewer();
+        assert_eq!(viewer.bounds, [0., -9.5, 9.5, 0.]);
+        assert!((viewer.duration - 0.95).abs() < 1e-6);
+    }
+
+    #[test]
+    fn trail_up_to_playhead() {
+        let viewer = viewer();
+        assert!(viewer.trail(SimSide::Client, 0.01, 1.).is_empty());
+        assert_eq!(viewer.trail(SimSide::Server, 0., 1.), &[0., 0.]);
+        // the frames after 0.12 up to 0.22
+        assert_eq!(viewer.trail(SimSide::Server, 0.22, 0.1), &[2., -2.]);
+        assert_eq!(viewer.trail(SimSide::Client, 0.22, 0.1), &[1.5, -1.5]);
+        assert_eq!(
+            viewer.trail(SimSide::Server, 0.22, 0.3),
+            &[0., 0., 1., -1., 2., -2.]
+        );
+        let all = viewer.trail(SimSide::Client, 10., 10.);
+        assert_eq!(all.len(), 20);
+        assert_eq!(&all[18..], &[9.5, -9.5]);
+    }
+}
@@ -0,0 +1,172 @@
+// Loads the wasm32 build of the library and draws the run given by the page's query string on
+// the canvas. The library replays the run and picks the positions to draw, see src/web.rs, the
+// page only draws them. Positions are drawn on the ground plane, the server's in blue and the
+// client's in green as in the default palette of the native viewer
+'use strict';
+
+const SERVER_COLOR = 'rgb(77, 77, 255)';
+const CLIENT_COLOR = 'rgb(128, 255, 128)';
+// sides as passed to ns_viewer_trail
+const CLIENT = 0;
+const SERVER = 1;
+// seconds of positions drawn behind the playhead
+const TRAIL = 1;
+
+const params = new URLSearchParams(location.search);
+
+// API of src/ffi.rs on top of the module's exports
+class Library {
+  constructor(instance) {
+    this.api = instance.exports;
+  }
+  bytes() {
+    // views of the memory are detached whenever it grows
+    return new Uint8Array(this.api.memory.buffer);
+  }
+  // Runs `f` with a nul-terminated copy of `text` in the module's memory
+  withString(text, f) {
+    const encoded = new TextEncoder().encode(text);
+    const ptr = this.api.ns_alloc(encoded.length + 1);
+    const bytes = this.bytes();
+    bytes.set(encoded, ptr);
+    bytes[ptr + encoded.length] = 0;
+    try {
+      return f(ptr);
+    } finally {
+      this.api.ns_free(ptr, encoded.length + 1);
+    }
+  }
+  lastError() {
+    const ptr = this.api.ns_last_error();
+    if (ptr === 0) {
+      return 'unknown error';
+    }
+    const bytes = this.bytes();
+    let end = ptr;
+    while (bytes[end] !== 0) {
+      end += 1;
+    }
+    return new TextDecoder().decode(bytes.subarray(ptr, end));
+  }
+  // Viewer of `result` replayed into the client of `settings`
+  viewer(settings, result) {
+    const api = this.api;
+    const viewer = this.withString(settings, (settingsPtr) =>
+      this.withString(result, (resultPtr) => api.ns_viewer_new(settingsPtr, resultPtr)),
+    );
+    if (viewer === 0) {
+      throw new Error(this.lastError());
+    }
+    const out = api.ns_alloc(16);
+    api.ns_viewer_bounds(viewer, out);
+    const [minX, minY, maxX, maxY] = new Float32Array(api.memory.buffer, out, 4);
+    api.ns_free(out, 16);
+    return {
+      duration: api.ns_viewer_duration(viewer),
+      bounds: { minX, minY, maxX, maxY },
+      // Positions of `side` from TRAIL seconds before `t` up to `t` as x and y pairs
+      trail: (side, t) => {
+        const len = api.ns_alloc(4);
+        const positions = api.ns_viewer_trail(viewer, side, t, TRAIL, len);
+        const count = new Uint32Array(api.memory.buffer, len, 1)[0];
+        api.ns_free(len, 4);
+        return new Float32Array(api.memory.buffer, positions, count * 2);
+      },
+    };
+  }
+}
+
+function draw(canvas, viewer, t) {
+  const bounds = viewer.bounds;
+  const ctx = canvas.getContext('2d');
+  canvas.width = canvas.clientWidth * devicePixelRatio;
+  canvas.height = canvas.clientHeight * devicePixelRatio;
+  ctx.fillStyle = '#000';
+  ctx.fillRect(0, 0, canvas.width, canvas.height);
+  const margin = 20 * devicePixelRatio;
+  const scale = Math.min(
+    (canvas.width - 2 * margin) / Math.max(bounds.maxX - bounds.minX, 1e-3),
+    (canvas.height - 2 * margin) / Math.max(bounds.maxY - bounds.minY, 1e-3),
+  );
+  const toCanvas = (x, y) => [
+    margin + (x - bounds.minX) * scale,
+    canvas.height - margin - (y - bounds.minY) * scale,
+  ];
+  for (const [side, color] of [[SERVER, SERVER_COLOR], [CLIENT, CLIENT_COLOR]]) {
+    const trail = viewer.trail(side, t);
+    if (trail.length === 0) {
+      continue;
+    }
+    ctx.strokeStyle = color;
+    ctx.lineWidth = devicePixelRatio;
+    ctx.beginPath();
+    for (let idx = 0; idx < trail.length; idx += 2) {
+      ctx.lineTo(...toCanvas(trail[idx], trail[idx + 1]));
+    }
+    ctx.stroke();
+    const [x, y] = toCanvas(trail[trail.length - 2], trail[trail.length - 1]);
+    ctx.fillStyle = color;
+    ctx.beginPath();
+    ctx.arc(x, y, 5 * devicePixelRatio, 0, 2 * Math.PI);
+    ctx.fill();
+  }
+}
+
+async function main() {
+  const [module, settings, result] = await Promise.all([
+    fetch(params.get('wasm') || 'network_sim.wasm')
+      .then((response) => response.arrayBuffer())
+      .then((bytes) => WebAssembly.instantiate(bytes, {})),
+    fetch(params.get('settings')).then((response) => response.text()),
+    fetch(params.get('result')).then((response) => response.text()),
+  ]);
+  const viewer = new Library(module.instance).viewer(settings, result);
+  const duration = viewer.duration;
+
+  const canvas = document.getElementById('view');
+  const playhead = document.getElementById('playhead');
+  const play = document.getElementById('play');
+  const time = document.getElementById('time');
+  playhead.max = duration;
+  const show = () => {
+    const t = Number(playhead.value);
+    time.textContent = `${t.toFixed(2)} s`;
+    draw(canvas, viewer, t);
+  };
+  let playing = null;
+  const step = (now) => {
+    if (playing === null) {
+      return;
+    }
+    const t = Number(playhead.value) + (now - playing) / 1000;
+    playing = now;
+    playhead.value = Math.min(t, duration);
+    show();
+    if (t >= duration) {
+      playing = null;
+      play.textContent = 'Play';
+    } else {
+      requestAnimationFrame(step);
+    }
+  };
+  play.onclick = () => {
+    if (playing === null) {
+      if (Number(playhead.value) >= duration) {
+        playhead.value = 0;
+      }
+      playing = performance.now();
+      play.textContent = 'Pause';
+      requestAnimationFrame(step);
+    } else {
+      playing = null;
+      play.textContent = 'Play';
+    }
+  };
+  playhead.oninput = show;
+  window.onresize = show;
+  show();
+}
+
+main().catch((e) => {
+  document.getElementById('error').textContent = e.message;
+});